@@ -0,0 +1,125 @@
+//! Loading of game files (maps, textures, string tables)
+//!
+//! The files that come with the game are compiled into the binary, so it runs from anywhere.
+//! On the desktop a file is first looked for in the asset directory (`ULVESTEIN_ASSETS` or `assets/`),
+//! then at the path itself, so any of them can be replaced and new ones added without recompiling.
+//! The browser has no file system, so there only the compiled in files exist.
+//! A mounted package is searched before anything else.
+
+use std::{io, path::Path, sync::RwLock};
+
+use crate::package::Package;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{env, fs, path::PathBuf};
+
+/// Environment variable pointing to the directory with files to use instead of the built-in ones
+#[cfg(not(target_arch = "wasm32"))]
+const ASSETS_VAR: &str = "ULVESTEIN_ASSETS";
+#[cfg(not(target_arch = "wasm32"))]
+const DEFAULT_ASSETS_DIR: &str = "assets";
+
+macro_rules! embed {
+    ($($path:literal),* $(,)?) => {
+        &[$(($path, include_bytes!(concat!("../", $path)))),*]
+    };
+}
+
+const EMBEDDED: &[(&str, &[u8])] = embed![
+    "map.txt",
+    "demos/map.dem",
+    "arena.txt",
+    "episodes.toml",
+    "lang/en.toml",
+    "lang/da.toml",
+    "tex/blue_brick.png",
+    "tex/blue_brick_dark.png",
+    "tex/blue_brick2.png",
+    "tex/blue_brick2_dark.png",
+    "tex/brick1.png",
+    "tex/brick1_dark.png",
+    "tex/brick2.png",
+    "tex/brick2_dark.png",
+    "tex/chalice.png",
+    "tex/conveyor.png",
+    "tex/cross.png",
+    "tex/crown.png",
+    "tex/door.png",
+    "tex/door_dark.png",
+    "tex/glass.png",
+    "tex/glass_dark.png",
+    "tex/gun.png",
+    "tex/icon.png",
+    "tex/jail_door.png",
+    "tex/jail_door_dark.png",
+    "tex/jail_skelly.png",
+    "tex/jail_skelly_dark.png",
+    "tex/mirror.png",
+    "tex/player.png",
+    "tex/statue.png",
+    "sfx/door_blocked.wav",
+    "sfx/door_close.wav",
+    "sfx/door_locked.wav",
+    "sfx/door_open.wav",
+    "sfx/explosion.wav",
+    "sfx/gunshot.wav",
+    "sfx/sizzle.wav",
+    "sfx/splash.wav",
+    "sfx/step_ice.wav",
+    "sfx/step_metal.wav",
+    "sfx/step_stone.wav",
+    "sfx/step_water.wav",
+    "sfx/step_wood.wav",
+];
+
+static PACKAGE: RwLock<Option<&'static Package>> = RwLock::new(None);
+
+/// Makes the files of the package available instead of those of the package mounted before, if any
+pub fn mount(package: Package) {
+    // Packages are only mounted at startup and when dropped onto the window, so the old ones are just kept around
+    *PACKAGE.write().unwrap() = Some(Box::leak(Box::new(package)));
+}
+
+pub fn package() -> Option<&'static Package> {
+    *PACKAGE.read().unwrap()
+}
+
+fn embedded(path: &Path) -> Option<&'static [u8]> {
+    EMBEDDED.iter().find(|(p, _)| Path::new(p) == path).map(|&(_, bytes)| bytes)
+}
+
+/// Where a file is on disk, if it is there
+#[cfg(not(target_arch = "wasm32"))]
+fn find_on_disk(path: &Path) -> Option<PathBuf> {
+    let dir = env::var_os(ASSETS_VAR).map(PathBuf::from).unwrap_or_else(|| PathBuf::from(DEFAULT_ASSETS_DIR));
+    [dir.join(path), path.to_owned()].into_iter().find(|p| p.is_file())
+}
+
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
+    let path = path.as_ref();
+    if let Some(contents) = package().and_then(|p| p.read(path)) {
+        return Ok(contents);
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(p) = find_on_disk(path) {
+        return fs::read(p);
+    }
+    embedded(path)
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} could not be found", path.display())))
+}
+
+pub fn read_to_string<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    String::from_utf8(read(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn exists<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    if package().is_some_and(|p| p.contains(path)) {
+        return true;
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if find_on_disk(path).is_some() {
+        return true;
+    }
+    embedded(path).is_some()
+}
@@ -0,0 +1,280 @@
+//! Playing the sound effects in `sfx/` that are heard in the world
+//!
+//! Sounds are mixed as the output device asks for more samples, through `cpal`.
+//! Without a device to play on, or in the browser, the sounds are only shown as captions.
+
+use std::{collections::HashMap, f32::consts::TAU, sync::{Arc, mpsc::Sender}};
+
+use log::warn;
+
+use crate::{assets, sound::Heard};
+
+/// Samples per second of each channel of what's played
+pub const SAMPLE_RATE: u32 = 22_050;
+/// Frequency above which sounds coming through walls are cut off
+const MUFFLED_CUTOFF: f32 = 700.;
+
+/// The samples of a sound effect, mono at `SAMPLE_RATE` from -1 to 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clip(Vec<f32>);
+
+impl Clip {
+    /// Reads an uncompressed 8 or 16 bit WAV file, mixing it down to mono and resampling it to `SAMPLE_RATE`
+    pub fn from_wav(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+            return Err("not a WAV file".to_owned());
+        }
+        // Channels, sample rate and bits per sample
+        let mut format = None;
+        let mut rest = &bytes[12..];
+        while rest.len() >= 8 {
+            let (id, size) = (&rest[0..4], u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize);
+            let body = rest.get(8..8 + size).ok_or("a chunk is cut off")?;
+            match id {
+                b"fmt " if body.len() >= 16 => {
+                    let u16_at = |i: usize| u16::from_le_bytes([body[i], body[i + 1]]);
+                    if u16_at(0) != 1 {
+                        return Err("only uncompressed samples can be read".to_owned());
+                    }
+                    let rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+                    format = Some((u16_at(2) as usize, rate, u16_at(14)));
+                }
+                b"data" => {
+                    let (channels, rate, bits) = format.filter(|&(c, r, _)| c > 0 && r > 0).ok_or("there's no valid format before the samples")?;
+                    let samples: Vec<f32> = match bits {
+                        8 => body.iter().map(|&s| (s as f32 - 128.) / 128.).collect(),
+                        16 => body.chunks_exact(2).map(|s| i16::from_le_bytes([s[0], s[1]]) as f32 / 32768.).collect(),
+                        _ => return Err(format!("{bits} bit samples can't be read")),
+                    };
+                    let mono: Vec<f32> = samples.chunks_exact(channels).map(|frame| frame.iter().sum::<f32>() / channels as f32).collect();
+                    let len = mono.len() as u64 * SAMPLE_RATE as u64 / rate as u64;
+                    return Ok(Clip((0..len).map(|i| mono[(i * rate as u64 / SAMPLE_RATE as u64) as usize]).collect()));
+                }
+                _ => (),
+            }
+            // Chunks are padded to an even length
+            rest = rest.get(8 + size + size % 2..).unwrap_or_default();
+        }
+        Err("there are no samples".to_owned())
+    }
+}
+
+/// A clip being played
+#[derive(Debug)]
+pub struct Voice {
+    clip: Arc<Clip>,
+    /// Index of the next sample to be played
+    at: usize,
+    /// Volume in the left and the right channel
+    gains: [f32; 2],
+    /// The last sample of a muffled clip after its high frequencies were cut off, `None` if it isn't muffled
+    low_pass: Option<f32>,
+}
+
+impl Voice {
+    /// Plays `clip` the way it's `heard` at a master volume of `volume`
+    pub fn new(clip: Arc<Clip>, heard: &Heard, volume: f32) -> Self {
+        let volume = volume * heard.volume;
+        let pan = heard.pan.clamp(-1., 1.);
+        // The side the sound is on is as loud as it is, the other side gets quieter
+        let gains = [volume * (1. - pan).min(1.), volume * (1. + pan).min(1.)];
+        Voice { clip, at: 0, gains, low_pass: heard.muffled.then_some(0.) }
+    }
+}
+
+/// Adds together the clips being played
+#[derive(Debug, Default)]
+pub struct Mixer {
+    voices: Vec<Voice>,
+}
+
+impl Mixer {
+    pub fn play(&mut self, voice: Voice) {
+        self.voices.push(voice);
+    }
+    /// Fills `out` with frames of a left and a right sample, letting go of the clips that have finished
+    pub fn mix(&mut self, out: &mut [f32]) {
+        // How much of the way a muffled sample moves towards the next one, which smooths out high frequencies
+        let smoothing = 1. - (-TAU * MUFFLED_CUTOFF / SAMPLE_RATE as f32).exp();
+        out.fill(0.);
+        for voice in &mut self.voices {
+            let samples = &voice.clip.0[voice.at..];
+            for (frame, &s) in out.chunks_exact_mut(2).zip(samples) {
+                let s = match &mut voice.low_pass {
+                    Some(last) => {
+                        *last += smoothing * (s - *last);
+                        *last
+                    }
+                    None => s,
+                };
+                frame[0] += voice.gains[0] * s;
+                frame[1] += voice.gains[1] * s;
+            }
+            voice.at += samples.len().min(out.len() / 2);
+        }
+        self.voices.retain(|voice| voice.at < voice.clip.0.len());
+    }
+}
+
+/// Where sounds are sent to be played
+pub struct Audio {
+    /// `None` when there's nothing to play sounds with
+    sender: Option<Sender<Voice>>,
+    /// The sounds are played for as long as this is kept
+    _stream: Option<output::Stream>,
+    /// Clips by their file name, `None` for the ones that couldn't be read
+    clips: HashMap<String, Option<Arc<Clip>>>,
+}
+
+impl Audio {
+    pub fn start() -> Self {
+        let (sender, stream) = output::start().unzip();
+        Audio { sender, _stream: stream, clips: HashMap::new() }
+    }
+    /// Plays `sfx/{file_name}` the way it's `heard`, with the master volume at `volume` from 0 to 1
+    pub fn play(&mut self, file_name: &str, heard: &Heard, volume: f32) {
+        let Some(sender) = &self.sender else { return };
+        if volume * heard.volume <= 0. {
+            return;
+        }
+        let clip = self.clips.entry(file_name.to_owned()).or_insert_with(|| {
+            let path = format!("sfx/{file_name}");
+            match assets::read(&path).map_err(|e| e.to_string()).and_then(|bytes| Clip::from_wav(&bytes)) {
+                Ok(clip) => Some(Arc::new(clip)),
+                Err(e) => {
+                    warn!("could not read {path}: {e}");
+                    None
+                }
+            }
+        });
+        if let Some(clip) = clip {
+            if sender.send(Voice::new(clip.clone(), heard, volume)).is_err() {
+                warn!("sound stopped playing");
+                self.sender = None;
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod output {
+    use std::sync::mpsc::{self, Sender};
+
+    use cpal::{traits::{DeviceTrait, HostTrait, StreamTrait}, BufferSize, SampleRate, StreamConfig};
+    use log::{info, warn};
+
+    use super::{Mixer, Voice, SAMPLE_RATE};
+
+    pub(super) use cpal::Stream;
+
+    /// Opens the default output device with a stream mixing the clips sent to it, unless there's no device to play on
+    pub(super) fn start() -> Option<(Sender<Voice>, Stream)> {
+        let Some(device) = cpal::default_host().default_output_device() else {
+            info!("playing no sound, there is no output device");
+            return None;
+        };
+        let config = StreamConfig { channels: 2, sample_rate: SampleRate(SAMPLE_RATE), buffer_size: BufferSize::Default };
+
+        let (sender, receiver) = mpsc::channel();
+        let mut mixer = Mixer::default();
+        let stream = device.build_output_stream(&config, move |out: &mut [f32], _: &_| {
+            // Once the game is over the stream is dropped along with it, so there's no need to tell that apart from no new voices
+            while let Ok(voice) = receiver.try_recv() {
+                mixer.play(voice);
+            }
+            mixer.mix(out);
+        }, |e| warn!("sound stopped playing: {e}"), None)
+            .map_err(|e| info!("playing no sound, the output device can't be played on: {e}"))
+            .ok()?;
+        stream.play()
+            .map_err(|e| info!("playing no sound, the output device can't be played on: {e}"))
+            .ok()?;
+        Some((sender, stream))
+    }
+}
+
+/// The browser has no threads to mix on
+#[cfg(target_arch = "wasm32")]
+mod output {
+    use std::sync::mpsc::Sender;
+
+    use super::Voice;
+
+    pub(super) type Stream = ();
+
+    pub(super) fn start() -> Option<(Sender<Voice>, Stream)> {
+        None
+    }
+}
+
+#[test]
+fn wav_files_are_mixed_down_and_resampled() {
+    // Two channels of 16 bit samples at twice the sample rate
+    let samples: [[i16; 2]; 4] = [[16384, 0], [16384, 0], [-16384, -16384], [-16384, -16384]];
+    let data: Vec<u8> = samples.iter().flatten().flat_map(|s| s.to_le_bytes()).collect();
+    let mut wav = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+    wav.extend(16u32.to_le_bytes());
+    wav.extend(1u16.to_le_bytes());
+    wav.extend(2u16.to_le_bytes());
+    wav.extend((2 * SAMPLE_RATE).to_le_bytes());
+    wav.extend((8 * SAMPLE_RATE).to_le_bytes());
+    wav.extend(4u16.to_le_bytes());
+    wav.extend(16u16.to_le_bytes());
+    wav.extend(b"data");
+    wav.extend((data.len() as u32).to_le_bytes());
+    wav.extend(data);
+
+    assert_eq!(Clip::from_wav(&wav), Ok(Clip(vec![0.25, -0.5])));
+    assert!(Clip::from_wav(b"RIFF\0\0\0\0WAVE").is_err());
+}
+
+#[test]
+fn clips_are_added_together_until_they_finish() {
+    use crate::sound::Sound;
+
+    let ahead = Heard { sound: Sound::Splash, volume: 1., pan: 0., front: 1., muffled: false };
+    let mut mixer = Mixer::default();
+    mixer.play(Voice::new(Arc::new(Clip(vec![0.5; 3])), &ahead, 1.));
+    mixer.play(Voice::new(Arc::new(Clip(vec![0.5; 1])), &ahead, 0.5));
+    let mut out = [0.; 4];
+    mixer.mix(&mut out);
+    assert_eq!(out, [0.75, 0.75, 0.5, 0.5]);
+    mixer.mix(&mut out);
+    assert_eq!(out, [0.5, 0.5, 0., 0.]);
+    assert!(mixer.voices.is_empty());
+}
+
+#[test]
+fn sounds_are_panned_and_muffled() {
+    use crate::sound::Sound;
+
+    let clip = Arc::new(Clip(vec![1.; 4]));
+    let mut mixer = Mixer::default();
+    mixer.play(Voice::new(clip.clone(), &Heard { sound: Sound::Splash, volume: 1., pan: -1., front: 0., muffled: false }, 1.));
+    let mut out = [0.; 2];
+    mixer.mix(&mut out);
+    assert_eq!(out, [1., 0.]);
+
+    // Cutting off the high frequencies makes the sudden start of the clip come in gradually
+    let mut mixer = Mixer::default();
+    mixer.play(Voice::new(clip, &Heard { sound: Sound::Splash, volume: 1., pan: 0., front: 1., muffled: true }, 1.));
+    let mut out = [0.; 8];
+    mixer.mix(&mut out);
+    assert!(out[0] > 0. && out[0] < out[2] && out[2] < out[4] && out[6] < 1.);
+}
+
+#[test]
+fn every_sound_has_a_clip() {
+    use crate::{map::Surface, sound::Sound, world::doors::DoorChange};
+
+    let surfaces = [Surface::Stone, Surface::Metal, Surface::Wood, Surface::Water, Surface::Ice];
+    let doors = [DoorChange::Opening, DoorChange::Closing, DoorChange::Blocked, DoorChange::Locked];
+    let sounds = surfaces.map(Sound::Footstep).into_iter()
+        .chain([Sound::Splash, Sound::Sizzle, Sound::Explosion, Sound::Gunshot])
+        .chain(doors.map(|change| Sound::Door(change, None)));
+    for sound in sounds {
+        let path = format!("sfx/{}", sound.file_name());
+        let bytes = assets::read(&path).unwrap_or_else(|e| panic!("{path}: {e}"));
+        assert!(Clip::from_wav(&bytes).is_ok_and(|clip| !clip.0.is_empty()), "{path} can't be played");
+    }
+}
@@ -0,0 +1,188 @@
+//! Order of the levels and which one is being played
+//!
+//! The levels are listed in `episodes.toml` (which a package can have its own of):
+//!
+//! ```toml
+//! [escape]
+//! name = "Escape from the Castle"
+//! levels = ["maps/e1m1.txt", "maps/e1m2.txt", "maps/e1m3.txt"]
+//!
+//! # The level that the secret exit of a level leads to
+//! [escape.secret_exits]
+//! "maps/e1m2.txt" = "maps/e1secret.txt"
+//! ```
+//!
+//! Leaving a secret level goes on to the level after the one with the secret exit.
+//! A package without `episodes.toml` is played as one episode of the levels in its manifest.
+
+use std::{collections::HashMap, path::Path};
+
+use log::info;
+
+use crate::{assets, map::Exit, settings::{parse_toml, Value}};
+
+pub const EPISODES_FILE: &str = "episodes.toml";
+
+#[derive(Debug, Clone, Default)]
+pub struct Episode {
+    /// Name of the table the episode is in
+    pub id: String,
+    pub name: String,
+    pub levels: Vec<String>,
+    secret_exits: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Campaign {
+    pub episodes: Vec<Episode>,
+    episode: usize,
+    level: usize,
+    /// Path of the secret level being played
+    secret_level: Option<String>,
+}
+
+impl Campaign {
+    /// Loads the episodes of the mounted package or the game
+    pub fn load() -> Self {
+        Self::try_load().unwrap_or_else(|e| panic!("{e}"))
+    }
+    /// Like `load`, with the problem with the episodes as an error
+    pub fn try_load() -> Result<Self, String> {
+        let package = assets::package();
+        let episodes = if package.is_some_and(|p| !p.contains(Path::new(EPISODES_FILE))) {
+            let package = package.unwrap();
+            vec![Episode {
+                id: package.name.clone(),
+                name: package.name.clone(),
+                levels: package.levels.clone(),
+                secret_exits: HashMap::new(),
+            }]
+        } else {
+            let src = assets::read_to_string(EPISODES_FILE).map_err(|e| format!("could not read {EPISODES_FILE}: {e}"))?;
+            parse_episodes(&src).map_err(|e| format!("invalid {EPISODES_FILE}: {e}"))?
+        };
+        if episodes.iter().all(|e| e.levels.is_empty()) {
+            return Err("there are no levels to play".to_owned());
+        }
+
+        let mut campaign = Campaign {
+            episodes,
+            episode: 0,
+            level: 0,
+            secret_level: None,
+        };
+        campaign.skip_empty_episodes();
+        Ok(campaign)
+    }
+    fn skip_empty_episodes(&mut self) {
+        while self.episodes.get(self.episode).is_some_and(|e| self.level >= e.levels.len()) {
+            self.episode += 1;
+            self.level = 0;
+        }
+    }
+    pub fn episode(&self) -> &Episode {
+        &self.episodes[self.episode]
+    }
+    /// Path of the map being played
+    pub fn current_level(&self) -> &str {
+        self.secret_level.as_deref().unwrap_or_else(|| &self.episode().levels[self.level])
+    }
+    /// Identifies the current level across packages, for keeping statistics
+    pub fn level_key(&self) -> String {
+        match assets::package() {
+            Some(package) => format!("{}/{}", package.name, self.current_level()),
+            None => self.current_level().to_owned(),
+        }
+    }
+    /// Whether the current level is reached through a secret exit
+    pub fn in_secret_level(&self) -> bool {
+        self.secret_level.is_some()
+    }
+    /// Moves on from the current level to the next one
+    ///
+    /// Returns the path of the next map, or `None` when the last episode has been finished.
+    pub fn advance(&mut self, exit: Exit) -> Option<&str> {
+        let finished = self.current_level().to_owned();
+
+        let secret = match exit {
+            Exit::Secret if self.secret_level.is_none() => self.episode().secret_exits.get(&finished).cloned(),
+            _ => None,
+        };
+        match secret {
+            Some(secret) => self.secret_level = Some(secret),
+            None => {
+                self.secret_level = None;
+                self.level += 1;
+                if self.level == self.episode().levels.len() {
+                    info!("finished episode {}", self.episode().name);
+                }
+                self.skip_empty_episodes();
+            }
+        }
+
+        (self.episode < self.episodes.len()).then(|| self.current_level())
+    }
+    /// Goes back to the first level of the first episode for a new game, returning its path
+    pub fn start_over(&mut self) -> &str {
+        self.episode = 0;
+        self.level = 0;
+        self.secret_level = None;
+        self.skip_empty_episodes();
+        self.current_level()
+    }
+}
+
+fn parse_episodes(src: &str) -> Result<Vec<Episode>, String> {
+    let mut episodes: Vec<Episode> = Vec::new();
+    parse_toml(src, |table, key, value| {
+        let (id, sub) = table.split_once('.').unwrap_or((table, ""));
+        if id.is_empty() {
+            return Err(format!("{key} has to be in an episode table"));
+        }
+        let i = match episodes.iter().position(|e| e.id == id) {
+            Some(i) => i,
+            None => {
+                episodes.push(Episode { id: id.to_owned(), name: id.to_owned(), ..Episode::default() });
+                episodes.len() - 1
+            }
+        };
+        let episode = &mut episodes[i];
+
+        match (sub, key, value) {
+            ("", "name", Value::Str(name)) => episode.name = name,
+            ("", "levels", Value::Array(levels)) => episode.levels = levels,
+            ("secret_exits", level, Value::Str(secret)) => {
+                episode.secret_exits.insert(level.to_owned(), secret);
+            }
+            (_, key, value) => return Err(format!("invalid episode entry {key} = {value:?}")),
+        }
+        Ok(())
+    })?;
+
+    for episode in &episodes {
+        if let Some(level) = episode.secret_exits.keys().find(|l| !episode.levels.contains(l)) {
+            return Err(format!("{level} has a secret exit but isn't in episode {}", episode.id));
+        }
+    }
+    Ok(episodes)
+}
+
+#[test]
+fn secret_exit_returns_to_next_level() {
+    let episodes = parse_episodes(r#"
+        [one]
+        levels = ["a.txt", "b.txt", "c.txt"]
+        [one.secret_exits]
+        "a.txt" = "secret.txt"
+        [two]
+        levels = ["d.txt"]
+    "#).unwrap();
+    let mut campaign = Campaign { episodes, episode: 0, level: 0, secret_level: None };
+
+    assert_eq!(campaign.advance(Exit::Secret), Some("secret.txt"));
+    assert_eq!(campaign.advance(Exit::Secret), Some("b.txt"));
+    assert_eq!(campaign.advance(Exit::Secret), Some("c.txt"));
+    assert_eq!(campaign.advance(Exit::Normal), Some("d.txt"));
+    assert_eq!(campaign.advance(Exit::Normal), None);
+    assert_eq!(campaign.start_over(), "a.txt");
+}
@@ -0,0 +1,97 @@
+//! Cheat codes typed while playing, which run the same commands as the console
+
+use winit::event::VirtualKeyCode;
+
+/// What to type and the console commands it runs
+const CHEATS: [(&str, &[&str]); 4] = [
+    ("mlid", &["give ammo 99", "give health 100", "give armor 100"]),
+    ("iddqd", &["god"]),
+    ("iddt", &["reveal"]),
+    ("wall", &["noclip"]),
+];
+/// Characters kept of what's been typed, enough for the longest code
+const MAX_TYPED: usize = 8;
+
+/// What a typed character did to the cheat codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Typed {
+    /// It isn't part of any code
+    Other,
+    /// It's part of the way through a code
+    Partway,
+    /// It finished a code, which runs these commands
+    Cheat(&'static [&'static str]),
+}
+
+impl Typed {
+    /// Whether the key it was typed with went to a code, so it shouldn't do anything else
+    pub fn taken(self) -> bool {
+        self != Typed::Other
+    }
+}
+
+/// Watches what's typed for cheat codes
+#[derive(Debug, Default)]
+pub struct Cheats {
+    typed: String,
+}
+
+impl Cheats {
+    /// Adds a typed character, telling whether it finished a cheat code or got part of the way through one
+    pub fn type_char(&mut self, c: char) -> Typed {
+        if !c.is_ascii_alphabetic() {
+            self.typed.clear();
+            return Typed::Other;
+        }
+        if self.typed.len() == MAX_TYPED {
+            self.typed.remove(0);
+        }
+        self.typed.push(c.to_ascii_lowercase());
+        if let Some((_, commands)) = CHEATS.iter().find(|(code, _)| self.typed.ends_with(code)) {
+            self.typed.clear();
+            return Typed::Cheat(commands);
+        }
+        let partway = CHEATS.iter().any(|(code, _)| (1..code.len()).any(|n| self.typed.ends_with(&code[..n])));
+        if partway { Typed::Partway } else { Typed::Other }
+    }
+}
+
+/// The letter key a character is typed with
+pub fn letter_key(c: char) -> Option<VirtualKeyCode> {
+    use VirtualKeyCode::*;
+    const LETTERS: [VirtualKeyCode; 26] = [A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z];
+    c.is_ascii_alphabetic().then(|| LETTERS[(c.to_ascii_lowercase() as u8 - b'a') as usize])
+}
+
+#[test]
+fn codes_are_found_in_what_is_typed() {
+    let mut cheats = Cheats::default();
+    let mut typed = |text: &str| text.chars().filter_map(|c| match cheats.type_char(c) {
+        Typed::Cheat(commands) => Some(commands),
+        _ => None,
+    }).last();
+    assert_eq!(typed("wasdwall"), Some(&["noclip"][..]));
+    assert_eq!(typed("IDDQD"), Some(&["god"][..]));
+    assert_eq!(typed("iddt"), Some(&["reveal"][..]));
+    // Anything else in between starts over
+    assert_eq!(typed("ml id"), None);
+    assert_eq!(typed("mli"), None);
+    assert_eq!(cheats.type_char('x'), Typed::Other);
+    assert_eq!(cheats.type_char('w'), Typed::Partway);
+}
+
+#[test]
+fn typing_a_code_toggles_no_views() {
+    use crate::settings::{Action, Bindings, Button};
+
+    let bindings = Bindings::default();
+    for (code, _) in CHEATS {
+        let mut cheats = Cheats::default();
+        let pressed: Vec<_> = code.chars().filter_map(letter_key).map(Button::Key).collect();
+        let taken: Vec<_> = code.chars().filter(|&c| cheats.type_char(c).taken()).filter_map(letter_key).map(Button::Key).collect();
+        for action in [Action::TopDown, Action::Tactical, Action::Inventory, Action::MessageLog, Action::Objectives] {
+            let toggled = bindings.buttons(action).iter().any(|b| pressed.contains(b) && !taken.contains(b));
+            assert!(!toggled, "typing {code} presses {}", action.name());
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::VecDeque;
+
+use crate::{assets, font, hud, profiles::Profile, lang::{tr, tr_args}, settings::Settings, tex::{Colour, Frame}, vec::Point2, world::{World, daylight}};
+
+/// How many lines of output are kept
+const HISTORY: usize = 12;
+const MAX_INPUT: usize = 64;
+
+const TEXT_COLOUR: Colour = Colour::new(0xe0, 0xe0, 0xe0);
+const INPUT_COLOUR: Colour = Colour::new(0xff, 0xff, 0x60);
+
+/// Drop-down console for debug commands
+#[derive(Debug, Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    output: VecDeque<String>,
+}
+
+impl Console {
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+    pub fn type_char(&mut self, c: char) {
+        // The key opening the console types a backtick too
+        // Only ASCII can be drawn by the font
+        if (c.is_ascii_graphic() || c == ' ') && c != '`' && self.input.len() < MAX_INPUT {
+            self.input.push(c);
+        }
+    }
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+    pub fn print(&mut self, line: impl Into<String>) {
+        if self.output.len() == HISTORY {
+            self.output.pop_front();
+        }
+        self.output.push_back(line.into());
+    }
+    /// Runs the command that has been typed in
+    pub fn submit(&mut self, world: &mut World, profile: &Profile, settings: &Settings) {
+        let line = std::mem::take(&mut self.input);
+        if line.trim().is_empty() {
+            return;
+        }
+        self.print(format!("> {line}"));
+        match run_command(&line, world, profile, settings) {
+            Ok(msg) | Err(msg) => self.print(msg),
+        }
+    }
+    pub fn draw(&self, frame: &mut Frame) {
+        if !self.open {
+            return;
+        }
+        let height = (HISTORY as u32 + 1) * font::LINE_HEIGHT + 4;
+        hud::draw_panel(frame, 0, 0, frame.width(), height);
+
+        for (i, line) in self.output.iter().enumerate() {
+            font::draw_text(frame, 2, 2 + i as u32 * font::LINE_HEIGHT, line, TEXT_COLOUR);
+        }
+        let input_y = 2 + HISTORY as u32 * font::LINE_HEIGHT;
+        // Only the end of the input is shown if it's too long to fit
+        let fits = (frame.width() / font::ADVANCE).saturating_sub(3) as usize;
+        let shown = &self.input[self.input.len().saturating_sub(fits)..];
+        font::draw_text(frame, 2, input_y, &format!("> {shown}_"), INPUT_COLOUR);
+    }
+}
+
+/// `what` is the key of the text describing the argument
+fn parse<T: std::str::FromStr>(arg: Option<&str>, what: &str) -> Result<T, String> {
+    let what = tr(what);
+    let arg = arg.ok_or_else(|| tr_args("console.missing", &[("what", &what)]))?;
+    arg.parse().map_err(|_| tr_args("console.invalid", &[("arg", &arg), ("what", &what)]))
+}
+
+fn on_off(on: bool) -> String {
+    tr(if on { "console.on" } else { "console.off" })
+}
+
+/// Runs a command on the world, returning the message to show
+pub fn run_command(line: &str, world: &mut World, profile: &Profile, settings: &Settings) -> Result<String, String> {
+    let mut args = line.split_whitespace();
+    let cmd = args.next().unwrap_or_default();
+
+    match cmd {
+        "god" => {
+            world.god = !world.god;
+            Ok(tr_args("console.god", &[("state", &on_off(world.god))]))
+        }
+        "noclip" => {
+            world.clip = !world.clip;
+            Ok(tr_args("console.noclip", &[("state", &on_off(!world.clip))]))
+        }
+        "reveal" => {
+            world.reveal_map();
+            Ok(tr("console.revealed"))
+        }
+        "give" => {
+            let item = args.next().ok_or_else(|| tr("console.give_what"))?;
+            let amount: u32 = parse(args.next(), "console.amount")?;
+            match item {
+                "ammo" => world.ammo = world.ammo.saturating_add(amount),
+                "health" => world.player_health += amount as f32,
+                "armor" => world.player_armor += amount as f32,
+                _ => return Err(tr_args("console.unknown_item", &[("item", &item)])),
+            }
+            Ok(tr_args("console.gave", &[("amount", &amount), ("item", &item)]))
+        }
+        "map" => {
+            let name = args.next().ok_or_else(|| tr("console.which_map"))?;
+            let path = [format!("{name}.txt"), format!("maps/{name}.txt"), name.to_owned()]
+                .into_iter()
+                .find(|p| assets::exists(p))
+                .ok_or_else(|| tr_args("console.no_map", &[("name", &name)]))?;
+            world.load_map(&path, settings).map_err(|e| tr_args("console.map_failed", &[("name", &name), ("error", &e)]))?;
+            Ok(tr_args("console.loaded", &[("name", &world.map.name)]))
+        }
+        "tp" => {
+            let x = parse(args.next(), "console.x_coordinate")?;
+            let y = parse(args.next(), "console.y_coordinate")?;
+            world.teleport(Point2::new(x, y));
+            Ok(tr_args("console.teleported", &[("x", &x), ("y", &y)]))
+        }
+        "set" => {
+            let setting = args.next().ok_or_else(|| tr("console.set_what"))?;
+            match setting {
+                "fov" => {
+                    let fov: f32 = parse(args.next(), "console.fov")?;
+                    if !(1. ..180.).contains(&fov) {
+                        return Err(tr("console.fov_range"));
+                    }
+                    world.fov.set_degrees(fov);
+                    Ok(tr_args("console.fov_set", &[("fov", &fov)]))
+                }
+                "time" => {
+                    let time = args.next();
+                    let hour = match daylight::NAMED_HOURS.iter().find(|&&(name, _)| Some(name) == time) {
+                        Some(&(_, hour)) => hour,
+                        None => parse(time, "console.time")?,
+                    };
+                    if !world.set_time_of_day(hour) {
+                        return Err(tr("console.no_time_of_day"));
+                    }
+                    Ok(tr_args("console.time_set", &[("time", &time.unwrap_or_default())]))
+                }
+                _ => Err(tr_args("console.unknown_setting", &[("setting", &setting)])),
+            }
+        }
+        "stats" => {
+            let stats = &profile.stats;
+            Ok(tr_args("console.stats", &[
+                ("profile", &profile.name),
+                ("levels", &stats.completed.len()),
+                ("kills", &(stats.kills + world.kills)),
+                ("secrets", &stats.secrets.len()),
+            ]))
+        }
+        _ => Err(tr_args("console.unknown_command", &[("command", &cmd)])),
+    }
+}
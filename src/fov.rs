@@ -1,34 +1,151 @@
 use log::info;
 
-use super::{WIDTH, HEIGHT};
+use crate::vec::Vector2;
 
+/// Smallest and largest FOV in degrees that still make a sensible projection
+const MIN_DEGREES: f32 = 1.;
+const MAX_DEGREES: f32 = 179.;
+
+/// Projection of the view onto a flat camera plane one unit in front of the camera
+///
+/// Rays are cast from the camera through evenly spaced points along the plane,
+/// so straight walls stay straight and the projected heights only depend on the depth.
 #[derive(Debug, Copy, Clone)]
 pub struct Fov {
     pub fov: f32,
     pub fov_vert: f32,
-    /// Tangent of half of the FOV (used for finding the coordinates of the first ray)
+    /// Tangent of half of the FOV, which is how far the camera plane goes out to each side
     pub tan_half_fov: f32,
-    /// Projected height of wall with height of 1 at distance of 1
+    /// Projected height of wall with height of 1 at a depth of 1
     pub height_coefficient: f32,
+    /// The FOV in degrees as it was set, so stepping it back and forth doesn't drift
+    degrees: f32,
+    /// Size of the image in pixels the FOV is projected onto
+    width: u32,
+    height: u32,
 }
 
 impl Fov {
-    pub fn new_from_degrees(fov_deg: f32) -> Self {
+    pub fn new_from_degrees(fov_deg: f32, width: u32, height: u32) -> Self {
         let fov = fov_deg.to_radians();
-        let fov_vert = 2. * (HEIGHT as f32 / WIDTH as f32 * (0.5 * fov).sin()).atan();
         let tan_half_fov = (0.5 * fov).tan();
-        // Happens to also be the same as the distance to the projection plane
-        let height_coefficient = 0.5 * WIDTH as f32 / (0.5 * fov).sin();
+        // Also the distance in pixels from the camera to the projection plane
+        let height_coefficient = 0.5 * width as f32 / tan_half_fov;
+        // Pixels are square, so the vertical FOV follows from the same distance
+        let fov_vert = 2. * (0.5 * height as f32 / height_coefficient).atan();
 
         Fov {
             fov,
             fov_vert,
             tan_half_fov,
             height_coefficient,
+            degrees: fov_deg,
+            width,
+            height,
         }
     }
+    pub fn degrees(&self) -> f32 {
+        self.degrees
+    }
+    pub fn set_degrees(&mut self, fov_deg: f32) {
+        *self = Self::new_from_degrees(fov_deg, self.width, self.height);
+    }
     pub fn change_fov(&mut self, deg_diff: f32) {
-        *self = Self::new_from_degrees(self.fov.to_degrees() + deg_diff);
+        self.set_degrees((self.degrees + deg_diff).clamp(MIN_DEGREES, MAX_DEGREES));
         info!("fov: {:.0} - {:.0}", self.fov.to_degrees(), self.fov_vert.to_degrees());
     }
+
+    /// The FOV `amount` (from 0 to 1) of the way towards `zoom_deg` along an ease-in-out curve
+    pub fn zoomed(&self, zoom_deg: f32, amount: f32) -> Self {
+        if amount <= 0. {
+            return *self;
+        }
+        let amount = amount.min(1.);
+        let eased = amount * amount * (3. - 2. * amount);
+        Self {
+            degrees: self.degrees,
+            ..Self::new_from_degrees(self.degrees + eased * (zoom_deg - self.degrees), self.width, self.height)
+        }
+    }
+
+    /// The same FOV projected onto an image of another size
+    pub fn for_size(&self, width: u32, height: u32) -> Self {
+        if (width, height) == (self.width, self.height) {
+            return *self;
+        }
+        Self { degrees: self.degrees, ..Self::new_from_degrees(self.fov.to_degrees(), width, height) }
+    }
+    /// Vector from the middle to the right edge of the camera plane when looking along the unit vector `dir`
+    pub fn camera_plane(&self, dir: Vector2) -> Vector2 {
+        self.tan_half_fov * dir.hat()
+    }
+    /// Ray through the middle of column `x` when looking along the unit vector `dir`
+    ///
+    /// The ray goes from the camera to the camera plane, so its length along `dir` is always 1
+    pub fn column_ray(&self, dir: Vector2, x: u32) -> Vector2 {
+        let plane_x = 2. * (x as f32 + 0.5) / self.width as f32 - 1.;
+        dir + plane_x * self.camera_plane(dir)
+    }
+    /// Height on screen of something with a height of 1 at `depth` in front of the camera plane
+    pub fn projected_height(&self, depth: f32) -> f32 {
+        self.height_coefficient / depth
+    }
+}
+
+#[test]
+fn projected_wall_heights() {
+    // At 90 degrees the camera plane is as wide as it is far away
+    let fov = Fov::new_from_degrees(90., 320, 240);
+    assert!((fov.height_coefficient - 160.).abs() < 1e-3);
+    assert!((fov.projected_height(2.) - 80.).abs() < 1e-3);
+    assert!((fov.fov_vert - 2. * 0.75f32.atan()).abs() < 1e-6);
+
+    // A wall straight across the view at a depth of 3 has the same height in every column
+    let fov = Fov::new_from_degrees(100., 320, 240);
+    let dir = Vector2::unit_from_angle(0.7);
+    let expected = 0.5 * 320. / 50f32.to_radians().tan() / 3.;
+    for x in [0, 1, 100, 159, 160, 250, 319] {
+        let ray = fov.column_ray(dir, x);
+        // How far the ray goes before hitting the wall and the correction for the angle it's at
+        let hit_dist = 3. * ray.norm() / ray.dot(dir);
+        let depth = hit_dist * ray.dot(dir) / ray.norm();
+        assert!((fov.projected_height(depth) - expected).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn column_rays_span_the_fov() {
+    let fov = Fov::new_from_degrees(120., 100, 100);
+    let dir = Vector2::unit_from_angle(0.);
+
+    // The outer columns are half a pixel inside the edges of the FOV and mirror each other
+    let (left, right) = (fov.column_ray(dir, 0), fov.column_ray(dir, 99));
+    assert!((right.y + left.y).abs() < 1e-5);
+    let edge_angle = (0.99 * 60f32.to_radians().tan()).atan();
+    assert!((right.y.atan2(right.x).abs() - edge_angle).abs() < 1e-5);
+}
+
+#[test]
+fn zoom_eases_between_fovs() {
+    let fov = Fov::new_from_degrees(90., 320, 240);
+    assert_eq!(fov.zoomed(30., 0.).fov, fov.fov);
+    assert!((fov.zoomed(30., 1.).fov.to_degrees() - 30.).abs() < 1e-4);
+    assert!((fov.zoomed(30., 0.5).fov.to_degrees() - 60.).abs() < 1e-4);
+    // Zooming doesn't change the FOV it goes back to
+    assert_eq!(fov.zoomed(30., 0.7).degrees(), 90.);
+}
+
+#[test]
+fn fov_changes_are_exact() {
+    let mut fov = Fov::new_from_degrees(65., 320, 240);
+    for _ in 0..7 {
+        fov.change_fov(5.);
+    }
+    for _ in 0..7 {
+        fov.change_fov(-5.);
+    }
+    assert_eq!(fov.degrees(), 65.);
+
+    fov.change_fov(-100.);
+    assert_eq!(fov.degrees(), MIN_DEGREES);
 }
@@ -0,0 +1,320 @@
+//! Drawing the walls, floors and ceilings on the GPU
+//!
+//! Only maps of plain opaque walls can be drawn like this. Doors, glass, mirrors, moving blocks and conveyors
+//! make `GpuWalls::prepare` turn it down, and then everything is drawn in software like without the feature.
+//! Things and everything on top of the view are still drawn in software into a see-through frame that the shader
+//! puts in front of the walls, so the translucent edges of things come out blended with black instead of the walls.
+
+use std::{collections::HashMap, num::NonZeroU32};
+
+use pixels::{wgpu, Pixels, PixelsContext};
+
+use crate::{map::Side, tex::{Colour, Texture}, vec::Vector2, world::{Camera, World}};
+
+/// Size every wall texture is resampled to
+const TEX_SIZE: u32 = 64;
+/// Sides of each cell in the order the shader expects their layers in
+const SIDES: [Side; 4] = [Side::Right, Side::Down, Side::Left, Side::Up];
+/// Holds the layer + 1 of the texture of each side of a cell
+const CELLS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Uint;
+/// Number of `f32`s in the uniforms of the shader
+const UNIFORMS_LEN: usize = 6 * 4;
+
+#[derive(Debug)]
+pub struct GpuWalls {
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+    uniforms: wgpu::Buffer,
+    cells: wgpu::Texture,
+    cells_size: (u32, u32),
+    /// Revision of the map the cells were uploaded from, or `None` if they have to be uploaded again
+    revision: Option<u32>,
+    /// Whether the walls of the map the cells were uploaded from can be drawn on the GPU
+    drawable: bool,
+    walls: wgpu::Texture,
+    /// What's in each layer of `walls` so only the ones that changed are uploaded again
+    layers: Vec<Texture>,
+    layer_capacity: u32,
+    /// The view is drawn here and copied into the frame texture, which the shader reads the software frame from
+    target: wgpu::Texture,
+}
+
+impl GpuWalls {
+    pub fn new(pixels: &Pixels) -> Self {
+        let context = pixels.context();
+        let device = &context.device;
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("walls shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("gpu/walls.wgsl").into()),
+        });
+        let texture_entry = |binding, sample_type, view_dimension| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture { sample_type, view_dimension, multisampled: false },
+            count: None,
+        };
+        let float = wgpu::TextureSampleType::Float { filterable: false };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("walls bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
+                    count: None,
+                },
+                texture_entry(1, wgpu::TextureSampleType::Uint, wgpu::TextureViewDimension::D2),
+                texture_entry(2, float, wgpu::TextureViewDimension::D2Array),
+                texture_entry(3, float, wgpu::TextureViewDimension::D2),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("walls pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("walls pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState { module: &shader, entry_point: "vs_main", buffers: &[] },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState { format: context.texture_format, blend: None, write_mask: wgpu::ColorWrites::ALL })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        let uniforms = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("walls uniforms"),
+            size: (UNIFORMS_LEN * 4) as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("walls target"),
+            size: context.texture_extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: context.texture_format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        });
+
+        GpuWalls {
+            pipeline,
+            layout,
+            uniforms,
+            cells: cells_texture(device, 1, 1),
+            cells_size: (1, 1),
+            revision: None,
+            drawable: false,
+            walls: walls_texture(device, 1),
+            layers: Vec::new(),
+            layer_capacity: 1,
+            target,
+        }
+    }
+    /// Uploads the view from `camera` and the map if it has changed, returning whether the walls can be drawn on the GPU
+    pub fn prepare(&mut self, pixels: &Pixels, world: &World, camera: &Camera) -> bool {
+        let context = pixels.context();
+        if self.revision != Some(world.map.revision()) {
+            self.drawable = self.upload_map(context, world);
+        }
+        if !self.drawable {
+            return false;
+        }
+
+        let uniforms = view_uniforms(context, world, camera);
+        let uniform_bytes: Vec<u8> = uniforms.iter().flat_map(|f| f.to_ne_bytes()).collect();
+        context.queue.write_buffer(&self.uniforms, 0, &uniform_bytes);
+        true
+    }
+    /// Uploads the cells of the map and the textures of their walls, returning whether they can be drawn on the GPU
+    fn upload_map(&mut self, context: &PixelsContext, world: &World) -> bool {
+        let map = &world.map;
+        // Animated walls change the cells as time goes by, so they're uploaded again every frame
+        self.revision = Some(map.revision());
+        // The shader doesn't know about conveyors or rays going around the map
+        if map.conveyor_tex().is_some() || map.wrap {
+            return false;
+        }
+
+        let (width, height) = (map.width() as u32, map.height() as u32);
+        let mut cells = Vec::with_capacity(width as usize * height as usize * 4);
+        let mut layer_of = HashMap::new();
+        let mut textures = Vec::new();
+        for y in 0..map.height() {
+            for x in 0..map.width() {
+                let mat = map.get(x, y).expect("cell to be inside the map");
+                match map.plain_cell(mat) {
+                    None => return false,
+                    Some(false) => cells.extend([0u16; 4]),
+                    Some(true) => {
+                        if map.is_animated(mat) {
+                            self.revision = None;
+                        }
+                        for side in SIDES {
+                            let tex = map.get_tex(mat, side, world.time, (x, y));
+                            let layer = *layer_of.entry(tex as *const Texture).or_insert_with(|| {
+                                textures.push(tex);
+                                textures.len() as u16
+                            });
+                            cells.push(layer);
+                        }
+                    }
+                }
+            }
+        }
+        let max_layers = context.device.limits().max_texture_array_layers;
+        if textures.len() > max_layers as usize {
+            return false;
+        }
+
+        if self.cells_size != (width, height) {
+            self.cells = cells_texture(&context.device, width, height);
+            self.cells_size = (width, height);
+        }
+        let cell_bytes: Vec<u8> = cells.iter().flat_map(|c| c.to_ne_bytes()).collect();
+        context.queue.write_texture(
+            self.cells.as_image_copy(),
+            &cell_bytes,
+            wgpu::ImageDataLayout { offset: 0, bytes_per_row: NonZeroU32::new(width * 8), rows_per_image: NonZeroU32::new(height) },
+            wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+        );
+
+        if textures.len() as u32 > self.layer_capacity {
+            self.layer_capacity = (textures.len() as u32).next_power_of_two().min(max_layers);
+            self.walls = walls_texture(&context.device, self.layer_capacity);
+            self.layers.clear();
+        }
+        for (i, &tex) in textures.iter().enumerate() {
+            if self.layers.get(i) == Some(tex) {
+                continue;
+            }
+            context.queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &self.walls,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x: 0, y: 0, z: i as u32 },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &resample(tex),
+                wgpu::ImageDataLayout { offset: 0, bytes_per_row: NonZeroU32::new(TEX_SIZE * 4), rows_per_image: NonZeroU32::new(TEX_SIZE) },
+                wgpu::Extent3d { width: TEX_SIZE, height: TEX_SIZE, depth_or_array_layers: 1 },
+            );
+            if i < self.layers.len() {
+                self.layers[i] = tex.clone();
+            } else {
+                self.layers.push(tex.clone());
+            }
+        }
+        true
+    }
+    /// Draws the walls prepared last with the frame on top of them and shows the result
+    pub fn render(&self, pixels: &Pixels) -> Result<(), pixels::Error> {
+        pixels.render_with(|encoder, render_target, context| {
+            let cells = self.cells.create_view(&wgpu::TextureViewDescriptor::default());
+            let walls = self.walls.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2Array),
+                ..Default::default()
+            });
+            let software = context.texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("walls bind group"),
+                layout: &self.layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: self.uniforms.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&cells) },
+                    wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&walls) },
+                    wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(&software) },
+                ],
+            });
+
+            let target = self.target.create_view(&wgpu::TextureViewDescriptor::default());
+            {
+                let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("walls pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &target,
+                        resolve_target: None,
+                        ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::BLACK), store: true },
+                    })],
+                    depth_stencil_attachment: None,
+                });
+                pass.set_pipeline(&self.pipeline);
+                pass.set_bind_group(0, &bind_group, &[]);
+                pass.draw(0..3, 0..1);
+            }
+            // The frame texture is what gets scaled to the window
+            encoder.copy_texture_to_texture(self.target.as_image_copy(), context.texture.as_image_copy(), context.texture_extent);
+            context.scaling_renderer.render(encoder, render_target);
+            Ok(())
+        })
+    }
+}
+
+fn cells_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("walls cells"),
+        size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: CELLS_FORMAT,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    })
+}
+
+fn walls_texture(device: &wgpu::Device, layers: u32) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("walls textures"),
+        size: wgpu::Extent3d { width: TEX_SIZE, height: TEX_SIZE, depth_or_array_layers: layers },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    })
+}
+
+/// The texels of a texture stretched or squashed to `TEX_SIZE` × `TEX_SIZE`
+fn resample(tex: &Texture) -> Vec<u8> {
+    let texel_centre = |i: u32| (i as f32 + 0.5) / TEX_SIZE as f32;
+    (0..TEX_SIZE)
+        .flat_map(|y| (0..TEX_SIZE).map(move |x| (x, y)))
+        .flat_map(|(x, y)| tex.get_pixel_f(texel_centre(x), texel_centre(y)).array())
+        .collect()
+}
+
+/// The uniforms laid out like `View` in the shader
+fn view_uniforms(context: &PixelsContext, world: &World, camera: &Camera) -> [f32; UNIFORMS_LEN] {
+    let fov = world.view_fov();
+    let dir = Vector2::unit_from_angle(camera.angle);
+    let plane = fov.camera_plane(dir);
+    let size = context.texture_extent;
+    // The shader can't count on infinity making it through
+    let max_dist = world.view_distance().min(f32::MAX);
+
+    let mut uniforms = [0.; UNIFORMS_LEN];
+    uniforms[..12].copy_from_slice(&[
+        camera.pos.x, camera.pos.y, dir.x, dir.y,
+        plane.x, plane.y, size.width as f32, size.height as f32,
+        fov.height_coefficient, camera.height, max_dist, 0.,
+    ]);
+    for (chunk, colour) in uniforms[12..].chunks_exact_mut(4).zip([world.map.fog_colour, world.sky_colour(), world.palette.floor]) {
+        chunk.copy_from_slice(&linear(colour));
+    }
+    uniforms
+}
+
+/// The colour in linear light, which is what the shader works in
+fn linear(c: Colour) -> [f32; 4] {
+    c.array().map(|c| {
+        let c = c as f32 / 255.;
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    })
+}
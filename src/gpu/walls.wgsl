@@ -0,0 +1,113 @@
+// Casts a ray through every pixel to draw the walls, floors and ceilings
+// like the software renderer does and puts the software frame (premultiplied) on top
+
+struct View {
+    // Position of the camera and the way it looks
+    pos_dir: vec4<f32>,
+    // Vector from the middle to the right edge of the camera plane, and the size of the frame
+    plane_size: vec4<f32>,
+    // Height coefficient of the FOV, eye height and how far rays are cast
+    projection: vec4<f32>,
+    fog: vec4<f32>,
+    ceiling: vec4<f32>,
+    floor: vec4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> view: View;
+// Texture layer + 1 of each side of every cell (right, down, left, up), 0 where there's no wall
+@group(0) @binding(1) var cells: texture_2d<u32>;
+@group(0) @binding(2) var walls: texture_2d_array<f32>;
+@group(0) @binding(3) var software: texture_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) i: u32) -> @builtin(position) vec4<f32> {
+    // One triangle covering the whole frame
+    let uv = vec2<f32>(f32((i << 1u) & 2u), f32(i & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// Side hit when moving along x or y in the positive direction or not, like `Side::along_x` and `along_y`
+fn side_along(axis: i32, positive: bool) -> i32 {
+    if axis == 0 {
+        return select(0, 2, positive);
+    }
+    return select(1, 3, positive);
+}
+
+// Where along the wall the ray hits it, the texture layer of the wall, its depth and 1,
+// or no wall at the depth where the fog starts and 0 if it doesn't hit any
+fn cast(ray: vec2<f32>) -> vec4<f32> {
+    let pos = view.pos_dir.xy;
+    let size = vec2<i32>(textureDimensions(cells));
+    let max_depth = view.projection.z / length(ray);
+
+    var cell = vec2<i32>(floor(pos));
+    let step = vec2<i32>(sign(ray));
+    // Rays straight along an axis never cross the other one
+    let delta = abs(1.0 / select(ray, vec2<f32>(1e-9), ray == vec2<f32>(0.0)));
+    var next = (sign(ray) * (vec2<f32>(cell) - pos) + sign(ray) * 0.5 + 0.5) * delta;
+
+    for (var i = 0; i < size.x + size.y + 2; i += 1) {
+        var depth: f32;
+        var axis: i32;
+        if next.x < next.y {
+            depth = next.x;
+            next.x += delta.x;
+            cell.x += step.x;
+            axis = 0;
+        } else {
+            depth = next.y;
+            next.y += delta.y;
+            cell.y += step.y;
+            axis = 1;
+        }
+        if depth > max_depth || any(cell < vec2<i32>(0)) || any(cell >= size) {
+            break;
+        }
+        let side = side_along(axis, ray[axis] > 0.0);
+        let layer = textureLoad(cells, cell, 0)[side];
+        if layer != 0u {
+            let p = pos + depth * ray;
+            var u: f32;
+            switch side {
+                case 0: { u = 1.0 - fract(p.y); }
+                case 1: { u = fract(p.x); }
+                case 2: { u = fract(p.y); }
+                default: { u = 1.0 - fract(p.x); }
+            }
+            return vec4<f32>(u, f32(layer - 1u), depth, 1.0);
+        }
+    }
+    return vec4<f32>(0.0, 0.0, max_depth, 0.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) frag: vec4<f32>) -> @location(0) vec4<f32> {
+    let size = view.plane_size.zw;
+    let plane_x = 2.0 * frag.x / size.x - 1.0;
+    let ray = view.pos_dir.zw + plane_x * view.plane_size.xy;
+    let hit = cast(ray);
+
+    // Same projection as the software renderer
+    let line_height = view.projection.x / hit.z;
+    let half_height = floor(size.y / 2.0);
+    let mat_top = half_height - (0.5 - view.projection.y) * line_height;
+    let mat_bot = half_height + (0.5 + view.projection.y) * line_height;
+
+    var colour: vec4<f32>;
+    if frag.y < mat_top {
+        colour = view.ceiling;
+    } else if frag.y >= mat_bot + 1.0 {
+        colour = view.floor;
+    } else if hit.w == 0.0 {
+        colour = view.fog;
+    } else {
+        let tex_size = vec2<i32>(textureDimensions(walls));
+        let v = clamp((frag.y - mat_top) / (mat_bot - mat_top), 0.0, 1.0);
+        let texel = min(vec2<i32>(vec2<f32>(hit.x, v) * vec2<f32>(tex_size)), tex_size - 1);
+        colour = textureLoad(walls, texel, i32(hit.y), 0);
+    }
+
+    let front = textureLoad(software, vec2<i32>(frag.xy), 0);
+    return vec4<f32>(colour.rgb * (1.0 - front.a) + front.rgb, 1.0);
+}
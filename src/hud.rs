@@ -0,0 +1,528 @@
+//! Text drawn over the view of the world
+
+use std::{collections::VecDeque, sync::OnceLock};
+
+use image::{Rgba, RgbaImage};
+
+use crate::{assets, font, lang, map::{Dialogue, Objective}, sound::Heard, tex::{Colour, TColour, Frame, Rect, Texture}, world::{talk::Talk, Camera, Kill, World}, render::{Renderer, top_down::TopDownRenderer}};
+
+const TIMER_COLOUR: Colour = Colour::new(0xff, 0xff, 0xff);
+const CAPTION_COLOUR: Colour = Colour::new(0xff, 0xff, 0x80);
+/// Seconds a caption stays on screen
+const CAPTION_TIME: f32 = 2.5;
+/// Most captions shown at once, the oldest go first
+const MAX_CAPTIONS: usize = 3;
+/// Sounds quieter than this aren't worth a caption
+const CAPTION_MIN_VOLUME: f32 = 0.02;
+const KILL_FEED_COLOUR: Colour = Colour::new(0xff, 0xc0, 0xc0);
+/// Seconds a kill stays in the feed
+const KILL_FEED_TIME: f32 = 4.;
+/// Most kills shown at once, the oldest go first
+const MAX_KILL_FEED: usize = 4;
+/// Size of the tactical view as a fraction of the frame height
+const TACTICAL_SIZE: f32 = 0.35;
+
+/// Formats seconds as `m:ss.cc`
+pub fn format_time(seconds: f32) -> String {
+    let centis = (seconds.max(0.) * 100.).round() as u32;
+    format!("{}:{:02}.{:02}", centis / 6000, centis / 100 % 60, centis % 100)
+}
+
+/// Formats the difference from a best time with a sign, so faster times are negative
+pub fn format_delta(seconds: f32) -> String {
+    let sign = if seconds < 0. { '-' } else { '+' };
+    format!("{sign}{:.2}", seconds.abs())
+}
+
+/// Draws the time left of a timed level at the top of the screen, turning red when it's nearly up
+pub fn draw_countdown(frame: &mut Frame, time_left: f32) {
+    const HURRY: f32 = 10.;
+    const HURRY_COLOUR: Colour = Colour::new(0xff, 0x40, 0x40);
+
+    let text = format_time(time_left);
+    let colour = if time_left < HURRY { HURRY_COLOUR } else { TIMER_COLOUR };
+    let x = frame.width().saturating_sub(font::text_width(&text)) / 2;
+    font::draw_text(frame, x, 2, &text, colour);
+}
+
+/// Draws how much health a boss has left as a bar at the top of the screen
+pub fn draw_boss_bar(frame: &mut Frame, health_left: f32) {
+    const HEIGHT: u32 = 4;
+    const TOP: i32 = 24;
+    const BACKGROUND: TColour = TColour::new(0x40, 0x00, 0x00, 0xc0);
+    const HEALTH: TColour = TColour::new(0xe0, 0x20, 0x20, 0xff);
+    const BORDER: TColour = TColour::new(0x00, 0x00, 0x00, 0xc0);
+
+    let width = frame.width() / 2;
+    let left = ((frame.width() - width) / 2) as i32;
+    let filled = (health_left.clamp(0., 1.) * width as f32).round() as u32;
+    frame.fill_rect(left + filled as i32, TOP, width - filled, HEIGHT, BACKGROUND);
+    frame.fill_rect(left, TOP, filled, HEIGHT, HEALTH);
+    frame.draw_rect(left - 1, TOP - 1, width + 2, HEIGHT + 2, BORDER);
+}
+
+/// Draws how much stamina is left for sprinting as a thin bar at the bottom of the screen
+pub fn draw_stamina(frame: &mut Frame, stamina: f32) {
+    const HEIGHT: u32 = 2;
+    const BOTTOM: u32 = 4;
+    const BACKGROUND: TColour = TColour::new(0x20, 0x20, 0x20, 0x80);
+    const STAMINA: TColour = TColour::new(0x40, 0xe0, 0x60, 0xc0);
+
+    let width = frame.width() / 4;
+    let left = ((frame.width() - width) / 2) as i32;
+    let top = frame.height().saturating_sub(BOTTOM + HEIGHT) as i32;
+    let filled = (stamina.clamp(0., 1.) * width as f32).round() as u32;
+    frame.fill_rect(left + filled as i32, top, width - filled, HEIGHT, BACKGROUND);
+    frame.fill_rect(left, top, filled, HEIGHT, STAMINA);
+}
+
+/// Draws how easily enemies can see and hear the player as two short bars above the stamina,
+/// visibility growing brighter and noise growing redder the more the player stands out
+pub fn draw_stealth(frame: &mut Frame, visibility: f32, noise: f32) {
+    const HEIGHT: u32 = 2;
+    const BOTTOM: u32 = 8;
+    const BACKGROUND: TColour = TColour::new(0x20, 0x20, 0x20, 0x80);
+
+    let width = frame.width() / 8;
+    let left = ((frame.width() - width) / 2) as i32;
+    for (i, amount) in [visibility, noise].into_iter().enumerate() {
+        let amount = amount.clamp(0., 1.);
+        let v = (0x60 as f32 + amount * 0x9f as f32) as u8;
+        let colour = if i == 0 { TColour::new(v, v, v / 2, 0xc0) } else { TColour::new(v, 0x40, 0x40, 0xc0) };
+        let top = frame.height().saturating_sub(BOTTOM + (i as u32 + 1) * (HEIGHT + 1)) as i32;
+        let filled = (amount * width as f32).round() as u32;
+        frame.fill_rect(left + filled as i32, top, width - filled, HEIGHT, BACKGROUND);
+        frame.fill_rect(left, top, filled, HEIGHT, colour);
+    }
+}
+
+/// Draws the score and lives left in the bottom right corner, out of the way of the tactical view
+pub fn draw_score(frame: &mut Frame, score: u32, lives: u32) {
+    let text = lang::tr_args("hud.score", &[("score", &score), ("lives", &lives)]);
+    let x = frame.width().saturating_sub(font::text_width(&text) + 2);
+    let y = frame.height().saturating_sub(font::LINE_HEIGHT + 2);
+    font::draw_text(frame, x, y, &text, TIMER_COLOUR);
+}
+
+/// Draws how many armor points the player has just above the score
+pub fn draw_armor(frame: &mut Frame, armor: f32) {
+    const ARMOR_COLOUR: Colour = Colour::new(0x80, 0xc0, 0xff);
+
+    let text = lang::tr_args("hud.armor", &[("points", &format!("{:.0}", armor.ceil()))]);
+    let x = frame.width().saturating_sub(font::text_width(&text) + 2);
+    let y = frame.height().saturating_sub(2 * font::LINE_HEIGHT + 2);
+    font::draw_text(frame, x, y, &text, ARMOR_COLOUR);
+}
+
+/// Draws black bars over the top and bottom of the view for a cutscene, with the subtitle in the bottom one
+pub fn draw_letterbox(frame: &mut Frame, subtitle: Option<&str>) {
+    const BAR: TColour = TColour::new(0, 0, 0, 0xff);
+
+    let bar = frame.height() / 8;
+    frame.fill_rect(0, 0, frame.width(), bar, BAR);
+    frame.fill_rect(0, frame.height().saturating_sub(bar) as i32, frame.width(), bar, BAR);
+    if let Some(subtitle) = subtitle {
+        let x = frame.width().saturating_sub(font::text_width(subtitle)) / 2;
+        let y = frame.height().saturating_sub((bar + font::GLYPH_HEIGHT) / 2);
+        font::draw_text(frame, x, y, subtitle, CAPTION_COLOUR);
+    }
+}
+
+/// Draws the time spent on the level in the top right corner
+pub fn draw_level_timer(frame: &mut Frame, time: f32) {
+    let text = format_time(time);
+    let x = frame.width().saturating_sub(font::text_width(&text) + 2);
+    font::draw_text(frame, x, 2, &text, TIMER_COLOUR);
+}
+
+const DIALOGUE_COLOUR: Colour = Colour::new(0xf0, 0xf0, 0xf0);
+const CHOICE_COLOUR: Colour = Colour::new(0xa0, 0xa0, 0xa0);
+const SELECTED_COLOUR: Colour = Colour::new(0xff, 0xff, 0x80);
+/// Pixels between the edge of the dialogue box and its text
+const DIALOGUE_MARGIN: u32 = 4;
+/// Texels along each edge of the panel texture that make up its border
+const PANEL_BORDER: u32 = 2;
+
+/// Uses `tex/panel.png` if there is one, otherwise a dark box with a thin light edge
+fn panel() -> &'static Texture {
+    static PANEL: OnceLock<Texture> = OnceLock::new();
+    PANEL.get_or_init(|| {
+        if assets::exists("tex/panel.png") {
+            return Texture::from_file("tex/panel.png");
+        }
+        const SIZE: u32 = 2 * PANEL_BORDER + 1;
+        let img = RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+            let from_edge = x.min(y).min(SIZE - 1 - x).min(SIZE - 1 - y);
+            Rgba(match from_edge {
+                0 => [0x80, 0x80, 0xa0, 0xe0],
+                1 => [0x08, 0x08, 0x10, 0xe0],
+                _ => [0x10, 0x10, 0x20, 0xd0],
+            })
+        });
+        Texture::from_rgba(&img)
+    })
+}
+
+/// Draws a box for text to go in, stretched to any size from the panel texture
+pub fn draw_panel(frame: &mut Frame, x: i32, y: i32, width: u32, height: u32) {
+    panel().draw_nine_patch(frame, x, y, width, height, PANEL_BORDER);
+}
+
+/// Draws the area around the player seen from above in a box in the bottom left corner
+pub fn draw_tactical(frame: &mut Frame, renderer: &mut TopDownRenderer, world: &World, camera: &Camera) {
+    let size = (frame.height() as f32 * TACTICAL_SIZE) as u32;
+    let (x, y) = (PANEL_BORDER + 2, frame.height().saturating_sub(size + PANEL_BORDER + 2));
+    let border = PANEL_BORDER as i32;
+    draw_panel(frame, x as i32 - border, y as i32 - border, size + 2 * PANEL_BORDER, size + 2 * PANEL_BORDER);
+    frame.with_viewport(Rect { x, y, width: size, height: size }, |frame| renderer.render(world, camera, frame));
+}
+
+/// Draws the current page of a dialogue in a box at the bottom of the screen,
+/// along with the choices on the last page
+pub fn draw_dialogue(frame: &mut Frame, dialogue: &Dialogue, talk: Talk) {
+    let max_chars = ((frame.width() - 2 * DIALOGUE_MARGIN) / font::ADVANCE) as usize;
+    let lines = wrap(&dialogue.pages[talk.page], max_chars);
+    let last_page = talk.page + 1 == dialogue.pages.len();
+    let choices = if last_page { &dialogue.choices[..] } else { &[] };
+
+    let text_lines = lines.len() + choices.len() + usize::from(!choices.is_empty());
+    let height = text_lines as u32 * font::LINE_HEIGHT + 2 * DIALOGUE_MARGIN;
+    let top = frame.height().saturating_sub(height);
+    draw_panel(frame, 0, top as i32, frame.width(), frame.height() - top);
+
+    // Choices too long to fit are cut off at the edge of the text
+    let inside = Rect { x: DIALOGUE_MARGIN, y: top + DIALOGUE_MARGIN, width: frame.width() - 2 * DIALOGUE_MARGIN, height: height - 2 * DIALOGUE_MARGIN };
+    frame.with_viewport(inside, |frame| {
+        let mut y = 0;
+        for line in &lines {
+            font::draw_text(frame, 0, y, line, DIALOGUE_COLOUR);
+            y += font::LINE_HEIGHT;
+        }
+        if !choices.is_empty() {
+            y += font::LINE_HEIGHT;
+        }
+        for (i, choice) in choices.iter().enumerate() {
+            let (marker, colour) = if i == talk.choice { ('>', SELECTED_COLOUR) } else { (' ', CHOICE_COLOUR) };
+            font::draw_text(frame, 0, y, &format!("{marker} {}", choice.text), colour);
+            y += font::LINE_HEIGHT;
+        }
+        // More pages to go
+        if !last_page {
+            let x = frame.width() - font::GLYPH_WIDTH;
+            font::draw_text(frame, x, frame.height() - font::GLYPH_HEIGHT, "v", SELECTED_COLOUR);
+        }
+    });
+}
+
+/// Splits text into lines of at most `max_chars` characters between words,
+/// words that are too long by themselves are split up
+fn wrap(text: &str, max_chars: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        while word.len() > max_chars {
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            lines.push(word.drain(..max_chars).collect());
+        }
+        let len = line.chars().count();
+        if len > 0 && len + 1 + word.len() > max_chars {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.extend(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+const OBJECTIVE_COLOUR: Colour = Colour::new(0xff, 0xff, 0xff);
+const DONE_COLOUR: Colour = Colour::new(0x80, 0xc0, 0x80);
+
+/// Lists the objectives in the top left corner, ticking off the completed ones
+pub fn draw_objectives<'a>(frame: &mut Frame, objectives: impl Iterator<Item = (&'a Objective, bool)>) {
+    let fits = (frame.width().saturating_sub(2) / font::ADVANCE) as usize;
+    font::draw_text(frame, 2, 2, &lang::tr("objectives.title"), TIMER_COLOUR);
+    for (i, (objective, done)) in objectives.enumerate() {
+        let (mark, colour) = if done { ('x', DONE_COLOUR) } else { (' ', OBJECTIVE_COLOUR) };
+        let text: String = format!("[{mark}] {}", objective.text).chars().take(fits).collect();
+        font::draw_text(frame, 2, 2 + (i as u32 + 1) * font::LINE_HEIGHT, &text, colour);
+    }
+}
+
+/// Message shown at the top of the screen for a few seconds
+#[derive(Debug, Default)]
+pub struct Toast {
+    /// The message and how many seconds it has left
+    message: Option<(String, f32)>,
+    /// Every message shown, to read again after it's gone
+    pub log: MessageLog,
+}
+
+impl Toast {
+    const TIME: f32 = 4.;
+
+    pub fn show(&mut self, message: String) {
+        self.log.push(message.clone());
+        self.message = Some((message, Self::TIME));
+    }
+    pub fn tick(&mut self, delta: f32) {
+        if let Some((_, time)) = &mut self.message {
+            *time -= delta;
+            if *time <= 0. {
+                self.message = None;
+            }
+        }
+    }
+    pub fn draw(&self, frame: &mut Frame) {
+        if let Some((text, _)) = &self.message {
+            // Long messages are cut off rather than wrapping around the frame
+            let fits = (frame.width() / font::ADVANCE) as usize;
+            let text: String = text.chars().take(fits).collect();
+            let x = frame.width().saturating_sub(font::text_width(&text)) / 2;
+            font::draw_text(frame, x, 12, &text, TIMER_COLOUR);
+        }
+    }
+}
+
+const LOG_COLOUR: Colour = Colour::new(0xe0, 0xe0, 0xe0);
+/// Pixels between the edge of the message log and its text
+const LOG_MARGIN: u32 = 6;
+/// How much the view behind the message log is darkened
+const LOG_DIM: u8 = 0x80;
+
+/// The last messages of the game, newest at the bottom, in a panel that scrolls back through older ones
+#[derive(Debug, Default)]
+pub struct MessageLog {
+    messages: VecDeque<String>,
+    pub open: bool,
+    /// How many messages back from the newest the bottom of the panel is
+    offset: usize,
+}
+
+impl MessageLog {
+    /// Older messages are forgotten
+    const MAX_MESSAGES: usize = 100;
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.offset = 0;
+    }
+    pub fn push(&mut self, message: String) {
+        if self.messages.len() == Self::MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(message);
+        // What's being read stays put while new messages come in
+        if self.offset > 0 {
+            self.offset = (self.offset + 1).min(self.messages.len() - 1);
+        }
+    }
+    /// Scrolls `step` messages back (or forwards if negative), stopping at the oldest and newest
+    pub fn scroll(&mut self, step: i32) {
+        self.offset = (self.offset as i32 + step).clamp(0, self.messages.len().saturating_sub(1) as i32) as usize;
+    }
+    /// Draws the panel over the dimmed view, filled from the bottom with as many messages as fit
+    pub fn draw(&self, frame: &mut Frame) {
+        frame.tint(Colour::new(0, 0, 0), LOG_DIM);
+        let (left, top) = (frame.width() / 8, frame.height() / 8);
+        let (width, height) = (frame.width() - 2 * left, frame.height() - 2 * top);
+        draw_panel(frame, left as i32, top as i32, width, height);
+        font::draw_text(frame, left + LOG_MARGIN, top + LOG_MARGIN, &lang::tr("hud.log"), TIMER_COLOUR);
+
+        let max_chars = (width.saturating_sub(2 * LOG_MARGIN) / font::ADVANCE) as usize;
+        // A blank line under the title
+        let fits = (height.saturating_sub(2 * LOG_MARGIN) / font::LINE_HEIGHT).saturating_sub(2) as usize;
+        if self.messages.is_empty() {
+            font::draw_text(frame, left + LOG_MARGIN, top + LOG_MARGIN + 2 * font::LINE_HEIGHT, &lang::tr("hud.log_empty"), CHOICE_COLOUR);
+            return;
+        }
+        let mut lines = Vec::new();
+        let newest = self.messages.len() - self.offset;
+        let mut shown = newest;
+        for message in self.messages.range(..newest).rev() {
+            let wrapped = wrap(message, max_chars);
+            if lines.len() + wrapped.len() > fits && !lines.is_empty() {
+                break;
+            }
+            lines.splice(0..0, wrapped);
+            shown -= 1;
+        }
+        // A message too long for the whole panel shows its start
+        lines.truncate(fits);
+
+        let bottom = top + height - LOG_MARGIN;
+        for (i, line) in lines.iter().rev().enumerate() {
+            font::draw_text(frame, left + LOG_MARGIN, bottom - (i as u32 + 1) * font::LINE_HEIGHT, line, LOG_COLOUR);
+        }
+        // More messages above or below
+        let x = left + width - LOG_MARGIN - font::GLYPH_WIDTH;
+        if shown > 0 {
+            font::draw_text(frame, x, top + LOG_MARGIN, "^", SELECTED_COLOUR);
+        }
+        if self.offset > 0 {
+            font::draw_text(frame, x, bottom - font::GLYPH_HEIGHT, "v", SELECTED_COLOUR);
+        }
+    }
+}
+
+/// Arrow drawn as the cursor when there's no cursor texture, `#` is the outline and `o` the inside
+const ARROW: [&str; 11] = [
+    "#",
+    "##",
+    "#o#",
+    "#oo#",
+    "#ooo#",
+    "#oooo#",
+    "#ooooo#",
+    "#oooooo#",
+    "#ooo####",
+    "#o##",
+    "##",
+];
+
+/// Cursor drawn into the frame, for when the system cursor is hidden
+#[derive(Debug)]
+pub struct Cursor {
+    tex: Option<Texture>,
+}
+
+impl Cursor {
+    /// Uses `tex/cursor.png` if there is one, with the tip of the cursor in its top left corner
+    pub fn load() -> Self {
+        Cursor {
+            tex: assets::exists("tex/cursor.png").then(|| Texture::from_file("tex/cursor.png")),
+        }
+    }
+    /// Draws the cursor with its tip at (`x`, `y`)
+    pub fn draw(&self, frame: &mut Frame, x: u32, y: u32) {
+        if let Some(tex) = &self.tex {
+            tex.draw_region(frame, tex.rect(), x as i32, y as i32, false, false);
+            return;
+        }
+        for (dy, row) in ARROW.iter().enumerate() {
+            for (dx, c) in row.chars().enumerate() {
+                let (px, py) = (x + dx as u32, y + dy as u32);
+                if px >= frame.width() || py >= frame.height() {
+                    continue;
+                }
+                match c {
+                    '#' => frame.draw_rgb(px, py, Colour::new(0, 0, 0)),
+                    'o' => frame.draw_rgb(px, py, Colour::new(0xff, 0xff, 0xff)),
+                    _ => (),
+                }
+            }
+        }
+    }
+}
+
+/// Short descriptions of significant sounds for playing without sound
+#[derive(Debug, Default)]
+pub struct Captions {
+    /// The text of each caption and how many seconds it has left
+    lines: Vec<(String, f32)>,
+}
+
+impl Captions {
+    /// Shows a caption for the sound if it's significant and loud enough
+    pub fn hear(&mut self, heard: &Heard) {
+        let Some(key) = heard.sound.caption_key() else { return };
+        if heard.volume < CAPTION_MIN_VOLUME {
+            return;
+        }
+        let text = lang::tr_args("captions.caption", &[
+            ("sound", &lang::tr(key)),
+            ("direction", &lang::tr(heard.direction_key())),
+        ]);
+
+        // The same sound again just stays up longer
+        self.lines.retain(|(line, _)| line != &text);
+        self.lines.push((text, CAPTION_TIME));
+        if self.lines.len() > MAX_CAPTIONS {
+            self.lines.remove(0);
+        }
+    }
+    pub fn tick(&mut self, delta: f32) {
+        for (_, time) in &mut self.lines {
+            *time -= delta;
+        }
+        self.lines.retain(|&(_, time)| time > 0.);
+    }
+    /// Draws the captions centred above the bottom of the frame, newest at the bottom
+    pub fn draw(&self, frame: &mut Frame) {
+        let bottom = frame.height().saturating_sub(font::LINE_HEIGHT * 3);
+
+        for (i, (text, _)) in self.lines.iter().rev().enumerate() {
+            let x = frame.width().saturating_sub(font::text_width(text)) / 2;
+            let y = bottom.saturating_sub(i as u32 * font::LINE_HEIGHT);
+            font::draw_text(frame, x, y, text, CAPTION_COLOUR);
+        }
+    }
+}
+
+/// Recent kills listed in the top right corner
+#[derive(Debug, Default)]
+pub struct KillFeed {
+    /// The text of each kill and how many seconds it has left
+    lines: Vec<(String, f32)>,
+}
+
+impl KillFeed {
+    pub fn hear(&mut self, kill: &Kill) {
+        let text = match &kill.killer {
+            Some(killer) => lang::tr_args("kill_feed.killed", &[("killer", killer), ("victim", &kill.victim)]),
+            None => lang::tr_args("kill_feed.died", &[("victim", &kill.victim)]),
+        };
+        self.lines.push((text, KILL_FEED_TIME));
+        if self.lines.len() > MAX_KILL_FEED {
+            self.lines.remove(0);
+        }
+    }
+    pub fn tick(&mut self, delta: f32) {
+        for (_, time) in &mut self.lines {
+            *time -= delta;
+        }
+        self.lines.retain(|&(_, time)| time > 0.);
+    }
+    /// Draws the kills right-aligned below the timer, newest at the bottom
+    pub fn draw(&self, frame: &mut Frame) {
+        const TOP: u32 = 12;
+
+        for (i, (text, _)) in self.lines.iter().enumerate() {
+            let x = frame.width().saturating_sub(font::text_width(text) + 2);
+            font::draw_text(frame, x, TOP + i as u32 * font::LINE_HEIGHT, text, KILL_FEED_COLOUR);
+        }
+    }
+}
+
+#[test]
+fn time_formatting() {
+    assert_eq!(format_time(0.), "0:00.00");
+    assert_eq!(format_time(83.456), "1:23.46");
+    assert_eq!(format_delta(-1.5), "-1.50");
+    assert_eq!(format_delta(0.25), "+0.25");
+}
+
+#[test]
+fn message_log_keeps_the_last_messages() {
+    let mut toast = Toast::default();
+    for i in 0..MessageLog::MAX_MESSAGES + 5 {
+        toast.show(format!("message {i}"));
+    }
+    let log = &mut toast.log;
+    assert_eq!(log.messages.len(), MessageLog::MAX_MESSAGES);
+    assert_eq!(log.messages[0], "message 5");
+
+    // Scrolling stops at the oldest message, and keeps it in view as new ones come in
+    log.scroll(1000);
+    assert_eq!(log.offset, MessageLog::MAX_MESSAGES - 1);
+    log.scroll(-2);
+    log.push("newer".to_owned());
+    assert_eq!(log.offset, MessageLog::MAX_MESSAGES - 2);
+}
@@ -0,0 +1,111 @@
+//! Screen shown between levels with how the level went
+
+use crate::{font, hud, lang::{tr, tr_args}, map::Exit, tex::{Colour, Frame}};
+
+const DIM: u8 = 0xc0;
+const TITLE_COLOUR: Colour = Colour::new(0xff, 0xff, 0x60);
+const TEXT_COLOUR: Colour = Colour::new(0xe0, 0xe0, 0xe0);
+const FASTER_COLOUR: Colour = Colour::new(0x60, 0xff, 0x60);
+const SLOWER_COLOUR: Colour = Colour::new(0xff, 0x60, 0x60);
+
+#[derive(Debug, Clone)]
+pub struct Intermission {
+    /// How the level was left, or `None` if the player ran out of time or lives
+    pub exit: Option<Exit>,
+    /// The player ran out of lives, so the game starts over
+    pub game_over: bool,
+    level_name: String,
+    time: f32,
+    /// Best time before this one
+    previous_best: Option<f32>,
+    kills: u32,
+    /// Percentage of the treasure of the level that was picked up, if it had any
+    treasure: Option<u32>,
+}
+
+impl Intermission {
+    pub fn new(exit: Exit, level_name: &str, time: f32, previous_best: Option<f32>, kills: u32, treasure: Option<u32>) -> Self {
+        Intermission {
+            exit: Some(exit),
+            game_over: false,
+            level_name: level_name.to_owned(),
+            time,
+            previous_best,
+            kills,
+            treasure,
+        }
+    }
+    /// Screen for a timed level that wasn't finished in time, after which the level starts over
+    pub fn out_of_time(level_name: &str, time: f32, kills: u32) -> Self {
+        Intermission {
+            exit: None,
+            game_over: false,
+            level_name: level_name.to_owned(),
+            time,
+            previous_best: None,
+            kills,
+            treasure: None,
+        }
+    }
+    /// Screen for dying with no lives left, after which the level starts over as a new game
+    pub fn game_over(level_name: &str, time: f32, kills: u32) -> Self {
+        Intermission { game_over: true, ..Self::out_of_time(level_name, time, kills) }
+    }
+    pub fn draw(&self, frame: &mut Frame) {
+        frame.tint(Colour::new(0, 0, 0), DIM);
+
+        let lines = if self.game_over {
+            self.game_over_lines()
+        } else if self.exit.is_some() {
+            self.complete_lines()
+        } else {
+            self.failed_lines()
+        };
+        let top = (frame.height() / 2).saturating_sub(lines.len() as u32 * font::LINE_HEIGHT / 2);
+        for (i, (line, colour)) in lines.iter().enumerate() {
+            let x = (frame.width() / 2).saturating_sub(font::text_width(line) / 2);
+            font::draw_text(frame, x, top + i as u32 * font::LINE_HEIGHT, line, *colour);
+        }
+    }
+    fn complete_lines(&self) -> Vec<(String, Colour)> {
+        let mut lines = vec![
+            (tr_args("intermission.complete", &[("level", &self.level_name)]), TITLE_COLOUR),
+            (tr_args("intermission.time", &[("time", &hud::format_time(self.time))]), TEXT_COLOUR),
+        ];
+        match self.previous_best {
+            Some(best) => {
+                let delta = self.time - best;
+                let colour = if delta < 0. { FASTER_COLOUR } else { SLOWER_COLOUR };
+                lines.push((tr_args("intermission.best", &[
+                    ("best", &hud::format_time(best)),
+                    ("delta", &hud::format_delta(delta)),
+                ]), colour));
+            }
+            None => lines.push((tr("intermission.first_time"), FASTER_COLOUR)),
+        }
+        lines.push((tr_args("intermission.kills", &[("kills", &self.kills)]), TEXT_COLOUR));
+        if let Some(percent) = self.treasure {
+            lines.push((tr_args("intermission.treasure", &[("percent", &percent)]), TEXT_COLOUR));
+        }
+        lines.push((String::new(), TEXT_COLOUR));
+        lines.push((tr("intermission.continue"), TEXT_COLOUR));
+        lines
+    }
+    fn failed_lines(&self) -> Vec<(String, Colour)> {
+        vec![
+            (tr_args("intermission.out_of_time", &[("level", &self.level_name)]), SLOWER_COLOUR),
+            (tr_args("intermission.time", &[("time", &hud::format_time(self.time))]), TEXT_COLOUR),
+            (tr_args("intermission.kills", &[("kills", &self.kills)]), TEXT_COLOUR),
+            (String::new(), TEXT_COLOUR),
+            (tr("intermission.retry"), TEXT_COLOUR),
+        ]
+    }
+    fn game_over_lines(&self) -> Vec<(String, Colour)> {
+        vec![
+            (tr("intermission.game_over"), SLOWER_COLOUR),
+            (tr_args("intermission.kills", &[("kills", &self.kills)]), TEXT_COLOUR),
+            (String::new(), TEXT_COLOUR),
+            (tr("intermission.new_game"), TEXT_COLOUR),
+        ]
+    }
+}
@@ -0,0 +1,116 @@
+//! Screen with what the player is carrying and how the level is going, the game is paused while it's open
+
+use crate::{font, hud, lang::{tr, tr_args}, tex::{Colour, Frame}, world::World};
+
+const DIM: u8 = 0x80;
+const TAB_COLOUR: Colour = Colour::new(0xa0, 0xa0, 0xa0);
+const SELECTED_TAB_COLOUR: Colour = Colour::new(0xff, 0xff, 0x60);
+const TEXT_COLOUR: Colour = Colour::new(0xe0, 0xe0, 0xe0);
+/// Pixels between the edge of the panel and its text
+const MARGIN: u32 = 6;
+/// Pixels between the names of the tabs
+const TAB_GAP: u32 = 12;
+
+/// Page of the inventory screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tab {
+    /// Weapons, ammo, armor and treasure
+    Items,
+    /// How the current level is going
+    Stats,
+}
+
+impl Tab {
+    const ALL: [Tab; 2] = [Tab::Items, Tab::Stats];
+
+    const fn key(self) -> &'static str {
+        match self {
+            Tab::Items => "inventory.items",
+            Tab::Stats => "inventory.stats",
+        }
+    }
+    fn lines(self, world: &World) -> Vec<String> {
+        match self {
+            Tab::Items => {
+                let mut lines = vec![
+                    tr("inventory.gun"),
+                    tr_args("inventory.ammo", &[("ammo", &world.ammo)]),
+                    tr_args("inventory.armor", &[("armor", &format!("{:.0}", world.player_armor.ceil()))]),
+                ];
+                if let Some(percent) = world.treasure_percent() {
+                    lines.push(tr_args("inventory.treasure", &[("percent", &percent)]));
+                }
+                lines
+            }
+            Tab::Stats => {
+                let (done, total) = world.objectives().fold((0, 0), |(done, total), (_, d)| (done + d as u32, total + 1));
+                vec![
+                    tr_args("inventory.health", &[("health", &format!("{:.0}", world.player_health.max(0.).ceil()))]),
+                    tr_args("inventory.time", &[("time", &hud::format_time(world.clock))]),
+                    tr_args("inventory.score", &[("score", &world.score)]),
+                    tr_args("inventory.lives", &[("lives", &world.lives)]),
+                    tr_args("inventory.kills", &[("kills", &world.kills)]),
+                    tr_args("inventory.objectives", &[("done", &done), ("total", &total)]),
+                ]
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Inventory {
+    pub open: bool,
+    /// Index of the tab being shown in `Tab::ALL`
+    tab: usize,
+}
+
+impl Inventory {
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+    /// Goes `step` tabs to the right, wrapping around at the ends
+    pub fn switch_tab(&mut self, step: i32) {
+        self.tab = (self.tab as i32 + step).rem_euclid(Tab::ALL.len() as i32) as usize;
+    }
+    pub fn tab(&self) -> Tab {
+        Tab::ALL[self.tab]
+    }
+    /// Draws the screen over the dimmed view as a panel in the middle with the tabs along its top
+    pub fn draw(&self, frame: &mut Frame, world: &World) {
+        frame.tint(Colour::new(0, 0, 0), DIM);
+
+        let lines = self.tab().lines(world);
+        let tabs: Vec<_> = Tab::ALL.iter().map(|tab| tr(tab.key())).collect();
+        let tabs_width = tabs.iter().map(|name| font::text_width(name)).sum::<u32>() + TAB_GAP * (tabs.len() as u32 - 1);
+        let text_width = lines.iter().map(|line| font::text_width(line)).max().unwrap_or(0).max(tabs_width);
+        // A blank line between the tabs and the lines of the tab
+        let text_height = (lines.len() as u32 + 2) * font::LINE_HEIGHT;
+
+        let (width, height) = (text_width + 2 * MARGIN, text_height + 2 * MARGIN);
+        let left = frame.width().saturating_sub(width) / 2;
+        let top = frame.height().saturating_sub(height) / 2;
+        hud::draw_panel(frame, left as i32, top as i32, width, height);
+
+        let mut x = left + MARGIN;
+        for (i, name) in tabs.iter().enumerate() {
+            let colour = if i == self.tab { SELECTED_TAB_COLOUR } else { TAB_COLOUR };
+            font::draw_text(frame, x, top + MARGIN, name, colour);
+            x += font::text_width(name) + TAB_GAP;
+        }
+        for (i, line) in lines.iter().enumerate() {
+            font::draw_text(frame, left + MARGIN, top + MARGIN + (i as u32 + 2) * font::LINE_HEIGHT, line, TEXT_COLOUR);
+        }
+    }
+}
+
+#[test]
+fn tabs_wrap_around() {
+    let mut inventory = Inventory::default();
+    assert_eq!(inventory.tab(), Tab::Items);
+    inventory.switch_tab(1);
+    assert_eq!(inventory.tab(), Tab::Stats);
+    inventory.switch_tab(1);
+    assert_eq!(inventory.tab(), Tab::Items);
+    inventory.switch_tab(-1);
+    assert_eq!(inventory.tab(), Tab::Stats);
+}
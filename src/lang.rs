@@ -0,0 +1,74 @@
+//! Text shown to the player, looked up by key in the string table of the chosen language
+//!
+//! String tables are the files in `lang/`, each a `[section]` of `key = "text"` entries that are looked up as `section.key`.
+//! Text can contain `{name}` placeholders that are filled in by `tr_args`.
+//! English is built in and used for anything the chosen language is missing.
+
+use std::{cell::RefCell, collections::HashMap};
+
+use log::{error, info};
+
+use crate::{assets, settings::{parse_toml, Value}};
+
+const ENGLISH: &str = include_str!("../lang/en.toml");
+
+#[derive(Debug, Default)]
+struct StringTable {
+    strings: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+
+thread_local! {
+    static TABLE: RefCell<StringTable> = RefCell::new(StringTable {
+        strings: HashMap::new(),
+        fallback: parse(ENGLISH).expect("built-in English strings to be valid"),
+    });
+}
+
+fn parse(src: &str) -> Result<HashMap<String, String>, String> {
+    let mut strings = HashMap::new();
+    parse_toml(src, |table, key, value| match value {
+        Value::Str(s) => {
+            let key = if table.is_empty() { key.to_owned() } else { format!("{table}.{key}") };
+            strings.insert(key, s);
+            Ok(())
+        }
+        v => Err(format!("{key} should be text, not {v:?}")),
+    })?;
+    Ok(strings)
+}
+
+/// Switches to the language in `lang/{name}.toml`, keeping the current one if it can't be loaded
+pub fn load(name: &str) {
+    let path = format!("lang/{name}.toml");
+    let strings = if name == "en" {
+        Ok(HashMap::new())
+    } else {
+        assets::read_to_string(&path).map_err(|e| e.to_string()).and_then(|src| parse(&src))
+    };
+
+    match strings {
+        Ok(strings) => {
+            info!("language: {name}");
+            TABLE.with(|t| t.borrow_mut().strings = strings);
+        }
+        Err(e) => error!("could not load {path}: {e}"),
+    }
+}
+
+/// Text for a key, or the key itself if no language has it
+pub fn tr(key: &str) -> String {
+    TABLE.with(|t| {
+        let t = t.borrow();
+        t.strings.get(key).or_else(|| t.fallback.get(key)).cloned().unwrap_or_else(|| key.to_owned())
+    })
+}
+
+/// Text for a key with each `{name}` replaced by its value
+pub fn tr_args(key: &str, args: &[(&str, &dyn std::fmt::Display)]) -> String {
+    let mut text = tr(key);
+    for (name, value) in args {
+        text = text.replace(&format!("{{{name}}}"), &value.to_string());
+    }
+    text
+}
@@ -0,0 +1,100 @@
+//! Loading the next map on another thread while a loading screen is shown, so the window keeps responding
+
+use std::{path::PathBuf, sync::mpsc::{self, Receiver, TryRecvError}};
+
+use log::info;
+
+use crate::{font, lang::tr, settings::Settings, tex::{Colour, TColour, Frame}, world::{Transition, World}};
+
+const BACKGROUND: Colour = Colour::new(0x10, 0x10, 0x10);
+const TEXT_COLOUR: Colour = Colour::new(0xe0, 0xe0, 0xe0);
+const BAR_BACKGROUND: TColour = TColour::new(0x30, 0x30, 0x30, 0xff);
+const BAR: TColour = TColour::new(0xe0, 0xc0, 0x40, 0xff);
+const BAR_HEIGHT: u32 = 3;
+/// Dots drawn after the text one at a time, a second for all of them
+const DOTS: usize = 3;
+/// Least change in progress that is sent back, so the channel isn't flooded
+const PROGRESS_STEP: f32 = 0.01;
+
+enum Message {
+    /// How much of the map has been loaded, from 0 to 1
+    Progress(f32),
+    /// The world, or why the map couldn't be loaded
+    Done(Result<Box<World>, String>),
+}
+
+/// A map being loaded in the background
+pub struct Loading {
+    /// How the world goes on to the map once it's loaded
+    pub transition: Transition,
+    path: PathBuf,
+    receiver: Receiver<Message>,
+    progress: f32,
+    /// Seconds since it started, for the animation
+    time: f32,
+}
+
+impl Loading {
+    /// Starts loading the map at `path` with the player's settings, which is done right away in the browser since it has no threads
+    pub fn start(path: PathBuf, transition: Transition, settings: &Settings) -> Self {
+        info!("loading {}", path.display());
+        let (sender, receiver) = mpsc::channel();
+        let load = {
+            let (path, settings) = (path.clone(), settings.clone());
+            move || {
+                let mut sent = 0.;
+                let world = World::from_map_file_with_progress(&path, &settings, |progress| {
+                    if progress - sent >= PROGRESS_STEP {
+                        sent = progress;
+                        // The loading screen might have been given up on already
+                        let _ = sender.send(Message::Progress(progress));
+                    }
+                });
+                let _ = sender.send(Message::Done(world.map(Box::new)));
+            }
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::spawn(load);
+        #[cfg(target_arch = "wasm32")]
+        load();
+
+        Loading {
+            transition,
+            path,
+            receiver,
+            progress: 0.,
+            time: 0.,
+        }
+    }
+    /// Takes what the loading thread has sent, giving the world once it's ready or why it couldn't be loaded
+    pub fn poll(&mut self, delta: f32) -> Option<Result<World, String>> {
+        self.time += delta;
+        loop {
+            match self.receiver.try_recv() {
+                Ok(Message::Progress(progress)) => self.progress = progress,
+                Ok(Message::Done(world)) => return Some(world.map(|world| *world)),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => return Some(Err(format!("loading {} stopped", self.path.display()))),
+            }
+        }
+    }
+    /// Draws the loading screen over all of the frame with the progress as a bar under the text
+    pub fn draw(&self, frame: &mut Frame) {
+        frame.clear(BACKGROUND);
+
+        let text = tr("loading.title");
+        let dots = (self.time.fract() * (DOTS + 1) as f32) as usize;
+        let (width, height) = (frame.width(), frame.height());
+        let x = (width / 2).saturating_sub(font::text_width(&text) / 2);
+        let y = (height / 2).saturating_sub(font::LINE_HEIGHT);
+        // The dots go after the centred text so it doesn't move as they come and go
+        font::draw_text(frame, x, y, &format!("{text}{}", ".".repeat(dots)), TEXT_COLOUR);
+
+        let bar_width = width / 2;
+        let left = (width / 4) as i32;
+        let top = (y + 2 * font::LINE_HEIGHT) as i32;
+        let filled = (self.progress.clamp(0., 1.) * bar_width as f32).round() as u32;
+        frame.fill_rect(left + filled as i32, top, bar_width - filled, BAR_HEIGHT, BAR_BACKGROUND);
+        frame.fill_rect(left, top, filled, BAR_HEIGHT, BAR);
+    }
+}
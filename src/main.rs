@@ -1,12 +1,14 @@
 use std::collections::VecDeque;
-use std::time::Instant;
+use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
 
-use log::{error, info};
-use pixels::{Error, Pixels, SurfaceTexture};
+use log::{debug, error, info};
+use pixels::{Pixels, PixelsBuilder, SurfaceTexture};
 use winit::dpi::LogicalSize;
-use winit::event::{Event, VirtualKeyCode};
+use winit::event::{Event, VirtualKeyCode, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
-use winit::window::WindowBuilder;
+use winit::window::{CursorGrabMode, Icon, Window, WindowBuilder};
 use winit_input_helper::WinitInputHelper;
 
 pub mod vec;
@@ -14,53 +16,488 @@ pub mod map;
 pub mod fov;
 pub mod tex;
 pub mod world;
+pub mod profile;
+pub mod font;
+pub mod console;
+pub mod cheats;
+pub mod settings;
+pub mod lang;
+pub mod assets;
+pub mod time;
+pub mod package;
+pub mod campaign;
+pub mod profiles;
+pub mod hud;
+pub mod intermission;
+pub mod title;
+pub mod loading;
+pub mod inventory;
+pub mod sound;
+pub mod audio;
+pub mod palette;
+pub mod pacing;
+pub mod overview;
+pub mod render;
+#[cfg(test)]
+mod testing;
+#[cfg(feature = "gpu")]
+pub mod gpu;
 
 use self::tex::*;
+use self::render::{Renderer, SoftwareRenderer, top_down::TopDownRenderer};
 use self::world::*;
+use self::world::thing::EntityId;
+use self::world::pickups::Pickup;
+use self::profile::{Stage, Timer};
+use self::console::Console;
+use self::cheats::{Cheats, Typed};
+use self::campaign::Campaign;
+use self::intermission::Intermission;
+use self::title::Title;
+use self::world::demo::Recorder;
+use self::loading::Loading;
+use self::inventory::Inventory;
+use self::audio::Audio;
+use self::settings::{Action, Bindings, Button, Settings, SettingsFile};
+use self::profiles::Profile;
+use self::time::Instant;
+use winit_input_helper::TextChar;
 
-const WIDTH: u32 = 320;
-const HEIGHT: u32 = 240;
-const FACTOR: u32 = 4;
-const FOV: f32 = 65.;
+/// Hides the cursor and keeps it in the window for mouse look, or gives it back
+///
+/// The system cursor stays hidden if the game draws its own
+fn grab_cursor(window: &Window, grab: bool, software_cursor: bool) {
+    let res = if grab {
+        window.set_cursor_grab(CursorGrabMode::Confined)
+            .or_else(|_| window.set_cursor_grab(CursorGrabMode::Locked))
+    } else {
+        window.set_cursor_grab(CursorGrabMode::None)
+    };
+    if let Err(e) = res {
+        error!("could not grab the cursor: {:?}", e);
+    }
+    window.set_cursor_visible(!grab && !software_cursor);
+}
+
+fn load_icon() -> Option<Icon> {
+    let bytes = assets::read("tex/icon.png").ok()?;
+    let img = image::load_from_memory(&bytes)
+        .map_err(|e| error!("could not load the window icon: {e}"))
+        .ok()?
+        .into_rgba8();
+    let (width, height) = img.dimensions();
+    Icon::from_rgba(img.into_raw(), width, height)
+        .map_err(|e| error!("could not use the window icon: {e:?}"))
+        .ok()
+}
+
+/// Most simulation steps run at once, so a long hitch doesn't make the game spiral trying to catch up
+const MAX_TICKS: u32 = 8;
+/// Systems taking longer than this much of a tick get logged
+const SLOW_SYSTEM: f32 = TICK / 4.;
+
+/// Runs the world in fixed steps for `delta` seconds plus the time left over from the last call
+///
+/// Mouse movement is applied in the first step, or saved for later if there wasn't a step.
+fn step_world(world: &mut World, leftover: &mut f32, delta: f32, mouse_dx: &mut f32, mut controls: Controls, mut recorder: Option<&mut Recorder>) {
+    let _update_timer = Timer::start(Stage::Update);
+    *leftover += delta;
+    let mut ticks = 0;
+    while *leftover >= TICK {
+        controls.mouse_dx = std::mem::take(mouse_dx);
+        if let Some(recorder) = &mut recorder {
+            recorder.tick(controls);
+        }
+        world.update_timed(TICK, controls, |system, took| {
+            if took.as_secs_f32() > SLOW_SYSTEM {
+                debug!("{} system took {took:?}", system.name);
+            }
+        });
+        *leftover -= TICK;
+        ticks += 1;
 
-fn main() -> Result<(), Error> {
+        if world.exited.is_some() {
+            break;
+        }
+        if ticks == MAX_TICKS {
+            *leftover = 0.;
+            break;
+        }
+    }
+}
+
+fn create_window(event_loop: &EventLoop<()>, settings: &Settings) -> Window {
+    lang::load(&settings.language);
+
+    let (width, height) = (settings.width, settings.height);
+    WindowBuilder::new()
+        .with_title(lang::tr("window.title"))
+        .with_inner_size(LogicalSize::new(settings.scale * width, settings.scale * height))
+        .with_min_inner_size(LogicalSize::new(width, height))
+        .with_window_icon(load_icon())
+        .build(event_loop)
+        .unwrap()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() -> Result<(), pixels::Error> {
     env_logger::init();
+
+    // Usage: ulvestein [--profile <name>] [--check-map <map>] [--render-map-overview <map> <png>] [--survival <map>] [--record-demo <demo>] [--timedemo <demo>] [package]
+    let mut profile_name = None;
+    let mut survival_map = None;
+    let mut record_demo = None;
+    let mut package_path = None;
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--profile" {
+            profile_name = args.next().map(|n| n.to_string_lossy().into_owned());
+        } else if arg == "--survival" {
+            let Some(map) = args.next() else {
+                eprintln!("--survival needs the path of an arena map");
+                std::process::exit(2);
+            };
+            survival_map = Some(map.to_string_lossy().into_owned());
+        } else if arg == "--record-demo" {
+            let Some(demo) = args.next() else {
+                eprintln!("--record-demo needs the path of the demo to save");
+                std::process::exit(2);
+            };
+            record_demo = Some(PathBuf::from(demo));
+        } else if arg == "--timedemo" {
+            let Some(demo) = args.next() else {
+                eprintln!("--timedemo needs the path of a demo");
+                std::process::exit(2);
+            };
+            timedemo(Path::new(&demo));
+        } else if arg == "--check-map" {
+            let Some(path) = args.next() else {
+                eprintln!("--check-map needs the path of a map");
+                std::process::exit(2);
+            };
+            check_map(Path::new(&path));
+        } else if arg == "--render-map-overview" {
+            let (Some(map), Some(out)) = (args.next(), args.next()) else {
+                eprintln!("--render-map-overview needs the path of a map and of the picture to make");
+                std::process::exit(2);
+            };
+            render_map_overview(Path::new(&map), Path::new(&out));
+        } else {
+            package_path = Some(arg);
+        }
+    }
+
+    // A game package can be given to play instead of the built-in map
+    if let Some(path) = package_path {
+        match package::Package::open(&path) {
+            Ok(package) => {
+                info!("playing {} ({} levels)", package.name, package.levels.len());
+                assets::mount(package);
+            }
+            Err(e) => {
+                error!("could not load package {}: {e}", Path::new(&path).display());
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let profile = Profile::load(profile_name.as_deref());
+    let settings_file = SettingsFile::load(profile.settings_path());
     let event_loop = EventLoop::new();
-    let mut input = WinitInputHelper::new();
-    let window = {
-        WindowBuilder::new()
-            .with_title("Ulvestein")
-            .with_inner_size(LogicalSize::new(FACTOR * WIDTH, FACTOR * HEIGHT))
-            .with_min_inner_size(LogicalSize::new(WIDTH, HEIGHT))
-            .build(&event_loop)
-            .unwrap()
-    };
+    let window = create_window(&event_loop, &settings_file.settings);
 
-    let mut pixels = {
+    let pixels = {
         let window_size = window.inner_size();
         let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        Pixels::new(WIDTH, HEIGHT, surface_texture)?
+        PixelsBuilder::new(settings_file.settings.width, settings_file.settings.height, surface_texture)
+            .enable_vsync(settings_file.settings.vsync)
+            .build()?
+    };
+    run(event_loop, window, pixels, settings_file, profile, survival_map, record_demo)
+}
+
+/// Prints all problems with a map and exits, unsuccessfully if there were any
+#[cfg(not(target_arch = "wasm32"))]
+fn check_map(path: &Path) -> ! {
+    let src = match std::fs::read_to_string(path) {
+        Ok(src) => src,
+        Err(e) => {
+            eprintln!("{}: {e}", path.display());
+            std::process::exit(2);
+        }
+    };
+    let problems = map::check::check(&src);
+    for problem in &problems {
+        eprintln!("{}: {problem}", path.display());
+    }
+    if problems.is_empty() {
+        println!("{}: ok", path.display());
+        std::process::exit(0);
+    }
+    std::process::exit(1);
+}
+
+/// Plays a demo as fast as it can, drawing a frame after every tick, prints how long that took and exits
+#[cfg(not(target_arch = "wasm32"))]
+fn timedemo(path: &Path) -> ! {
+    use std::time::{Duration, Instant};
+
+    let demo = match assets::read_to_string(path).map_err(|e| e.to_string()).and_then(|src| world::demo::Demo::parse(&src)) {
+        Ok(demo) => demo,
+        Err(e) => {
+            eprintln!("{}: {e}", path.display());
+            std::process::exit(2);
+        }
+    };
+    let settings = Settings::default();
+    let mut world = match World::from_map_file(&demo.map, &settings) {
+        Ok(world) => world,
+        Err(e) => {
+            eprintln!("{}: {e}", demo.map);
+            std::process::exit(2);
+        }
+    };
+    let mut renderer = SoftwareRenderer::new(settings.supersampling);
+    let mut buffer = vec![0; (settings.width * settings.height * 4) as usize];
+    let mut playback = world::demo::Playback::new(demo);
+
+    let (mut updating, mut drawing, mut ticks) = (Duration::ZERO, Duration::ZERO, 0);
+    loop {
+        let start = Instant::now();
+        if !playback.tick(&mut world) {
+            break;
+        }
+        world.events.clear();
+        let updated = Instant::now();
+        renderer.render(&world, &world.camera(), &mut Frame::new(&mut buffer, settings.width, settings.height));
+        updating += updated - start;
+        drawing += updated.elapsed();
+        ticks += 1;
+    }
+    let per_tick = |d: Duration| d.as_secs_f64() * 1000. / ticks.max(1) as f64;
+    println!("{}: {ticks} ticks, {:.3} ms updating and {:.3} ms drawing per tick, {:.0} fps",
+        path.display(), per_tick(updating), per_tick(drawing), ticks as f64 / (updating + drawing).as_secs_f64());
+    std::process::exit(0);
+}
+
+/// Saves a picture of the whole map from above and exits
+#[cfg(not(target_arch = "wasm32"))]
+fn render_map_overview(map_path: &Path, out: &Path) -> ! {
+    let (map, x, y, side, things, thing_texes) = match map::Map::from_file(map_path) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("{}: {e}", map_path.display());
+            std::process::exit(1);
+        }
     };
-    let mut world = World::new();
+    let img = overview::render(&map, (x, y, side), &things, &thing_texes);
+    match img.save(out) {
+        Ok(()) => {
+            println!("saved overview of {} to {}", map.name, out.display());
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("{}: {e}", out.display());
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    use winit::platform::web::WindowExtWebSys;
+
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    console_log::init_with_level(log::Level::Info).expect("logger to be set up");
+
+    let profile = Profile::load(None);
+    let settings_file = SettingsFile::load(profile.settings_path());
+    let event_loop = EventLoop::new();
+    let window = create_window(&event_loop, &settings_file.settings);
+
+    web_sys::window()
+        .and_then(|w| w.document())
+        .and_then(|d| d.body())
+        .and_then(|body| body.append_child(&window.canvas()).ok())
+        .expect("canvas to be added to the page");
+
+    // The GPU can only be set up asynchronously in the browser
+    wasm_bindgen_futures::spawn_local(async move {
+        let pixels = {
+            let window_size = window.inner_size();
+            let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
+            PixelsBuilder::new(settings_file.settings.width, settings_file.settings.height, surface_texture)
+                .enable_vsync(settings_file.settings.vsync)
+                .build_async()
+                .await
+                .expect("pixels to be created")
+        };
+        run(event_loop, window, pixels, settings_file, profile, None, None)
+    });
+}
+
+/// Saves what was recorded of the first level played as a demo
+fn save_demo(path: &Path, recorder: &Recorder) {
+    match std::fs::write(path, recorder.demo().to_src()) {
+        Ok(()) => info!("saved demo to {}", path.display()),
+        Err(e) => error!("could not save the demo to {}: {e}", path.display()),
+    }
+}
 
+/// Runs the game, playing survival mode on `survival_map` if given instead of the campaign
+///
+/// The first level played is recorded as a demo to `record_demo` if given, in which case the title screen is skipped.
+fn run(event_loop: EventLoop<()>, window: Window, mut pixels: Pixels, mut settings_file: SettingsFile, mut profile: Profile, survival_map: Option<String>, record_demo: Option<PathBuf>) -> ! {
+    let settings = settings_file.settings.clone();
+    let (width, height) = (settings.width, settings.height);
+    let mut input = WinitInputHelper::new();
+    grab_cursor(&window, true, settings.software_cursor);
+
+    let mut campaign = Campaign::load();
+    let map_path = survival_map.as_deref().unwrap_or(campaign.current_level());
+    let mut world = World::from_map_file(map_path, &settings).and_then(|mut world| {
+        if survival_map.is_some() {
+            world.start_survival()?;
+        }
+        Ok(world)
+    }).unwrap_or_else(|e| {
+        error!("could not play {map_path}: {e}");
+        std::process::exit(1);
+    });
+    // Wave of survival mode that was last announced
+    let mut announced_wave = 0;
+    let mut recording = record_demo.map(|path| (path, Recorder::new(&world)));
+    let mut title = (survival_map.is_none() && recording.is_none()).then(|| Title::new(&settings));
+    let mut console = Console::default();
+    let mut cheats = Cheats::default();
+    let mut intermission: Option<Intermission> = None;
+    // The next map being loaded, which the world is switched to once it's ready
+    let mut loading: Option<Loading> = None;
+    // Where the campaign goes on to once the map being loaded is ready, nowhere if it fails to load
+    let mut next_campaign: Option<Campaign> = None;
+    let mut audio = Audio::start();
+    let mut captions = hud::Captions::default();
+    let mut kill_feed = hud::KillFeed::default();
+    let cursor = hud::Cursor::load();
+    let mut toast = hud::Toast::default();
+    let mut show_objectives = false;
+    let mut inventory = Inventory::default();
+    let mut software = SoftwareRenderer::new(settings.supersampling);
+    software.reprojection = settings.reprojection.then(|| Reprojection::new(settings.reprojection_quality));
+    software.dynamic_resolution = settings.dynamic_resolution.then(|| pacing::DynamicResolution::new(settings.target_fps));
+    let mut top_down = TopDownRenderer::default();
+    let mut show_top_down = false;
+    // Smaller and without the rays, which would only clutter it
+    let mut tactical = TopDownRenderer::new(4., false);
+    tactical.seen_only = true;
+    let mut show_tactical = false;
+    // Whether the player walks slower and quieter, which is switched on and off
+    let mut sneaking = false;
+    #[cfg(feature = "gpu")]
+    let mut gpu_walls = settings.gpu_walls.then(|| gpu::GpuWalls::new(&pixels));
+    #[cfg(not(feature = "gpu"))]
+    if settings.gpu_walls {
+        log::warn!("gpu_walls is set, but the game was built without the gpu feature");
+    }
+
+    let mut limiter = pacing::FrameLimiter::new(settings.max_fps);
     let mut last_draw = Instant::now();
     let mut last_fpss = VecDeque::new();
 
     let mut last_update = last_draw;
+    // Time not simulated yet and mouse movement not applied yet
+    let mut leftover = 0.;
+    let mut mouse_dx = 0.;
 
     event_loop.run(move |event, _, control_flow| {
         // Draw the current frame
         if let Event::RedrawRequested(_) = event {
-            world.draw(Frame::from_pixels(&mut pixels));
+            // Where the software cursor is in the frame, when the cursor isn't grabbed
+            let cursor_pos = input.mouse()
+                .filter(|_| settings.software_cursor && console.open)
+                .and_then(|p| pixels.window_pos_to_pixel(p).ok());
+            let camera = world.camera_at((leftover / TICK).min(1.));
+            #[cfg(feature = "gpu")]
+            {
+                software.things_only = title.is_none() && !show_top_down && gpu_walls.as_mut().is_some_and(|gpu| gpu.prepare(&pixels, &world, &camera));
+            }
+            let mut frame = Frame::from_pixels(&mut pixels, width, height);
+            if let Some(loading) = &loading {
+                loading.draw(&mut frame);
+            } else if let Some(title) = &title {
+                title.draw(&mut frame, &mut software);
+            } else {
+                let renderer: &mut dyn Renderer = if show_top_down { &mut top_down } else { &mut software };
+                renderer.render(&world, &camera, &mut frame);
+                if show_tactical && !show_top_down && intermission.is_none() && !world.in_cutscene() {
+                    hud::draw_tactical(&mut frame, &mut tactical, &world, &camera);
+                }
+                if let Some(intermission) = &intermission {
+                    intermission.draw(&mut frame);
+                } else if world.in_cutscene() {
+                    hud::draw_letterbox(&mut frame, world.cutscene_subtitle());
+                } else {
+                    if settings.show_timer {
+                        hud::draw_level_timer(&mut frame, world.clock);
+                    }
+                    if let Some(time_left) = world.time_left() {
+                        hud::draw_countdown(&mut frame, time_left);
+                    }
+                    if let Some(health_left) = world.boss_health() {
+                        hud::draw_boss_bar(&mut frame, health_left);
+                    }
+                    hud::draw_score(&mut frame, world.score, world.lives);
+                    if world.player_armor > 0. {
+                        hud::draw_armor(&mut frame, world.player_armor);
+                    }
+                    if world.stamina() < 1. {
+                        hud::draw_stamina(&mut frame, world.stamina());
+                    }
+                    if world.unnoticed() {
+                        hud::draw_stealth(&mut frame, world.player_visibility(), world.noise());
+                    }
+                }
+                if show_objectives {
+                    hud::draw_objectives(&mut frame, world.objectives());
+                }
+                if let Some((dialogue, talk)) = world.talking() {
+                    hud::draw_dialogue(&mut frame, dialogue, talk);
+                }
+                if inventory.open && intermission.is_none() {
+                    inventory.draw(&mut frame, &world);
+                }
+                if settings.subtitles {
+                    captions.draw(&mut frame);
+                }
+                kill_feed.draw(&mut frame);
+            }
+            toast.draw(&mut frame);
+            if toast.log.open {
+                toast.log.draw(&mut frame);
+            }
+            console.draw(&mut frame);
+            if let Some((x, y)) = cursor_pos {
+                cursor.draw(&mut frame, x as u32, y as u32);
+            }
 
-            if pixels
-                .render()
+            limiter.wait();
+            let present_timer = Timer::start(Stage::Present);
+            #[cfg(feature = "gpu")]
+            let rendered = match &gpu_walls {
+                Some(gpu) if software.things_only => gpu.render(&pixels),
+                _ => pixels.render(),
+            };
+            #[cfg(not(feature = "gpu"))]
+            let rendered = pixels.render();
+            if rendered
                 .map_err(|e| error!("pixels.render() failed: {}", e))
                 .is_err()
             {
                 *control_flow = ControlFlow::Exit;
                 return;
             }
+            present_timer.stop();
+            profile::end_frame();
 
             let now = Instant::now();
             let fps = 1. / (now - last_draw).as_secs_f64();
@@ -69,10 +506,56 @@ fn main() -> Result<(), Error> {
                 last_fpss.pop_front();
             }
             let avg_fps = last_fpss.iter().copied().sum::<f64>() / last_fpss.len() as f64;
-            window.set_title(&format!("Ulvestein - FPS {avg_fps:.0}"));
+            if settings.show_fps {
+                window.set_title(&lang::tr_args("window.title_fps", &[("fps", &format!("{avg_fps:.0}"))]));
+            }
             last_draw = now;
         }
 
+        // Maps dropped onto the window are played right away, which is handy when making them,
+        // and so are packages, from their first level
+        if let Event::WindowEvent { event: WindowEvent::DroppedFile(path), .. } = &event {
+            let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().into_owned();
+            let loaded = if path.is_dir() || path.extension() == Some("zip".as_ref()) {
+                Some(package::Package::open(path).and_then(|package| {
+                    info!("playing {} ({} levels)", package.name, package.levels.len());
+                    assets::mount(package);
+                    campaign = Campaign::try_load()?;
+                    world.load_map(campaign.current_level(), &settings)
+                }))
+            } else if path.extension() == Some("txt".as_ref()) {
+                Some(world.load_map(path, &settings))
+            } else {
+                None
+            };
+            match loaded {
+                Some(Ok(())) => {
+                    intermission = None;
+                    toast.show(lang::tr_args("drop.loaded", &[("name", &name)]));
+                }
+                Some(Err(e)) => {
+                    error!("could not load {}: {e}", path.display());
+                    toast.show(lang::tr_args("drop.failed", &[("name", &name), ("error", &e)]));
+                }
+                None => toast.show(lang::tr_args("drop.not_a_map", &[("name", &name)])),
+            }
+            window.request_redraw();
+        }
+
+        if let Event::LoopDestroyed = event {
+            if let Some((path, recorder)) = &recording {
+                save_demo(path, recorder);
+            }
+            profile::dump("profile");
+            settings_file.settings.fov = world.fov.degrees();
+            settings_file.save();
+            // Kills on the unfinished level count too
+            if intermission.is_none() {
+                profile.stats.kills += world.kills;
+            }
+            profile.save();
+        }
+
         // Handle input events
         if input.update(&event) {
             let now = Instant::now();
@@ -84,30 +567,294 @@ fn main() -> Result<(), Error> {
                 return;
             }
 
+            toast.tick(delta);
+
             // Resize the window
             if let Some(size) = input.window_resized() {
                 pixels.resize_surface(size.width, size.height);
             }
 
-            let left = input.key_held(VirtualKeyCode::Left);
-            let right = input.key_held(VirtualKeyCode::Right);
-            let forwards = input.key_held(VirtualKeyCode::Up) || input.key_held(VirtualKeyCode::W);
-            let backwards = input.key_held(VirtualKeyCode::Down) || input.key_held(VirtualKeyCode::S);
-            let go_right = input.key_held(VirtualKeyCode::D);
-            let go_left = input.key_held(VirtualKeyCode::A);
+            if let Some(load) = &mut loading {
+                match load.poll(delta) {
+                    Some(Ok(fresh)) => {
+                        let transition = load.transition;
+                        world.switch_to(fresh, transition);
+                        loading = None;
+                        intermission = None;
+                        if survival_map.is_some() && transition == Transition::Restart {
+                            if let Err(e) = world.start_survival() {
+                                error!("could not start survival mode again: {e}");
+                                toast.show(lang::tr_args("survival.failed", &[("error", &e)]));
+                            }
+                        }
+                        if let Some(next) = next_campaign.take() {
+                            campaign = next;
+                            if campaign.in_secret_level() {
+                                profile.found_secret(&campaign.level_key());
+                            }
+                        }
+                    }
+                    // The intermission is still there to try again from
+                    Some(Err(e)) => {
+                        error!("could not load the next map: {e}");
+                        toast.show(lang::tr_args("loading.failed", &[("error", &e)]));
+                        loading = None;
+                        next_campaign = None;
+                    }
+                    None => (),
+                }
+                window.request_redraw();
+                last_update = now;
+                return;
+            }
+
+            // Pressing anything on the title starts the game, and during a demo goes back to the title
+            if let Some(screen) = &mut title {
+                if Bindings::any_pressed(&input) && screen.press() {
+                    title = None;
+                } else {
+                    screen.tick(delta, &settings);
+                }
+                window.request_redraw();
+                last_update = now;
+                return;
+            }
+
+            let bindings = &settings.bindings;
+
+            if bindings.pressed(&input, Action::Console) {
+                console.toggle();
+                grab_cursor(&window, !console.open, settings.software_cursor);
+            }
+            if console.open {
+                for c in input.text() {
+                    match c {
+                        TextChar::Char(c) => console.type_char(c),
+                        TextChar::Back => console.backspace(),
+                    }
+                }
+                if input.key_pressed(VirtualKeyCode::Return) {
+                    console.submit(&mut world, &profile, &settings);
+                }
+
+                if intermission.is_none() {
+                    step_world(&mut world, &mut leftover, delta, &mut 0., Controls::default(), recording.as_mut().map(|(_, r)| r));
+                }
+                window.request_redraw();
+                last_update = now;
+                return;
+            }
+
+            if let Some((exit, game_over)) = intermission.as_ref().map(|i| (i.exit, i.game_over)) {
+                if input.key_pressed(VirtualKeyCode::Space) || input.key_pressed(VirtualKeyCode::Return) {
+                    // The campaign only moves on once the next level has loaded
+                    let mut next = campaign.clone();
+                    match exit.map(|exit| next.advance(exit).map(str::to_owned)) {
+                        // A new game starts the campaign over
+                        None if game_over => {
+                            loading = Some(Loading::start(next.start_over().into(), Transition::NewGame, &settings));
+                            next_campaign = Some(next);
+                        }
+                        // Running out of time starts the level over
+                        None => loading = Some(Loading::start(world.map_path().to_owned(), Transition::Restart, &settings)),
+                        Some(Some(level)) => {
+                            loading = Some(Loading::start(level.into(), Transition::NextLevel, &settings));
+                            next_campaign = Some(next);
+                        }
+                        Some(None) => {
+                            info!("all episodes finished");
+                            *control_flow = ControlFlow::Exit;
+                            return;
+                        }
+                    }
+                }
+                window.request_redraw();
+                last_update = now;
+                return;
+            }
 
-            if input.key_pressed(VirtualKeyCode::N) {
+            // Cheat codes are looked for first, the keys they're typed with don't do anything else
+            let mut taken = Vec::new();
+            for c in input.text() {
+                let TextChar::Char(c) = c else { continue };
+                let typed = cheats.type_char(c);
+                if typed.taken() {
+                    taken.extend(cheats::letter_key(c).map(Button::Key));
+                }
+                if let Typed::Cheat(commands) = typed {
+                    info!("cheat activated");
+                    for command in commands {
+                        if let Err(e) = console::run_command(command, &mut world, &profile, &settings) {
+                            error!("cheat command {command} failed: {e}");
+                        }
+                    }
+                    toast.show(lang::tr("cheats.activated"));
+                }
+            }
+            // Intermissions took all the input above, so the log only opens while playing
+            if bindings.pressed_unless(&input, Action::MessageLog, &taken) {
+                toast.log.toggle();
+            }
+            // The world also stands still while reading back through the message log
+            if toast.log.open {
+                let back = bindings.pressed_os(&input, Action::Forwards) as i32 - bindings.pressed_os(&input, Action::Backwards) as i32;
+                toast.log.scroll(back + input.scroll_diff().round() as i32);
+                window.request_redraw();
+                last_update = now;
+                return;
+            }
+            if bindings.pressed_unless(&input, Action::Inventory, &taken) {
+                inventory.toggle();
+            }
+            // The world stands still while the inventory is open
+            if inventory.open {
+                let right = bindings.pressed_unless(&input, Action::TurnRight, &taken) || bindings.pressed_unless(&input, Action::StrafeRight, &taken);
+                let left = bindings.pressed_unless(&input, Action::TurnLeft, &taken) || bindings.pressed_unless(&input, Action::StrafeLeft, &taken);
+                inventory.switch_tab(right as i32 - left as i32);
+                window.request_redraw();
+                last_update = now;
+                return;
+            }
+
+            if bindings.pressed_unless(&input, Action::Sneak, &taken) {
+                sneaking = !sneaking;
+                info!("sneaking {}", if sneaking { "on" } else { "off" });
+            }
+            let controls = Controls {
+                turn_left: bindings.held(&input, Action::TurnLeft),
+                turn_right: bindings.held(&input, Action::TurnRight),
+                forwards: bindings.held(&input, Action::Forwards),
+                backwards: bindings.held(&input, Action::Backwards),
+                strafe_left: bindings.held(&input, Action::StrafeLeft),
+                strafe_right: bindings.held(&input, Action::StrafeRight),
+                mouse_dx: 0.,
+                // Right mouse button
+                zoom: bindings.held(&input, Action::Zoom),
+                sprint: bindings.held(&input, Action::Sprint),
+                // Sneaking is switched on and off instead of held
+                sneak: sneaking,
+            };
+
+            if bindings.pressed_unless(&input, Action::Use, &taken) {
+                world.interact();
+                if let Some((_, recorder)) = &mut recording {
+                    recorder.interact();
+                }
+            }
+            if bindings.pressed_unless(&input, Action::Fire, &taken) {
+                world.shoot();
+                if let Some((_, recorder)) = &mut recording {
+                    recorder.fire();
+                }
+            }
+            if world.talking().is_some() {
+                let step = bindings.pressed_unless(&input, Action::Backwards, &taken) as i32 - bindings.pressed_unless(&input, Action::Forwards, &taken) as i32;
+                world.select_choice(step);
+                if let Some((_, recorder)) = &mut recording {
+                    recorder.select_choice(step);
+                }
+            }
+            if bindings.pressed_unless(&input, Action::Noclip, &taken) {
                 info!("noclip {}", if world.clip { "on" } else { "off" });
                 world.clip = !world.clip;
             }
-            if input.key_pressed_os(VirtualKeyCode::Plus) {
+            if bindings.pressed_os(&input, Action::FovUp) {
                 world.fov.change_fov(5.);
             }
-            if input.key_pressed_os(VirtualKeyCode::Minus) {
+            if bindings.pressed_os(&input, Action::FovDown) {
                 world.fov.change_fov(-5.);
             }
+            if bindings.pressed_unless(&input, Action::Objectives, &taken) {
+                show_objectives = !show_objectives;
+            }
+            if bindings.pressed_unless(&input, Action::TopDown, &taken) {
+                show_top_down = !show_top_down;
+            }
+            if bindings.pressed_unless(&input, Action::Tactical, &taken) {
+                show_tactical = !show_tactical;
+            }
 
-            world.update(delta, left, right, forwards, backwards, go_left, go_right);
+            mouse_dx += input.mouse_diff().0;
+            step_world(&mut world, &mut leftover, delta, &mut mouse_dx, controls, recording.as_mut().map(|(_, r)| r));
+            let camera = world.camera();
+            // Taken before a death in survival mode restarts the level
+            for event in world.events.drain(..) {
+                match event {
+                    GameEvent::SoundEmitted(sound) => {
+                        // Captions go by how loud the sound is in the world, not how loud the game is set to be
+                        let heard = sound.heard_by(camera, &world.map, 1.);
+                        if settings.subtitles {
+                            captions.hear(&heard);
+                        }
+                        audio.play(&world.map.sound_file_name(heard.sound), &heard, settings.volume);
+                    }
+                    GameEvent::Killed(kill) => {
+                        if let Some(killer) = &kill.killer {
+                            profile.credit_kill(killer);
+                        }
+                        kill_feed.hear(&kill);
+                    }
+                    GameEvent::Damage { victim, amount, attacker } => {
+                        profile.count_damage(amount, victim == EntityId::PLAYER, attacker == Some(EntityId::PLAYER));
+                    }
+                    GameEvent::PickedUp(Pickup::BonusTime(seconds)) => {
+                        toast.show(lang::tr_args("challenge.bonus_time", &[("seconds", &format!("{seconds:.0}"))]));
+                    }
+                    GameEvent::PickedUp(Pickup::Speed(_)) => toast.show(lang::tr("pickups.speed")),
+                    GameEvent::PickedUp(Pickup::Invulnerable(_)) => toast.show(lang::tr("pickups.invulnerable")),
+                    GameEvent::PickedUp(Pickup::Armor(points)) => {
+                        toast.show(lang::tr_args("pickups.armor", &[("points", &format!("{points:.0}"))]));
+                    }
+                    GameEvent::PickedUp(Pickup::Treasure(points)) => {
+                        toast.show(lang::tr_args("pickups.treasure", &[("points", &points)]));
+                    }
+                    GameEvent::ExtraLife => toast.show(lang::tr("pickups.extra_life")),
+                    GameEvent::CheckpointReached => toast.show(lang::tr("lives.checkpoint")),
+                    GameEvent::ObjectiveCompleted(i) => {
+                        let objective = &world.map.objectives()[i].text;
+                        toast.show(lang::tr_args("objectives.completed", &[("objective", objective)]));
+                    }
+                    // Shown as the caption of the sound the door makes
+                    GameEvent::DoorChanged { .. } => (),
+                    GameEvent::TriggerFired(_) => (),
+                    GameEvent::TextureFailed(e) => toast.show(lang::tr_args("loading.texture_failed", &[("error", &e)])),
+                    GameEvent::DialoguePage { dialogue, page } => toast.log.push(world.map.dialogue(dialogue).pages[page].clone()),
+                }
+            }
+            if let Some(survival) = world.survival {
+                if survival.wave != announced_wave {
+                    announced_wave = survival.wave;
+                    toast.show(lang::tr_args("survival.wave", &[("wave", &survival.wave)]));
+                }
+                // Dying ends the run and starts a new one
+                if world.player_health <= 0. {
+                    let waves = survival.survived();
+                    let most = profile.survived(survival_map.as_deref().unwrap_or_default(), waves);
+                    toast.show(lang::tr_args("survival.over", &[("waves", &waves), ("most", &most)]));
+                    // The run is over, the next one starts once the map has loaded again
+                    world.survival = None;
+                    loading = Some(Loading::start(world.map_path().to_owned(), Transition::Restart, &settings));
+                    announced_wave = 0;
+                }
+            }
+            captions.tick(delta);
+            kill_feed.tick(delta);
+            if let Some(exit) = world.exited {
+                let key = campaign.level_key();
+                let previous_best = profile.best_time(&key);
+                profile.finish_level(&key, world.clock, world.kills);
+                intermission = Some(Intermission::new(exit, &world.map.name, world.clock, previous_best, world.kills, world.treasure_percent()));
+            } else if world.out_of_time {
+                intermission = Some(Intermission::out_of_time(&world.map.name, world.clock, world.kills));
+            } else if world.game_over {
+                intermission = Some(Intermission::game_over(&world.map.name, world.clock, world.kills));
+            }
+            // Only the first level is recorded
+            if intermission.is_some() {
+                if let Some((path, recorder)) = recording.take() {
+                    save_demo(&path, &recorder);
+                }
+            }
             window.request_redraw();
             last_update = now;
         }
@@ -1,37 +1,547 @@
-use std::{path::Path, fs::File, io::{BufReader, BufRead}, collections::HashMap};
+use std::{path::Path, collections::HashMap, sync::atomic::{AtomicU32, Ordering}};
 
-use crate::{vec::*, Texture, world::thing::Thing};
+use crate::{vec::*, sound::Sound, tex::{Texture, Colour, decode, grade::ColourGrade}, world::{thing::Thing, pickups::Pickup, daylight::{DayNight, NAMED_HOURS}, doors::DoorChange, boss::Boss, enemy::Enemy, spawner::Spawner, faction::Faction}, assets};
+
+/// Returns an error with the message if the condition doesn't hold, for the map parsers
+macro_rules! ensure {
+    ($cond:expr, $($problem:tt)+) => {
+        match $cond {
+            true => (),
+            false => return Err(format!($($problem)+)),
+        }
+    };
+}
 
 mod mat;
 mod ray_caster;
+mod dialogue;
+mod cutscene;
+mod chunks;
+mod triggers;
+pub mod check;
 
 pub use ray_caster::*;
 pub use mat::*;
+pub use dialogue::{Dialogue, Choice};
+pub use cutscene::{Cutscene, Shot};
+use chunks::Chunked;
+pub use triggers::{Switch, Trigger, TriggerAction, Logic, LogicNode};
 
 #[derive(Debug, Clone)]
 pub struct Map {
     pub name: Box<str>,
-    textures: Vec<(Texture, Texture)>,
+    /// How far rays are cast before the fog colour is drawn instead
+    pub render_distance: Option<f32>,
+    /// Seconds the player has to finish the level in, if it's a timed challenge
+    pub time_limit: Option<f32>,
+    /// How long a day takes and the hour it starts at, if the map has a time of day
+    pub day_night: Option<DayNight>,
+    pub fog_colour: Colour,
+    /// Colour grade of the whole view, for the mood of the map
+    pub grade: Option<ColourGrade>,
+    /// How many times a ray can be reflected, the mirror after the last reflection is drawn as an opaque wall
+    pub max_reflections: usize,
+    /// Going off one edge of the map comes back on the opposite edge
+    pub wrap: bool,
+    textures: Vec<MatTextures>,
+    /// Wall textures that are stand-ins until they've been decoded in the background, and where they go
+    pending_textures: Vec<(TexSlot, Box<str>)>,
     properties: Vec<Properties>,
+    /// Cells of every layer, one layer after the other from the bottom up
     grid: Vec<Mat>,
+    /// What the floor of each cell is like on each layer
+    floors: Vec<Chunked<Floor>>,
+    /// Texture drawn on conveyor floors, only loaded if there are any
+    conveyor_tex: Option<Texture>,
+    /// What friendly things say, things refer to these by index
+    dialogues: Vec<Dialogue>,
+    cutscenes: HashMap<String, Cutscene>,
+    /// What the player has to do, things refer to these by index
+    objectives: Vec<Objective>,
+    /// Name of each thing texture, which is what things are called in the kill feed
+    thing_names: Vec<String>,
+    /// Things bosses and spawners create, they refer to these by index
+    summons: Vec<Thing>,
+    /// Names of the sounds doors make instead of the usual ones, doors refer to these by index
+    door_sounds: Vec<String>,
+    /// Names of the signals of switches, plates and logic, triggers and logic refer to these by index
+    signals: Vec<String>,
+    /// Logic nodes in the order they're worked out in
+    logic: Vec<LogicNode>,
+    triggers: Vec<Trigger>,
     width: i32,
+    height: i32,
+    /// The layer everything is currently happening on
+    layer: usize,
+    /// Doors can't be walked through, like while an elevator is moving
+    doors_locked: bool,
+    /// Seconds into the game, which decides where moving blocks are
+    time: f32,
+    /// Changes whenever a cell or wall texture does and is different on every map,
+    /// so what keeps its own copy of the cells knows when it's out of date
+    revision: u32,
+}
+
+/// Hands out revisions so that no two maps or changes to them share one
+static NEXT_REVISION: AtomicU32 = AtomicU32::new(0);
+
+fn next_revision() -> u32 {
+    NEXT_REVISION.fetch_add(1, Ordering::Relaxed)
 }
 
+/// Light and dark textures of a material, cycled through every `frame_time` seconds if there are multiple frames
+///
+/// Multiple frames without a frame time are variants, one of which is picked for each cell.
+/// A side with its own texture always uses that instead
+#[derive(Debug, Clone)]
+struct MatTextures {
+    frames: Vec<(Texture, Texture)>,
+    frame_time: f32,
+    /// Indexed by `Side`
+    sides: [Option<Texture>; 4],
+}
+
+/// Where a wall texture decoded in the background goes
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TexSlot {
+    /// The light and/or dark texture of a frame of a material
+    Frame { mat: usize, frame: usize, light: bool, dark: bool },
+    /// The texture of a side of a material
+    Side { mat: usize, side: Side },
+}
+
+/// How the map looks to rays of light
+impl Medium for Map {
+    type Mat = Mat;
+
+    fn classify(&self, x: i32, y: i32) -> CellClass<Mat> {
+        let Some(mat) = self.get(x, y) else {
+            return CellClass::Void;
+        };
+        let props = self.props(&mat);
+        // Doors that are all the way open are out of the way
+        if props.door && mat.state == u8::MAX {
+            return CellClass::Empty(mat);
+        }
+        // Partly open doors look like what is left of them to bump into
+        if props.door {
+            if let Shape::Line(a, b) = self.shape(x, y) {
+                return CellClass::Line(mat, a, b);
+            }
+        }
+        // Rays go under moving blocks that aren't all the way down
+        let extent = self.extent(mat, self.time);
+        if extent <= 0. {
+            CellClass::Empty(mat)
+        } else if extent < 1. {
+            CellClass::PassThrough(mat)
+        } else if !props.transparent {
+            CellClass::Terminator(mat)
+        } else if !props.solid {
+            CellClass::Empty(mat)
+        } else if props.reflective {
+            CellClass::Reflector(mat)
+        } else if let Some(index) = props.refraction {
+            CellClass::Refractor(mat, index)
+        } else {
+            CellClass::PassThrough(mat)
+        }
+    }
+}
+
+/// The map as something to bump into, where only solid materials stop rays
+pub struct Solid<'a>(pub &'a Map);
+
+impl Medium for Solid<'_> {
+    type Mat = Mat;
+
+    fn classify(&self, x: i32, y: i32) -> CellClass<Mat> {
+        match self.0.get(x, y) {
+            None => CellClass::Void,
+            Some(mat) => match self.0.shape(x, y) {
+                Shape::Full => CellClass::Terminator(mat),
+                // Lines are found separately, since a ray can go through the cell without crossing it
+                Shape::Line(..) | Shape::Open => CellClass::Empty(mat),
+            },
+        }
+    }
+}
+
+/// What part of a cell stops things from moving through it
+#[derive(Debug, Clone, Copy)]
+pub enum Shape {
+    Open,
+    Full,
+    /// A wall as thin as a line between two points, relative to the top left corner of the cell
+    Line(Point2, Point2),
+}
+
+/// The map as something sound goes through, where walls and doors muffle it
+struct Muffling<'a>(&'a Map);
+
+impl Medium for Muffling<'_> {
+    type Mat = Mat;
+
+    fn classify(&self, x: i32, y: i32) -> CellClass<Mat> {
+        match self.0.get(x, y) {
+            None => CellClass::Void,
+            Some(mat) if self.0.props(&mat).solid || self.0.props(&mat).door => CellClass::PassThrough(mat),
+            Some(mat) => CellClass::Empty(mat),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 struct Properties {
     solid: bool,
     transparent: bool,
     reflective: bool,
     door: bool,
+    /// Doors the player can't open
+    locked: bool,
+    /// Index of the sounds the door makes instead of the usual ones
+    door_sound: Option<usize>,
+    switch: Option<Switch>,
+    /// Hit points of breakable materials
+    hp: Option<u8>,
+    /// Touching the material finishes the level
+    exit: Option<Exit>,
+    /// Colour that reflections in the material are tinted towards and by how much (out of 255)
+    tint: Option<(Colour, u8)>,
+    /// Refractive index of see-through materials that bend rays
+    refraction: Option<f32>,
+    /// What walking through the material sounds like instead of the floor under it
+    surface: Option<Surface>,
+    /// Walking onto the material takes the player to another layer
+    stairs: Option<Stairs>,
+    /// Using the material takes the player to the next elevator above or below
+    elevator: bool,
+    /// The material comes down from the ceiling and goes back up on a schedule
+    moving: Option<Moving>,
+}
+
+/// Schedule of a block that moves up and down
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Moving {
+    /// Seconds it takes to go down and back up
+    period: f32,
+    /// Seconds into the period the block is at when the level starts
+    offset: f32,
+}
+
+const DEFAULT_REFRACTIVE_INDEX: f32 = 1.5;
+const DEFAULT_MAX_REFLECTIONS: usize = 4;
+/// How far down a moving block has to be to stop things, which gets crushed if they're in its way
+const CRUSHING_EXTENT: f32 = 0.6;
+/// Line of the grid that starts the next layer up
+const LAYER_SEPARATOR: &str = "---";
+
+/// Words that can start a line of the header
+pub(super) const HEADERS: [&str; 17] = [
+    "render_distance", "fog", "grade", "day_night", "reflections", "surface", "floor", "conveyor", "hazard", "poison", "checkpoint", "plate", "trigger", "logic",
+    "objective", "time_limit", "wrap",
+];
+/// Properties of materials and things that are just a word
+pub(super) const FLAGS: [&str; 24] = [
+    "door", "locked", "solid", "nonsolid", "walkthrough", "transparent", "seethrough", "opaque", "reflective",
+    "refractive", "thing", "dynamic", "prop", "explosive", "exit", "secret_exit", "stairs_up", "stairs_down", "elevator", "anim", "variants",
+    "npc", "boss", "enemy",
+];
+/// Properties of materials and things that are given as `key=value`
+pub(super) const PROPERTIES: [&str; 22] = [
+    "north", "south", "west", "east", "surface", "hp", "index", "time", "speed", "invulnerable", "armor", "treasure", "scale",
+    "summon", "spawner", "faction", "facing", "door_sound", "switch", "tint", "moving", "dialogue",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    Normal,
+    /// Leads to the secret level of the episode if there is one
+    Secret,
+}
+
+/// What the floor of a cell is made of and what it does to what's on it
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Floor {
+    surface: Surface,
+    conveyor: Option<Conveyor>,
+    /// Damage done to the player every `HAZARD_INTERVAL` seconds they stand on it
+    hazard: Option<f32>,
+    /// Damage per second and for how many seconds the player is poisoned by stepping on it
+    poison: Option<(f32, f32)>,
+    /// Where the player comes back after dying once they've stepped on it
+    checkpoint: bool,
+    /// Signal that is on while something stands on it
+    plate: Option<usize>,
+}
+
+/// Floor that moves everything on it along
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Conveyor {
+    pub dir: Side,
+    /// Units per second
+    pub speed: f32,
+}
+
+/// Something the player has to do on the level, which is about every thing drawn with a certain character
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Objective {
+    pub goal: Goal,
+    /// What the objectives overlay says, like "Destroy the generator"
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Goal {
+    /// The things have to be destroyed
+    Destroy,
+    /// The things have to be picked up by walking into them
+    Find,
+}
+
+impl Goal {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "destroy" => Some(Goal::Destroy),
+            "find" => Some(Goal::Find),
+            _ => None,
+        }
+    }
 }
 
+/// Seconds between each time a hazardous floor hurts
+pub const HAZARD_INTERVAL: f32 = 0.5;
+const DEFAULT_CONVEYOR_SPEED: f32 = 1.5;
+const CONVEYOR_TEX: &str = "tex/conveyor.png";
+
+/// Which way stairs go, they lead to the same place on the layer above or below
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stairs {
+    Up,
+    Down,
+}
+
+/// What something is made of, which decides what walking on it sounds like
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Surface {
+    Stone,
+    Metal,
+    Wood,
+    /// Shallow liquid that is slow to wade through
+    Water,
+    /// Slippery, so it takes a while to speed up and slow down
+    Ice,
+}
+
+impl Surface {
+    const ALL: [Surface; 5] = [Surface::Stone, Surface::Metal, Surface::Wood, Surface::Water, Surface::Ice];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Surface::Stone => "stone",
+            Surface::Metal => "metal",
+            Surface::Wood => "wood",
+            Surface::Water => "water",
+            Surface::Ice => "ice",
+        }
+    }
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.name() == name)
+    }
+    fn parse(name: &str) -> Result<Self, String> {
+        Self::from_name(name).ok_or_else(|| format!("unknown surface {name}"))
+    }
+    pub const fn is_liquid(self) -> bool {
+        matches!(self, Surface::Water)
+    }
+    /// How fast walking on the surface is compared to a dry floor
+    pub const fn speed(self) -> f32 {
+        match self {
+            Surface::Water => 0.55,
+            _ => 1.,
+        }
+    }
+    /// How quickly the speed of something on the surface changes to the speed it's trying to go, per second
+    ///
+    /// Only slippery surfaces don't change it right away
+    pub const fn traction(self) -> f32 {
+        match self {
+            Surface::Ice => 1.5,
+            _ => f32::INFINITY,
+        }
+    }
+}
+
+/// A map on the layer the player starts on, along with where the player starts, the things on each layer and the textures of the things
+pub type LoadedMap = (Map, i32, i32, Side, Vec<Vec<Thing>>, Vec<Texture>);
+
 impl Map {
-    pub fn from_file<P: AsRef<Path>>(path: P) -> (Self, i32, i32, Side, Vec<Thing>, Vec<Texture>) {
-        let f = BufReader::new(File::open(path).unwrap());
-        let mut lines = f.lines();
+    /// Loads a map with all of its textures, or tells what is wrong with it
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<LoadedMap, String> {
+        Self::from_file_with_progress(path, false, |_| ())
+    }
+    /// Like `from_file`, telling how much of the map (from 0 to 1) has been loaded as it goes.
+    /// With `background`, the wall textures are stand-ins until they're decoded from `take_pending_textures`
+    pub fn from_file_with_progress<P: AsRef<Path>>(path: P, background: bool, progress: impl FnMut(f32)) -> Result<LoadedMap, String> {
+        let src = assets::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::from_src_with_progress(&src, background, progress)
+    }
+    /// Like `from_file`, but with the map already read
+    pub fn from_src(src: &str) -> Result<LoadedMap, String> {
+        Self::from_src_with_progress(src, false, |_| ())
+    }
+    fn from_src_with_progress(src: &str, background: bool, mut progress: impl FnMut(f32)) -> Result<LoadedMap, String> {
+        // How far through the source the parsing is, the textures are loaded as their lines are parsed
+        let mut read = 0;
+        let mut lines = src.lines()
+            .inspect(|line| {
+                read += line.len() + 1;
+                progress((read as f32 / src.len() as f32).min(1.));
+            })
+            .peekable();
 
-        let name = lines.next().unwrap().unwrap().trim().to_owned().into_boxed_str();
-        assert_eq!(lines.next().unwrap().unwrap().trim(), "");
+        let name = lines.next().ok_or("map is empty")?.trim().to_owned().into_boxed_str();
+
+        let mut render_distance = None;
+        let mut time_limit = None;
+        let mut day_night = None;
+        let mut fog_colour = Colour::new(0, 0, 0);
+        let mut grade = None;
+        let mut max_reflections = DEFAULT_MAX_REFLECTIONS;
+        let mut wrap = false;
+        let mut floor_surface = Surface::Stone;
+        let mut floor_map = HashMap::new();
+        let mut conveyor_tex = None;
+        let mut pending_textures = Vec::new();
+        // Wall textures are stand-ins to be decoded later when decoding in the background
+        let mut load_wall_tex = |path: &str, slot: TexSlot| if background {
+            pending_textures.push((slot, path.into()));
+            Ok(decode::placeholder(path))
+        } else {
+            Texture::try_from_file(path)
+        };
+        let mut objectives = Vec::new();
+        // Character of the things each objective is about
+        let mut objective_chars = Vec::new();
+        let mut signals = Vec::new();
+        // Names of the signals of each trigger and what it does, with the material of doors as a character until they're known
+        let mut trigger_lines = Vec::new();
+        // Kind of each logic node, its signals and what follows them
+        let mut logic_lines = Vec::new();
+
+        loop {
+            match lines.next().unwrap_or_default().trim() {
+                "" => break,
+                s => {
+                    let mut elements = s.split_whitespace();
+                    let key = elements.next().unwrap();
+                    if !HEADERS.contains(&key) {
+                        return Err(format!("unknown map header {key}"));
+                    }
+                    match key {
+                        "render_distance" => {
+                            let distance = elements.next().ok_or("render distance has to be given")?;
+                            render_distance = Some(distance.parse::<f32>().map_err(|_| "render distance has to be a number")?);
+                        }
+                        "day_night" => {
+                            let length = elements.next().ok_or("length of a day has to be given")?.parse::<f32>().map_err(|_| "length of a day has to be a number")?;
+                            ensure!(length > 0., "length of a day has to be positive");
+                            let hour = elements.next().map(|h| h.parse::<f32>().map_err(|_| "hour of the day has to be a number")).transpose()?;
+                            day_night = Some(DayNight::new(length, hour));
+                        }
+                        "time_limit" => {
+                            let limit = elements.next().ok_or("time limit has to be given")?.parse::<f32>().map_err(|_| "time limit has to be a number")?;
+                            ensure!(limit > 0., "time limit has to be positive");
+                            time_limit = Some(limit);
+                        }
+                        "fog" => {
+                            let rgb = elements.map(str::parse::<u8>).collect::<Result<Vec<_>, _>>().map_err(|_| "fog colour has to be numbers from 0 to 255")?;
+                            let &[r, g, b] = &rgb[..] else {
+                                return Err("fog colour has to have three components".to_owned());
+                            };
+                            fog_colour = Colour::new(r, g, b);
+                        }
+                        "grade" => {
+                            let numbers = elements.map(str::parse::<f32>).collect::<Result<Vec<_>, _>>().map_err(|_| "colour grade has to be numbers")?;
+                            let &[r, g, b, contrast, saturation] = &numbers[..] else {
+                                return Err("colour grade needs a tint colour, a contrast and a saturation".to_owned());
+                            };
+                            ensure!([r, g, b].iter().all(|c| (0. ..=255.).contains(c)), "colour grade tint has to be numbers from 0 to 255");
+                            grade = Some(ColourGrade { tint: Colour::new(r as u8, g as u8, b as u8), contrast, saturation });
+                        }
+                        "reflections" => {
+                            let depth = elements.next().ok_or("reflection depth has to be given")?;
+                            max_reflections = depth.parse().map_err(|_| "reflection depth has to be a whole number")?;
+                        }
+                        "surface" => {
+                            let surface = elements.next().ok_or("floor surface has to be given")?;
+                            floor_surface = Surface::parse(surface)?;
+                        }
+                        "floor" => {
+                            let c = elements.next().and_then(|c| c.chars().next()).ok_or("floor needs a character")?;
+                            let surface = Surface::parse(elements.next().ok_or("floor needs a surface")?)?;
+                            floor_map.insert(c, Floor { surface, conveyor: None, hazard: None, poison: None, checkpoint: false, plate: None });
+                        }
+                        "conveyor" => {
+                            let c = elements.next().and_then(|c| c.chars().next()).ok_or("conveyor needs a character")?;
+                            let dir = elements.next().and_then(Side::from_direction).ok_or("conveyor has to go north, south, west or east")?;
+                            let speed = elements.next().map_or(Ok(DEFAULT_CONVEYOR_SPEED), |s| s.parse().map_err(|_| "conveyor speed has to be a number"))?;
+                            floor_map.insert(c, Floor { surface: Surface::Metal, conveyor: Some(Conveyor { dir, speed }), hazard: None, poison: None, checkpoint: false, plate: None });
+                            if conveyor_tex.is_none() {
+                                conveyor_tex = Some(Texture::try_from_file(CONVEYOR_TEX)?);
+                            }
+                        }
+                        "objective" => {
+                            let goal = elements.next().and_then(Goal::from_name).ok_or("objective has to be to destroy or find something")?;
+                            let c = elements.next().and_then(|c| c.chars().next()).ok_or("objective needs a character")?;
+                            let text = elements.collect::<Vec<_>>().join(" ");
+                            ensure!(!text.is_empty(), "objective {c} needs a text");
+                            objectives.push(Objective { goal, text });
+                            objective_chars.push(c);
+                        }
+                        "hazard" => {
+                            let c = elements.next().and_then(|c| c.chars().next()).ok_or("hazard needs a character")?;
+                            let damage = elements.next().ok_or("hazard needs a damage")?.parse().map_err(|_| "hazard damage has to be a number")?;
+                            let surface = elements.next().map_or(Ok(Surface::Stone), Surface::parse)?;
+                            floor_map.insert(c, Floor { surface, conveyor: None, hazard: Some(damage), poison: None, checkpoint: false, plate: None });
+                        }
+                        "poison" => {
+                            let c = elements.next().and_then(|c| c.chars().next()).ok_or("poison needs a character")?;
+                            let damage = elements.next().ok_or("poison needs a damage per second")?.parse().map_err(|_| "poison damage has to be a number")?;
+                            let seconds = elements.next().ok_or("poison needs to last some seconds")?.parse().map_err(|_| "poison time has to be a number")?;
+                            let surface = elements.next().map_or(Ok(Surface::Stone), Surface::parse)?;
+                            floor_map.insert(c, Floor { surface, conveyor: None, hazard: None, poison: Some((damage, seconds)), checkpoint: false, plate: None });
+                        }
+                        "checkpoint" => {
+                            let c = elements.next().and_then(|c| c.chars().next()).ok_or("checkpoint needs a character")?;
+                            let surface = elements.next().map_or(Ok(Surface::Stone), Surface::parse)?;
+                            floor_map.insert(c, Floor { surface, conveyor: None, hazard: None, poison: None, checkpoint: true, plate: None });
+                        }
+                        "plate" => {
+                            let c = elements.next().and_then(|c| c.chars().next()).ok_or("plate needs a character")?;
+                            let signal = triggers::signal_index(&mut signals, elements.next().ok_or("plate needs a signal")?)?;
+                            let surface = elements.next().map_or(Ok(Surface::Metal), Surface::parse)?;
+                            floor_map.insert(c, Floor { surface, conveyor: None, hazard: None, poison: None, checkpoint: false, plate: Some(signal) });
+                        }
+                        "trigger" => {
+                            let names: Vec<String> = elements.next().ok_or("trigger needs signals")?.split('+').map(str::to_owned).collect();
+                            let action = elements.next().ok_or("trigger needs an action")?;
+                            let arg = elements.next().ok_or("trigger action needs an argument")?.to_owned();
+                            trigger_lines.push((names, action.to_owned(), arg));
+                        }
+                        "logic" => {
+                            let kind = elements.next().ok_or("logic needs a kind")?.to_owned();
+                            let outputs = elements.next().ok_or("logic needs a signal")?.split('+')
+                                .map(|name| triggers::signal_index(&mut signals, name))
+                                .collect::<Result<Vec<_>, _>>()?;
+                            logic_lines.push((kind, outputs, elements.map(str::to_owned).collect::<Vec<_>>()));
+                        }
+                        "wrap" => wrap = true,
+                        key => return Err(format!("map header {key} can't be read")),
+                    }
+                }
+            }
+        }
 
         let mut textures = Vec::new();
         let mut properties = Vec::new();
@@ -43,23 +553,78 @@ impl Map {
         material_map.insert('v', Mat::air());
         let mut thing_map = HashMap::new();
         let mut thing_texes = Vec::new();
+        let mut thing_names = Vec::new();
+        let mut door_sounds = Vec::new();
+        // Names of the dialogues of friendly things, which come last in the file
+        let mut npc_dialogues = Vec::new();
+        // Bosses and spawners, the characters of the things they create and whether they're spawners
+        let mut summoners = Vec::new();
 
         loop {
-            match lines.next().unwrap().unwrap().trim() {
+            match lines.next().unwrap_or_default().trim() {
                 "" => break,
                 s => {
-                    let mut elements = s.split_whitespace();
+                    let mut elements: Vec<_> = s.split_whitespace().collect();
                     // TODO: check char length
-                    let c = elements.next().unwrap().chars().next().unwrap();
-                    let texture_dark = elements.next_back().unwrap();
-                    let texture = elements.next_back().unwrap();
+                    let c = elements.remove(0).chars().next().unwrap();
+
+                    // `anim <frame time>` is followed by the frames instead of the light and dark textures,
+                    // `variants` by pairs of light and dark textures to pick between for each cell
+                    let mut frame_time = 0.;
+                    let mut variants = false;
+                    let frames = if let Some(i) = elements.iter().position(|&e| e == "variants") {
+                        variants = true;
+                        let frames = elements.split_off(i + 1);
+                        elements.truncate(i);
+                        ensure!(!frames.is_empty() && frames.len() % 2 == 0, "variants of {c} have to be pairs of textures");
+                        frames
+                    } else if let Some(i) = elements.iter().position(|&e| e == "anim") {
+                        frame_time = elements.get(i + 1).ok_or("animation needs a frame time")?
+                            .parse::<f32>().map_err(|_| "frame time has to be a number")?;
+                        ensure!(frame_time > 0., "frame time of {c} has to be positive");
+                        let frames = elements.split_off(i + 2);
+                        elements.truncate(i);
+                        ensure!(!frames.is_empty(), "animation of {c} has no frames");
+                        frames
+                    } else {
+                        ensure!(elements.len() >= 2, "{c} needs two textures");
+                        elements.split_off(elements.len() - 2)
+                    };
+                    let animated = frame_time > 0.;
+                    let (texture, texture_dark) = (frames[0], *frames.last().unwrap());
 
                     let (mut solid, mut transparent, mut reflective, mut door) = (true, false, false, false);
-                    let mut thing = false;
+                    let (mut locked, mut door_sound) = (false, None);
+                    let mut switch = None;
+                    let (mut thing, mut dynamic, mut prop, mut explosive) = (false, false, false, false);
+                    let mut sides: [Option<Texture>; 4] = Default::default();
+                    let mut hp = None;
+                    let mut pickup = None;
+                    let mut boss = false;
+                    let mut scale = None;
+                    let mut summon = None;
+                    let mut enemy = false;
+                    let mut facing = None;
+                    let mut spawner = None;
+                    let mut faction = None;
+                    let mut exit = None;
+                    let mut tint = None;
+                    let mut refraction = None;
+                    let mut surface = None;
+                    let mut stairs = None;
+                    let mut elevator = false;
+                    let mut moving = None;
+                    let (mut npc, mut dialogue) = (false, None);
 
                     for property in elements {
+                        match property.split_once('=') {
+                            None if !FLAGS.contains(&property) => return Err(format!("unknown property {property} of {c}")),
+                            Some((key, _)) if !PROPERTIES.contains(&key) => return Err(format!("unknown property {property} of {c}")),
+                            _ => (),
+                        }
                         match property {
                             "door" => door = true,
+                            "locked" => locked = true,
                             "solid" => solid = true,
                             "nonsolid" | "walkthrough" => solid = false,
                             "transparent" | "seethrough" => transparent = true,
@@ -68,30 +633,210 @@ impl Map {
                                 transparent = true;
                                 reflective = true;
                             }
+                            "refractive" => {
+                                transparent = true;
+                                refraction = refraction.or(Some(DEFAULT_REFRACTIVE_INDEX));
+                            }
                             "thing" => thing = true,
-                            _ => panic!("uknown property {property} of texture {texture}"),
+                            "dynamic" => dynamic = true,
+                            "prop" => {
+                                thing = true;
+                                prop = true;
+                            }
+                            "explosive" => explosive = true,
+                            "enemy" => {
+                                thing = true;
+                                dynamic = true;
+                                enemy = true;
+                            }
+                            "boss" => {
+                                thing = true;
+                                dynamic = true;
+                                boss = true;
+                            }
+                            "npc" => {
+                                thing = true;
+                                npc = true;
+                            }
+                            "stairs_up" | "stairs_down" => {
+                                solid = false;
+                                stairs = Some(if property == "stairs_up" { Stairs::Up } else { Stairs::Down });
+                            }
+                            "elevator" => {
+                                solid = false;
+                                elevator = true;
+                            }
+                            "exit" => exit = Some(Exit::Normal),
+                            "secret_exit" => exit = Some(Exit::Secret),
+                            _ if property.starts_with("tint=") => {
+                                let rgba = property[5..].split(',').map(str::parse::<u8>).collect::<Result<Vec<_>, _>>().map_err(|_| "tint has to be numbers from 0 to 255")?;
+                                let &[r, g, b, a] = &rgba[..] else {
+                                    return Err("tint needs a colour and a strength".to_owned());
+                                };
+                                tint = Some((Colour::new(r, g, b), a));
+                            }
+                            _ if property.starts_with("index=") => {
+                                let index = property[6..].parse::<f32>().map_err(|_| "refractive index has to be a number")?;
+                                ensure!(index > 0., "refractive index has to be positive");
+                                refraction = Some(index);
+                            }
+                            _ if property.starts_with("surface=") => {
+                                surface = Some(Surface::parse(&property[8..])?);
+                            }
+                            _ if property.starts_with("switch=") => switch = Some(triggers::parse_switch(&property[7..], &mut signals)?),
+                            _ if property.starts_with("door_sound=") => {
+                                let name = &property[11..];
+                                ensure!(!name.is_empty(), "door sound needs a name");
+                                door_sound = Some(door_sounds.iter().position(|n| n == name).unwrap_or_else(|| {
+                                    door_sounds.push(name.to_owned());
+                                    door_sounds.len() - 1
+                                }));
+                            }
+                            _ if property.starts_with("dialogue=") => dialogue = Some(property[9..].to_owned()),
+                            _ if property.starts_with("moving=") => {
+                                let times = property[7..].split(',').map(str::parse::<f32>).collect::<Result<Vec<_>, _>>().map_err(|_| "moving block times have to be numbers")?;
+                                let (&period, offset) = times.split_first().ok_or("moving block needs a period")?;
+                                ensure!(period > 0., "period of moving block {c} has to be positive");
+                                moving = Some(Moving { period, offset: offset.first().copied().unwrap_or(0.) });
+                            }
+                            _ if property.starts_with("scale=") => {
+                                let s = property[6..].parse::<f32>().map_err(|_| "scale has to be a number")?;
+                                ensure!(s > 0., "scale of {c} has to be positive");
+                                scale = Some(s);
+                            }
+                            _ if property.starts_with("summon=") => {
+                                summon = Some(property[7..].chars().next().ok_or("boss has to summon a character")?);
+                            }
+                            _ if property.starts_with("spawner=") => {
+                                let mut parts = property[8..].split(',');
+                                let spawn = parts.next().and_then(|s| s.chars().next()).ok_or("spawner has to spawn a character")?;
+                                let (Some(interval), Some(cap)) = (parts.next(), parts.next()) else {
+                                    return Err("spawner needs an interval and a cap".to_owned());
+                                };
+                                let interval = interval.parse::<f32>().map_err(|_| "spawner interval has to be a number")?;
+                                ensure!(interval > 0., "spawner interval of {c} has to be positive");
+                                let cap = cap.parse::<u32>().map_err(|_| "spawner cap has to be a whole number")?;
+                                spawner = Some((spawn, Spawner::new(interval, cap)));
+                            }
+                            _ if property.starts_with("facing=") => {
+                                facing = Some(Side::from_direction(&property[7..]).ok_or("facing has to be north, south, east or west")?);
+                            }
+                            _ if property.starts_with("faction=") => {
+                                faction = Some(Faction::from_name(&property[8..]).ok_or("faction has to be player, guards or monsters")?);
+                            }
+                            _ if property.starts_with("time=") => {
+                                pickup = Some(Pickup::BonusTime(property[5..].parse::<f32>().map_err(|_| "extra time has to be a number")?));
+                            }
+                            _ if property.starts_with("speed=") => {
+                                pickup = Some(Pickup::Speed(property[6..].parse::<f32>().map_err(|_| "speed up time has to be a number")?));
+                            }
+                            _ if property.starts_with("invulnerable=") => {
+                                pickup = Some(Pickup::Invulnerable(property[13..].parse::<f32>().map_err(|_| "invulnerability time has to be a number")?));
+                            }
+                            _ if property.starts_with("armor=") => {
+                                pickup = Some(Pickup::Armor(property[6..].parse::<f32>().map_err(|_| "armor points have to be a number")?));
+                            }
+                            _ if property.starts_with("treasure=") => {
+                                pickup = Some(Pickup::Treasure(property[9..].parse::<u32>().map_err(|_| "treasure points have to be a whole number")?));
+                            }
+                            _ if property.starts_with("hp=") => {
+                                hp = Some(property[3..].parse::<f32>().map_err(|_| "hit points have to be a number")?);
+                            }
+                            _ => {
+                                let side = property.split_once('=')
+                                    .and_then(|(dir, path)| Some((Side::from_direction(dir)?, path)));
+                                let Some((side, path)) = side else {
+                                    return Err(format!("unknown property {property} of {c}"));
+                                };
+                                sides[side as usize] = Some(load_wall_tex(path, TexSlot::Side { mat: textures.len(), side })?);
+                            }
                         }
                     }
 
                     if thing {
-                        let width = texture.parse::<f32>().expect("width to be a number");
-                        let texture = Texture::from_file(texture_dark);
+                        ensure!(!animated, "thing {c} cannot be animated");
+                        ensure!(!variants, "thing {c} cannot have variants");
+                        ensure!(exit.is_none(), "thing {c} cannot be an exit");
+                        ensure!(stairs.is_none() && !elevator, "thing {c} cannot be stairs or an elevator");
+                        ensure!(moving.is_none(), "thing {c} cannot be a moving block");
+                        ensure!(sides.iter().all(Option::is_none), "thing {c} cannot have per-side textures");
+                        let width = texture.parse::<f32>().map_err(|_| format!("width of thing {c} has to be a number"))?;
+                        let name = Path::new(texture_dark).file_stem().map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+                        let texture = Texture::try_from_file(texture_dark)?;
 
                         let i = if let Some(i) = thing_texes.iter().position(|t| t == &texture) {
                             i
                         } else {
                             let i = thing_texes.len();
                             thing_texes.push(texture);
+                            thing_names.push(name);
                             i
                         };
 
-                        thing_map.insert(c, (width, i));
+                        ensure!(!(prop || explosive) || hp.is_some(), "prop {c} needs hit points");
+                        ensure!(npc == dialogue.is_some(), "only friendly things have dialogue and they need it");
+
+                        let mut proto = Thing::new(Point2::ORIGIN, width, i);
+                        proto.dynamic = dynamic;
+                        proto.health = hp;
+                        proto.explosive = explosive;
+                        proto.dialogue = dialogue.map(|name| {
+                            npc_dialogues.push(name);
+                            npc_dialogues.len() - 1
+                        });
+                        proto.pickup = pickup;
+                        proto.height = scale.unwrap_or(1.);
+                        if boss {
+                            let hp = hp.ok_or_else(|| format!("boss {c} needs hit points"))?;
+                            proto.boss = Some(Boss::new(hp));
+                            summoners.extend(summon.map(|s| (c, s, false)));
+                        }
+                        ensure!(boss || summon.is_none(), "only bosses can summon things");
+                        if enemy {
+                            ensure!(hp.is_some(), "enemy {c} needs hit points");
+                            proto.enemy = Some(facing.map_or_else(Enemy::default, |side| Enemy::facing_along(side.into_unit_vector())));
+                        }
+                        ensure!(enemy || facing.is_none(), "only enemies can face a way");
+                        // Enemies and bosses are monsters unless they're said to be on another side
+                        proto.faction = faction.or((enemy || boss).then_some(Faction::Monsters));
+                        if let Some((spawn, spawner)) = spawner {
+                            proto.spawner = Some(spawner);
+                            summoners.push((c, spawn, true));
+                        }
+                        proto.objective = objective_chars.iter().position(|&o| o == c);
+                        if let Some(i) = proto.objective {
+                            ensure!(objectives[i].goal != Goal::Destroy || hp.is_some(), "{c} needs hit points to be destroyed");
+                        }
+                        thing_map.insert(c, proto);
                         material_map.insert(c, Mat::air());
                     } else {
-                        let texture = Texture::from_file(texture);
-                        let texture_dark = Texture::from_file(texture_dark);
-                        textures.push((texture, texture_dark));
-                        properties.push(Properties {solid, transparent, reflective, door});
+                        let mat = textures.len();
+                        let frames = if variants {
+                            frames.chunks_exact(2).enumerate().map(|(frame, pair)| Ok((
+                                load_wall_tex(pair[0], TexSlot::Frame { mat, frame, light: true, dark: false })?,
+                                load_wall_tex(pair[1], TexSlot::Frame { mat, frame, light: false, dark: true })?,
+                            ))).collect::<Result<_, String>>()?
+                        } else if animated {
+                            // Animation frames are used on both the light and dark sides
+                            frames.into_iter().enumerate().map(|(frame, f)| {
+                                let texture = load_wall_tex(f, TexSlot::Frame { mat, frame, light: true, dark: true })?;
+                                Ok((texture.clone(), texture))
+                            }).collect::<Result<_, String>>()?
+                        } else {
+                            vec![(
+                                load_wall_tex(texture, TexSlot::Frame { mat, frame: 0, light: true, dark: false })?,
+                                load_wall_tex(texture_dark, TexSlot::Frame { mat, frame: 0, light: false, dark: true })?,
+                            )]
+                        };
+                        textures.push(MatTextures { frames, frame_time, sides });
+                        let hp = hp.map(|hp| hp.clamp(1., 255.) as u8);
+                        ensure!(tint.is_none() || reflective, "only reflective materials can have a tint");
+                        ensure!(pickup.is_none(), "only things can be picked up");
+                        ensure!(scale.is_none() && summon.is_none() && spawner.is_none(), "only things can be scaled or create things");
+                        ensure!(faction.is_none(), "only things can be in a faction");
+                        ensure!(refraction.is_none() || (transparent && !reflective), "only see-through materials can be refractive");
+                        ensure!(door || !locked && door_sound.is_none(), "only doors can be locked or have door sounds");
+                        properties.push(Properties {solid, transparent, reflective, door, locked, door_sound, switch, hp, exit, tint, refraction, surface, stairs, elevator, moving});
 
                         material_map.insert(c, Mat::from_len(textures.len()));
                     }
@@ -99,34 +844,90 @@ impl Map {
             }
         }
 
+        let door = |arg: &str| {
+            let c = arg.chars().next().ok_or("trigger door needs a material")?;
+            let mat = material_map.get(&c).filter(|mat| !mat.is_air()).ok_or_else(|| format!("trigger door {c} has to be a material"))?;
+            ensure!(properties[mat.index()].door, "trigger door {c} has to be a door");
+            Ok(mat.index())
+        };
+        let triggers = trigger_lines.into_iter().map(|(names, action, arg)| {
+            let signals = names.iter().map(|name| triggers::known_signal(&signals, name)).collect::<Result<_, _>>()?;
+            let action = match &*action {
+                "open" => TriggerAction::Open(door(&arg)?),
+                "close" => TriggerAction::Close(door(&arg)?),
+                "cutscene" => TriggerAction::Cutscene(arg),
+                "time" => TriggerAction::Time(match NAMED_HOURS.iter().find(|(name, _)| *name == arg) {
+                    Some(&(_, hour)) => hour,
+                    None => arg.parse().map_err(|_| "time has to be an hour or a time of day")?,
+                }),
+                _ => return Err(format!("unknown trigger action {action}")),
+            };
+            Ok(Trigger { signals, action })
+        }).collect::<Result<_, String>>()?;
+        let logic = logic_lines.into_iter()
+            .map(|(kind, outputs, args)| triggers::parse_logic(&kind, outputs, &args, &signals))
+            .collect::<Result<_, _>>()?;
+
+        let mut summons = Vec::new();
+        let mut summon_indices = HashMap::new();
+        for (c, summon, spawner) in summoners {
+            let &proto = thing_map.get(&summon).ok_or_else(|| format!("{c} creates {summon}, which isn't a thing"))?;
+            let i = *summon_indices.entry(summon).or_insert_with(|| {
+                summons.push(proto);
+                summons.len() - 1
+            });
+            let creator = thing_map.get_mut(&c).unwrap();
+            match (spawner, &mut creator.boss, &mut creator.spawner) {
+                (false, Some(boss), _) => boss.summon = Some(i),
+                (true, _, Some(spawner)) => spawner.spawn = i,
+                _ => unreachable!(),
+            }
+        }
+        for c in &objective_chars {
+            ensure!(thing_map.contains_key(c), "objective {c} has to be about a thing");
+        }
+
         let mut grid = Vec::new();
-        let mut things = Vec::new();
+        let mut things = vec![Vec::new()];
         let mut width = 0;
+        let mut height = 0;
+        let mut rows = 0;
         let mut player = None;
 
-        for line in lines {
-            let line = line.unwrap();
+        for line in lines.by_ref() {
             let line = line.trim();
+            // The floor grid can follow after an empty line
+            if line.is_empty() {
+                break;
+            }
+            if line == LAYER_SEPARATOR {
+                ensure!(height == 0 || rows == height, "layer {} was {rows} tall, but the first layer was {height}", things.len() - 1);
+                height = rows;
+                rows = 0;
+                things.push(Vec::new());
+                continue;
+            }
+            let layer = things.len() - 1;
             let mut len = 0;
 
             for c in line.chars() {
-                let mat = material_map[&c];
+                let mat = *material_map.get(&c).ok_or_else(|| format!("{c} is in the grid but wasn't declared"))?;
                 grid.push(mat);
 
                 if mat.is_air() {
-                    let w = if width == 0 { i32::MAX } else { width };
-                    let l = grid.len() as i32 - 1;
-                    let (i, j) = (l % w, l / w);
+                    let (i, j) = (len, rows);
 
                     match c {
-                        '>' => player = Some((i, j, Side::Right)),
-                        '<' => player = Some((i, j, Side::Left)),
-                        '^' => player = Some((i, j, Side::Up)),
-                        'v' => player = Some((i, j, Side::Down)),
+                        '>' => player = Some((layer, i, j, Side::Right)),
+                        '<' => player = Some((layer, i, j, Side::Left)),
+                        '^' => player = Some((layer, i, j, Side::Up)),
+                        'v' => player = Some((layer, i, j, Side::Down)),
                         ' ' => (),
                         _ => {
-                            let &(w, t) = thing_map.get(&c).expect("character was neither a player nor declared");
-                            things.push(Thing::new(Point2::new(i as f32 + 0.5, j as f32 + 0.5), w, t));
+                            let &proto = thing_map.get(&c).ok_or_else(|| format!("{c} was neither a player nor declared"))?;
+                            let mut thing = proto;
+                            thing.place(Point2::new(i as f32 + 0.5, j as f32 + 0.5));
+                            things[layer].push(thing);
                         }
                     }
                 }
@@ -136,107 +937,785 @@ impl Map {
             if width == 0 {
                 width = len;
             } else if width != len {
-                panic!("this line was {len} long, but previous lines were all {width}");
+                return Err(format!("line {} was {len} long, but previous lines were all {width}", rows + 1));
             }
+            rows += 1;
         }
+        ensure!(height == 0 || rows == height, "layer {} was {rows} tall, but the first layer was {height}", things.len() - 1);
+        let height = rows;
 
-        let (i, j, s) = player.expect("no player on map");
+        let (layer, i, j, s) = player.ok_or("no player on map")?;
 
-        (Self {
+        // Cells are on the default floor (`.` in the floor grid) unless a floor grid says otherwise,
+        // which has its layers separated the same way
+        let default_floor = Floor { surface: floor_surface, conveyor: None, hazard: None, poison: None, checkpoint: false, plate: None };
+        let mut floor = Vec::new();
+        let section_start = |l: &str| l.trim().starts_with(dialogue::DIALOGUE_START) || l.trim().starts_with(cutscene::CUTSCENE_START);
+        while let Some(line) = lines.next_if(|l| !section_start(l)) {
+            let line = line.trim();
+            if line.is_empty() || line == LAYER_SEPARATOR {
+                continue;
+            }
+            ensure!(line.chars().count() == width as usize, "floor grid has to be as wide as the map");
+            for c in line.chars() {
+                floor.push(match c {
+                    '.' => default_floor,
+                    c => *floor_map.get(&c).ok_or_else(|| format!("floor {c} wasn't declared"))?,
+                });
+            }
+        }
+        ensure!(floor.is_empty() || floor.len() == grid.len(), "floor grid has to be as tall as the map");
+        let layer_len = (width * height) as usize;
+        let floors = (0..things.len()).map(|layer| {
+            let mut floors = Chunked::new(width, height, default_floor);
+            let cells = floor.get(layer * layer_len..(layer + 1) * layer_len).unwrap_or_default();
+            for (i, &cell) in cells.iter().enumerate().filter(|&(_, &cell)| cell != default_floor) {
+                *floors.get_mut(i as i32 % width, i as i32 / width).unwrap() = cell;
+            }
+            floors
+        }).collect();
+
+        // Dialogues and cutscenes can come in any order, each line belongs to whichever started last
+        let (mut dialogue_lines, mut cutscene_lines) = (Vec::new(), Vec::new());
+        let mut in_cutscene = false;
+        for line in lines {
+            if section_start(line) {
+                in_cutscene = line.trim().starts_with(cutscene::CUTSCENE_START);
+            }
+            if in_cutscene { cutscene_lines.push(line) } else { dialogue_lines.push(line) }
+        }
+        let (dialogues, dialogue_names) = dialogue::parse(dialogue_lines.into_iter())?;
+        let cutscenes = cutscene::parse(cutscene_lines.into_iter())?;
+        for thing in things.iter_mut().flatten() {
+            if let Some(i) = thing.dialogue {
+                let name = &npc_dialogues[i];
+                thing.dialogue = Some(*dialogue_names.get(name).ok_or_else(|| format!("dialogue {name} doesn't exist"))?);
+            }
+        }
+
+        Ok((Self {
             name,
+            render_distance,
+            time_limit,
+            day_night,
+            fog_colour,
+            grade,
+            max_reflections,
+            wrap,
             textures,
             properties,
             grid,
+            floors,
+            conveyor_tex,
+            pending_textures,
+            dialogues,
+            cutscenes,
+            objectives,
+            thing_names,
+            summons,
+            door_sounds,
+            signals,
+            logic,
+            triggers,
             width,
-        }, i, j, s, things, thing_texes)
+            height,
+            layer,
+            doors_locked: false,
+            time: 0.,
+            revision: next_revision(),
+        }, i, j, s, things, thing_texes))
+    }
+
+    /// Takes the wall textures that still have to be decoded, which go back in with `set_texture`
+    pub fn take_pending_textures(&mut self) -> Vec<(TexSlot, Box<str>)> {
+        std::mem::take(&mut self.pending_textures)
+    }
+    /// Puts a texture that was decoded in the background in place of its stand-in
+    pub fn set_texture(&mut self, slot: TexSlot, texture: Texture) {
+        match slot {
+            TexSlot::Frame { mat, frame, light, dark } => {
+                let (light_tex, dark_tex) = &mut self.textures[mat].frames[frame];
+                match (light, dark) {
+                    (true, true) => (*light_tex, *dark_tex) = (texture.clone(), texture),
+                    (true, false) => *light_tex = texture,
+                    (false, _) => *dark_tex = texture,
+                }
+            }
+            TexSlot::Side { mat, side } => self.textures[mat].sides[side as usize] = Some(texture),
+        }
+        self.revision = next_revision();
     }
+    pub fn revision(&self) -> u32 {
+        self.revision
+    }
+    /// Whether the textures of the material change as time goes by
+    pub fn is_animated(&self, mat: Mat) -> bool {
+        !mat.is_air() && self.textures[mat.index()].frame_time > 0.
+    }
+    /// Texture of the given side of the material in `cell` at `time` seconds into the game
+    pub fn get_tex(&self, mat: Mat, side: Side, time: f32, cell: (i32, i32)) -> &Texture {
+        let MatTextures { frames, frame_time, sides } = &self.textures[mat.index()];
+        if let Some(tex) = &sides[side as usize] {
+            return tex;
+        }
+        let dark = matches!(side, Side::Left | Side::Right);
 
-    pub fn get_tex(&self, mat: Mat, dark: bool) -> &Texture {
-        let (light, non_light) = &self.textures[mat.index()];
+        let frame = if frames.len() == 1 {
+            0
+        } else if *frame_time > 0. {
+            (time / frame_time) as usize % frames.len()
+        } else {
+            cell_hash(cell) as usize % frames.len()
+        };
+        let (light, non_light) = &frames[frame];
         if dark {
             non_light
         } else {
             light
         }
     }
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+    /// How many layers are stacked on top of each other
+    pub fn layers(&self) -> usize {
+        self.grid.len() / self.layer_len()
+    }
+    pub fn layer(&self) -> usize {
+        self.layer
+    }
+    pub fn set_layer(&mut self, layer: usize) {
+        assert!(layer < self.layers(), "the map only has {} layers", self.layers());
+        self.layer = layer;
+        self.revision = next_revision();
+    }
     pub fn get(&self, x: i32, y: i32) -> Option<Mat> {
-        let x = x as isize as usize;
-        let y = y as isize as usize;
-        let w = self.width as isize as usize;
-
-        let index = y.checked_mul(w)?.checked_add(x)?;
-        self.grid.get(index).copied()
+        self.get_on_layer(self.layer, x, y)
+    }
+    /// Cell of a layer that isn't necessarily the current one
+    pub fn get_on_layer(&self, layer: usize, x: i32, y: i32) -> Option<Mat> {
+        self.index_on_layer(layer, x, y).map(|i| self.grid[i])
+    }
+    fn layer_len(&self) -> usize {
+        (self.width * self.height) as usize
+    }
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        self.index_on_layer(self.layer, x, y)
+    }
+    fn index_on_layer(&self, layer: usize, x: i32, y: i32) -> Option<usize> {
+        let (x, y) = self.wrapped(x, y);
+        let inside = (0..self.width).contains(&x) && (0..self.height).contains(&y) && layer < self.layers();
+        inside.then(|| layer * self.layer_len() + (y * self.width + x) as usize)
+    }
+    /// Sets the state of the cell, returning whether the cell exists
+    pub fn set_state(&mut self, x: i32, y: i32, state: u8) -> bool {
+        if let Some(i) = self.index(x, y) {
+            self.grid[i].state = state;
+            self.revision = next_revision();
+            true
+        } else {
+            false
+        }
     }
     fn props(&self, mat: &Mat) -> Properties {
-        if mat.is_air() { Properties { solid: false, transparent: true, reflective: false, door: false } } else {
+        if mat.is_air() { Properties { solid: false, transparent: true, reflective: false, door: false, locked: false, door_sound: None, switch: None, hp: None, exit: None, tint: None, refraction: None, surface: None, stairs: None, elevator: false, moving: None } } else {
             self.properties[mat.index()]
         }
     }
 
+    /// How a cell looks if it's simple: `Some(false)` if there's nothing to see, `Some(true)` for a plain opaque wall
+    /// and `None` for anything else, like doors, moving blocks, glass and mirrors
+    pub fn plain_cell(&self, mat: Mat) -> Option<bool> {
+        let props = self.props(&mat);
+        if !props.solid && props.transparent && props.moving.is_none() {
+            Some(false)
+        } else if props.solid && !props.transparent && !props.reflective && !props.door && props.moving.is_none() {
+            Some(true)
+        } else {
+            None
+        }
+    }
+    /// What the player walks on at `p`
+    pub fn surface_at(&self, p: Point2) -> Surface {
+        let (x, y) = (p.x.floor() as i32, p.y.floor() as i32);
+        let (Some(mat), Some(floor)) = (self.get(x, y), self.floor_at(x, y)) else {
+            return Surface::Stone;
+        };
+        self.props(&mat).surface.unwrap_or(floor.surface)
+    }
+    fn floor_at(&self, x: i32, y: i32) -> Option<&Floor> {
+        let (x, y) = self.wrapped(x, y);
+        self.floors[self.layer].get(x, y)
+    }
+    /// The cell inside the map that `x` and `y` are if the map wraps
+    fn wrapped(&self, x: i32, y: i32) -> (i32, i32) {
+        if self.wrap {
+            (x.rem_euclid(self.width), y.rem_euclid(self.height))
+        } else {
+            (x, y)
+        }
+    }
+    /// The point inside the map that `p` is if the map wraps
+    pub fn wrap_point(&self, p: Point2) -> Point2 {
+        if self.wrap {
+            Point2::new(p.x.rem_euclid(self.width as f32), p.y.rem_euclid(self.height as f32))
+        } else {
+            p
+        }
+    }
+    /// Where `p` is seen from `near` on a wrapping map, which is the nearest of its copies around the map
+    pub fn nearest_image(&self, p: Point2, near: Point2) -> Point2 {
+        if !self.wrap {
+            return p;
+        }
+        let nearest = |p: f32, near: f32, len: f32| near + (p - near + len / 2.).rem_euclid(len) - len / 2.;
+        Point2::new(nearest(p.x, near.x, self.width as f32), nearest(p.y, near.y, self.height as f32))
+    }
+    /// Conveyor on the floor at `p`
+    pub fn conveyor_at(&self, p: Point2) -> Option<Conveyor> {
+        self.floor_at(p.x.floor() as i32, p.y.floor() as i32).and_then(|floor| floor.conveyor)
+    }
+    /// Damage the floor at `p` does every `HAZARD_INTERVAL` seconds
+    pub fn hazard_at(&self, p: Point2) -> Option<f32> {
+        self.floor_at(p.x.floor() as i32, p.y.floor() as i32).and_then(|floor| floor.hazard)
+    }
+    /// Damage per second and for how many seconds the floor at `p` poisons the player
+    pub fn poison_at(&self, p: Point2) -> Option<(f32, f32)> {
+        self.floor_at(p.x.floor() as i32, p.y.floor() as i32).and_then(|floor| floor.poison)
+    }
+    /// Whether the floor at `p` is a checkpoint
+    pub fn checkpoint_at(&self, p: Point2) -> bool {
+        self.floor_at(p.x.floor() as i32, p.y.floor() as i32).is_some_and(|floor| floor.checkpoint)
+    }
+    pub fn cutscene(&self, name: &str) -> Option<&Cutscene> {
+        self.cutscenes.get(name)
+    }
+    pub fn dialogue(&self, i: usize) -> &Dialogue {
+        &self.dialogues[i]
+    }
+    pub fn objectives(&self) -> &[Objective] {
+        &self.objectives
+    }
+    /// What things with the given texture are called, only things from the map have names
+    pub fn thing_name(&self, tex: usize) -> Option<&str> {
+        self.thing_names.get(tex).map(String::as_str)
+    }
+    /// A new copy of a thing bosses or spawners create
+    pub fn summon(&self, i: usize) -> Thing {
+        self.summons[i]
+    }
+    /// The texture of conveyors if the map has any
+    pub fn conveyor_tex(&self) -> Option<&Texture> {
+        self.conveyor_tex.as_ref()
+    }
+
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time;
+    }
+    /// How far down the material is at `time`, from 0 when it's all the way up in the ceiling to 1 when it reaches the floor
+    ///
+    /// Only moving blocks are ever anything but all the way down
+    pub fn extent(&self, mat: Mat, time: f32) -> f32 {
+        let Some(Moving { period, offset }) = self.props(&mat).moving else {
+            return 1.;
+        };
+        // Coming down, staying down, going back up and staying up for a quarter of the period each
+        let t = 4. * ((time + offset) / period).rem_euclid(1.);
+        let ease = |t: f32| t * t * (3. - 2. * t);
+        match t as u8 {
+            0 => ease(t),
+            1 => 1.,
+            2 => 1. - ease(t - 2.),
+            _ => 0.,
+        }
+    }
+    /// Whether the material stops things right now
+    fn blocks(&self, mat: Mat) -> bool {
+        let props = self.props(&mat);
+        props.solid && (props.moving.is_none() || self.extent(mat, self.time) > CRUSHING_EXTENT)
+    }
+    /// What part of the cell stops things moving through it, outside of the map is `Full`
+    ///
+    /// Solid doors with a state are that many 255ths of the way open,
+    /// slid into the wall on one side and leaving the rest of the door across the middle of the cell.
+    pub fn shape(&self, x: i32, y: i32) -> Shape {
+        let Some(mat) = self.get(x, y) else {
+            return Shape::Full;
+        };
+        let props = self.props(&mat);
+        if props.door && self.doors_locked {
+            return Shape::Full;
+        }
+        if !self.blocks(mat) {
+            return Shape::Open;
+        }
+        if !props.door || mat.state == 0 {
+            return Shape::Full;
+        }
+
+        let closed = 1. - mat.state as f32 / 255.;
+        if closed <= 0. {
+            return Shape::Open;
+        }
+        // Doors go between the walls on either side of them
+        let wall = |x, y| self.get(x, y).is_some_and(|mat| self.blocks(mat));
+        if wall(x - 1, y) && wall(x + 1, y) {
+            Shape::Line(Point2::new(0., 0.5), Point2::new(closed, 0.5))
+        } else {
+            Shape::Line(Point2::new(0.5, 0.), Point2::new(0.5, closed))
+        }
+    }
+    /// Whether there's a moving block coming down on `p`
+    pub fn crushes(&self, p: Point2) -> bool {
+        self.get(p.x.floor() as i32, p.y.floor() as i32)
+            .is_some_and(|mat| self.props(&mat).moving.is_some() && self.blocks(mat))
+    }
+
+    /// Stairs in the cell `p` is in
+    pub fn stairs_at(&self, p: Point2) -> Option<Stairs> {
+        self.get(p.x.floor() as i32, p.y.floor() as i32).and_then(|mat| self.props(&mat).stairs)
+    }
+    /// Layer that stairs on the current layer lead to, if there is one in that direction
+    pub fn stairs_destination(&self, stairs: Stairs) -> Option<usize> {
+        match stairs {
+            Stairs::Up => Some(self.layer + 1).filter(|&l| l < self.layers()),
+            Stairs::Down => self.layer.checked_sub(1),
+        }
+    }
+
+    /// Layer the elevator in the cell `p` is in goes to, which is the closest layer above with an elevator in the same place,
+    /// or the lowest one when it's already at the top
+    pub fn elevator_destination(&self, p: Point2) -> Option<usize> {
+        let (x, y) = (p.x.floor() as i32, p.y.floor() as i32);
+        let is_elevator = |layer| self.get_on_layer(layer, x, y).is_some_and(|mat| self.props(&mat).elevator);
+        if !is_elevator(self.layer) {
+            return None;
+        }
+        (self.layer + 1..self.layers()).find(|&l| is_elevator(l))
+            .or_else(|| (0..self.layer).find(|&l| is_elevator(l)))
+    }
+    pub fn signal_count(&self) -> usize {
+        self.signals.len()
+    }
+    /// Index of the signal called `name`
+    #[cfg(test)]
+    pub fn signal_named(&self, name: &str) -> Option<usize> {
+        self.signals.iter().position(|s| s == name)
+    }
+    pub fn triggers(&self) -> &[Trigger] {
+        &self.triggers
+    }
+    pub fn logic(&self) -> &[LogicNode] {
+        &self.logic
+    }
+    /// Switch in a cell, if there is one
+    pub fn switch_at(&self, x: i32, y: i32) -> Option<Switch> {
+        self.get(x, y).and_then(|mat| self.props(&mat).switch)
+    }
+    /// Signal of the plate at `p`, if there is one
+    pub fn plate_at(&self, p: Point2) -> Option<usize> {
+        self.floor_at(p.x.floor() as i32, p.y.floor() as i32).and_then(|floor| floor.plate)
+    }
+    /// Cells of the current layer with doors of a material
+    pub fn door_cells(&self, door: usize) -> Vec<(i32, i32)> {
+        (0..self.height).flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.get(x, y).is_some_and(|mat| !mat.is_air() && mat.index() == door))
+            .collect()
+    }
+    pub fn lock_doors(&mut self, locked: bool) {
+        self.doors_locked = locked;
+    }
+    /// How many 255ths of the way open the door in a cell is, if there's a door there
+    pub fn door_state(&self, x: i32, y: i32) -> Option<u8> {
+        self.get(x, y).filter(|mat| self.props(mat).door).map(|mat| mat.state)
+    }
+    /// Whether the door in a cell can't be opened by the player, which all doors can't be while they're locked
+    pub fn door_locked(&self, x: i32, y: i32) -> bool {
+        self.doors_locked || self.get(x, y).is_some_and(|mat| self.props(&mat).locked)
+    }
+    /// Sound the door in a cell makes when it changes
+    pub fn door_sound(&self, x: i32, y: i32, change: DoorChange) -> Sound {
+        Sound::Door(change, self.get(x, y).and_then(|mat| self.props(&mat).door_sound))
+    }
+    /// Name of the sound effect in `sfx/`, which for doors with their own sounds is the name they were given
+    pub fn sound_file_name(&self, sound: Sound) -> String {
+        match sound {
+            Sound::Door(change, Some(i)) => format!("{}_{}.wav", self.door_sounds[i], change.name()),
+            _ => sound.file_name(),
+        }
+    }
+
+    /// Exit in any of the cells overlapped by the square of radius `r` around `p`, secret exits are preferred
+    pub fn exit_near(&self, p: Point2, r: f32) -> Option<Exit> {
+        let (x0, y0) = ((p.x - r).floor() as i32, (p.y - r).floor() as i32);
+        let (x1, y1) = ((p.x + r).floor() as i32, (p.y + r).floor() as i32);
+
+        (y0..=y1)
+            .flat_map(|y| (x0..=x1).map(move |x| (x, y)))
+            .filter_map(|(x, y)| self.get(x, y))
+            .filter_map(|mat| self.props(&mat).exit)
+            .max_by_key(|&exit| exit == Exit::Secret)
+    }
+
+    /// Tint of the reflections in a material
+    pub fn reflection_tint(&self, mat: Mat) -> Option<(Colour, u8)> {
+        self.props(&mat).tint
+    }
+
+    /// Damages a breakable cell, turning it into air once all of its hit points are gone
+    ///
+    /// Returns whether the cell was destroyed
+    pub fn damage_cell(&mut self, x: i32, y: i32, damage: f32) -> bool {
+        let Some(i) = self.index(x, y) else { return false };
+        let mat = self.grid[i];
+        let Some(hp) = self.props(&mat).hp else { return false };
+
+        let damage_taken = mat.state.saturating_add(damage.round().clamp(0., 255.) as u8);
+        self.revision = next_revision();
+        if damage_taken >= hp {
+            self.grid[i] = Mat::air();
+            true
+        } else {
+            self.grid[i].state = damage_taken;
+            false
+        }
+    }
+    pub fn is_breakable(&self, x: i32, y: i32) -> bool {
+        self.get(x, y).is_some_and(|m| self.props(&m).hp.is_some())
+    }
+
+    /// The first solid cell between two points, if any
+    pub fn first_solid_cell(&self, from: Point2, to: Point2) -> Option<(i32, i32)> {
+        let cast = ray_cast(from, to - from, true, 1, f32::INFINITY, &Solid(self), false);
+
+        cast.into_iter().find_map(|cp| match cp.cast_type {
+            CastPointType::Termination(_, side) => Some(side.cell_of_hit(cp.point)),
+            _ => None,
+        })
+    }
+    /// Whether there's nothing solid in the way between two points
+    #[inline]
+    pub fn line_of_sight(&self, from: Point2, to: Point2) -> bool {
+        self.first_solid_cell(from, to).is_none()
+    }
+
+    /// How many walls or doors a sound has to go through between two points
+    ///
+    /// A thick wall of the same material only counts once and the cell `from` is in (like an open doorway) doesn't count
+    pub fn walls_between(&self, from: Point2, to: Point2) -> usize {
+        const MAX_WALLS: usize = 8;
+        ray_cast(from, to - from, true, MAX_WALLS, f32::INFINITY, &Muffling(self), true)
+            .into_iter()
+            .filter(|cp| matches!(cp.cast_type, CastPointType::Pass(..)))
+            .count()
+    }
+
     /// Return the vector going into a solid material to be **clip**ped off
+    ///
+    /// Movement into a wall slides along it and what's left of it is checked again,
+    /// so sliding can't go into another wall around a corner however far it goes at once.
     pub fn move_ray_cast(&self, orig_p: Point2, dp: Vector2) -> Vector2 {
-        let (clip, side) = ray_cast(orig_p, dp, true, 8,
-            |x, y| self.get(x, y),
-            |m| self.props(m).solid,
-            |m| self.props(m).solid,
-            |_| false,
-            |m| !self.props(m).solid,
-            false,
-        ).clip();
-
         const PUSH: f32 = 0.005;
+        // More than two only happens going back and forth into a narrow gap
+        const MAX_SLIDES: usize = 4;
 
-        if let Some(side) = side {
-            let wall_dir = side.flip().into_unit_vector();
-            let to_wall = clip.proj(wall_dir);
-            to_wall + PUSH * wall_dir
-        } else { clip }
+        let mut p = orig_p;
+        let mut left = dp;
+        for _ in 0..MAX_SLIDES {
+            let Some((hit, into_wall)) = self.move_hit(p, left) else {
+                return orig_p + dp - (p + left);
+            };
+            let past_wall = p + left - hit;
+            p = hit - PUSH * into_wall;
+            left = past_wall - past_wall.proj(into_wall);
+        }
+        // Still going into walls, so it stops where it got to
+        orig_p + dp - p
     }
+    /// Where moving by `dp` from `p` first hits something and the direction going into it
+    fn move_hit(&self, p: Point2, dp: Vector2) -> Option<(Point2, Vector2)> {
+        let (clip, side) = ray_cast(p, dp, true, 8, f32::INFINITY, &Solid(self), false).clip();
+        let reached = p + dp - clip;
+        // A thin wall on the way to the first solid cell stops it before that
+        self.line_hit(p, reached).or_else(|| side.map(|side| (reached, side.flip().into_unit_vector())))
+    }
+
+    /// First point where moving from `from` to `to` crosses the `Line` of a cell,
+    /// along with the direction across the line from the side it was hit on
+    fn line_hit(&self, from: Point2, to: Point2) -> Option<(Point2, Vector2)> {
+        let d = to - from;
+        let (x0, x1) = (from.x.min(to.x).floor() as i32, from.x.max(to.x).floor() as i32);
+        let (y0, y1) = (from.y.min(to.y).floor() as i32, from.y.max(to.y).floor() as i32);
 
-    /// Returns a vector of (dark, u, distance, material) in order of increasing distance
+        let mut first: Option<(f32, Vector2)> = None;
+        for (x, y) in (y0..=y1).flat_map(|y| (x0..=x1).map(move |x| (x, y))) {
+            let Shape::Line(a, b) = self.shape(x, y) else { continue };
+            let corner = Vector2::new(x as f32, y as f32);
+            let (a, along) = (a + corner, b - a);
+
+            // Where the two segments cross, as fractions of each of them
+            let denominator = along.dot(d.hat());
+            if denominator == 0. {
+                continue;
+            }
+            let t = along.dot((a - from).hat()) / denominator;
+            let s = d.dot((a - from).hat()) / denominator;
+            if (0. ..=1.).contains(&t) && (0. ..=1.).contains(&s) && !first.is_some_and(|(first, _)| t >= first) {
+                let normal = along.hat();
+                let into_line = if normal.dot(d) > 0. { normal } else { -normal };
+                first = Some((t, into_line.set_len(1.)));
+            }
+        }
+        first.map(|(t, into_line)| (from + t * d, into_line))
+    }
+
+    /// Returns a vector of hits in order of increasing distance
     /// that show what the ray encountered travelling in this direction
     ///
     /// Since rays do not stop at every node, this is a list and should be drawn in reverse order
-    pub fn render_ray_cast(&self, orig_p: Point2, dp: Vector2) -> Vec<(bool, f32, (Point2, Vector2, f32), f32, Mat)> {
-        let cast = ray_cast(orig_p, dp, false, 8,
-            |x, y| self.get(x, y),
-            |m| self.props(m).solid || !self.props(m).transparent,
-            |m| !self.props(m).transparent,
-            |m| self.props(m).reflective,
-            |m| self.props(m).transparent,
-            true,
-        );
+    pub fn render_ray_cast(&self, orig_p: Point2, dp: Vector2, max_dist: f32) -> Vec<RenderHit> {
+        let cast = ray_cast(orig_p, dp, false, 8, max_dist, self, true);
 
         let mut last_point = orig_p;
-        let mut total_distance = 0.;
+        let mut last_dist = 0.;
+        let mut reflected = false;
+
+        let mut reflections = 0;
+        let mut out_of_reflections = false;
 
-        cast.into_iter().filter_map(|cp| {
-                let last_dist = total_distance;
-                let p = last_point;
+        cast.into_iter().map_while(|cp| {
+                if out_of_reflections {
+                    return None;
+                }
+                // A mirror seen after too many reflections is drawn as a wall and nothing after it is
+                let cp = match cp.cast_type {
+                    CastPointType::Reflection(mat, side) if reflections == self.max_reflections => {
+                        out_of_reflections = true;
+                        CastPoint { cast_type: CastPointType::Termination(mat, side), ..cp }
+                    }
+                    CastPointType::Reflection(..) => {
+                        reflections += 1;
+                        cp
+                    }
+                    _ => cp,
+                };
+                Some(cp)
+            }).filter_map(|cp| {
+                let seg_dist = last_dist;
+                let seg_start = last_point;
 
-                let dist_vect = cp.point - last_point;
-                total_distance += dist_vect.norm();
+                let seg = cp.point - last_point;
                 last_point = cp.point;
-                let dist = total_distance;
+                last_dist = cp.dist;
+                let dist = cp.dist;
+
+                let seg_reflected = reflected;
 
                 match cp.cast_type {
                     CastPointType::Void(_) => None,
+                    CastPointType::Limit(side) => {
+                        Some(RenderHit { side, u: 0., seg_start, seg, seg_dist, dist, mat: None, reflected: seg_reflected })
+                    }
                     // TODO: fix reflection
                     CastPointType::Reflection(mat, side)
+                    | CastPointType::Refraction(mat, side)
                     | CastPointType::Pass(mat, side)
                     | CastPointType::Termination(mat, side) => {
-                        let dark = matches!(side, Side::Left | Side::Right);
+                        if let CastPointType::Reflection(..) | CastPointType::Refraction(..) = cp.cast_type {
+                            reflected = true;
+                        }
+
+                        // The texture of a door slides along with it into the wall
+                        let (x, y) = side.cell_of_hit(cp.point);
+                        let open = if let Shape::Line(..) = self.shape(x, y) { mat.state as f32 / 255. } else { 0. };
                         let u = match side {
-                            Side::Left => cp.point.y.fract(),
-                            Side::Up => 1. - cp.point.x.fract(),
-                            Side::Right => 1. - cp.point.y.fract(),
-                            Side::Down => cp.point.x.fract(),
+                            Side::Left => cp.point.y.fract() + open,
+                            Side::Up => 1. - cp.point.x.fract() - open,
+                            Side::Right => 1. - cp.point.y.fract() - open,
+                            Side::Down => cp.point.x.fract() + open,
                         };
 
-                        Some((dark, u, (p, dist_vect, last_dist), dist, mat))
+                        Some(RenderHit { side, u, seg_start, seg, seg_dist, dist, mat: Some(mat), reflected: seg_reflected })
                     }
                     CastPointType::Destination => unreachable!(),
                 }
             }).collect::<Vec<_>>()
     }
 }
+
+/// Number that looks random for each cell, but is always the same for the same cell
+fn cell_hash((x, y): (i32, i32)) -> u32 {
+    let mut h = (x as u32).wrapping_mul(0x9e37_79b1) ^ (y as u32).wrapping_mul(0x85eb_ca77);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2c1b_3c6d);
+    h ^ (h >> 13)
+}
+
+/// A material hit by a render ray cast
+#[derive(Debug, Clone, Copy)]
+pub struct RenderHit {
+    /// Side of the material that was hit
+    pub side: Side,
+    pub u: f32,
+    /// Start of the ray segment ending in this hit
+    pub seg_start: Point2,
+    /// The ray segment ending in this hit
+    pub seg: Vector2,
+    /// Distance the ray travelled before the segment
+    pub seg_dist: f32,
+    /// Distance the ray travelled to get to this hit
+    pub dist: f32,
+    /// `None` if the ray reached the render distance and the fog should be drawn
+    pub mat: Option<Mat>,
+    /// Whether the ray had been reflected or refracted before the segment, so it doesn't come straight from the camera
+    pub reflected: bool,
+}
+
+#[test]
+fn cells_outside_are_none() {
+    let src = crate::testing::test_map("Edges", &[], &[], "\
+bbb
+b>b
+bbb
+");
+    let (map, ..) = Map::from_src(&src).unwrap();
+    assert!(map.get(0, 0).is_some());
+    assert!(map.get(2, 2).is_some());
+    for (x, y) in [(-1, 0), (0, -1), (-1, -1), (3, 0), (0, 3), (i32::MIN, 0), (0, i32::MAX)] {
+        assert_eq!(map.get(x, y), None, "({x}, {y}) is outside");
+    }
+    assert_eq!(map.get_on_layer(1, 0, 0), None);
+}
+
+#[test]
+fn variants_are_picked_the_same_for_a_cell() {
+    let src = "\
+Variants
+
+b variants tex/brick1.png tex/brick1_dark.png tex/brick2.png tex/brick2_dark.png
+
+bbbbbb
+b>   b
+bbbbbb
+";
+    let (map, ..) = Map::from_src(src).unwrap();
+    let mat = map.get(0, 0).unwrap();
+    let variant = |x, y| {
+        let tex = map.get_tex(mat, Side::Up, 0., (x, y));
+        map.textures[mat.index()].frames.iter().position(|(light, _)| std::ptr::eq(light, tex)).unwrap()
+    };
+    assert_eq!(variant(3, 7), variant(3, 7));
+    let picked: Vec<_> = (0..16).map(|x| variant(x, 0)).collect();
+    assert!(picked.contains(&0) && picked.contains(&1));
+}
+
+#[test]
+fn doors_block_what_is_left_of_them() {
+    let src = crate::testing::test_map("Door", &[], &["d door tex/door.png tex/door_dark.png"], "\
+bbbbb
+b v b
+bbdbb
+b   b
+bbbbb
+");
+    let (mut map, ..) = Map::from_src(&src).unwrap();
+    let end = |map: &Map, x: f32| {
+        let (from, dp) = (Point2::new(x, 1.5), Vector2::new(0., 2.));
+        (from + dp - map.move_ray_cast(from, dp)).y
+    };
+    // Closed, stopping at the edge of the cell
+    assert!((end(&map, 2.5) - 1.995).abs() < 1e-4);
+    // Half open, the middle is still in the way, stopping at the door across the middle of the cell
+    map.set_state(2, 2, 128);
+    assert!((end(&map, 2.3) - 2.495).abs() < 1e-4);
+    assert!((end(&map, 2.7) - 3.5).abs() < 1e-4);
+    map.set_state(2, 2, u8::MAX);
+    assert!((end(&map, 2.3) - 3.5).abs() < 1e-4);
+}
+
+#[test]
+fn doors_look_like_what_is_left_of_them() {
+    let src = crate::testing::test_map("Door", &[], &["d door tex/door.png tex/door_dark.png"], "\
+bbbbb
+b v b
+bbdbb
+b   b
+bbbbb
+");
+    let (mut map, ..) = Map::from_src(&src).unwrap();
+    let hit = |map: &Map, x: f32| {
+        let hits = map.render_ray_cast(Point2::new(x, 1.5), Vector2::new(0., 1.), 10.);
+        let hit = hits.last().expect("a wall to be hit");
+        (hit.seg_start + hit.seg, hit.u)
+    };
+    // The same as what stops movement in `doors_block_what_is_left_of_them`
+    assert!((hit(&map, 2.5).0.y - 2.).abs() < 1e-4);
+    map.set_state(2, 2, 128);
+    let (point, u) = hit(&map, 2.3);
+    assert!((point.y - 2.5).abs() < 1e-4);
+    // The door has slid half of its texture into the wall
+    assert!((u - (0.7 - 128. / 255.)).abs() < 1e-4);
+    assert!((hit(&map, 2.7).0.y - 4.).abs() < 1e-4);
+}
+
+#[test]
+fn cells_changing_change_the_revision() {
+    let src = crate::testing::test_map("Crates", &[], &["c hp=10 tex/brick1.png tex/brick1_dark.png"], "\
+bbbbb
+b>c b
+bbbbb
+");
+    let (mut map, ..) = Map::from_src(&src).unwrap();
+    let (other, ..) = Map::from_src(&src).unwrap();
+    assert_ne!(map.revision(), other.revision());
+
+    let before = map.revision();
+    assert!(!map.damage_cell(2, 1, 4.));
+    let damaged = map.revision();
+    assert_ne!(damaged, before);
+    assert!(map.damage_cell(2, 1, 10.));
+    assert_ne!(map.revision(), damaged);
+    // Walls that can't be broken stay the way they are
+    let broken = map.revision();
+    assert!(!map.damage_cell(0, 0, 10.));
+    assert_eq!(map.revision(), broken);
+}
+
+#[test]
+fn fast_movement_stays_out_of_walls() {
+    let src = crate::testing::test_map("Room", &[], &[], "\
+bbbbbbb
+b     b
+b  b  b
+b  >  b
+b     b
+b    bb
+bbbbbbb
+");
+    let (map, ..) = Map::from_src(&src).unwrap();
+    for i in 0..256 {
+        let mut p = Point2::new(3.5, 3.5);
+        for step in 0..64 {
+            // Into walls and corners from every direction, up to several cells in one step
+            let angle = i as f32 * 0.173 + step as f32 * 0.61;
+            let dp = Vector2::unit_from_angle(angle).set_len(0.1 + (step as f32 * 0.37) % 4.);
+            p = p + dp - map.move_ray_cast(p, dp);
+            assert!(matches!(map.shape(p.x.floor() as i32, p.y.floor() as i32), Shape::Open), "went into a wall at ({}, {})", p.x, p.y);
+        }
+    }
+}
+
+#[test]
+fn problems_with_maps_are_errors() {
+    let src = crate::testing::test_map("Broken", &[], &[], "\
+bbbb
+b>xb
+bbbb
+");
+    assert_eq!(Map::from_src(&src).err().as_deref(), Some("x is in the grid but wasn't declared"));
+    let src = src.replace("b tex", "b shiny tex");
+    assert_eq!(Map::from_src(&src).err().as_deref(), Some("unknown property shiny of b"));
+}
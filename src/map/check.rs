@@ -0,0 +1,585 @@
+//! Finding everything wrong with a map file at once, instead of stopping at the first problem like loading does
+
+use std::{collections::{HashMap, HashSet}, fmt};
+
+use crate::{assets, world::faction::Faction};
+
+use super::{Map, Surface, Stairs, Side, Goal, LAYER_SEPARATOR, HEADERS, FLAGS, PROPERTIES, dialogue::{DIALOGUE_START, CHOICE_ARROW}, cutscene::CUTSCENE_START};
+
+/// Something wrong with a map
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    /// Line of the file it's on, starting from 1, or 0 if it's about the whole map
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+const PLAYER_STARTS: [char; 4] = ['<', '>', '^', 'v'];
+
+/// What the flood fill needs to know about each character of the grid
+#[derive(Debug, Clone, Copy)]
+struct Symbol {
+    solid: bool,
+    exit: bool,
+    stairs: Option<Stairs>,
+    elevator: bool,
+}
+
+const OPEN: Symbol = Symbol { solid: false, exit: false, stairs: None, elevator: false };
+const UNKNOWN: Symbol = Symbol { solid: true, exit: false, stairs: None, elevator: false };
+
+/// Cells by layer, row and column
+type Layers = Vec<Vec<Vec<Symbol>>>;
+
+struct Checker {
+    problems: Vec<Problem>,
+}
+
+impl Checker {
+    fn report(&mut self, line: usize, message: impl Into<String>) {
+        self.problems.push(Problem { line, message: message.into() });
+    }
+    fn texture(&mut self, line: usize, path: &str) {
+        if !assets::exists(path) {
+            self.report(line, format!("texture {path} doesn't exist"));
+        }
+    }
+}
+
+/// Every problem with the map in `src`, in the order they appear
+pub fn check(src: &str) -> Vec<Problem> {
+    let mut checker = Checker { problems: Vec::new() };
+    let mut lines = src.lines().enumerate().map(|(i, l)| (i + 1, l.trim()));
+
+    if !matches!(lines.next(), Some((_, name)) if !name.is_empty()) {
+        checker.report(1, "the map has no name");
+    }
+
+    // Header
+    let mut floor_chars = HashSet::new();
+    let mut objectives = Vec::new();
+    let mut wrap = false;
+    for (n, line) in lines.by_ref() {
+        if line.is_empty() {
+            break;
+        }
+        let mut elements = line.split_whitespace();
+        match elements.next() {
+            Some("floor") => {
+                let c = elements.next().and_then(|c| c.chars().next());
+                match (c, elements.next()) {
+                    (Some(c), Some(surface)) => {
+                        floor_chars.insert(c);
+                        if Surface::from_name(surface).is_none() {
+                            checker.report(n, format!("unknown surface {surface}"));
+                        }
+                    }
+                    _ => checker.report(n, "floor needs a character and a surface"),
+                }
+            }
+            Some("conveyor") => {
+                let c = elements.next().and_then(|c| c.chars().next());
+                let dir = elements.next().and_then(Side::from_direction);
+                let speed_ok = !matches!(elements.next(), Some(s) if s.parse::<f32>().is_err());
+                match (c, dir) {
+                    (Some(c), Some(_)) if speed_ok => {
+                        floor_chars.insert(c);
+                    }
+                    _ => checker.report(n, "conveyor needs a character, a direction and optionally a speed"),
+                }
+            }
+            Some("hazard") => {
+                let c = elements.next().and_then(|c| c.chars().next());
+                let damage = elements.next().and_then(|d| d.parse::<f32>().ok());
+                let surface_ok = !matches!(elements.next(), Some(s) if Surface::from_name(s).is_none());
+                match (c, damage) {
+                    (Some(c), Some(_)) if surface_ok => {
+                        floor_chars.insert(c);
+                    }
+                    _ => checker.report(n, "hazard needs a character, a damage and optionally a surface"),
+                }
+            }
+            Some("poison") => {
+                let c = elements.next().and_then(|c| c.chars().next());
+                let damage = elements.next().and_then(|d| d.parse::<f32>().ok());
+                let seconds = elements.next().and_then(|d| d.parse::<f32>().ok());
+                let surface_ok = !matches!(elements.next(), Some(s) if Surface::from_name(s).is_none());
+                match (c, damage, seconds) {
+                    (Some(c), Some(_), Some(_)) if surface_ok => {
+                        floor_chars.insert(c);
+                    }
+                    _ => checker.report(n, "poison needs a character, a damage per second, seconds and optionally a surface"),
+                }
+            }
+            Some("checkpoint") => {
+                let c = elements.next().and_then(|c| c.chars().next());
+                let surface_ok = !matches!(elements.next(), Some(s) if Surface::from_name(s).is_none());
+                match c {
+                    Some(c) if surface_ok => {
+                        floor_chars.insert(c);
+                    }
+                    _ => checker.report(n, "checkpoint needs a character and optionally a surface"),
+                }
+            }
+            Some("plate") => {
+                let c = elements.next().and_then(|c| c.chars().next());
+                let signal = elements.next();
+                let surface_ok = !matches!(elements.next(), Some(s) if Surface::from_name(s).is_none());
+                match (c, signal) {
+                    (Some(c), Some(_)) if surface_ok => {
+                        floor_chars.insert(c);
+                    }
+                    _ => checker.report(n, "plate needs a character, a signal and optionally a surface"),
+                }
+            }
+            Some("trigger") => {
+                let signals_ok = elements.next().is_some_and(|signals| signals.split('+').all(|s| !s.is_empty()));
+                let action_ok = matches!(elements.next(), Some("open" | "close" | "cutscene" | "time"));
+                if !signals_ok || !action_ok || elements.next().is_none() {
+                    checker.report(n, "trigger needs signals joined by +, an action of open, close, cutscene or time and what it acts on");
+                }
+            }
+            Some("logic") => {
+                let kind = elements.next();
+                let outputs = elements.next().map_or(0, |outputs| outputs.split('+').filter(|s| !s.is_empty()).count());
+                let args: Vec<_> = elements.collect();
+                let ok = match (kind, &args[..]) {
+                    (Some("counter"), [count, _]) => outputs == 1 && count.parse::<u32>().is_ok(),
+                    (Some("delay"), [seconds, _]) => outputs == 1 && seconds.parse::<f32>().is_ok_and(|s| s >= 0.),
+                    (Some("and" | "or"), [inputs]) => outputs == 1 && inputs.split('+').all(|s| !s.is_empty()),
+                    (Some("random"), [_]) => outputs > 0,
+                    _ => false,
+                };
+                if !ok {
+                    checker.report(n, "logic needs to be a counter, and, or, delay or random with its signal and what it works from");
+                }
+            }
+            Some("objective") => {
+                let goal = elements.next().and_then(Goal::from_name);
+                let c = elements.next().and_then(|c| c.chars().next());
+                match (goal, c, elements.next()) {
+                    (Some(goal), Some(c), Some(_)) => objectives.push((n, goal, c)),
+                    _ => checker.report(n, "objective needs to be destroy or find, a character and a text"),
+                }
+            }
+            Some("wrap") => wrap = true,
+            Some("grade") => {
+                let numbers: Vec<_> = elements.map(|n| n.parse::<f32>().ok()).collect();
+                let tint_ok = numbers.get(..3).is_some_and(|tint| tint.iter().all(|c| c.is_some_and(|c| (0. ..=255.).contains(&c))));
+                if numbers.len() != 5 || !tint_ok || numbers[3..].iter().any(Option::is_none) {
+                    checker.report(n, "colour grade needs a tint colour from 0 to 255, a contrast and a saturation");
+                }
+            }
+            Some("time_limit") if !elements.next().and_then(|t| t.parse::<f32>().ok()).is_some_and(|t| t > 0.) => {
+                checker.report(n, "time limit needs to be a positive number of seconds");
+            }
+            Some("day_night") => {
+                let length = elements.next().and_then(|l| l.parse::<f32>().ok());
+                let hour = elements.next().map(|h| h.parse::<f32>().ok());
+                if !length.is_some_and(|l| l > 0.) || hour.is_some_and(|h| h.is_none()) {
+                    checker.report(n, "day and night needs a positive number of seconds per day and optionally the hour it starts at");
+                }
+            }
+            Some("surface") if elements.next().and_then(Surface::from_name).is_none() => {
+                checker.report(n, "surface needs to be stone, metal, wood, water or ice");
+            }
+            Some(key) if !HEADERS.contains(&key) => checker.report(n, format!("unknown map header {key}")),
+            _ => (),
+        }
+    }
+
+    // Materials and things
+    let mut npc_dialogues = Vec::new();
+    // Whether each thing has hit points
+    let mut thing_hp = HashMap::new();
+    let mut summoned = Vec::new();
+    let mut symbols: HashMap<char, Symbol> = [' ', '<', '>', '^', 'v'].into_iter()
+        .map(|c| (c, OPEN))
+        .collect();
+    for (n, line) in lines.by_ref() {
+        if line.is_empty() {
+            break;
+        }
+        let elements: Vec<_> = line.split_whitespace().collect();
+        let mut chars = elements[0].chars();
+        let c = chars.next().unwrap();
+        if chars.next().is_some() {
+            checker.report(n, format!("{} has to be a single character", elements[0]));
+        }
+        if symbols.contains_key(&c) {
+            checker.report(n, format!("{c} is already used"));
+        }
+
+        let thing = elements.iter().any(|&e| matches!(e, "thing" | "prop" | "npc" | "boss" | "enemy"));
+        let npc = elements.contains(&"npc");
+        if npc != elements.iter().any(|e| e.starts_with("dialogue=")) {
+            checker.report(n, "only friendly things have dialogue and they need it");
+        }
+        let mut symbol = Symbol { solid: !thing, ..OPEN };
+        let mut properties = &elements[1..];
+
+        if let Some(i) = properties.iter().position(|&e| e == "anim") {
+            if !properties.get(i + 1).is_some_and(|t| t.parse::<f32>().is_ok_and(|t| t > 0.)) {
+                checker.report(n, "animation needs a positive frame time");
+            }
+            let frames = properties.get(i + 2..).unwrap_or_default();
+            if frames.is_empty() {
+                checker.report(n, "animation has no frames");
+            }
+            for frame in frames {
+                checker.texture(n, frame);
+            }
+            properties = &properties[..i];
+        } else if let Some(i) = properties.iter().position(|&e| e == "variants") {
+            let textures = &properties[i + 1..];
+            if textures.is_empty() || textures.len() % 2 != 0 {
+                checker.report(n, "variants have to be pairs of textures");
+            }
+            if thing {
+                checker.report(n, format!("thing {c} cannot have variants"));
+            }
+            for texture in textures {
+                checker.texture(n, texture);
+            }
+            properties = &properties[..i];
+        } else if properties.len() < 2 {
+            checker.report(n, format!("{c} needs two textures"));
+            properties = &[];
+        } else {
+            let (rest, textures) = properties.split_at(properties.len() - 2);
+            if thing {
+                if textures[0].parse::<f32>().is_err() {
+                    checker.report(n, format!("width of thing {c} has to be a number"));
+                }
+            } else {
+                checker.texture(n, textures[0]);
+            }
+            checker.texture(n, textures[1]);
+            properties = rest;
+        }
+
+        for &property in properties {
+            match property.split_once('=') {
+                None if FLAGS.contains(&property) => match property {
+                    "nonsolid" | "walkthrough" => symbol.solid = false,
+                    "locked" if !elements.contains(&"door") => checker.report(n, "only doors can be locked"),
+                    "exit" | "secret_exit" => symbol.exit = true,
+                    "stairs_up" | "stairs_down" => {
+                        symbol.solid = false;
+                        symbol.stairs = Some(if property == "stairs_up" { Stairs::Up } else { Stairs::Down });
+                    }
+                    "elevator" => {
+                        symbol.solid = false;
+                        symbol.elevator = true;
+                    }
+                    _ => (),
+                },
+                Some(("north" | "south" | "west" | "east", path)) => checker.texture(n, path),
+                Some(("surface", s)) if Surface::from_name(s).is_none() => checker.report(n, format!("unknown surface {s}")),
+                Some(("hp" | "index" | "time" | "speed" | "invulnerable" | "armor" | "scale", num)) if num.parse::<f32>().is_err() => checker.report(n, format!("{num} is not a number")),
+                Some(("treasure", num)) if num.parse::<u32>().is_err() => checker.report(n, format!("{num} is not a whole number")),
+                Some(("moving", times)) if !valid_schedule(times) => {
+                    checker.report(n, "moving needs a positive period and optionally an offset");
+                }
+                Some(("spawner", spawner)) if !valid_spawner(spawner) => {
+                    checker.report(n, "spawner needs a character, a positive interval and a whole number cap");
+                }
+                Some(("faction", f)) if Faction::from_name(f).is_none() => {
+                    checker.report(n, "faction needs to be player, guards or monsters");
+                }
+                Some(("switch", switch)) if !valid_switch(switch) => {
+                    checker.report(n, "switch needs a signal and optionally a positive number of seconds until it turns back off");
+                }
+                Some(("door_sound", name)) if name.is_empty() || !elements.contains(&"door") => {
+                    checker.report(n, "only doors can have door sounds and they need a name");
+                }
+                Some(("facing", dir)) if Side::from_direction(dir).is_none() || !elements.contains(&"enemy") => {
+                    checker.report(n, "only enemies can face a way and it needs to be north, south, east or west");
+                }
+                Some(("tint", rgba)) if rgba.split(',').filter(|c| c.parse::<u8>().is_ok()).count() != 4 => {
+                    checker.report(n, "tint needs to be four numbers from 0 to 255");
+                }
+                Some(("dialogue", name)) => npc_dialogues.push((n, name.to_owned())),
+                Some((key, _)) if PROPERTIES.contains(&key) => (),
+                _ => checker.report(n, format!("unknown property {property}")),
+            }
+        }
+        if thing {
+            let hp = properties.iter().any(|p| p.starts_with("hp="));
+            if let Some(kind) = properties.iter().find(|&&p| p == "boss" || p == "enemy").filter(|_| !hp) {
+                checker.report(n, format!("{kind} {c} needs hit points"));
+            }
+            thing_hp.insert(c, hp);
+        }
+        summoned.extend(properties.iter().find_map(|p| p.strip_prefix("summon=")).map(|s| (n, s.chars().next())));
+        summoned.extend(properties.iter().find_map(|p| p.strip_prefix("spawner=")).map(|s| (n, s.chars().next())));
+        symbols.insert(c, symbol);
+    }
+    for (n, c) in summoned {
+        if !matches!(c, Some(c) if thing_hp.contains_key(&c)) {
+            checker.report(n, "bosses and spawners have to create a thing");
+        }
+    }
+    for (n, goal, c) in objectives {
+        match thing_hp.get(&c) {
+            None => checker.report(n, format!("objective {c} has to be about a thing")),
+            Some(false) if goal == Goal::Destroy => checker.report(n, format!("{c} needs hit points to be destroyed")),
+            _ => (),
+        }
+    }
+
+    // Grid
+    let mut layers: Layers = vec![Vec::new()];
+    let mut width = None;
+    let mut player = None;
+    for (n, line) in lines.by_ref() {
+        if line.is_empty() {
+            break;
+        }
+        if line == LAYER_SEPARATOR {
+            if layers.len() > 1 && layers.last().unwrap().len() != layers[0].len() {
+                checker.report(n, "this layer isn't as tall as the first layer");
+            }
+            layers.push(Vec::new());
+            continue;
+        }
+        let layer = layers.len() - 1;
+        let grid = &mut layers[layer];
+        let row: Vec<_> = line.chars().collect();
+        match width {
+            None => width = Some(row.len()),
+            Some(w) if w != row.len() => checker.report(n, format!("this line is {} long, but the first line was {w}", row.len())),
+            _ => (),
+        }
+        for (x, &c) in row.iter().enumerate() {
+            if PLAYER_STARTS.contains(&c) {
+                if player.is_some() {
+                    checker.report(n, "there's more than one player start");
+                }
+                player = Some((layer, x, grid.len()));
+            }
+            if !symbols.contains_key(&c) {
+                checker.report(n, format!("{c} was never declared"));
+            }
+        }
+        grid.push(row.into_iter().map(|c| symbols.get(&c).copied().unwrap_or(UNKNOWN)).collect());
+    }
+
+    if layers[0].is_empty() {
+        checker.report(0, "the map has no grid");
+    } else if layers.len() > 1 && layers.last().unwrap().len() != layers[0].len() {
+        checker.report(0, "the last layer isn't as tall as the first layer");
+    }
+    match player {
+        None => checker.report(0, "there's no player start"),
+        Some(start) if layers.iter().flatten().flatten().any(|s| s.exit) && !exit_reachable(&layers, start, wrap) => {
+            checker.report(0, "no exit can be reached from the player start");
+        }
+        _ => (),
+    }
+
+    // Floor grid
+    let mut floor_rows = 0;
+    let mut lines = lines.filter(|&(_, l)| !l.is_empty() && l != LAYER_SEPARATOR).peekable();
+    while let Some((n, line)) = lines.next_if(|(_, l)| !l.starts_with(DIALOGUE_START) && !l.starts_with(CUTSCENE_START)) {
+        floor_rows += 1;
+        if Some(line.chars().count()) != width {
+            checker.report(n, "the floor grid has to be as wide as the map");
+        }
+        for c in line.chars().filter(|&c| c != '.' && !floor_chars.contains(&c)) {
+            checker.report(n, format!("floor {c} was never declared"));
+        }
+    }
+    if floor_rows > 0 && floor_rows != layers.iter().map(Vec::len).sum::<usize>() {
+        checker.report(0, "the floor grid has to be as tall as the map");
+    }
+
+    // Dialogues and cutscenes
+    let mut dialogue_names = HashSet::new();
+    let mut cutscene_names = HashSet::new();
+    let mut leads_to = Vec::new();
+    // Whether the lines belong to a cutscene and whether it has had a shot yet
+    let mut cutscene = None;
+    for (n, line) in lines {
+        if let Some(name) = line.strip_prefix(DIALOGUE_START) {
+            if !dialogue_names.insert(name.trim()) {
+                checker.report(n, format!("there's already a dialogue called {}", name.trim()));
+            }
+            cutscene = None;
+            continue;
+        }
+        if let Some(name) = line.strip_prefix(CUTSCENE_START) {
+            if !cutscene_names.insert(name.trim()) {
+                checker.report(n, format!("there's already a cutscene called {}", name.trim()));
+            }
+            cutscene = Some(false);
+            continue;
+        }
+        if let Some(has_shot) = &mut cutscene {
+            match line.split_once(' ') {
+                Some(("shot", numbers)) => {
+                    let numbers: Vec<_> = numbers.split_whitespace().map(|n| n.parse::<f32>().ok()).collect();
+                    if !matches!(numbers[..], [Some(_), Some(_), Some(_), Some(_), Some(time)] if time > 0.) {
+                        checker.report(n, "shot needs a position, a point to look at and a positive time");
+                    }
+                    *has_shot = true;
+                }
+                Some(("say", _)) if *has_shot => (),
+                Some(("say", _)) => checker.report(n, "subtitles have to come after a shot"),
+                _ => checker.report(n, "cutscene lines have to be a shot or a subtitle"),
+            }
+            continue;
+        }
+        if dialogue_names.is_empty() {
+            checker.report(n, "dialogue lines have to come after the name of a dialogue");
+        }
+        match line.split_once(' ') {
+            Some(("page", _)) => (),
+            Some(("choice", choice)) => leads_to.extend(choice.split_once(CHOICE_ARROW).map(|(_, next)| (n, next.trim()))),
+            _ => checker.report(n, "dialogue lines have to be a page or a choice"),
+        }
+    }
+    let undefined = npc_dialogues.iter().map(|(n, name)| (*n, name.as_str())).chain(leads_to);
+    for (n, name) in undefined.filter(|(_, name)| !dialogue_names.contains(name)) {
+        checker.report(n, format!("dialogue {name} doesn't exist"));
+    }
+
+    // Anything else loading would stop at, which is only found once the problems above are fixed
+    if checker.problems.is_empty() {
+        if let Err(e) = Map::from_src(src) {
+            checker.report(0, e);
+        }
+    }
+
+    checker.problems
+}
+
+/// Whether `spawner` is a character, a positive interval and a cap
+fn valid_spawner(spawner: &str) -> bool {
+    let parts: Vec<_> = spawner.split(',').collect();
+    matches!(parts[..], [c, interval, cap]
+        if c.chars().count() == 1 && interval.parse::<f32>().is_ok_and(|i| i > 0.) && cap.parse::<u32>().is_ok())
+}
+
+/// Whether `times` is a positive period optionally followed by an offset
+fn valid_schedule(times: &str) -> bool {
+    let times: Vec<_> = times.split(',').map(|t| t.parse::<f32>().ok()).collect();
+    matches!(times[..], [Some(period)] | [Some(period), Some(_)] if period > 0.)
+}
+
+/// Whether `switch` is a signal and optionally a positive number of seconds until it turns back off
+fn valid_switch(switch: &str) -> bool {
+    match switch.split_once(',') {
+        Some((signal, seconds)) => !signal.is_empty() && seconds.parse::<f32>().is_ok_and(|s| s > 0.),
+        None => !switch.is_empty(),
+    }
+}
+
+/// Whether walking from `start` (layer, column and row) can get next to an exit, going across the edges if the map `wrap`s
+fn exit_reachable(layers: &Layers, start: (usize, usize, usize), wrap: bool) -> bool {
+    let get = |l: usize, x: usize, y: usize| layers.get(l).and_then(|grid| grid.get(y)).and_then(|row| row.get(x));
+    let height = layers[0].len();
+    let width = layers[0].first().map_or(0, Vec::len);
+    // Going off one edge comes back on the other
+    let step = |n: usize, forwards: bool, len: usize| match (forwards, wrap) {
+        (true, true) => (n + 1) % len,
+        (false, true) => (n + len - 1) % len,
+        (true, false) => n + 1,
+        (false, false) => n.wrapping_sub(1),
+    };
+    let mut seen = HashSet::from([start]);
+    let mut stack = vec![start];
+
+    while let Some((l, x, y)) = stack.pop() {
+        let mut neighbours = vec![
+            (l, step(x, true, width), y),
+            (l, step(x, false, width), y),
+            (l, x, step(y, true, height)),
+            (l, x, step(y, false, height)),
+        ];
+        // Stairs lead to the same cell on the next layer
+        match get(l, x, y).and_then(|s| s.stairs) {
+            Some(Stairs::Up) => neighbours.push((l + 1, x, y)),
+            Some(Stairs::Down) => neighbours.push((l.wrapping_sub(1), x, y)),
+            None => (),
+        }
+        // Elevators go round every layer with an elevator in the same place
+        if get(l, x, y).is_some_and(|s| s.elevator) {
+            neighbours.extend((0..layers.len()).filter(|&nl| get(nl, x, y).is_some_and(|s| s.elevator)).map(|nl| (nl, x, y)));
+        }
+        for (nl, nx, ny) in neighbours {
+            let Some(symbol) = get(nl, nx, ny) else { continue };
+            // Exits only have to be touched
+            if symbol.exit && nl == l {
+                return true;
+            }
+            if !symbol.solid && seen.insert((nl, nx, ny)) {
+                stack.push((nl, nx, ny));
+            }
+        }
+    }
+    false
+}
+
+#[test]
+fn finds_all_problems() {
+    use crate::testing::test_map;
+
+    let src = test_map("Broken", &["fog 0 0 0", "gravity 2"], &["x shiny tex/brick1.png tex/missing.png", "e exit tex/door.png tex/door_dark.png"], "\
+bbbbbb
+b> b e
+bbbbbbb
+b?   b
+bbbbbb
+");
+    let problems: Vec<_> = check(&src).into_iter().map(|p| p.line).collect();
+    // Unknown header, unknown property and missing texture, wrong width, undeclared symbol, exit walled off
+    assert_eq!(problems, [3, 6, 6, 11, 12, 0]);
+
+    let map = assets::read_to_string("map.txt").unwrap();
+    assert_eq!(check(&map), []);
+    // Signals nobody turns on are only found by loading the map
+    let (name, rest) = map.split_once('\n').unwrap();
+    let silent = format!("{name}\ntrigger nothing cutscene intro\n{rest}");
+    assert_eq!(check(&silent), [Problem { line: 0, message: "nothing turns on signal nothing".to_owned() }]);
+}
+
+#[test]
+fn stairs_lead_to_exits() {
+    use crate::testing::test_map;
+
+    let src = test_map("Upstairs", &[], &[
+        "u stairs_up tex/door.png tex/door_dark.png",
+        "d stairs_down tex/door.png tex/door_dark.png",
+        "e exit tex/door.png tex/door_dark.png",
+    ], "\
+bbbbb
+b> ub
+bbbbb
+---
+bbbbb
+be db
+bbbbb
+");
+    assert_eq!(check(&src), []);
+    // The only exit is on the layer above
+    let no_stairs = src.replace("b> ub", "b> bb");
+    assert_eq!(check(&no_stairs).into_iter().map(|p| p.line).collect::<Vec<_>>(), [0]);
+
+    let elevators = src.replace("stairs_up", "elevator").replace("stairs_down", "elevator");
+    assert_eq!(check(&elevators), []);
+
+    // The last layer is missing a row
+    let uneven = src.replace("be db\nbbbbb\n", "be db\n");
+    assert_eq!(check(&uneven).into_iter().map(|p| p.line).collect::<Vec<_>>(), [0]);
+}
@@ -0,0 +1,68 @@
+//! Floor state of each cell kept in square chunks that are only made once something in them differs from the default
+//!
+//! Huge maps are mostly the same, so most chunks never take up any memory.
+
+/// Cells along each side of a chunk
+const CHUNK_SIZE: i32 = 32;
+const CHUNK_LEN: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+#[derive(Debug, Clone)]
+pub struct Chunked<T> {
+    /// Size in cells
+    width: i32,
+    height: i32,
+    /// How many chunks there are along the width
+    chunks_wide: i32,
+    chunks: Vec<Option<Box<[T]>>>,
+    /// What every cell of a chunk that hasn't been made yet is
+    default: T,
+}
+
+impl<T: Clone> Chunked<T> {
+    /// `width` by `height` cells all of `default`
+    pub fn new(width: i32, height: i32, default: T) -> Self {
+        let chunks_wide = chunks_along(width);
+        let len = (chunks_wide * chunks_along(height)) as usize;
+        Chunked { width, height, chunks_wide, chunks: vec![None; len], default }
+    }
+    /// Index of the chunk the cell is in and the index of the cell in the chunk
+    fn locate(&self, x: i32, y: i32) -> Option<(usize, usize)> {
+        if !(0..self.width).contains(&x) || !(0..self.height).contains(&y) {
+            return None;
+        }
+        let chunk = y / CHUNK_SIZE * self.chunks_wide + x / CHUNK_SIZE;
+        let cell = y % CHUNK_SIZE * CHUNK_SIZE + x % CHUNK_SIZE;
+        Some((chunk as usize, cell as usize))
+    }
+    /// `None` outside of the chunks
+    pub fn get(&self, x: i32, y: i32) -> Option<&T> {
+        let (chunk, cell) = self.locate(x, y)?;
+        Some(self.chunks[chunk].as_ref().map_or(&self.default, |chunk| &chunk[cell]))
+    }
+    /// Makes the chunk of the cell if it doesn't exist yet
+    pub fn get_mut(&mut self, x: i32, y: i32) -> Option<&mut T> {
+        let (chunk, cell) = self.locate(x, y)?;
+        let default = &self.default;
+        let cells = self.chunks[chunk].get_or_insert_with(|| vec![default.clone(); CHUNK_LEN].into_boxed_slice());
+        Some(&mut cells[cell])
+    }
+}
+
+const fn chunks_along(cells: i32) -> i32 {
+    (cells + CHUNK_SIZE - 1) / CHUNK_SIZE
+}
+
+#[test]
+fn chunks_are_made_when_changed() {
+    let mut cells = Chunked::new(70, 40, 0u8);
+    assert_eq!(cells.get(69, 39), Some(&0));
+    assert_eq!(cells.get(70, 0), None);
+    assert_eq!(cells.get(-1, 0), None);
+    assert_eq!(cells.chunks.iter().flatten().count(), 0);
+
+    *cells.get_mut(65, 33).unwrap() = 7;
+    assert_eq!(cells.get(65, 33), Some(&7));
+    assert_eq!(cells.get(64, 33), Some(&0));
+    // Only the chunk in the third column of the second row
+    assert!(cells.chunks.iter().enumerate().all(|(i, chunk)| chunk.is_some() == (i == 5)));
+}
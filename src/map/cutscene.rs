@@ -0,0 +1,120 @@
+//! Camera moves that take over the view, written at the end of the map file like dialogues
+//!
+//! ```text
+//! cutscene intro
+//! shot 3.5 1.5 10.5 1.5 2
+//! say The guards are waiting upstairs.
+//! shot 8.5 4.5 12.5 4.5 3
+//! ```
+//!
+//! Each shot is where the camera is, the point it looks at and how many seconds it takes to get there
+//! from the shot before, the first one is held that long instead. A subtitle is shown while the camera is on the way to its shot.
+//! A cutscene called `intro` plays when the level starts and one called `boss` the first time a boss sees the player.
+
+use std::collections::HashMap;
+
+use crate::vec::Point2;
+
+/// Line starting a new cutscene, followed by its name
+pub(super) const CUTSCENE_START: &str = "cutscene ";
+
+#[derive(Debug, Clone)]
+pub struct Cutscene {
+    pub shots: Vec<Shot>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Shot {
+    pub pos: Point2,
+    pub look_at: Point2,
+    /// Seconds it takes to get here from the shot before
+    pub time: f32,
+    pub subtitle: Option<String>,
+}
+
+impl Cutscene {
+    /// Seconds from the start of the first shot to the end of the last one
+    pub fn duration(&self) -> f32 {
+        self.shots.iter().map(|shot| shot.time).sum()
+    }
+    /// Where the camera is `t` seconds in, which way it's looking and the subtitle shown then
+    pub fn at(&self, mut t: f32) -> (Point2, f32, Option<&str>) {
+        let mut from = &self.shots[0];
+        for shot in &self.shots {
+            if t < shot.time {
+                let s = (t / shot.time).clamp(0., 1.);
+                let eased = s * s * (3. - 2. * s);
+                let pos = lerp(from.pos, shot.pos, eased);
+                let look_at = lerp(from.look_at, shot.look_at, eased);
+                return (pos, (look_at - pos).direction_angle(), shot.subtitle.as_deref());
+            }
+            t -= shot.time;
+            from = shot;
+        }
+        (from.pos, (from.look_at - from.pos).direction_angle(), from.subtitle.as_deref())
+    }
+}
+
+fn lerp(a: Point2, b: Point2, t: f32) -> Point2 {
+    a + t * (b - a)
+}
+
+/// Parses the lines of every cutscene, returning them by name
+pub(super) fn parse<'a>(lines: impl Iterator<Item = &'a str>) -> Result<HashMap<String, Cutscene>, String> {
+    let mut parsed: Vec<(String, Cutscene)> = Vec::new();
+
+    for line in lines.map(str::trim).filter(|l| !l.is_empty()) {
+        if let Some(name) = line.strip_prefix(CUTSCENE_START) {
+            parsed.push((name.trim().to_owned(), Cutscene { shots: Vec::new() }));
+            continue;
+        }
+        let (name, Cutscene { shots }) = parsed.last_mut().ok_or("cutscene lines have to come after the name of a cutscene")?;
+        match line.split_once(' ') {
+            Some(("shot", numbers)) => {
+                let numbers: Vec<f32> = numbers.split_whitespace()
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| format!("shot of cutscene {name} has to be made of numbers"))?;
+                let &[x, y, look_x, look_y, time] = &numbers[..] else {
+                    return Err(format!("shot of cutscene {name} needs a position, a point to look at and a time"));
+                };
+                ensure!(time > 0., "shot of cutscene {name} has to take some time");
+                shots.push(Shot { pos: Point2::new(x, y), look_at: Point2::new(look_x, look_y), time, subtitle: None });
+            }
+            Some(("say", text)) => {
+                let shot = shots.last_mut().ok_or_else(|| format!("subtitle of cutscene {name} has to come after a shot"))?;
+                shot.subtitle = Some(text.trim().to_owned());
+            }
+            _ => return Err(format!("unknown cutscene line {line}")),
+        }
+    }
+
+    parsed.into_iter().map(|(name, cutscene)| {
+        ensure!(!cutscene.shots.is_empty(), "cutscene {name} has no shots");
+        Ok((name, cutscene))
+    }).collect()
+}
+
+#[test]
+fn shots_are_moved_between() {
+    let src = "
+cutscene intro
+shot 0 0 0 1 1
+say Look up
+shot 2 0 2 1 2
+";
+    let cutscenes = parse(src.lines()).unwrap();
+    let intro = &cutscenes["intro"];
+    assert_eq!(intro.duration(), 3.);
+
+    let (pos, _, subtitle) = intro.at(0.5);
+    assert_eq!((pos.x, pos.y), (0., 0.));
+    assert_eq!(subtitle, Some("Look up"));
+    // Halfway through the move to the second shot
+    let (pos, angle, subtitle) = intro.at(2.);
+    assert!((pos.x - 1.).abs() < 1e-5);
+    assert!((angle - std::f32::consts::FRAC_PI_2).abs() < 1e-5);
+    assert_eq!(subtitle, None);
+    let (pos, _, _) = intro.at(10.);
+    assert_eq!((pos.x, pos.y), (2., 0.));
+}
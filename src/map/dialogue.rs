@@ -0,0 +1,94 @@
+//! Conversations with friendly things, written at the end of the map file
+//!
+//! ```text
+//! dialogue keeper
+//! page Welcome to the earth.
+//! page Mind the crusher upstairs.
+//! choice Thanks!
+//! choice Who are you? -> keeper_who
+//! ```
+
+use std::collections::HashMap;
+
+/// Line starting a new dialogue, followed by its name
+pub(super) const DIALOGUE_START: &str = "dialogue ";
+/// Separates the text of a choice from the dialogue it leads to
+pub(super) const CHOICE_ARROW: &str = "->";
+
+/// What a friendly thing says when talked to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dialogue {
+    /// Text shown one page at a time
+    pub pages: Vec<String>,
+    /// Answers that can be picked on the last page, the dialogue just ends if there are none
+    pub choices: Vec<Choice>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Choice {
+    pub text: String,
+    /// Index of the dialogue it leads to, or `None` if it ends the conversation
+    pub next: Option<usize>,
+}
+
+/// A dialogue as it's written, before the names its choices lead to are looked up
+struct Unresolved {
+    name: String,
+    pages: Vec<String>,
+    choices: Vec<(String, Option<String>)>,
+}
+
+/// Parses the dialogue section, returning the dialogues and the index of the dialogue with each name
+pub(super) fn parse<'a>(lines: impl Iterator<Item = &'a str>) -> Result<(Vec<Dialogue>, HashMap<String, usize>), String> {
+    let mut parsed: Vec<Unresolved> = Vec::new();
+
+    for line in lines.map(str::trim).filter(|l| !l.is_empty()) {
+        if let Some(name) = line.strip_prefix(DIALOGUE_START) {
+            parsed.push(Unresolved { name: name.trim().to_owned(), pages: Vec::new(), choices: Vec::new() });
+            continue;
+        }
+        let Unresolved { pages, choices, .. } = parsed.last_mut().ok_or("dialogue lines have to come after the name of a dialogue")?;
+        match line.split_once(' ') {
+            Some(("page", text)) => pages.push(text.trim().to_owned()),
+            Some(("choice", choice)) => choices.push(match choice.split_once(CHOICE_ARROW) {
+                Some((text, next)) => (text.trim().to_owned(), Some(next.trim().to_owned())),
+                None => (choice.trim().to_owned(), None),
+            }),
+            _ => return Err(format!("unknown dialogue line {line}")),
+        }
+    }
+
+    let names: HashMap<_, _> = parsed.iter().enumerate().map(|(i, d)| (d.name.clone(), i)).collect();
+    let dialogues = parsed.into_iter().map(|Unresolved { name, pages, choices }| {
+        ensure!(!pages.is_empty(), "dialogue {name} has no pages");
+        let choices = choices.into_iter().map(|(text, next)| Ok(Choice {
+            text,
+            next: next.map(|next| names.get(&next).copied().ok_or_else(|| format!("dialogue {next} doesn't exist"))).transpose()?,
+        })).collect::<Result<_, String>>()?;
+        Ok(Dialogue { pages, choices })
+    }).collect::<Result<_, String>>()?;
+
+    Ok((dialogues, names))
+}
+
+#[test]
+fn choices_lead_to_dialogues() {
+    let src = "
+dialogue hello
+page Hi!
+page How are you?
+choice Fine -> fine
+choice Bye
+
+dialogue fine
+page Good to hear.
+";
+    let (dialogues, names) = parse(src.lines()).unwrap();
+    assert_eq!(names["fine"], 1);
+    assert_eq!(dialogues[0].pages, ["Hi!", "How are you?"]);
+    assert_eq!(dialogues[0].choices, [
+        Choice { text: "Fine".to_owned(), next: Some(1) },
+        Choice { text: "Bye".to_owned(), next: None },
+    ]);
+    assert!(dialogues[1].choices.is_empty());
+}
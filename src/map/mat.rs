@@ -1,18 +1,26 @@
+/// A cell of the map grid
+///
+/// Kept small since there is one of these per cell
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Mat {
-    id: u8,
+    id: u16,
+    /// State of this cell of the material, like how open a door is or how damaged a wall is
+    pub state: u8,
 }
 
 impl Mat {
+    pub(super) const MAX_MATERIALS: usize = u16::MAX as usize;
+
     pub(super) const fn air() -> Self {
-        Mat { id: 0 }
+        Mat { id: 0, state: 0 }
     }
-    pub(super) const fn is_air(self) -> bool {
+    pub const fn is_air(self) -> bool {
         self.id == 0
     }
     pub(super) fn from_len(index: usize) -> Self {
         debug_assert_ne!(index, 0, "air should be constructed with ::air()");
-        Mat { id: index as u8 }
+        assert!(index <= Self::MAX_MATERIALS, "a map can have at most {} materials", Self::MAX_MATERIALS);
+        Mat { id: index as u16, state: 0 }
     }
     pub(super) fn index(&self) -> usize {
         self.id as usize - 1
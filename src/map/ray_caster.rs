@@ -1,23 +1,41 @@
 use crate::vec::{Point2, Vector2};
 
-pub fn ray_cast<M: PartialEq + Clone, FG, FN, FT, FR, FP>(from: Point2, dist: Vector2, finite: bool, node_limit: usize, get_mat: FG, is_node: FN,
-    is_terminator: FT, is_reflector: FR, is_pass_througher: FP, skip_first_check: bool) -> CastPoints<M>
-where FG: Fn(i32, i32) -> Option<M>, FN: Fn(&M) -> bool, FT: Fn(&M) -> bool, FR: Fn(&M) -> bool, FP: Fn(&M) -> bool {
+/// What a ray does when it enters a cell
+#[derive(Debug, Clone, PartialEq)]
+pub enum CellClass<M> {
+    /// Outside of the grid, the ray stops
+    Void,
+    /// The ray goes through without leaving a point
+    Empty(M),
+    /// The ray stops here
+    Terminator(M),
+    /// The ray bounces off
+    Reflector(M),
+    /// The ray leaves a point when entering the material and keeps going
+    PassThrough(M),
+    /// The ray leaves a point when entering and leaving the material and is bent by its refractive index
+    Refractor(M, f32),
+    /// The ray stops if it crosses a wall as thin as a line between two points, relative to the top left corner of the cell
+    Line(M, Point2, Point2),
+}
+
+/// A grid that rays can be cast through
+pub trait Medium {
+    type Mat: PartialEq + Clone;
+
+    /// Has to be `Void` for cells outside of the medium, including negative ones
+    fn classify(&self, x: i32, y: i32) -> CellClass<Self::Mat>;
+}
+
+/// `max_dist` is how far the ray may travel before it stops with a `Limit` point
+///
+/// Each point has the distance travelled along the ray (including before any reflections or refractions) to get to it.
+pub fn ray_cast<M: Medium>(from: Point2, dist: Vector2, finite: bool, node_limit: usize, max_dist: f32, medium: &M, skip_first_check: bool) -> CastPoints<M::Mat> {
     let dest = from + dist;
 
+    let mut seg = Segment::new(from, dist, 0.);
     let mut cur = from;
-    let (mut gx, mut gy) = (cur.x.floor() as i32, cur.y.floor() as i32);
-    let x_dir = Direction::new(dist.x);
-    let y_dir = Direction::new(dist.y);
-    
-    // If you're on a grid boundary, make sure you are only stuck on the wall if you're going towards it
-    if cur.x.fract() == 0. && x_dir == Direction::Neg {
-        gx -= 1;
-    }
-    if cur.y.fract() == 0. && y_dir == Direction::Neg {
-        gy -= 1;
-    }
-
+    let (mut gx, mut gy) = seg.start_cell();
 
     let mut points = Vec::with_capacity(2);
 
@@ -25,84 +43,139 @@ where FG: Fn(i32, i32) -> Option<M>, FN: Fn(&M) -> bool, FT: Fn(&M) -> bool, FR:
 
     let mut do_mat_check = !skip_first_check;
     let mut last_hit_material = None;
+    // The refractive material the ray is currently going through and its index
+    let mut inside: Option<(M::Mat, f32)> = None;
+
+    // How far along the segment `cur` is, in lengths of `seg.dist`
+    let mut t = 0.;
 
     loop {
         if points.len() >= node_limit {
             break;
         }
 
-        if finite && (cur - dest).dot(dist) / dist.norm() >= 0. {
-            points.push(CastPoint::dest(dest));
+        if finite && t >= 1. {
+            points.push(CastPoint::dest(seg.from + seg.dist, seg.base + seg.len));
             break;
         }
 
+        let travelled = seg.base + t * seg.len;
+        if travelled >= max_dist {
+            points.push(CastPoint::limit(seg.from + seg.dist.set_len(max_dist - seg.base), max_dist, side));
+            break;
+        }
+
+        let class = medium.classify(gx, gy);
+        // A thin wall stops the ray wherever it crosses it, even in the cell the ray starts in
+        if let CellClass::Line(mat, a, b) = &class {
+            if let Some(s) = seg.line_crossing(cur, (gx, gy), *a, *b).filter(|&s| !finite || t + s <= 1.) {
+                let dist = seg.base + (t + s) * seg.len;
+                if dist < max_dist {
+                    points.push(CastPoint::terminated(cur + s * seg.dist, dist, mat.clone(), seg.side_of_line(*b - *a)));
+                    break;
+                }
+            }
+        }
+
         if do_mat_check {
-            if cur.x < 0. || cur.y < 0. {
-                points.push(CastPoint::void(cur, side));
-                break; 
+
+            // Going into a material with a different refractive index bends the ray
+            let index = inside.as_ref().map_or(1., |(_, n)| *n);
+            let new_index = match &class {
+                CellClass::Refractor(_, n) => Some(*n),
+                CellClass::Empty(_) | CellClass::Line(..) | CellClass::PassThrough(_) => Some(1.),
+                _ => None,
+            };
+            if let Some(new_index) = new_index.filter(|&n| n != index) {
+                let mat = match (&class, &inside) {
+                    (CellClass::Refractor(mat, _), _) | (_, Some((mat, _))) => mat.clone(),
+                    _ => unreachable!("the index can only change going in or out of a refractive material"),
+                };
+                points.push(CastPoint::refract(cur, travelled, mat, side));
+
+                let remaining = if finite { (seg.from + seg.dist - cur).norm() } else { seg.len };
+                let dist = match refract(seg.dist, side, index, new_index) {
+                    Some(dir) => {
+                        inside = match class {
+                            CellClass::Refractor(mat, n) => Some((mat, n)),
+                            _ => None,
+                        };
+                        remaining * dir
+                    }
+                    // Total internal reflection, the ray stays inside
+                    None => reflect(seg.dist.set_len(remaining), side),
+                };
+
+                seg = Segment::new(cur, dist, travelled);
+                (gx, gy) = seg.start_cell();
+                t = 0.;
+                last_hit_material = None;
+                continue;
             }
 
-            let mat = get_mat(gx, gy);
-
-            if let Some(mat) = mat {
-                let mat_copy = mat.clone();
-                if is_node(&mat) {
-                    if is_terminator(&mat) {
-                        points.push(CastPoint::terminated(cur, mat, side));
-                        break;
-                    } else if is_reflector(&mat) {
-                        points.push(CastPoint::reflect(cur, mat, side));
-
-                        let mut dist = if finite { dest - cur } else { dist };
-                        match side {
-                            Side::Left | Side::Right => dist.x = -dist.x,
-                            Side::Up | Side::Down => dist.y = -dist.y,
-                        }
-
-                        let cps = ray_cast(cur, dist, finite, node_limit-points.len(), get_mat, is_node, is_terminator, is_reflector, is_pass_througher, false);
-                        points.extend(cps);
-
-                        break;
-                    } else if is_pass_througher(&mat) {
-                        let mut node_point = true;
-                        if let Some(lm) = last_hit_material {
-                            if lm == mat {
-                                node_point = false;
-                            }
-                        }
-                        if node_point {
-                            points.push(CastPoint::pass(cur, mat, side));
-                        }
+            match class {
+                CellClass::Void => {
+                    points.push(CastPoint::void(cur, travelled, side));
+                    break;
+                }
+                CellClass::Empty(mat) | CellClass::Line(mat, ..) => {
+                    inside = None;
+                    last_hit_material = Some(mat);
+                }
+                CellClass::Terminator(mat) => {
+                    points.push(CastPoint::terminated(cur, travelled, mat, side));
+                    break;
+                }
+                CellClass::Reflector(mat) => {
+                    points.push(CastPoint::reflect(cur, travelled, mat, side));
+
+                    let dist = if finite { seg.from + seg.dist - cur } else { seg.dist };
+
+                    // Start over from the mirror, checking the cell the ray came from again
+                    seg = Segment::new(cur, reflect(dist, side), travelled);
+                    (gx, gy) = seg.start_cell();
+                    t = 0.;
+                    last_hit_material = None;
+                    continue;
+                }
+                CellClass::PassThrough(mat) => {
+                    inside = None;
+                    // Going through several cells of the same material only leaves a point at the first
+                    if last_hit_material.as_ref() != Some(&mat) {
+                        points.push(CastPoint::pass(cur, travelled, mat.clone(), side));
                     }
+                    last_hit_material = Some(mat);
+                }
+                CellClass::Refractor(mat, n) => {
+                    inside = Some((mat.clone(), n));
+                    last_hit_material = Some(mat);
                 }
-                last_hit_material = Some(mat_copy);
-            } else {
-                points.push(CastPoint::void(cur, side));
-                break;
             }
         }
         do_mat_check = true;
 
-        let nearest_corner = Point2::new(x_dir.on(gx as f32), y_dir.on(gy as f32));
+        let nearest_corner = Point2::new(seg.x_dir.on(gx as f32), seg.y_dir.on(gy as f32));
         let distance = nearest_corner - cur;
 
         // Time until we hit the next corner in the x and y direction respectively
-        let time = (distance.x/dist.x, distance.y/dist.y);
+        let time = (distance.x/seg.dist.x, distance.y/seg.dist.y);
 
         if time.0 < time.1 {
-            side = Side::along_x(dist.x.is_sign_positive());
+            side = Side::along_x(seg.dist.x.is_sign_positive());
             // Going along x
             cur.x = nearest_corner.x;
-            cur.y += time.0 * dist.y;
+            cur.y += time.0 * seg.dist.y;
+            t += time.0;
 
-            gx = x_dir.on_i32(gx);
+            gx = seg.x_dir.on_i32(gx);
         } else {
-            side = Side::along_y(dist.y.is_sign_positive());
+            side = Side::along_y(seg.dist.y.is_sign_positive());
             // Going along y
             cur.y = nearest_corner.y;
-            cur.x += time.1 * dist.x;
+            cur.x += time.1 * seg.dist.x;
+            t += time.1;
 
-            gy = y_dir.on_i32(gy);
+            gy = seg.y_dir.on_i32(gy);
         }
     }
 
@@ -110,8 +183,8 @@ where FG: Fn(i32, i32) -> Option<M>, FN: Fn(&M) -> bool, FT: Fn(&M) -> bool, FR:
 
     if finite {
         target = Some(dest);
-        if let Some(CastPointType::Void(_)) = points.last().map(|p| &p.cast_type) {
-            points.push(CastPoint::dest(dest));
+        if let Some(CastPointType::Void(_) | CastPointType::Limit(_)) = points.last().map(|p| &p.cast_type) {
+            points.push(CastPoint::dest(seg.from + seg.dist, seg.base + seg.len));
         }
     } else {
         target = None;
@@ -124,6 +197,91 @@ where FG: Fn(i32, i32) -> Option<M>, FN: Fn(&M) -> bool, FT: Fn(&M) -> bool, FR:
     }
 }
 
+/// A straight stretch of a ray, a new one starts every time the ray changes direction
+struct Segment {
+    from: Point2,
+    dist: Vector2,
+    len: f32,
+    x_dir: Direction,
+    y_dir: Direction,
+    /// Distance travelled along the ray before the segment
+    base: f32,
+}
+
+impl Segment {
+    fn new(from: Point2, dist: Vector2, base: f32) -> Self {
+        Segment {
+            from,
+            dist,
+            len: dist.norm(),
+            x_dir: Direction::new(dist.x),
+            y_dir: Direction::new(dist.y),
+            base,
+        }
+    }
+    /// The grid cell the segment starts in
+    fn start_cell(&self) -> (i32, i32) {
+        let (mut gx, mut gy) = (self.from.x.floor() as i32, self.from.y.floor() as i32);
+
+        // If you're on a grid boundary, make sure you are only stuck on the wall if you're going towards it
+        if self.from.x.fract() == 0. && self.x_dir == Direction::Neg {
+            gx -= 1;
+        }
+        if self.from.y.fract() == 0. && self.y_dir == Direction::Neg {
+            gy -= 1;
+        }
+        (gx, gy)
+    }
+    /// How far from `cur`, in lengths of the segment, it crosses the line from `a` to `b` in the cell `(x, y)`
+    fn line_crossing(&self, cur: Point2, (x, y): (i32, i32), a: Point2, b: Point2) -> Option<f32> {
+        let (a, along) = (a + Vector2::new(x as f32, y as f32), b - a);
+
+        // Where the two cross, as fractions of the segment and the line
+        let denominator = along.dot(self.dist.hat());
+        if denominator == 0. {
+            return None;
+        }
+        let s = along.dot((a - cur).hat()) / denominator;
+        let r = self.dist.dot((a - cur).hat()) / denominator;
+        (s >= 0. && (0. ..=1.).contains(&r)).then_some(s)
+    }
+    /// The side of a line going `along` that the segment hits
+    fn side_of_line(&self, along: Vector2) -> Side {
+        if along.x.abs() > along.y.abs() {
+            Side::along_y(self.dist.y.is_sign_positive())
+        } else {
+            Side::along_x(self.dist.x.is_sign_positive())
+        }
+    }
+}
+
+/// Mirrors `dist` off a wall on `side`
+fn reflect(mut dist: Vector2, side: Side) -> Vector2 {
+    match side {
+        Side::Left | Side::Right => dist.x = -dist.x,
+        Side::Up | Side::Down => dist.y = -dist.y,
+    }
+    dist
+}
+
+/// Direction of a ray going along `dist` after crossing `side` from a material with refractive index `from` into one with `to`
+///
+/// Gives `None` when the ray is reflected back instead (total internal reflection)
+fn refract(dist: Vector2, side: Side, from: f32, to: f32) -> Option<Vector2> {
+    let dir = dist.set_len(1.);
+    // Normal of the surface facing back towards where the ray came from
+    let normal = side.into_unit_vector();
+    let ratio = from / to;
+
+    let cos_in = -dir.dot(normal);
+    let k = 1. - ratio * ratio * (1. - cos_in * cos_in);
+    if k < 0. {
+        None
+    } else {
+        Some(ratio * dir + (ratio * cos_in - k.sqrt()) * normal)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CastPoints<M> {
     inner: Vec<CastPoint<M>>,
@@ -140,11 +298,11 @@ impl<M> CastPoints<M> {
         for cp in &self.inner {
             point = cp.point;
             match cp.cast_type {
-                CastPointType::Reflection(_, s) | CastPointType::Pass(_, s) | CastPointType::Termination(_, s) => {
+                CastPointType::Reflection(_, s) | CastPointType::Refraction(_, s) | CastPointType::Pass(_, s) | CastPointType::Termination(_, s) => {
                     side = Some(s);
                     break;
                 }
-                CastPointType::Void(s) => side = Some(s),
+                CastPointType::Void(s) | CastPointType::Limit(s) => side = Some(s),
                 CastPointType::Destination => side = None,
             }
         }
@@ -165,24 +323,32 @@ impl<M> IntoIterator for CastPoints<M> {
 #[derive(Debug, Clone)]
 pub struct CastPoint<M> {
     pub point: Point2,
+    /// Distance travelled along the ray from its origin to the point
+    pub dist: f32,
     pub cast_type: CastPointType<M>,
 }
 
 impl<M> CastPoint<M> {
-    const fn terminated(point: Point2, mat: M, side: Side) -> Self {
-        CastPoint { point, cast_type: CastPointType::Termination(mat, side) }
+    const fn terminated(point: Point2, dist: f32, mat: M, side: Side) -> Self {
+        CastPoint { point, dist, cast_type: CastPointType::Termination(mat, side) }
     }
-    const fn dest(point: Point2) -> Self {
-        CastPoint { point, cast_type: CastPointType::Destination }
+    const fn dest(point: Point2, dist: f32) -> Self {
+        CastPoint { point, dist, cast_type: CastPointType::Destination }
     }
-    const fn void(point: Point2, side: Side) -> Self {
-        CastPoint { point, cast_type: CastPointType::Void(side) }
+    const fn void(point: Point2, dist: f32, side: Side) -> Self {
+        CastPoint { point, dist, cast_type: CastPointType::Void(side) }
     }
-    const fn reflect(point: Point2, mat: M, side: Side) -> Self {
-        CastPoint { point, cast_type: CastPointType::Reflection(mat, side) }
+    const fn limit(point: Point2, dist: f32, side: Side) -> Self {
+        CastPoint { point, dist, cast_type: CastPointType::Limit(side) }
     }
-    const fn pass(point: Point2, mat: M, side: Side) -> Self {
-        CastPoint { point, cast_type: CastPointType::Pass(mat, side) }
+    const fn reflect(point: Point2, dist: f32, mat: M, side: Side) -> Self {
+        CastPoint { point, dist, cast_type: CastPointType::Reflection(mat, side) }
+    }
+    const fn refract(point: Point2, dist: f32, mat: M, side: Side) -> Self {
+        CastPoint { point, dist, cast_type: CastPointType::Refraction(mat, side) }
+    }
+    const fn pass(point: Point2, dist: f32, mat: M, side: Side) -> Self {
+        CastPoint { point, dist, cast_type: CastPointType::Pass(mat, side) }
     }
 }
 
@@ -190,10 +356,14 @@ impl<M> CastPoint<M> {
 pub enum CastPointType<M> {
     /// Indicates that the cast was reflected by a reflective material
     Reflection(M, Side),
+    /// The cast was bent going into or out of a refractive material
+    Refraction(M, Side),
     /// Encountered a solid, see-through material here, also end point if edge is reached
     Pass(M, Side),
     /// Encountered the void, end point if non-finite
     Void(Side),
+    /// Travelled the maximum distance, end point
+    Limit(Side),
     /// Ray cast hit a solid, opaue material, end point
     Termination(M, Side),
     /// Reached its destination, only finite casts, end point
@@ -232,6 +402,16 @@ impl Side {
         }
     }
 
+    /// Side facing the way of a compass direction, like in the map format
+    pub fn from_direction(name: &str) -> Option<Self> {
+        match name {
+            "north" => Some(Self::Up),
+            "south" => Some(Self::Down),
+            "west" => Some(Self::Left),
+            "east" => Some(Self::Right),
+            _ => None,
+        }
+    }
     pub const fn flip(self) -> Self {
         match self {
             Side::Right => Side::Left,
@@ -241,6 +421,18 @@ impl Side {
         }
     } 
 
+    /// The grid cell that a ray hit at `point` on this side of it
+    pub fn cell_of_hit(self, point: Point2) -> (i32, i32) {
+        let (x, y) = (point.x.floor() as i32, point.y.floor() as i32);
+        match self {
+            // The ray was going in the negative direction and `point` is on the far edge of the cell,
+            // unless it hit a thin wall inside of it
+            Side::Right if point.x.fract() == 0. => (x - 1, y),
+            Side::Down if point.y.fract() == 0. => (x, y - 1),
+            _ => (x, y),
+        }
+    }
+
     pub const fn into_unit_vector(self) -> Vector2 {
         match self {
             Side::Right => Vector2::new(1., 0.),
@@ -281,3 +473,110 @@ impl Direction {
         }
     }
 }
+
+/// Grid of characters where `#` stops rays, `|` reflects them, `=` is passed through, `o` refracts them like glass and `.` is empty
+#[cfg(test)]
+struct CharGrid(&'static [&'static str]);
+
+#[cfg(test)]
+impl Medium for CharGrid {
+    type Mat = char;
+
+    fn classify(&self, x: i32, y: i32) -> CellClass<char> {
+        let c = usize::try_from(y).ok()
+            .and_then(|y| self.0.get(y))
+            .zip(usize::try_from(x).ok())
+            .and_then(|(row, x)| row.chars().nth(x));
+        match c {
+            None => CellClass::Void,
+            Some(c @ '#') => CellClass::Terminator(c),
+            Some(c @ '|') => CellClass::Reflector(c),
+            Some(c @ '=') => CellClass::PassThrough(c),
+            Some(c @ 'o') => CellClass::Refractor(c, 1.5),
+            Some(c) => CellClass::Empty(c),
+        }
+    }
+}
+
+#[test]
+fn ray_passes_reflects_and_stops() {
+    let grid = CharGrid(&[
+        "######",
+        "#.==.|",
+        "######",
+    ]);
+    let cast = ray_cast(Point2::new(1.5, 1.5), Vector2::new(1., 0.), false, 8, f32::INFINITY, &grid, false);
+    let types: Vec<_> = cast.into_iter().map(|cp| cp.cast_type).collect();
+
+    assert_eq!(types, [
+        CastPointType::Pass('=', Side::Left),
+        CastPointType::Reflection('|', Side::Left),
+        CastPointType::Pass('=', Side::Right),
+        CastPointType::Termination('#', Side::Right),
+    ]);
+}
+
+#[test]
+fn distances_carry_over_reflections() {
+    let grid = CharGrid(&[
+        "######",
+        "#.==.|",
+        "######",
+    ]);
+    let cast = ray_cast(Point2::new(1.5, 1.5), Vector2::new(2., 0.), false, 8, f32::INFINITY, &grid, false);
+    let dists: Vec<_> = cast.into_iter().map(|cp| cp.dist).collect();
+
+    // Into the glass, off the mirror, back into the glass from the other side, into the wall
+    assert_eq!(dists, [0.5, 3.5, 4.5, 7.5]);
+}
+
+#[test]
+fn glass_bends_rays_both_ways() {
+    let grid = CharGrid(&[
+        "#######",
+        "#..o..#",
+        "#######",
+    ]);
+    // Going straight through isn't bent at all
+    let cast = ray_cast(Point2::new(1.5, 1.5), Vector2::new(1., 0.), false, 8, f32::INFINITY, &grid, false);
+    let points: Vec<_> = cast.into_iter().collect();
+    assert_eq!(points.len(), 3);
+    assert_eq!((points[2].point.x, points[2].point.y), (6., 1.5));
+
+    let grid = CharGrid(&[
+        "#######",
+        "#.....#",
+        "#..o..#",
+        "#.....#",
+        "#######",
+    ]);
+    let cast = ray_cast(Point2::new(1.5, 1.5), Vector2::new(1., 0.5), false, 8, f32::INFINITY, &grid, false);
+    let points: Vec<_> = cast.into_iter().collect();
+    let types: Vec<_> = points.iter().map(|cp| cp.cast_type.clone()).collect();
+    assert_eq!(types, [
+        CastPointType::Refraction('o', Side::Left),
+        CastPointType::Refraction('o', Side::Left),
+        CastPointType::Termination('#', Side::Left),
+    ]);
+
+    // Bent towards the normal inside the glass and back to the original direction after
+    let inside = points[1].point - points[0].point;
+    let after = points[2].point - points[1].point;
+    assert!(inside.y / inside.x < 0.5);
+    assert!((after.y / after.x - 0.5).abs() < 1e-4);
+}
+
+#[test]
+fn rays_end_in_the_void_on_every_side() {
+    let grid = CharGrid(&[
+        "...",
+        "...",
+    ]);
+    for (dir, end) in [((-1., 0.), (0., 1.5)), ((0., -1.), (1.5, 0.)), ((1., 0.), (3., 1.5)), ((0., 1.), (1.5, 2.))] {
+        let cast = ray_cast(Point2::new(1.5, 1.5), Vector2::new(dir.0, dir.1), false, 8, f32::INFINITY, &grid, false);
+        let points: Vec<_> = cast.into_iter().collect();
+        assert_eq!(points.len(), 1);
+        assert!(matches!(points[0].cast_type, CastPointType::Void(_)));
+        assert_eq!((points[0].point.x, points[0].point.y), end);
+    }
+}
@@ -0,0 +1,124 @@
+//! Triggers, which do something once all the signals they wait for are on
+//!
+//! ```text
+//! trigger gate_lever+gate_plate open d
+//! trigger dusk_switch time dusk
+//! l switch=gate_lever,5 tex/lever.png tex/lever_dark.png
+//! plate p gate_plate metal
+//! ```
+//!
+//! Signals are named by whatever turns them on. Switches are materials the player uses to turn their signal on and off,
+//! which turns back off by itself after the number of seconds after the comma if there is one.
+//! Plates are floors that are on while the player or something that moves around stands on them.
+//! The signals of a trigger are joined by `+` and all have to be on for it to go off.
+//!
+//! Logic works out signals from other signals:
+//!
+//! ```text
+//! logic counter three_kills 3 kill_plate
+//! logic and both a+b
+//! logic or either a+b
+//! logic delay later 2.5 both
+//! logic random left+middle+right either
+//! ```
+//!
+//! A counter turns on once its signal has turned on that many times, `and` is on while all its signals are and `or` while any is.
+//! A delay does what its signal did that many seconds later. Each time its signal turns on,
+//! `random` picks one of its signals to be on until it turns off again.
+
+/// A material the player uses to turn a signal on or off
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Switch {
+    pub signal: usize,
+    /// Seconds until it turns back off by itself, it stays on until it's used again if there's none
+    pub revert: Option<f32>,
+}
+
+/// What a trigger does
+#[derive(Debug, Clone, PartialEq)]
+pub enum TriggerAction {
+    /// Opens the doors of a material while the trigger is on and closes them again when it turns off
+    Open(usize),
+    /// Closes the doors of a material while the trigger is on and opens them again when it turns off
+    Close(usize),
+    Cutscene(String),
+    /// Sets the time of day to an hour
+    Time(f32),
+}
+
+/// How a logic node works out its signals from others
+#[derive(Debug, Clone, PartialEq)]
+pub enum Logic {
+    /// On once `input` has turned on `count` times
+    Counter { input: usize, count: u32 },
+    And(Vec<usize>),
+    Or(Vec<usize>),
+    /// Follows `input` some seconds later
+    Delay { input: usize, seconds: f32 },
+    /// One of the outputs, picked each time `input` turns on, is on while `input` is
+    Random { input: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicNode {
+    /// Signals the node sets, only random nodes have more than one
+    pub outputs: Vec<usize>,
+    pub logic: Logic,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trigger {
+    /// Signals that all have to be on
+    pub signals: Vec<usize>,
+    pub action: TriggerAction,
+}
+
+/// Index of the signal called `name`, which is added if it's new
+pub(super) fn signal_index(signals: &mut Vec<String>, name: &str) -> Result<usize, String> {
+    ensure!(!name.is_empty(), "signals need a name");
+    Ok(signals.iter().position(|s| s == name).unwrap_or_else(|| {
+        signals.push(name.to_owned());
+        signals.len() - 1
+    }))
+}
+
+/// Index of the signal called `name`, once everything that turns on signals is known
+pub(super) fn known_signal(signals: &[String], name: &str) -> Result<usize, String> {
+    signals.iter().position(|s| s == name).ok_or_else(|| format!("nothing turns on signal {name}"))
+}
+
+/// Reads a `logic` line, given the kind of node, its signals and what follows them, once everything that turns on signals is known
+pub(super) fn parse_logic(kind: &str, outputs: Vec<usize>, args: &[String], signals: &[String]) -> Result<LogicNode, String> {
+    let arg = |i: usize| args.get(i).map(String::as_str).ok_or_else(|| format!("{kind} logic needs more to go on"));
+    let all = |i| arg(i)?.split('+').map(|name| known_signal(signals, name)).collect::<Result<_, _>>();
+    ensure!(kind == "random" || outputs.len() == 1, "only random logic has more than one signal");
+    let logic = match kind {
+        "counter" => {
+            let count = arg(0)?.parse().map_err(|_| "counter has to count to a whole number")?;
+            Logic::Counter { input: known_signal(signals, arg(1)?)?, count }
+        }
+        "and" => Logic::And(all(0)?),
+        "or" => Logic::Or(all(0)?),
+        "delay" => {
+            let seconds = arg(0)?.parse::<f32>().map_err(|_| "delay has to be a number of seconds")?;
+            ensure!(seconds >= 0., "delay can't be negative");
+            Logic::Delay { input: known_signal(signals, arg(1)?)?, seconds }
+        }
+        "random" => Logic::Random { input: known_signal(signals, arg(0)?)? },
+        _ => return Err(format!("unknown logic {kind}")),
+    };
+    Ok(LogicNode { outputs, logic })
+}
+
+/// Reads the value of a `switch=` property
+pub(super) fn parse_switch(value: &str, signals: &mut Vec<String>) -> Result<Switch, String> {
+    let (name, revert) = match value.split_once(',') {
+        Some((name, seconds)) => {
+            let seconds = seconds.parse::<f32>().map_err(|_| "switch has to turn back off after a number of seconds")?;
+            ensure!(seconds > 0., "switch {name} has to turn back off after a positive number of seconds");
+            (name, Some(seconds))
+        }
+        None => (value, None),
+    };
+    Ok(Switch { signal: signal_index(signals, name)?, revert })
+}
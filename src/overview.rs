@@ -0,0 +1,86 @@
+//! Top-down pictures of whole maps, for documenting map packs
+
+use image::{Rgba, RgbaImage};
+
+use crate::{map::{Map, Side}, tex::{Colour, Texture}, world::thing::Thing};
+
+/// Size in pixels of each cell of the map
+const CELL: u32 = 8;
+const FLOOR_COLOUR: Colour = Colour::new(0x30, 0x30, 0x30);
+const GRID_COLOUR: Colour = Colour::new(0x28, 0x28, 0x28);
+const PLAYER_COLOUR: Colour = Colour::new(0x40, 0xff, 0x40);
+
+/// Draws the map from above with walls in the average colour of their material,
+/// things as small versions of their textures and the player start as an arrow
+///
+/// Layers are drawn from the bottom layer down with a cell of space between them.
+/// `things` are the things of each layer and the player starts on the current layer of the map.
+pub fn render(map: &Map, player: (i32, i32, Side), things: &[Vec<Thing>], thing_texes: &[Texture]) -> RgbaImage {
+    let (width, height) = (map.width() as u32, map.height() as u32);
+    let layers = map.layers() as u32;
+    let mut img = RgbaImage::new(width * CELL, (layers * (height + 1) - 1) * CELL);
+    // First row of cells of each layer
+    let layer_top = |layer: usize| layer as u32 * (height + 1);
+
+    let fill_cell = |img: &mut RgbaImage, cx: u32, cy: u32, colour: Colour| {
+        for y in 0..CELL {
+            for x in 0..CELL {
+                // A darker line around the cells makes them easier to count
+                let c = if x == 0 || y == 0 { darken(colour, GRID_COLOUR) } else { colour };
+                img.put_pixel(cx * CELL + x, cy * CELL + y, Rgba(c.array()));
+            }
+        }
+    };
+
+    for layer in 0..map.layers() {
+        for cy in 0..height {
+            for cx in 0..width {
+                let colour = match map.get_on_layer(layer, cx as i32, cy as i32) {
+                    Some(mat) if !mat.is_air() => map.get_tex(mat, Side::Up, 0., (cx as i32, cy as i32)).average_colour(),
+                    _ => FLOOR_COLOUR,
+                };
+                fill_cell(&mut img, cx, layer_top(layer) + cy, colour);
+            }
+        }
+    }
+
+    for (layer, thing) in things.iter().enumerate().flat_map(|(l, things)| things.iter().map(move |t| (l, t))) {
+        let tex = &thing_texes[thing.tex()];
+        let size = ((2. * thing.width() * CELL as f32).round() as u32).clamp(2, CELL);
+        let x0 = ((thing.pos.x * CELL as f32) as u32).saturating_sub(size / 2);
+        let y0 = (((layer_top(layer) as f32 + thing.pos.y) * CELL as f32) as u32).saturating_sub(size / 2);
+        for y in 0..size {
+            for x in 0..size {
+                let texel = tex.get_pixel_f((x as f32 + 0.5) / size as f32, (y as f32 + 0.5) / size as f32);
+                let (px, py) = (x0 + x, y0 + y);
+                if texel.array()[3] > 0x80 && px < img.width() && py < img.height() {
+                    img.put_pixel(px, py, Rgba(texel.rgb().array()));
+                }
+            }
+        }
+    }
+
+    // Arrow pointing the way the player starts out facing
+    let (px, py, side) = player;
+    let dir = side.into_unit_vector();
+    for y in 0..CELL {
+        for x in 0..CELL {
+            // Position in the cell from -1 to 1, turned so the arrow points along +x
+            let (u, v) = (2. * (x as f32 + 0.5) / CELL as f32 - 1., 2. * (y as f32 + 0.5) / CELL as f32 - 1.);
+            let (along, across) = (u * dir.x + v * dir.y, v * dir.x - u * dir.y);
+            if along > -0.7 && across.abs() < 0.5 * (0.8 - along) {
+                img.put_pixel(px as u32 * CELL + x, (layer_top(map.layer()) + py as u32) * CELL + y, Rgba(PLAYER_COLOUR.array()));
+            }
+        }
+    }
+
+    img
+}
+
+/// Average of two colours, for lines that show what's under them a bit
+fn darken(a: Colour, b: Colour) -> Colour {
+    let [ar, ag, ab, _] = a.array();
+    let [br, bg, bb, _] = b.array();
+    let avg = |a: u8, b: u8| ((a as u16 + b as u16) / 2) as u8;
+    Colour::new(avg(ar, br), avg(ag, bg), avg(ab, bb))
+}
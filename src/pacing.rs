@@ -0,0 +1,128 @@
+//! Keeping frames from coming faster than the frame rate limit, and from taking longer than the target to render
+
+use std::time::Duration;
+
+use log::info;
+
+use crate::time::Instant;
+
+/// How long before the next frame sleeping stops and spinning takes over, since sleeps tend to overshoot
+#[cfg(not(target_arch = "wasm32"))]
+const SPIN_TIME: Duration = Duration::from_millis(2);
+
+#[derive(Debug)]
+pub struct FrameLimiter {
+    /// `None` if the frame rate isn't limited
+    frame_time: Option<Duration>,
+    next_frame: Instant,
+}
+
+impl FrameLimiter {
+    /// `max_fps` of 0 means no limit
+    pub fn new(max_fps: u32) -> Self {
+        FrameLimiter {
+            frame_time: (max_fps > 0).then(|| Duration::from_secs_f64(1. / max_fps as f64)),
+            next_frame: Instant::now(),
+        }
+    }
+    /// Waits until it's time for the next frame
+    pub fn wait(&mut self) {
+        let Some(frame_time) = self.frame_time else { return };
+
+        // The browser decides when frames are drawn and blocking it would freeze the page
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let now = Instant::now();
+            if self.next_frame > now + SPIN_TIME {
+                std::thread::sleep(self.next_frame - now - SPIN_TIME);
+            }
+            while Instant::now() < self.next_frame {
+                std::hint::spin_loop();
+            }
+        }
+
+        // Frames that came late don't make the next ones come early to catch up
+        let now = Instant::now();
+        self.next_frame = if now - self.next_frame > frame_time { now + frame_time } else { self.next_frame + frame_time };
+    }
+}
+
+/// Frames in a row that have to be too slow or fast enough before the resolution changes
+const FRAMES_TO_CHANGE: u32 = 10;
+/// Most columns a ray is drawn across
+const MAX_STRIP: u32 = 4;
+/// Part of the target frame time a frame at the next resolution up is guessed to be able to take without going over
+const HEADROOM: f32 = 0.8;
+
+/// Casts fewer rays, drawing each across a wider strip of columns, while rendering takes longer than the target frame time
+#[derive(Debug)]
+pub struct DynamicResolution {
+    target: Duration,
+    /// Columns each ray is drawn across
+    strip: u32,
+    /// Frames in a row that were too slow
+    slow: u32,
+    /// Frames in a row that left enough time to go up a resolution
+    fast: u32,
+}
+
+impl DynamicResolution {
+    pub fn new(target_fps: u32) -> Self {
+        DynamicResolution {
+            target: Duration::from_secs_f64(1. / target_fps.max(1) as f64),
+            strip: 1,
+            slow: 0,
+            fast: 0,
+        }
+    }
+    pub fn strip(&self) -> u32 {
+        self.strip
+    }
+    /// Tells how long rendering a frame took, changing the resolution for the next ones if that has been off for a while
+    pub fn frame_took(&mut self, time: Duration) {
+        // Casting for every column of a strip one narrower costs about that much more
+        let finer = self.strip > 1 && time.mul_f32(self.strip as f32 / (self.strip - 1) as f32) < self.target.mul_f32(HEADROOM);
+        if time > self.target {
+            self.slow += 1;
+            self.fast = 0;
+        } else if finer {
+            self.fast += 1;
+            self.slow = 0;
+        } else {
+            self.slow = 0;
+            self.fast = 0;
+        }
+
+        if self.slow >= FRAMES_TO_CHANGE && self.strip < MAX_STRIP {
+            self.strip += 1;
+            self.slow = 0;
+            info!("rendering too slow, drawing rays {} columns wide", self.strip);
+        } else if self.fast >= FRAMES_TO_CHANGE {
+            self.strip -= 1;
+            self.fast = 0;
+            info!("rendering fast enough again, drawing rays {} columns wide", self.strip);
+        }
+    }
+}
+
+#[test]
+fn resolution_follows_the_load() {
+    let mut resolution = DynamicResolution::new(50);
+    let slow = Duration::from_millis(30);
+    for _ in 0..FRAMES_TO_CHANGE - 1 {
+        resolution.frame_took(slow);
+    }
+    assert_eq!(resolution.strip(), 1);
+    resolution.frame_took(slow);
+    assert_eq!(resolution.strip(), 2);
+
+    // Twice this would still be over the target
+    for _ in 0..FRAMES_TO_CHANGE {
+        resolution.frame_took(Duration::from_millis(15));
+    }
+    assert_eq!(resolution.strip(), 2);
+    for _ in 0..FRAMES_TO_CHANGE {
+        resolution.frame_took(Duration::from_millis(5));
+    }
+    assert_eq!(resolution.strip(), 1);
+}
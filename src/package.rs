@@ -0,0 +1,115 @@
+//! Game packages: a whole campaign (maps, textures, sounds, scripts) in a directory or zip archive
+//!
+//! A package has a `package.toml` manifest at its root:
+//!
+//! ```toml
+//! name = "The Dungeon"
+//! author = "Someone"
+//! description = "Three levels under the castle"
+//! levels = ["maps/cellar.txt", "maps/crypt.txt", "maps/throne.txt"]
+//! ```
+//!
+//! Paths in the package are relative to its root and take precedence over all other assets while it's loaded.
+
+use std::{collections::HashMap, fs::{self, File}, io::{self, Read}, path::{Path, PathBuf}};
+
+use crate::settings::{parse_toml, Value};
+
+pub const MANIFEST: &str = "package.toml";
+
+#[derive(Debug)]
+enum Source {
+    Directory(PathBuf),
+    /// The files of a zip archive, unpacked into memory
+    Zip(HashMap<PathBuf, Vec<u8>>),
+}
+
+#[derive(Debug)]
+pub struct Package {
+    pub name: String,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    /// Paths of the maps in the order they are played
+    pub levels: Vec<String>,
+    source: Source,
+}
+
+impl Package {
+    /// Opens a package directory or zip archive
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let source = if path.is_dir() {
+            Source::Directory(path.to_owned())
+        } else {
+            Source::Zip(unzip(path).map_err(|e| format!("could not read {}: {e}", path.display()))?)
+        };
+
+        let manifest = source.read(Path::new(MANIFEST))
+            .ok_or_else(|| format!("{} has no {MANIFEST}", path.display()))?;
+        let manifest = String::from_utf8(manifest).map_err(|e| format!("{MANIFEST}: {e}"))?;
+
+        let mut package = Package {
+            name: path.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+            author: None,
+            description: None,
+            levels: Vec::new(),
+            source,
+        };
+        parse_toml(&manifest, |table, key, value| package.set(table, key, value))
+            .map_err(|e| format!("{MANIFEST}: {e}"))?;
+
+        if let Some(missing) = package.levels.iter().find(|l| !package.contains(Path::new(l))) {
+            return Err(format!("level {missing} is not in the package"));
+        }
+        Ok(package)
+    }
+    fn set(&mut self, table: &str, key: &str, value: Value) -> Result<(), String> {
+        use self::Value::*;
+
+        match (table, key, value) {
+            ("", "name", Str(s)) => self.name = s,
+            ("", "author", Str(s)) => self.author = Some(s),
+            ("", "description", Str(s)) => self.description = Some(s),
+            ("", "levels", Array(levels)) => self.levels = levels,
+            (_, key, value) => return Err(format!("invalid manifest entry {key} = {value:?}")),
+        }
+        Ok(())
+    }
+    /// Contents of a file in the package
+    pub fn read(&self, path: &Path) -> Option<Vec<u8>> {
+        self.source.read(path)
+    }
+    pub fn contains(&self, path: &Path) -> bool {
+        match &self.source {
+            Source::Directory(dir) => dir.join(path).is_file(),
+            Source::Zip(files) => files.contains_key(path),
+        }
+    }
+}
+
+impl Source {
+    fn read(&self, path: &Path) -> Option<Vec<u8>> {
+        match self {
+            Source::Directory(dir) => fs::read(dir.join(path)).ok(),
+            Source::Zip(files) => files.get(path).cloned(),
+        }
+    }
+}
+
+fn unzip(path: &Path) -> io::Result<HashMap<PathBuf, Vec<u8>>> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut files = HashMap::new();
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        // Entries with names going outside of the archive (like `../x`) are left out
+        let name = match file.enclosed_name() {
+            Some(name) if file.is_file() => name.to_owned(),
+            _ => continue,
+        };
+        let mut contents = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut contents)?;
+        files.insert(name, contents);
+    }
+    Ok(files)
+}
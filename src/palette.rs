@@ -0,0 +1,79 @@
+//! Colours of what's drawn without textures, with alternatives that are easier to tell apart
+
+use crate::tex::Colour;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub ceiling: Colour,
+    pub floor: Colour,
+    /// Drawn around things in high contrast mode
+    pub outline: Colour,
+}
+
+impl Palette {
+    pub const DEFAULT: Palette = Palette {
+        ceiling: Colour::new(0x00, 0x00, 0xff),
+        floor: Colour::new(0xff, 0x00, 0x00),
+        outline: Colour::new(0xff, 0xff, 0xff),
+    };
+    /// Red and green look alike, so the floor is kept away from both
+    pub const DEUTERANOPIA: Palette = Palette {
+        ceiling: Colour::new(0x00, 0x40, 0xc0),
+        floor: Colour::new(0xe0, 0xa0, 0x00),
+        outline: Colour::new(0xff, 0xff, 0xff),
+    };
+    /// Reds look dark, so the floor is made brighter
+    pub const PROTANOPIA: Palette = Palette {
+        ceiling: Colour::new(0x00, 0x40, 0xc0),
+        floor: Colour::new(0xf0, 0xd0, 0x40),
+        outline: Colour::new(0xff, 0xff, 0xff),
+    };
+    /// Blue and yellow look alike, so it's told apart with red and cyan instead
+    pub const TRITANOPIA: Palette = Palette {
+        ceiling: Colour::new(0x00, 0xa0, 0xa0),
+        floor: Colour::new(0xd0, 0x20, 0x40),
+        outline: Colour::new(0xff, 0xff, 0xff),
+    };
+    /// Black and white with bright yellow outlines
+    pub const HIGH_CONTRAST: Palette = Palette {
+        ceiling: Colour::new(0x00, 0x00, 0x00),
+        floor: Colour::new(0xff, 0xff, 0xff),
+        outline: Colour::new(0xff, 0xff, 0x00),
+    };
+
+    pub const ALL: [(&'static str, Palette); 5] = [
+        ("default", Palette::DEFAULT),
+        ("deuteranopia", Palette::DEUTERANOPIA),
+        ("protanopia", Palette::PROTANOPIA),
+        ("tritanopia", Palette::TRITANOPIA),
+        ("high_contrast", Palette::HIGH_CONTRAST),
+    ];
+
+    pub fn by_name(name: &str) -> Option<Palette> {
+        Self::ALL.into_iter().find(|&(n, _)| n == name).map(|(_, palette)| palette)
+    }
+}
+
+/// Parses a colour written like `#ff8000`
+pub fn parse_colour(s: &str) -> Option<Colour> {
+    let hex = s.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(Colour::new(channel(0)?, channel(2)?, channel(4)?))
+}
+
+pub fn format_colour(c: Colour) -> String {
+    let [r, g, b, _] = c.array();
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+#[test]
+fn colour_round_trip() {
+    let c = Colour::new(0xff, 0x80, 0x01);
+    assert_eq!(format_colour(c), "#ff8001");
+    assert_eq!(parse_colour("#ff8001"), Some(c));
+    assert_eq!(parse_colour("ff8001"), None);
+    assert_eq!(parse_colour("#ff80"), None);
+}
@@ -0,0 +1,176 @@
+//! Per-stage frame timings for finding performance regressions in the renderer
+//!
+//! Nothing is recorded unless the `profiling` feature is enabled,
+//! without it the timers compile down to nothing.
+
+#[cfg(feature = "profiling")]
+use std::{cell::RefCell, collections::VecDeque, fs::File, io::{self, Write, BufWriter}, time::Duration};
+#[cfg(feature = "profiling")]
+use crate::time::Instant;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Stage {
+    /// Updating the world, however many ticks that takes before the frame
+    Update,
+    Raycast,
+    Walls,
+    Sprites,
+    Hud,
+    Present,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 6] = [Stage::Update, Stage::Raycast, Stage::Walls, Stage::Sprites, Stage::Hud, Stage::Present];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Stage::Update => "update",
+            Stage::Raycast => "raycast",
+            Stage::Walls => "walls",
+            Stage::Sprites => "sprites",
+            Stage::Hud => "hud",
+            Stage::Present => "present",
+        }
+    }
+}
+
+/// How many frames are kept before the oldest are thrown away
+#[cfg(feature = "profiling")]
+const CAPACITY: usize = 4096;
+
+#[cfg(feature = "profiling")]
+#[derive(Debug, Clone)]
+struct FrameTimes {
+    start: Duration,
+    stages: [Duration; Stage::ALL.len()],
+}
+
+#[cfg(feature = "profiling")]
+struct Recorder {
+    epoch: Instant,
+    frame_start: Instant,
+    current: [Duration; Stage::ALL.len()],
+    frames: VecDeque<FrameTimes>,
+}
+
+#[cfg(feature = "profiling")]
+thread_local! {
+    static RECORDER: RefCell<Recorder> = RefCell::new(Recorder {
+        epoch: Instant::now(),
+        frame_start: Instant::now(),
+        current: Default::default(),
+        frames: VecDeque::with_capacity(CAPACITY),
+    });
+}
+
+/// Adds the time from its creation until it is dropped to a stage of the current frame
+///
+/// Stages can be timed multiple times per frame (like walls and sprites for each column), the times are summed.
+pub struct Timer {
+    #[cfg(feature = "profiling")]
+    stage: Stage,
+    #[cfg(feature = "profiling")]
+    start: Instant,
+}
+
+impl Timer {
+    #[inline(always)]
+    #[cfg_attr(not(feature = "profiling"), allow(unused_variables))]
+    pub fn start(stage: Stage) -> Self {
+        Timer {
+            #[cfg(feature = "profiling")]
+            stage,
+            #[cfg(feature = "profiling")]
+            start: Instant::now(),
+        }
+    }
+    /// Stops the timer before the end of its scope
+    #[inline(always)]
+    pub fn stop(self) {}
+}
+
+#[cfg(feature = "profiling")]
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        RECORDER.with(|r| r.borrow_mut().current[self.stage as usize] += elapsed);
+    }
+}
+
+/// Finishes recording the current frame and starts the next
+#[inline(always)]
+pub fn end_frame() {
+    #[cfg(feature = "profiling")]
+    RECORDER.with(|r| {
+        let mut r = r.borrow_mut();
+        let now = Instant::now();
+
+        if r.frames.len() == CAPACITY {
+            r.frames.pop_front();
+        }
+        let frame = FrameTimes {
+            start: r.frame_start - r.epoch,
+            stages: std::mem::take(&mut r.current),
+        };
+        r.frames.push_back(frame);
+        r.frame_start = now;
+    });
+}
+
+/// Writes the recorded frames to `{path}.csv` and as a chrome://tracing file to `{path}.json`
+#[cfg_attr(not(feature = "profiling"), allow(unused_variables))]
+pub fn dump(path: &str) {
+    #[cfg(feature = "profiling")]
+    RECORDER.with(|r| {
+        let r = r.borrow();
+        for (ext, write) in [("csv", write_csv as fn(&mut BufWriter<File>, &VecDeque<FrameTimes>) -> io::Result<()>), ("json", write_trace)] {
+            let file = format!("{path}.{ext}");
+            let res = File::create(&file).map(BufWriter::new).and_then(|mut f| {
+                write(&mut f, &r.frames)?;
+                f.flush()
+            });
+            match res {
+                Ok(()) => log::info!("wrote frame timings to {file}"),
+                Err(e) => log::error!("could not write frame timings to {file}: {e}"),
+            }
+        }
+    });
+}
+
+#[cfg(feature = "profiling")]
+fn write_csv(f: &mut BufWriter<File>, frames: &VecDeque<FrameTimes>) -> io::Result<()> {
+    write!(f, "frame_start_us")?;
+    for stage in Stage::ALL {
+        write!(f, ",{}_us", stage.name())?;
+    }
+    writeln!(f)?;
+
+    for frame in frames {
+        write!(f, "{}", frame.start.as_micros())?;
+        for d in frame.stages {
+            write!(f, ",{}", d.as_micros())?;
+        }
+        writeln!(f)?;
+    }
+    Ok(())
+}
+
+/// Stages that are timed in several pieces are written as one event,
+/// so each frame's stages are laid out one after another from the start of the frame
+#[cfg(feature = "profiling")]
+fn write_trace(f: &mut BufWriter<File>, frames: &VecDeque<FrameTimes>) -> io::Result<()> {
+    write!(f, "{{\"traceEvents\":[")?;
+    let mut first = true;
+    for frame in frames {
+        let mut ts = frame.start;
+        for (stage, d) in Stage::ALL.into_iter().zip(frame.stages) {
+            if !first {
+                write!(f, ",")?;
+            }
+            first = false;
+            write!(f, "{{\"name\":\"{}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":0,\"tid\":0}}", stage.name(), ts.as_micros(), d.as_micros())?;
+            ts += d;
+        }
+    }
+    writeln!(f, "]}}")
+}
@@ -0,0 +1,217 @@
+//! Named player profiles, each with its own settings and statistics kept between runs
+//!
+//! A profile is the directory `profiles/<name>/` with `settings.toml` and `stats.toml` in it.
+//! The profile used last is started with unless another is chosen with `--profile <name>`.
+
+use std::{collections::BTreeMap, fmt::Write, fs, path::PathBuf};
+
+use log::{info, warn, error};
+
+use crate::settings::{parse_toml, Value};
+
+pub const PROFILES_DIR: &str = "profiles";
+pub const DEFAULT_PROFILE: &str = "player";
+/// File with the name of the profile used last
+const LAST_PROFILE_FILE: &str = "profiles/last";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    /// Keys (see `Campaign::level_key`) of the levels that have been finished
+    pub completed: Vec<String>,
+    pub kills: u32,
+    /// Keys of the secret levels that have been found
+    pub secrets: Vec<String>,
+    /// Fastest time in seconds each level has been finished in
+    pub best_times: BTreeMap<String, f32>,
+    /// Most waves survived on each map in survival mode
+    pub most_waves: BTreeMap<String, u32>,
+    /// How many kills the player and each kind of thing have gotten
+    pub kills_by: BTreeMap<String, u32>,
+    /// Health the player has lost and taken from others
+    pub damage_taken: f32,
+    pub damage_dealt: f32,
+}
+
+impl Stats {
+    fn parse(src: &str) -> Result<Self, String> {
+        let mut stats = Stats::default();
+        parse_toml(src, |table, key, value| {
+            match (table, key, value) {
+                ("", "completed", Value::Array(levels)) => stats.completed = levels,
+                ("", "kills", Value::Number(n)) => stats.kills = n as u32,
+                ("", "secrets", Value::Array(levels)) => stats.secrets = levels,
+                ("", "damage_taken", Value::Number(n)) => stats.damage_taken = n,
+                ("", "damage_dealt", Value::Number(n)) => stats.damage_dealt = n,
+                ("best_times", level, Value::Number(t)) => {
+                    stats.best_times.insert(level.to_owned(), t);
+                }
+                ("most_waves", map, Value::Number(n)) => {
+                    stats.most_waves.insert(map.to_owned(), n as u32);
+                }
+                ("kills_by", killer, Value::Number(n)) => {
+                    stats.kills_by.insert(killer.to_owned(), n as u32);
+                }
+                (_, key, value) => return Err(format!("invalid stat {key} = {value:?}")),
+            }
+            Ok(())
+        })?;
+        Ok(stats)
+    }
+    fn to_toml(&self) -> String {
+        let list = |levels: &[String]| levels.iter().map(|l| format!("\"{l}\"")).collect::<Vec<_>>().join(", ");
+
+        let mut s = String::new();
+        // Writing to a string can't fail
+        let _ = (|| -> std::fmt::Result {
+            writeln!(s, "completed = [{}]", list(&self.completed))?;
+            writeln!(s, "kills = {}", self.kills)?;
+            writeln!(s, "secrets = [{}]", list(&self.secrets))?;
+            writeln!(s, "damage_taken = {}", self.damage_taken)?;
+            writeln!(s, "damage_dealt = {}", self.damage_dealt)?;
+            writeln!(s)?;
+            writeln!(s, "[best_times]")?;
+            for (level, time) in &self.best_times {
+                writeln!(s, "\"{level}\" = {time}")?;
+            }
+            writeln!(s)?;
+            writeln!(s, "[most_waves]")?;
+            for (map, waves) in &self.most_waves {
+                writeln!(s, "\"{map}\" = {waves}")?;
+            }
+            writeln!(s)?;
+            writeln!(s, "[kills_by]")?;
+            for (killer, kills) in &self.kills_by {
+                writeln!(s, "\"{killer}\" = {kills}")?;
+            }
+            Ok(())
+        })();
+        s
+    }
+}
+
+#[derive(Debug)]
+pub struct Profile {
+    pub name: String,
+    pub stats: Stats,
+}
+
+impl Profile {
+    /// Loads the profile with the given name, or the one used last
+    pub fn load(name: Option<&str>) -> Self {
+        let name = name.map(str::to_owned)
+            .or_else(|| fs::read_to_string(LAST_PROFILE_FILE).ok().map(|n| n.trim().to_owned()))
+            .filter(|n| !n.is_empty())
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_owned());
+        let name = if valid_name(&name) {
+            name
+        } else {
+            warn!("{name:?} can't be the name of a profile, playing as {DEFAULT_PROFILE} instead");
+            DEFAULT_PROFILE.to_owned()
+        };
+
+        let profile = Profile {
+            stats: Self::load_stats(&name),
+            name,
+        };
+        info!("profile: {}", profile.name);
+
+        if let Err(e) = fs::create_dir_all(profile.dir()).and_then(|()| fs::write(LAST_PROFILE_FILE, &profile.name)) {
+            warn!("could not set up profile {}: {e}", profile.name);
+        }
+        profile
+    }
+    fn load_stats(name: &str) -> Stats {
+        let path = PathBuf::from(PROFILES_DIR).join(name).join("stats.toml");
+        match fs::read_to_string(&path) {
+            Ok(src) => Stats::parse(&src).unwrap_or_else(|e| {
+                error!("could not read {}: {e}", path.display());
+                Stats::default()
+            }),
+            Err(_) => Stats::default(),
+        }
+    }
+    fn dir(&self) -> PathBuf {
+        PathBuf::from(PROFILES_DIR).join(&self.name)
+    }
+    pub fn settings_path(&self) -> PathBuf {
+        self.dir().join("settings.toml")
+    }
+    pub fn save(&self) {
+        let path = self.dir().join("stats.toml");
+        if let Err(e) = fs::write(&path, self.stats.to_toml()) {
+            warn!("could not save stats to {}: {e}", path.display());
+        }
+    }
+    pub fn best_time(&self, level: &str) -> Option<f32> {
+        self.stats.best_times.get(level).copied()
+    }
+    /// Records a finished level, `time` is how many seconds it took
+    pub fn finish_level(&mut self, level: &str, time: f32, kills: u32) {
+        let stats = &mut self.stats;
+        if !stats.completed.iter().any(|l| l == level) {
+            stats.completed.push(level.to_owned());
+        }
+        stats.kills += kills;
+        let best = stats.best_times.entry(level.to_owned()).or_insert(time);
+        *best = best.min(time);
+        self.save();
+    }
+    /// Records how many waves were survived on a map, returning the most ever survived there
+    pub fn survived(&mut self, map: &str, waves: u32) -> u32 {
+        let most = self.stats.most_waves.entry(map.to_owned()).or_default();
+        *most = (*most).max(waves);
+        let most = *most;
+        self.save();
+        most
+    }
+    /// Counts a kill for whoever got it, saved with the rest of the stats
+    pub fn credit_kill(&mut self, killer: &str) {
+        *self.stats.kills_by.entry(killer.to_owned()).or_default() += 1;
+    }
+    /// Counts damage the player took or dealt, saved with the rest of the stats
+    pub fn count_damage(&mut self, amount: f32, to_player: bool, by_player: bool) {
+        if to_player {
+            self.stats.damage_taken += amount;
+        } else if by_player {
+            self.stats.damage_dealt += amount;
+        }
+    }
+    pub fn found_secret(&mut self, level: &str) {
+        if !self.stats.secrets.iter().any(|l| l == level) {
+            self.stats.secrets.push(level.to_owned());
+            self.save();
+        }
+    }
+}
+
+/// Whether `name` can only mean a directory right inside the profiles directory
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains("..") && !name.contains(std::path::is_separator)
+}
+
+#[test]
+fn profile_names_stay_in_the_profiles_directory() {
+    assert!(valid_name("player") && valid_name("Anna B."));
+    for name in ["", "..", "a/b", "../player", "a..b"] {
+        assert!(!valid_name(name), "{name:?} shouldn't be allowed");
+    }
+}
+
+#[test]
+fn stats_round_trip() {
+    let mut stats = Stats {
+        completed: vec!["map.txt".to_owned(), "maps/e1m2.txt".to_owned()],
+        kills: 12,
+        secrets: vec!["maps/secret.txt".to_owned()],
+        damage_taken: 130.5,
+        damage_dealt: 412.25,
+        ..Stats::default()
+    };
+    stats.best_times.insert("map.txt".to_owned(), 42.5);
+    stats.best_times.insert("maps/e1m2.txt".to_owned(), 70.25);
+    stats.most_waves.insert("arena.txt".to_owned(), 7);
+    stats.kills_by.insert("player".to_owned(), 12);
+    stats.kills_by.insert("barrel".to_owned(), 3);
+
+    assert_eq!(Stats::parse(&stats.to_toml()), Ok(stats));
+}
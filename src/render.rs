@@ -0,0 +1,95 @@
+//! Ways of drawing the world into a frame
+//!
+//! The software raycaster is the one the game is played with. Others can draw the same world differently,
+//! and the GPU wall pass (with the `gpu` feature) draws behind what the software renderer leaves see-through.
+
+use crate::{tex::{Frame, columns::ViewBuffer, grade::GradeLut}, world::{Camera, World, Reprojection}, pacing::DynamicResolution, time::Instant};
+
+pub mod top_down;
+#[cfg(test)]
+mod golden;
+
+pub trait Renderer {
+    /// Draws the world as seen by `camera` to the frame
+    fn render(&mut self, world: &World, camera: &Camera, target: &mut Frame);
+}
+
+/// The raycaster that draws everything on the CPU, one column at a time
+#[derive(Debug, Default)]
+pub struct SoftwareRenderer {
+    /// Where the view is drawn before the frame, if that's faster
+    view: ViewBuffer,
+    /// Leaves out the walls, floors and ceilings so they can be drawn behind the frame by something else
+    pub things_only: bool,
+    /// How many times wider and taller the view is drawn before it's scaled down to the frame, 0 or 1 for not at all
+    pub supersampling: u32,
+    /// Where the view is drawn when supersampling
+    supersampled: Vec<u8>,
+    /// Casts only half the rays each frame and reuses the walls from the frame before for the rest
+    pub reprojection: Option<Reprojection>,
+    /// Casts fewer rays while rendering is too slow, the overlays are still drawn at the full resolution
+    pub dynamic_resolution: Option<DynamicResolution>,
+    /// Colour grade of the last map that had one
+    grade: Option<GradeLut>,
+}
+
+impl SoftwareRenderer {
+    pub fn new(supersampling: u32) -> Self {
+        SoftwareRenderer { supersampling, ..Self::default() }
+    }
+    fn render_at_size(&mut self, world: &World, camera: &Camera, target: &mut Frame) {
+        let strip = self.dynamic_resolution.as_ref().map_or(1, DynamicResolution::strip);
+        if self.things_only {
+            // The walls are drawn behind whatever is left see-through
+            target.clear_transparent();
+            world.draw_view(target, camera, false, strip, self.reprojection.as_mut());
+        } else {
+            let start = Instant::now();
+            let used_columns = match self.view.target(target.width(), target.height()) {
+                Some(columns) => {
+                    world.draw_view(columns, camera, true, strip, self.reprojection.as_mut());
+                    true
+                }
+                None => {
+                    world.draw_view(target, camera, true, strip, self.reprojection.as_mut());
+                    false
+                }
+            };
+            self.view.finish(target, used_columns, start.elapsed());
+        }
+        world.draw_overlays(target, camera);
+    }
+    fn render_scaled(&mut self, world: &World, camera: &Camera, target: &mut Frame) {
+        // What the GPU draws behind the frame is only ever the size of the frame
+        let factor = self.supersampling;
+        if factor <= 1 || self.things_only {
+            self.render_at_size(world, camera, target);
+            return;
+        }
+        let (width, height) = (target.width() * factor, target.height() * factor);
+        let mut buffer = std::mem::take(&mut self.supersampled);
+        buffer.resize(width as usize * height as usize * 4, 0);
+        self.render_at_size(world, camera, &mut Frame::new(&mut buffer, width, height));
+        target.downsample(&buffer, factor);
+        self.supersampled = buffer;
+    }
+}
+
+impl Renderer for SoftwareRenderer {
+    fn render(&mut self, world: &World, camera: &Camera, target: &mut Frame) {
+        let start = Instant::now();
+        self.render_scaled(world, camera, target);
+        // What the GPU draws behind the frame isn't graded, so it's left alone rather than only graded in parts
+        if let Some(grade) = world.map.grade.filter(|_| !self.things_only) {
+            if !self.grade.as_ref().is_some_and(|lut| lut.grade() == grade) {
+                self.grade = Some(GradeLut::new(grade));
+            }
+            if let Some(lut) = &self.grade {
+                target.grade(lut);
+            }
+        }
+        if let Some(resolution) = &mut self.dynamic_resolution {
+            resolution.frame_took(start.elapsed());
+        }
+    }
+}
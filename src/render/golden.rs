@@ -0,0 +1,109 @@
+//! Renders of a small map from fixed places, compared against pictures of how they looked before
+//!
+//! The pictures are kept in `tests/golden`. After a change that is meant to look different,
+//! or to add a new one, set `ULVESTEIN_BLESS` to make them from the render, and look at them before committing.
+
+use std::{env, f32::consts, fs, path::PathBuf};
+use image::RgbaImage;
+use crate::{hud, testing::test_map, tex::Frame, vec::Point2, world::{Camera, World}};
+use super::{Renderer, SoftwareRenderer};
+
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 240;
+/// How much a channel can be off without the pixel counting as different, for rounding
+const TOLERANCE: u8 = 4;
+/// How many pixels in a thousand can be different before the render doesn't match
+const MAX_DIFFERENT: usize = 2;
+
+/// Glass between the start and a statue, a statue in the open and a wall of mirrors
+fn gallery() -> World {
+    World::from_map_src(&test_map("Gallery", &["objective find s Find the statue"], &[
+        "w transparent tex/glass.png tex/glass_dark.png",
+        "m reflective tint=200,220,255,40 tex/mirror.png tex/mirror.png",
+        "s thing 0.25 tex/statue.png",
+    ], "\
+bbbbbbbbbbbbb
+b   b       m
+b s w  >    m
+b   b    s  m
+bbbbbbbbbbbbb
+"))
+}
+
+fn render(world: &World, pos: (f32, f32), angle: f32, with_hud: bool) -> RgbaImage {
+    let mut buffer = vec![0; WIDTH as usize * HEIGHT as usize * 4];
+    let mut frame = Frame::new(&mut buffer, WIDTH, HEIGHT);
+    let camera = Camera { pos: Point2::new(pos.0, pos.1), angle, height: 0., alpha: 1. };
+    SoftwareRenderer::default().render(world, &camera, &mut frame);
+    if with_hud {
+        hud::draw_level_timer(&mut frame, 83.5);
+        hud::draw_objectives(&mut frame, world.objectives());
+    }
+    RgbaImage::from_raw(WIDTH, HEIGHT, buffer).expect("buffer to be the size of the picture")
+}
+
+fn assert_matches_golden(name: &str, render: &RgbaImage) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{name}.png"));
+    if env::var_os("ULVESTEIN_BLESS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        render.save(&path).expect("golden image to be saved");
+        eprintln!("made golden image {}", path.display());
+        return;
+    }
+    assert!(path.exists(), "there is no golden image {}, set ULVESTEIN_BLESS to make it", path.display());
+
+    let golden = image::open(&path).expect("golden image to be readable").to_rgba8();
+    assert_eq!(golden.dimensions(), render.dimensions(), "{name} is a different size than the golden image");
+    let different = golden.pixels()
+        .zip(render.pixels())
+        .filter(|(a, b)| a.0.iter().zip(b.0).any(|(&a, b)| a.abs_diff(b) > TOLERANCE))
+        .count();
+    if different * 1000 > MAX_DIFFERENT * golden.pixels().len() {
+        let failed = path.with_extension("failed.png");
+        render.save(&failed).expect("failed render to be saved");
+        panic!("{different} pixels of {name} are different from the golden image, the render is saved as {}", failed.display());
+    }
+}
+
+#[test]
+fn walls() {
+    let world = gallery();
+    assert_matches_golden("walls", &render(&world, (8.5, 2.5), -consts::FRAC_PI_2, false));
+}
+
+#[test]
+fn transparency_and_sprites() {
+    let world = gallery();
+    // Through the glass to the statue behind it
+    assert_matches_golden("glass", &render(&world, (7.5, 2.5), consts::PI, false));
+    // A statue in the open with the mirrors behind it
+    assert_matches_golden("sprites", &render(&world, (6.5, 3.5), 0., false));
+}
+
+#[test]
+fn mirrors() {
+    let world = gallery();
+    // The player is seen in the mirror
+    assert_matches_golden("mirror", &render(&world, (9.5, 2.5), 0., false));
+}
+
+#[test]
+fn hud() {
+    let world = gallery();
+    assert_matches_golden("hud", &render(&world, (8.5, 2.5), -consts::FRAC_PI_2, true));
+}
+
+#[test]
+fn strips_are_one_column_wide() {
+    const STRIP: u32 = 4;
+    let world = gallery();
+    let mut buffer = vec![0; WIDTH as usize * HEIGHT as usize * 4];
+    let mut frame = Frame::new(&mut buffer, WIDTH, HEIGHT);
+    // The mirrors show reflected things too
+    let camera = Camera { pos: Point2::new(9.5, 2.5), angle: 0., height: 0., alpha: 1. };
+    world.draw_view(&mut frame, &camera, true, STRIP, None);
+    let render = RgbaImage::from_raw(WIDTH, HEIGHT, buffer).expect("buffer to be the size of the picture");
+    for (x, y) in (0..WIDTH).flat_map(|x| (0..HEIGHT).map(move |y| (x, y))) {
+        assert_eq!(render.get_pixel(x, y), render.get_pixel(x - x % STRIP, y), "column {x} is different from its strip");
+    }
+}
@@ -0,0 +1,110 @@
+//! The map seen from above, for debugging
+//!
+//! It draws into whatever frame it's given, so it works both on the whole frame and inside a viewport.
+
+use std::collections::HashMap;
+
+use crate::{map::Side, tex::{Colour, Frame, TColour, Texture}, vec::{Point2, Vector2}, world::{Camera, World, PLAYER_WIDTH, projectile::PROJECTILE_WIDTH}};
+
+use super::Renderer;
+
+const BACKGROUND: Colour = Colour::new(0x10, 0x10, 0x10);
+const PLAYER_COLOUR: TColour = TColour::new(0x40, 0xe0, 0x40, 0xff);
+const THING_COLOUR: TColour = TColour::new(0xb0, 0xb0, 0xb0, 0xff);
+const ENEMY_COLOUR: TColour = TColour::new(0xe0, 0x40, 0x40, 0xff);
+const PROJECTILE_COLOUR: TColour = TColour::new(0xff, 0xe0, 0x40, 0xff);
+const RAY_COLOUR: TColour = TColour::new(0xff, 0xff, 0x80, 0x50);
+const TARGET_COLOUR: TColour = TColour::new(0xff, 0x90, 0x30, 0xc0);
+/// Every this many columns of the view, the ray through it is drawn
+const RAY_STEP: u32 = 8;
+
+#[derive(Debug)]
+pub struct TopDownRenderer {
+    /// Pixels per cell of the map
+    pub zoom: f32,
+    /// Whether the rays of the view are drawn
+    pub rays: bool,
+    /// Whether only the cells the player has seen are drawn
+    pub seen_only: bool,
+    /// Colour each wall texture is drawn with, which takes a while to work out
+    colours: HashMap<*const Texture, Colour>,
+    /// The map the colours are of
+    colours_of: Box<str>,
+}
+
+impl TopDownRenderer {
+    pub fn new(zoom: f32, rays: bool) -> Self {
+        TopDownRenderer { zoom, rays, seen_only: false, colours: HashMap::new(), colours_of: "".into() }
+    }
+}
+
+impl Default for TopDownRenderer {
+    fn default() -> Self {
+        Self::new(8., true)
+    }
+}
+
+impl Renderer for TopDownRenderer {
+    fn render(&mut self, world: &World, camera: &Camera, target: &mut Frame) {
+        let map = &world.map;
+        if self.colours_of != map.name {
+            self.colours.clear();
+            self.colours_of = map.name.clone();
+        }
+        target.clear(BACKGROUND);
+
+        // The camera is in the middle of the frame
+        let zoom = self.zoom;
+        let (half_width, half_height) = (target.width() as f32 / 2., target.height() as f32 / 2.);
+        let to_frame = |p: Point2| (((p.x - camera.pos.x) * zoom + half_width).floor() as i32, ((p.y - camera.pos.y) * zoom + half_height).floor() as i32);
+        let radius = |width: f32| (width * zoom).round().max(1.) as u32;
+
+        let (x0, y0) = ((camera.pos.x - half_width / zoom).floor() as i32, (camera.pos.y - half_height / zoom).floor() as i32);
+        let (x1, y1) = ((camera.pos.x + half_width / zoom).ceil() as i32, (camera.pos.y + half_height / zoom).ceil() as i32);
+        let cell_size = zoom.ceil() as u32;
+        for y in y0.max(0)..y1.min(map.height()) {
+            for x in x0.max(0)..x1.min(map.width()) {
+                if self.seen_only && !world.seen(x, y) {
+                    continue;
+                }
+                let Some(mat) = map.get(x, y).filter(|mat| !mat.is_air()) else { continue };
+                let tex = map.get_tex(mat, Side::Up, world.time, (x, y));
+                let colour = *self.colours.entry(tex as *const Texture).or_insert_with(|| tex.average_colour());
+                let (fx, fy) = to_frame(Point2::new(x as f32, y as f32));
+                target.fill_rect(fx, fy, cell_size, cell_size, colour.alpha(0xff));
+            }
+        }
+
+        // The rays of the view and where they've been reflected
+        let dir = Vector2::unit_from_angle(camera.angle);
+        let fov = world.view_fov();
+        let columns = (0..target.width()).step_by(RAY_STEP as usize).chain([target.width().saturating_sub(1)]);
+        for x in columns.filter(|_| self.rays) {
+            for hit in map.render_ray_cast(camera.pos, fov.column_ray(dir, x), world.view_distance()) {
+                target.draw_line(to_frame(hit.seg_start), to_frame(hit.seg_start + hit.seg), RAY_COLOUR);
+            }
+        }
+
+        for thing in world.things().iter().map(|t| t.interpolated(camera.alpha)) {
+            let (fx, fy) = to_frame(thing.pos);
+            let colour = if thing.enemy.is_some() {
+                ENEMY_COLOUR
+            } else {
+                THING_COLOUR
+            };
+            target.draw_circle(fx, fy, radius(thing.width()), colour);
+            // What enemies are going for, which they head straight at
+            if let Some(enemy_target) = thing.enemy.and_then(|enemy| enemy.target()) {
+                target.draw_line((fx, fy), to_frame(enemy_target), TARGET_COLOUR);
+            }
+        }
+        for p in world.projectiles().positions(camera.alpha) {
+            let (fx, fy) = to_frame(p);
+            target.draw_circle(fx, fy, radius(PROJECTILE_WIDTH), PROJECTILE_COLOUR);
+        }
+
+        let player = to_frame(camera.pos);
+        target.draw_circle(player.0, player.1, radius(PLAYER_WIDTH), PLAYER_COLOUR);
+        target.draw_line(player, to_frame(camera.pos + dir), PLAYER_COLOUR);
+    }
+}
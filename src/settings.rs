@@ -0,0 +1,537 @@
+//! Settings loaded from and saved to the `settings.toml` of the player's profile
+//!
+//! Only the small part of TOML that the settings need is understood:
+//! `key = value` pairs of numbers, booleans, strings and arrays of strings, and `[table]` headers.
+
+use std::{fs, io, fmt::Write, path::{Path, PathBuf}};
+
+use log::{info, warn, error};
+use winit::event::VirtualKeyCode;
+use winit_input_helper::WinitInputHelper;
+
+use crate::{palette::{self, Palette}, tex::Colour};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// Width of the rendered image in pixels
+    pub width: u32,
+    /// Height of the rendered image in pixels
+    pub height: u32,
+    /// How many times bigger than the rendered image the window starts out
+    pub scale: u32,
+    /// How many times wider and taller the view is rendered before being scaled down to smooth the edges, 1, 2 or 4
+    pub supersampling: u32,
+    /// Casts rays for every other column each frame and moves the walls of the frame before into the rest (experimental)
+    pub reprojection: bool,
+    /// Casts more of the rays reprojection would skip to smear less
+    pub reprojection_quality: bool,
+    /// Casts fewer rays while rendering takes longer than a frame at `target_fps` should
+    pub dynamic_resolution: bool,
+    pub target_fps: u32,
+    /// Starts levels with wall textures of a single colour and swaps in the real ones as they're decoded
+    pub background_textures: bool,
+    /// Horizontal field of view in degrees
+    pub fov: f32,
+    /// Degrees turned per second when turning with the keyboard
+    pub turn_speed: f32,
+    /// Degrees turned per pixel the mouse moves
+    pub mouse_sensitivity: f32,
+    pub volume: f32,
+    /// Overrides how far the maps are rendered
+    pub render_distance: Option<f32>,
+    pub show_fps: bool,
+    /// Waits for the screen to refresh before showing a frame
+    pub vsync: bool,
+    /// Most frames drawn per second, 0 for no limit
+    pub max_fps: u32,
+    /// Shows how long the current level has been played
+    pub show_timer: bool,
+    /// Shows captions for significant sounds
+    pub subtitles: bool,
+    /// Name of the palette of untextured colours
+    pub palette: String,
+    /// Colours used instead of the ones from the palette
+    pub ceiling_colour: Option<Colour>,
+    pub floor_colour: Option<Colour>,
+    /// Outlines things so they stand out from the walls
+    pub high_contrast: bool,
+    /// Shows flashes as borders around the screen instead of over all of it
+    pub reduce_flashing: bool,
+    /// Draws the cursor in the game instead of using the system one
+    pub software_cursor: bool,
+    /// Draws the walls, floors and ceilings on the GPU when the map is simple enough, if built with the `gpu` feature
+    pub gpu_walls: bool,
+    /// Name of the file in `lang/` the text is taken from
+    pub language: String,
+    /// Demos played on the title screen when nothing is pressed for a while, in turn
+    pub demos: Vec<String>,
+    pub bindings: Bindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            width: 320,
+            height: 240,
+            scale: 4,
+            supersampling: 1,
+            reprojection: false,
+            reprojection_quality: false,
+            dynamic_resolution: false,
+            target_fps: 60,
+            background_textures: true,
+            fov: 65.,
+            turn_speed: 105.,
+            mouse_sensitivity: 0.2,
+            volume: 1.,
+            render_distance: None,
+            show_fps: true,
+            vsync: true,
+            max_fps: 0,
+            show_timer: false,
+            subtitles: false,
+            palette: "default".to_owned(),
+            ceiling_colour: None,
+            floor_colour: None,
+            high_contrast: false,
+            reduce_flashing: false,
+            software_cursor: false,
+            gpu_walls: false,
+            language: "en".to_owned(),
+            demos: vec!["demos/map.dem".to_owned()],
+            bindings: Bindings::default(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    Forwards,
+    Backwards,
+    TurnLeft,
+    TurnRight,
+    StrafeLeft,
+    StrafeRight,
+    Use,
+    Noclip,
+    FovUp,
+    FovDown,
+    Console,
+    Objectives,
+    TopDown,
+    Tactical,
+    Sprint,
+    Inventory,
+    Sneak,
+    MessageLog,
+    Fire,
+    Zoom,
+}
+
+impl Action {
+    pub const ALL: [Action; 20] = [
+        Action::Forwards, Action::Backwards, Action::TurnLeft, Action::TurnRight, Action::StrafeLeft, Action::StrafeRight,
+        Action::Use, Action::Noclip, Action::FovUp, Action::FovDown, Action::Console, Action::Objectives, Action::TopDown,
+        Action::Tactical, Action::Sprint, Action::Inventory, Action::Sneak, Action::MessageLog, Action::Fire,
+        Action::Zoom,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Action::Forwards => "forwards",
+            Action::Backwards => "backwards",
+            Action::TurnLeft => "turn_left",
+            Action::TurnRight => "turn_right",
+            Action::StrafeLeft => "strafe_left",
+            Action::StrafeRight => "strafe_right",
+            Action::Use => "use",
+            Action::Noclip => "noclip",
+            Action::FovUp => "fov_up",
+            Action::FovDown => "fov_down",
+            Action::Console => "console",
+            Action::Objectives => "objectives",
+            Action::TopDown => "top_down",
+            Action::Tactical => "tactical",
+            Action::Sprint => "sprint",
+            Action::Inventory => "inventory",
+            Action::Sneak => "sneak",
+            Action::MessageLog => "message_log",
+            Action::Fire => "fire",
+            Action::Zoom => "zoom",
+        }
+    }
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.name() == name)
+    }
+}
+
+/// A key or mouse button an action can be bound to
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Button {
+    Key(VirtualKeyCode),
+    /// Numbered like `WinitInputHelper` does, 0 is the left button, 1 the right and 2 the middle
+    Mouse(usize),
+}
+
+/// Names of the mouse buttons that can be bound, by their number
+const MOUSE_BUTTONS: [&str; 3] = ["MouseLeft", "MouseRight", "MouseMiddle"];
+
+impl Button {
+    /// Named like its `VirtualKeyCode` variant or one of `MOUSE_BUTTONS`
+    fn name(self) -> String {
+        match self {
+            Button::Key(k) => format!("{k:?}"),
+            Button::Mouse(b) => MOUSE_BUTTONS[b].to_owned(),
+        }
+    }
+    fn from_name(name: &str) -> Option<Self> {
+        match MOUSE_BUTTONS.iter().position(|&b| b == name) {
+            Some(b) => Some(Button::Mouse(b)),
+            None => key_from_name(name).map(Button::Key),
+        }
+    }
+    fn held(self, input: &WinitInputHelper) -> bool {
+        match self {
+            Button::Key(k) => input.key_held(k),
+            Button::Mouse(b) => input.mouse_held(b),
+        }
+    }
+    fn pressed(self, input: &WinitInputHelper) -> bool {
+        match self {
+            Button::Key(k) => input.key_pressed(k),
+            Button::Mouse(b) => input.mouse_pressed(b),
+        }
+    }
+}
+
+/// Keys and buttons bound to each action, indexed by `Action`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bindings([Vec<Button>; Action::ALL.len()]);
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use VirtualKeyCode::*;
+        let keys = |keys: &[VirtualKeyCode]| keys.iter().copied().map(Button::Key).collect();
+        Bindings([
+            keys(&[Up, W]),
+            keys(&[Down, S]),
+            keys(&[Left]),
+            keys(&[Right]),
+            keys(&[A]),
+            keys(&[D]),
+            keys(&[E, Space]),
+            keys(&[N]),
+            keys(&[Plus, Equals]),
+            keys(&[Minus]),
+            keys(&[Grave]),
+            keys(&[Tab]),
+            keys(&[M]),
+            keys(&[T]),
+            keys(&[LShift, RShift]),
+            keys(&[I]),
+            keys(&[C]),
+            keys(&[H]),
+            vec![Button::Mouse(0)],
+            vec![Button::Mouse(1)],
+        ])
+    }
+}
+
+impl Bindings {
+    pub fn buttons(&self, action: Action) -> &[Button] {
+        &self.0[action as usize]
+    }
+    pub fn held(&self, input: &WinitInputHelper, action: Action) -> bool {
+        self.buttons(action).iter().any(|b| b.held(input))
+    }
+    pub fn pressed(&self, input: &WinitInputHelper, action: Action) -> bool {
+        self.pressed_unless(input, action, &[])
+    }
+    /// Like `pressed`, but not counting the buttons in `taken`, which went to something else this frame
+    pub fn pressed_unless(&self, input: &WinitInputHelper, action: Action, taken: &[Button]) -> bool {
+        self.buttons(action).iter().any(|b| !taken.contains(b) && b.pressed(input))
+    }
+    /// Whether any key or mouse button that can be bound was pressed
+    pub fn any_pressed(input: &WinitInputHelper) -> bool {
+        BINDABLE_KEYS.iter().any(|&k| input.key_pressed(k)) || (0..MOUSE_BUTTONS.len()).any(|b| input.mouse_pressed(b))
+    }
+    /// Like `pressed`, but also true when the key repeats from being held down
+    pub fn pressed_os(&self, input: &WinitInputHelper, action: Action) -> bool {
+        self.buttons(action).iter().any(|&b| match b {
+            Button::Key(k) => input.key_pressed_os(k),
+            Button::Mouse(_) => b.pressed(input),
+        })
+    }
+}
+
+/// Keys that can be bound, named like their `VirtualKeyCode` variant
+const BINDABLE_KEYS: &[VirtualKeyCode] = {
+    use VirtualKeyCode::*;
+    &[
+        Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+        F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+        Left, Up, Right, Down, Space, Return, Tab, Back, Escape, Grave, Plus, Minus, Equals,
+        LShift, RShift, LControl, RControl, LAlt, RAlt, Comma, Period, Slash,
+    ]
+};
+
+fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+    BINDABLE_KEYS.iter().copied().find(|k| format!("{k:?}") == name)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Number(f32),
+    Bool(bool),
+    Str(String),
+    Array(Vec<String>),
+}
+
+fn parse_value(s: &str) -> Option<Value> {
+    let s = s.trim();
+    if let Some(s) = s.strip_prefix('"') {
+        Some(Value::Str(s.strip_suffix('"')?.to_owned()))
+    } else if let Some(s) = s.strip_prefix('[') {
+        let s = s.strip_suffix(']')?;
+        s.split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+            .map(|e| e.strip_prefix('"')?.strip_suffix('"').map(str::to_owned))
+            .collect::<Option<_>>()
+            .map(Value::Array)
+    } else {
+        match s {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => s.parse().ok().map(Value::Number),
+        }
+    }
+}
+
+/// Calls `f` with the table, key and value of each entry in the file
+pub(crate) fn parse_toml<F: FnMut(&str, &str, Value) -> Result<(), String>>(src: &str, mut f: F) -> Result<(), String> {
+    let mut table = String::new();
+
+    for (n, line) in src.lines().enumerate() {
+        let n = n + 1;
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[') {
+            table = name.strip_suffix(']').ok_or(format!("line {n}: unclosed table header"))?.trim().to_owned();
+            continue;
+        }
+
+        let (key, value) = line.split_once('=').ok_or(format!("line {n}: expected `key = value`"))?;
+        let key = key.trim();
+        // Quoted keys can contain characters like `.` and `/`
+        let key = key.strip_prefix('"').and_then(|k| k.strip_suffix('"')).unwrap_or(key);
+        let value = parse_value(value).ok_or(format!("line {n}: invalid value for {key}"))?;
+        f(&table, key, value).map_err(|e| format!("line {n}: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Cuts off a `#` comment, unless the `#` is inside a string
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => (),
+        }
+    }
+    line
+}
+
+impl Settings {
+    /// Parses the contents of a settings file, using the defaults for anything it doesn't set
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut settings = Settings::default();
+        parse_toml(src, |table, key, value| settings.set(table, key, value))?;
+        Ok(settings)
+    }
+    fn set(&mut self, table: &str, key: &str, value: Value) -> Result<(), String> {
+        use self::Value::*;
+
+        match (table, key, value) {
+            ("", "width", Number(n)) if n >= 1. => self.width = n as u32,
+            ("", "height", Number(n)) if n >= 1. => self.height = n as u32,
+            ("", "scale", Number(n)) if n >= 1. => self.scale = n as u32,
+            ("", "supersampling", Number(n)) if [1., 2., 4.].contains(&n) => self.supersampling = n as u32,
+            ("", "reprojection", Bool(b)) => self.reprojection = b,
+            ("", "reprojection_quality", Bool(b)) => self.reprojection_quality = b,
+            ("", "dynamic_resolution", Bool(b)) => self.dynamic_resolution = b,
+            ("", "target_fps", Number(n)) if n >= 1. => self.target_fps = n as u32,
+            ("", "background_textures", Bool(b)) => self.background_textures = b,
+            ("", "fov", Number(n)) if (1. ..180.).contains(&n) => self.fov = n,
+            ("", "turn_speed", Number(n)) => self.turn_speed = n,
+            ("", "mouse_sensitivity", Number(n)) => self.mouse_sensitivity = n,
+            ("", "volume", Number(n)) => self.volume = n.clamp(0., 1.),
+            ("", "render_distance", Number(n)) => self.render_distance = (n > 0.).then_some(n),
+            ("", "show_fps", Bool(b)) => self.show_fps = b,
+            ("", "vsync", Bool(b)) => self.vsync = b,
+            ("", "max_fps", Number(n)) if n >= 0. => self.max_fps = n as u32,
+            ("", "show_timer", Bool(b)) => self.show_timer = b,
+            ("", "subtitles", Bool(b)) => self.subtitles = b,
+            ("", "palette", Str(name)) if Palette::by_name(&name).is_some() => self.palette = name,
+            ("", "high_contrast", Bool(b)) => self.high_contrast = b,
+            ("", "reduce_flashing", Bool(b)) => self.reduce_flashing = b,
+            ("", "software_cursor", Bool(b)) => self.software_cursor = b,
+            ("", "gpu_walls", Bool(b)) => self.gpu_walls = b,
+            ("palette", "ceiling", Str(c)) => self.ceiling_colour = Some(palette::parse_colour(&c).ok_or(format!("invalid colour {c}"))?),
+            ("palette", "floor", Str(c)) => self.floor_colour = Some(palette::parse_colour(&c).ok_or(format!("invalid colour {c}"))?),
+            ("", "language", Str(lang)) => self.language = lang,
+            ("", "demos", Array(demos)) => self.demos = demos,
+            ("bindings", action, Array(buttons)) => {
+                let action = Action::from_name(action).ok_or(format!("unknown action {action}"))?;
+                let buttons = buttons.iter()
+                    .map(|b| Button::from_name(b).ok_or(format!("unknown key {b}")))
+                    .collect::<Result<_, _>>()?;
+                self.bindings.0[action as usize] = buttons;
+            }
+            (_, key, value) => return Err(format!("invalid setting {key} = {value:?}")),
+        }
+        Ok(())
+    }
+    /// The chosen palette with the colours that were set by hand
+    pub fn palette(&self) -> Palette {
+        let palette = Palette::by_name(&self.palette).unwrap_or(Palette::DEFAULT);
+        Palette {
+            ceiling: self.ceiling_colour.unwrap_or(palette.ceiling),
+            floor: self.floor_colour.unwrap_or(palette.floor),
+            ..palette
+        }
+    }
+    pub fn to_toml(&self) -> String {
+        let mut s = String::new();
+        // Writing to a string can't fail
+        let _ = (|| -> std::fmt::Result {
+            writeln!(s, "width = {}", self.width)?;
+            writeln!(s, "height = {}", self.height)?;
+            writeln!(s, "scale = {}", self.scale)?;
+            writeln!(s, "supersampling = {}", self.supersampling)?;
+            writeln!(s, "reprojection = {}", self.reprojection)?;
+            writeln!(s, "reprojection_quality = {}", self.reprojection_quality)?;
+            writeln!(s, "dynamic_resolution = {}", self.dynamic_resolution)?;
+            writeln!(s, "target_fps = {}", self.target_fps)?;
+            writeln!(s, "background_textures = {}", self.background_textures)?;
+            writeln!(s, "fov = {}", self.fov)?;
+            writeln!(s, "turn_speed = {}", self.turn_speed)?;
+            writeln!(s, "mouse_sensitivity = {}", self.mouse_sensitivity)?;
+            writeln!(s, "volume = {}", self.volume)?;
+            // 0 means the maps decide
+            writeln!(s, "render_distance = {}", self.render_distance.unwrap_or(0.))?;
+            writeln!(s, "show_fps = {}", self.show_fps)?;
+            writeln!(s, "vsync = {}", self.vsync)?;
+            // 0 means no limit
+            writeln!(s, "max_fps = {}", self.max_fps)?;
+            writeln!(s, "show_timer = {}", self.show_timer)?;
+            writeln!(s, "subtitles = {}", self.subtitles)?;
+            writeln!(s, "palette = \"{}\"", self.palette)?;
+            writeln!(s, "high_contrast = {}", self.high_contrast)?;
+            writeln!(s, "reduce_flashing = {}", self.reduce_flashing)?;
+            writeln!(s, "software_cursor = {}", self.software_cursor)?;
+            writeln!(s, "gpu_walls = {}", self.gpu_walls)?;
+            writeln!(s, "language = \"{}\"", self.language)?;
+            let demos: Vec<_> = self.demos.iter().map(|d| format!("\"{d}\"")).collect();
+            writeln!(s, "demos = [{}]", demos.join(", "))?;
+            if self.ceiling_colour.is_some() || self.floor_colour.is_some() {
+                writeln!(s)?;
+                writeln!(s, "[palette]")?;
+                if let Some(c) = self.ceiling_colour {
+                    writeln!(s, "ceiling = \"{}\"", palette::format_colour(c))?;
+                }
+                if let Some(c) = self.floor_colour {
+                    writeln!(s, "floor = \"{}\"", palette::format_colour(c))?;
+                }
+            }
+            writeln!(s)?;
+            writeln!(s, "[bindings]")?;
+            for action in Action::ALL {
+                let buttons: Vec<_> = self.bindings.buttons(action).iter().map(|b| format!("\"{}\"", b.name())).collect();
+                writeln!(s, "{} = [{}]", action.name(), buttons.join(", "))?;
+            }
+            Ok(())
+        })();
+        s
+    }
+}
+
+/// Settings together with where they are saved
+#[derive(Debug)]
+pub struct SettingsFile {
+    pub settings: Settings,
+    path: PathBuf,
+    /// Settings that couldn't be read aren't written back, so the file isn't overwritten with the defaults
+    writable: bool,
+    saved: Settings,
+}
+
+impl SettingsFile {
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_owned();
+        // There's nowhere to keep the settings in the browser
+        #[cfg(target_arch = "wasm32")]
+        let (settings, writable) = (Settings::default(), false);
+        #[cfg(not(target_arch = "wasm32"))]
+        let (settings, writable) = match fs::read_to_string(&path) {
+            Ok(src) => match Settings::parse(&src) {
+                Ok(settings) => (settings, true),
+                Err(e) => {
+                    error!("could not read {}: {e}", path.display());
+                    (Settings::default(), false)
+                }
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                info!("no {} yet, using default settings", path.display());
+                (Settings::default(), true)
+            }
+            Err(e) => {
+                error!("could not open {}: {e}", path.display());
+                (Settings::default(), false)
+            }
+        };
+
+        SettingsFile {
+            saved: settings.clone(),
+            settings,
+            path,
+            writable,
+        }
+    }
+    /// Writes the settings to the file if they have changed
+    pub fn save(&mut self) {
+        if !self.writable || self.settings == self.saved {
+            return;
+        }
+        match fs::write(&self.path, self.settings.to_toml()) {
+            Ok(()) => {
+                info!("saved settings to {}", self.path.display());
+                self.saved = self.settings.clone();
+            }
+            Err(e) => warn!("could not save settings to {}: {e}", self.path.display()),
+        }
+    }
+}
+
+#[test]
+fn settings_round_trip() {
+    let mut settings = Settings {
+        fov: 90.,
+        render_distance: Some(12.5),
+        language: "da".to_owned(),
+        palette: "tritanopia".to_owned(),
+        floor_colour: Some(Colour::new(0x12, 0x34, 0x56)),
+        gpu_walls: true,
+        demos: vec!["demos/a.dem".to_owned(), "demos/b.dem".to_owned()],
+        ..Settings::default()
+    };
+    settings.bindings.0[Action::Forwards as usize] = vec![Button::Key(VirtualKeyCode::I)];
+    settings.bindings.0[Action::Fire as usize] = vec![Button::Mouse(2), Button::Key(VirtualKeyCode::LControl)];
+
+    assert_eq!(Settings::parse(&settings.to_toml()), Ok(settings));
+}
@@ -0,0 +1,144 @@
+//! Sounds made in the world, collected each update for whatever plays them
+
+use crate::{map::{Map, Surface}, vec::{Point2, Vector2}, world::{Camera, doors::DoorChange}};
+
+/// Distance at which sounds can't be heard anymore
+const HEARING_DISTANCE: f32 = 24.;
+/// How much of the volume is left after going through each wall or door
+const WALL_DAMPING: f32 = 0.35;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sound {
+    Footstep(Surface),
+    /// Walking into liquid
+    Splash,
+    /// Standing on a floor that hurts
+    Sizzle,
+    Explosion,
+    /// The player firing their gun
+    Gunshot,
+    /// A door changing, with the index of the sounds of the map it makes instead of the usual ones
+    Door(DoorChange, Option<usize>),
+}
+
+impl Sound {
+    /// How far away enemies hear the sound when nothing is in the way
+    pub const fn noise_radius(self) -> f32 {
+        match self {
+            Sound::Footstep(_) => 6.,
+            Sound::Splash => 8.,
+            Sound::Sizzle => 3.,
+            Sound::Explosion => 20.,
+            Sound::Gunshot => 15.,
+            Sound::Door(DoorChange::Opening | DoorChange::Closing, _) => 8.,
+            Sound::Door(DoorChange::Blocked | DoorChange::Locked, _) => 4.,
+        }
+    }
+    /// Name of the sound effect in `sfx/`
+    pub fn file_name(self) -> String {
+        match self {
+            Sound::Footstep(surface) => format!("step_{}.wav", surface.name()),
+            Sound::Splash => "splash.wav".to_owned(),
+            Sound::Sizzle => "sizzle.wav".to_owned(),
+            Sound::Explosion => "explosion.wav".to_owned(),
+            Sound::Gunshot => "gunshot.wav".to_owned(),
+            Sound::Door(change, _) => format!("door_{}.wav", change.name()),
+        }
+    }
+    /// Key of the caption shown for the sound, only significant sounds have one
+    pub const fn caption_key(self) -> Option<&'static str> {
+        match self {
+            Sound::Footstep(_) | Sound::Splash => None,
+            Sound::Sizzle => Some("captions.sizzle"),
+            Sound::Explosion => Some("captions.explosion"),
+            Sound::Gunshot => Some("captions.gunshot"),
+            Sound::Door(DoorChange::Opening, _) => Some("captions.door_opening"),
+            Sound::Door(DoorChange::Closing, _) => Some("captions.door_closing"),
+            Sound::Door(DoorChange::Blocked, _) => Some("captions.door_blocked"),
+            Sound::Door(DoorChange::Locked, _) => Some("captions.door_locked"),
+        }
+    }
+}
+
+/// A sound played somewhere in the world
+#[derive(Debug, Clone, Copy)]
+pub struct SoundEvent {
+    pub sound: Sound,
+    pub pos: Point2,
+    /// How far away enemies hear it when nothing is in the way
+    pub radius: f32,
+}
+
+/// How a sound reaches the listener
+#[derive(Debug, Clone, Copy)]
+pub struct Heard {
+    pub sound: Sound,
+    /// From 0 (silent) to 1
+    pub volume: f32,
+    /// From -1 (all the way left) to 1 (all the way right)
+    pub pan: f32,
+    /// From -1 (right behind) to 1 (straight ahead)
+    pub front: f32,
+    /// The sound came through walls and should have its high frequencies cut off
+    pub muffled: bool,
+}
+
+impl SoundEvent {
+    pub fn new(sound: Sound, pos: Point2) -> Self {
+        SoundEvent { sound, pos, radius: sound.noise_radius() }
+    }
+    /// Whether the sound reaches `p`, which is less far through walls
+    pub fn reaches(&self, p: Point2, map: &Map) -> bool {
+        let dist = (p - self.pos).norm();
+        dist <= self.radius && dist <= self.radius * WALL_DAMPING.powi(map.walls_between(self.pos, p) as i32)
+    }
+    /// How the sound sounds to someone at the camera with the master volume at `volume`
+    pub fn heard_by(&self, camera: Camera, map: &Map, volume: f32) -> Heard {
+        let to_sound = self.pos - camera.pos;
+        let dist = to_sound.norm();
+        let walls = if dist > 0. { map.walls_between(camera.pos, self.pos) } else { 0 };
+
+        let dir = Vector2::unit_from_angle(camera.angle);
+        let (pan, front) = if dist > 0. {
+            (to_sound.dot(dir.hat()) / dist, to_sound.dot(dir) / dist)
+        } else {
+            (0., 1.)
+        };
+
+        Heard {
+            sound: self.sound,
+            volume: volume * loudness(dist, walls),
+            pan,
+            front,
+            muffled: walls > 0,
+        }
+    }
+}
+
+impl Heard {
+    /// Key of the text saying which way the sound came from
+    pub fn direction_key(&self) -> &'static str {
+        if self.pan.abs() > self.front.abs() {
+            if self.pan < 0. { "captions.left" } else { "captions.right" }
+        } else if self.front < 0. {
+            "captions.behind"
+        } else {
+            "captions.ahead"
+        }
+    }
+}
+
+/// How loud a sound is after travelling `dist` through `walls` walls
+fn loudness(dist: f32, walls: usize) -> f32 {
+    let falloff = (1. - dist / HEARING_DISTANCE).max(0.);
+    falloff * falloff * WALL_DAMPING.powi(walls as i32)
+}
+
+#[test]
+fn walls_muffle_sounds() {
+    assert_eq!(loudness(0., 0), 1.);
+    assert_eq!(loudness(HEARING_DISTANCE, 0), 0.);
+    assert!(loudness(4., 1) < loudness(4., 0));
+    assert!(loudness(4., 2) < loudness(4., 1));
+    assert!(loudness(2., 0) > loudness(6., 0));
+}
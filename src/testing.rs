@@ -0,0 +1,49 @@
+//! Helpers the tests share for making small maps and playing the world forward
+
+use crate::world::{Controls, World, TICK};
+
+/// The walls the test maps are built from
+const BRICKS: &str = "b tex/brick1.png tex/brick1_dark.png";
+
+/// A straight corridor with the player at the west end, facing east
+pub const CORRIDOR: &str = "\
+Corridor
+
+b tex/brick1.png tex/brick1_dark.png
+
+bbbbbbbbbbbb
+b>         b
+bbbbbbbbbbbb
+";
+
+/// Source of a map called `name` with `headers` after the name, `b` declared as brick walls
+/// before the rest of the `legend`, and `grid` (with any layers and floor maps) after that
+pub fn test_map(name: &str, headers: &[&str], legend: &[&str], grid: &str) -> String {
+    let head: Vec<_> = [name].iter().chain(headers).copied().collect();
+    let legend: Vec<_> = [BRICKS].iter().chain(legend).copied().collect();
+    format!("{}\n\n{}\n\n{grid}", head.join("\n"), legend.join("\n"))
+}
+
+/// Runs the world through each of `inputs` for their number of ticks, like the game loop does
+pub fn replay(world: &mut World, inputs: &[(u32, Controls)]) {
+    for &(ticks, controls) in inputs {
+        for _ in 0..ticks {
+            world.update(TICK, controls);
+        }
+    }
+}
+
+/// Lets at least `seconds` go by without the player doing anything,
+/// a tick more than they come to since the ticks don't add up to it exactly
+pub fn wait_for(world: &mut World, seconds: f32) {
+    replay(world, &[((seconds / TICK) as u32 + 1, Controls::default())]);
+}
+
+#[test]
+fn test_maps_are_laid_out_like_map_files() {
+    assert_eq!(test_map("Corridor", &[], &[], "\
+bbbbbbbbbbbb
+b>         b
+bbbbbbbbbbbb
+"), CORRIDOR);
+}
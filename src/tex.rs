@@ -1,7 +1,15 @@
 use image::RgbaImage;
 use pixels::Pixels;
 
-use crate::WIDTH;
+use crate::assets;
+
+mod simd;
+pub mod atlas;
+pub mod columns;
+pub mod decode;
+pub mod grade;
+
+use self::columns::Columns;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Colour {
@@ -11,7 +19,7 @@ pub struct Colour {
 }
 
 impl Colour {
-    pub fn new(r: u8, g: u8, b: u8) -> Self {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
         Colour { r, g, b }
     }
     pub fn array(self) -> [u8; 4] {
@@ -26,22 +34,91 @@ impl Colour {
     }
 }
 
+/// Rectangle of texels in a texture
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Texture {
     buffer: Box<[TColour]>,
     width: u16,
+    /// Colour of the whole texture seen from afar
+    average: Colour,
+    /// Colour of each column seen from afar, for walls too far away to show more
+    column_averages: Box<[Colour]>,
+}
+
+/// The colour texels blend into, ignoring see-through ones
+fn average<'a>(texels: impl Iterator<Item = &'a TColour>) -> Colour {
+    let (mut r, mut g, mut b, mut n) = (0u64, 0u64, 0u64, 0u64);
+    for texel in texels.filter(|t| t.a > 0) {
+        let a = texel.a as u64;
+        r += texel.r as u64 * a;
+        g += texel.g as u64 * a;
+        b += texel.b as u64 * a;
+        n += a;
+    }
+    if n == 0 {
+        return Colour::new(0, 0, 0);
+    }
+    Colour::new((r / n) as u8, (g / n) as u8, (b / n) as u8)
 }
 
 impl Texture {
+    /// Texture of rows of `width` texels, working out the averages up front
+    fn from_texels(buffer: Box<[TColour]>, width: u16) -> Self {
+        let column_averages = (0..width as usize)
+            .map(|x| average(buffer.iter().skip(x).step_by(width as usize)))
+            .collect();
+        Texture { average: average(buffer.iter()), column_averages, buffer, width }
+    }
     pub fn from_rgba(img: &RgbaImage) -> Self {
-        Texture {
-            width: img.width() as u16,
-            buffer: img.pixels().map(|p| TColour { r: p[0], g: p[1], b: p[2], a: p[3] }).collect()
-        }
+        Self::from_texels(img.pixels().map(|p| TColour { r: p[0], g: p[1], b: p[2], a: p[3] }).collect(), img.width() as u16)
+    }
+    /// Texture of a single colour
+    pub fn solid(c: TColour, width: u16, height: u16) -> Self {
+        Self::from_texels(vec![c; width as usize * height as usize].into_boxed_slice(), width)
     }
     pub fn from_file(path: &str) -> Self {
-        let img = image::open(path).unwrap().to_rgba8();
-        Self::from_rgba(&img)
+        Self::try_from_file(path).unwrap()
+    }
+    pub fn try_from_file(path: &str) -> Result<Self, String> {
+        let bytes = assets::read(path).map_err(|e| format!("could not read {path}: {e}"))?;
+        let img = image::load_from_memory(&bytes).map_err(|e| format!("could not decode {path}: {e}"))?.to_rgba8();
+        let texture = Self::from_rgba(&img);
+        decode::remember_average(path, &texture);
+        Ok(texture)
+    }
+    /// Copy of the texture where the see-through texels next to solid ones are filled with a colour
+    pub fn outlined(&self, c: Colour) -> Self {
+        let (width, height) = (self.width(), self.height());
+        let solid = |x: usize, y: usize| self.buffer[y * width + x].a == 255;
+
+        let buffer = self.buffer.iter().enumerate().map(|(i, &texel)| {
+            let (x, y) = (i % width, i / width);
+            let next_to_solid = (x > 0 && solid(x - 1, y))
+                || (x + 1 < width && solid(x + 1, y))
+                || (y > 0 && solid(x, y - 1))
+                || (y + 1 < height && solid(x, y + 1));
+
+            if texel.a == 0 && next_to_solid { c.alpha(255) } else { texel }
+        }).collect();
+
+        Self::from_texels(buffer, self.width)
+    }
+    /// The colour of the texture seen from afar, ignoring see-through texels
+    pub fn average_colour(&self) -> Colour {
+        self.average
+    }
+    /// The colour of column `u` (from 0 to 1) seen from afar, ignoring see-through texels
+    pub fn column_average(&self, u: f32) -> Colour {
+        let x = ((u.rem_euclid(1.) * self.width as f32) as usize).min(self.width() - 1);
+        self.column_averages[x]
     }
     pub fn width(&self) -> usize {
         self.width as usize
@@ -59,22 +136,105 @@ impl Texture {
 
         self.buffer[y*self.width as usize+x]
     }
-    pub fn draw_line_at(&self, frame: &mut Frame, x: u32, y: u32, u: f32, h: u32) {
-        for (y, v) in (y..y.saturating_add(h)).map(|sy| (sy, (sy as f32 - y as f32) / h as f32)) {
-            frame.draw_rgba(x, y, self.get_pixel_f(u, v));
+    /// Endless iterator going down column `u` of the texture starting at `v` and moving `dv` each step
+    ///
+    /// The position is stepped in 16.16 fixed point, so there's no float maths per texel
+    pub fn column_texels(&self, u: f32, v: f32, dv: f32) -> impl Iterator<Item=TColour> + '_ {
+        const ONE: f32 = (1 << 16) as f32;
+
+        let width = self.width();
+        let height = self.height();
+        let x = ((u.rem_euclid(1.) * width as f32) as usize).min(width - 1);
+        let column = &self.buffer[x..];
+
+        // Position in texels in 16.16 fixed point wrapping at the bottom of the texture
+        let wrap = (height as u32) << 16;
+        let mut pos = (v.rem_euclid(1.) * height as f32 * ONE) as u32 % wrap;
+        let step = (dv.rem_euclid(1.) * height as f32 * ONE) as u32 % wrap;
+
+        std::iter::repeat_with(move || {
+            let texel = column[(pos >> 16) as usize * width];
+            pos = pos.wrapping_add(step) % wrap;
+            texel
+        })
+    }
+    /// Draws column `u` of the texture stretched to `h` pixels down from `y`, which can be above the frame
+    pub fn draw_line_at<C: Columns>(&self, frame: &mut C, x: u32, y: i32, u: f32, h: u32) {
+        let dv = 1. / h as f32;
+        let skipped = y.min(0).unsigned_abs();
+        if skipped >= h {
+            return;
         }
+        let texels = self.column_texels(u, skipped as f32 * dv, dv);
+        let y = y.max(0) as u32;
+        frame.blit_column(x, y, y.saturating_add(h - skipped), texels);
     }
-    /// Draws texture at offset
-    pub fn draw_at(&self, frame: &mut Frame, x: u32, y: u32) {
-        for (i, &c) in self.buffer.iter().enumerate() {
-            let bx = i as u32 % self.width as u32;
-            let by = i as u32 / self.width as u32;
-
-            frame.draw_rgba(x+bx, y+by, c);
+    /// The whole texture as a rectangle
+    pub fn rect(&self) -> Rect {
+        Rect { x: 0, y: 0, width: self.width() as u32, height: self.height() as u32 }
+    }
+    /// Draws the texture stretched to `width` by `height` pixels with its top left corner at (`x`, `y`),
+    /// cutting off what goes past any edge of the frame
+    pub fn draw_scaled(&self, frame: &mut Frame, x: i32, y: i32, width: u32, height: u32) {
+        self.draw_region_scaled(frame, self.rect(), (x, y), (width, height), false, false);
+    }
+    /// Draws the texels of `src` at their own size with their top left corner at (`dst_x`, `dst_y`),
+    /// mirrored left to right if `flip_x` and upside down if `flip_y`
+    pub fn draw_region(&self, frame: &mut Frame, src: Rect, dst_x: i32, dst_y: i32, flip_x: bool, flip_y: bool) {
+        self.draw_region_scaled(frame, src, (dst_x, dst_y), (src.width, src.height), flip_x, flip_y);
+    }
+    /// Draws the texture stretched to `width` by `height` pixels as a nine-patch:
+    /// the corners `border` texels wide keep their size, the edges between them are only stretched along the edge
+    /// and the middle is stretched to fill the rest
+    pub fn draw_nine_patch(&self, frame: &mut Frame, x: i32, y: i32, width: u32, height: u32, border: u32) {
+        // Where each of the three columns and rows starts in the texture and the frame, and how big it is in each
+        let split = |tex_len: u32, len: u32, start: i32| {
+            let border = border.min(tex_len / 2).min(len / 2);
+            [
+                (0, border, start, border),
+                (border, tex_len - 2 * border, start + border as i32, len - 2 * border),
+                (tex_len - border, border, start + (len - border) as i32, border),
+            ]
+        };
+        let columns = split(self.width() as u32, width, x);
+        for (src_y, src_height, dst_y, dst_height) in split(self.height() as u32, height, y) {
+            for (src_x, src_width, dst_x, dst_width) in columns {
+                let src = Rect { x: src_x, y: src_y, width: src_width, height: src_height };
+                self.draw_region_scaled(frame, src, (dst_x, dst_y), (dst_width, dst_height), false, false);
+            }
+        }
+    }
+    /// Draws the texels of `src` stretched to `size` with their top left corner at `dst` and flipped like `draw_region`,
+    /// cutting off what goes past any edge of the frame
+    pub fn draw_region_scaled(&self, frame: &mut Frame, src: Rect, dst: (i32, i32), size: (u32, u32), flip_x: bool, flip_y: bool) {
+        let ((x, y), (width, height)) = (dst, size);
+        if width == 0 || height == 0 || src.width == 0 || src.height == 0 {
+            return;
+        }
+        let tex_width = self.width();
+        let skipped = x.min(0).unsigned_abs().min(width);
+        // Texel of the source for each pixel drawn, counting from the left or top of it
+        let source_x = |dx: u32| {
+            let sx = dx as usize * src.width as usize / width as usize;
+            if flip_x { src.width as usize - 1 - sx } else { sx }
+        };
+        let mut row = Vec::with_capacity((width - skipped) as usize);
+        for dy in y.min(0).unsigned_abs()..height {
+            let frame_y = (y + dy as i32) as u32;
+            if frame_y >= frame.height() {
+                break;
+            }
+            let sy = dy as usize * src.height as usize / height as usize;
+            let sy = if flip_y { src.height as usize - 1 - sy } else { sy };
+            let texels = &self.buffer[(src.y as usize + sy) * tex_width + src.x as usize..][..src.width as usize];
+            row.clear();
+            row.extend((skipped..width).map(|dx| texels[source_x(dx)]));
+            frame.blend_row(x.max(0) as u32, frame_y, &row);
         }
     }
 }
 
+#[repr(C)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct TColour {
     r: u8,
@@ -84,7 +244,7 @@ pub struct TColour {
 }
 
 impl TColour {
-    pub fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+    pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
         TColour { r, g, b, a }
     }
     pub fn array(self) -> [u8; 4] {
@@ -111,53 +271,436 @@ impl TColour {
 #[derive(Debug)]
 pub struct Frame<'a> {
     buffer: &'a mut [u8],
+    width: u32,
+    height: u32,
+    /// Pixels from the start of one row of the buffer to the next,
+    /// which is more than the width when drawing to a viewport of a bigger frame
+    stride: u32,
 }
 
 impl<'a> Frame<'a> {
-    pub fn from_pixels(pixels: &'a mut Pixels) -> Self {
-        Frame { buffer: pixels.get_frame_mut() }
+    /// `width` and `height` have to be the size the pixel buffer was created with
+    pub fn from_pixels(pixels: &'a mut Pixels, width: u32, height: u32) -> Self {
+        Self::new(pixels.get_frame_mut(), width, height)
+    }
+    /// Frame drawing to a buffer of RGBA pixels, `width` by `height`, that isn't shown anywhere
+    pub fn new(buffer: &'a mut [u8], width: u32, height: u32) -> Self {
+        assert_eq!(buffer.len(), width as usize * height as usize * 4, "buffer to be the size of the frame");
+        Frame { buffer, width, height, stride: width }
+    }
+    /// Draws with `f` in the part of the frame inside `rect`, as if that part was a whole frame of its own
+    ///
+    /// Whatever is drawn outside of the viewport is cut off.
+    pub fn with_viewport<R>(&mut self, rect: Rect, f: impl FnOnce(&mut Frame) -> R) -> R {
+        // The part of the rect that is inside the frame
+        let x = rect.x.min(self.width);
+        let y = rect.y.min(self.height);
+        let width = rect.width.min(self.width - x);
+        let height = rect.height.min(self.height - y);
+        if width == 0 || height == 0 {
+            return f(&mut Frame { buffer: &mut [], width: 0, height: 0, stride: self.stride });
+        }
+        let start = coords_to_index(x, y, self.stride) * 4;
+        let mut viewport = Frame { buffer: &mut self.buffer[start..], width, height, stride: self.stride };
+        f(&mut viewport)
+    }
+    pub fn width(&self) -> u32 {
+        self.width
     }
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+    /// Fills the frame with `src`, a frame `factor` times as wide and tall, averaging each `factor` by `factor` block of it
+    pub fn downsample(&mut self, src: &[u8], factor: u32) {
+        let src_width = self.width * factor;
+        assert_eq!(src.len(), (src_width * self.height * factor) as usize * 4, "source to be factor times the size of the frame");
+        let samples = factor * factor;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let mut sum = [0u32; 4];
+                for sy in y * factor..(y + 1) * factor {
+                    let row = coords_to_index(x * factor, sy, src_width) * 4;
+                    for texel in src[row..row + factor as usize * 4].chunks_exact(4) {
+                        for (sum, &c) in sum.iter_mut().zip(texel) {
+                            *sum += c as u32;
+                        }
+                    }
+                }
+                let i = coords_to_index(x, y, self.stride) * 4;
+                for (dst, sum) in self.buffer[i..i + 4].iter_mut().zip(sum) {
+                    *dst = ((sum + samples / 2) / samples) as u8;
+                }
+            }
+        }
+    }
+    /// Pixels outside of the frame are left alone
     pub fn draw_rgb(&mut self, x: u32, y: u32, p: Colour) {
-        let i = coords_to_index(x, y);
-        if let Some(slice) = self.buffer.get_mut(i*4..i*4+4) {
-            slice.copy_from_slice(&p.array());
+        if x < self.width && y < self.height {
+            let i = coords_to_index(x, y, self.stride) * 4;
+            self.buffer[i..i+4].copy_from_slice(&p.array());
         }
     }
+    /// Pixels outside of the frame are left alone
     pub fn draw_rgba(&mut self, x: u32, y: u32, p: TColour) {
-        let alpha = p.a;
-        if alpha != 0 {
-            if alpha == 255 {
-                self.draw_rgb(x, y, p.rgb());
+        if x < self.width && y < self.height {
+            let i = coords_to_index(x, y, self.stride) * 4;
+            blend_pixel(&mut self.buffer[i..i+4], p);
+        }
+    }
+    /// Like `draw_rgb`, but without checking that the pixel is inside the frame, for loops that already know it is
+    ///
+    /// # Safety
+    /// `x` has to be less than the width and `y` less than the height of the frame
+    #[inline]
+    pub unsafe fn draw_rgb_unchecked(&mut self, x: u32, y: u32, p: Colour) {
+        debug_assert!(x < self.width && y < self.height);
+        let i = coords_to_index(x, y, self.stride) * 4;
+        self.buffer.get_unchecked_mut(i..i+4).copy_from_slice(&p.array());
+    }
+    /// Like `draw_rgba`, but without checking that the pixel is inside the frame
+    ///
+    /// # Safety
+    /// `x` has to be less than the width and `y` less than the height of the frame
+    #[inline]
+    pub unsafe fn draw_rgba_unchecked(&mut self, x: u32, y: u32, p: TColour) {
+        debug_assert!(x < self.width && y < self.height);
+        let i = coords_to_index(x, y, self.stride) * 4;
+        blend_pixel(self.buffer.get_unchecked_mut(i..i+4), p);
+    }
+    /// Calls `f` with the bytes of rows `y0` up to (not including) `y1`,
+    /// all at once if they follow each other in the buffer and one row at a time if they don't
+    fn for_rows(&mut self, y0: u32, y1: u32, mut f: impl FnMut(&mut [u8])) {
+        let (y0, y1) = (y0.min(self.height), y1.min(self.height));
+        if y0 >= y1 {
+            return;
+        }
+        if self.stride == self.width {
+            let (start, end) = (coords_to_index(0, y0, self.stride), coords_to_index(0, y1, self.stride));
+            f(&mut self.buffer[start * 4..end * 4]);
+        } else {
+            for y in y0..y1 {
+                let start = coords_to_index(0, y, self.stride) * 4;
+                f(&mut self.buffer[start..start + self.width as usize * 4]);
+            }
+        }
+    }
+    /// Fills the whole frame with a colour
+    pub fn clear(&mut self, p: Colour) {
+        self.for_rows(0, self.height, |pixels| simd::fill(pixels, p.array()));
+    }
+    /// Makes the whole frame see-through, for when something else is drawn behind it
+    pub fn clear_transparent(&mut self) {
+        self.for_rows(0, self.height, |pixels| simd::fill(pixels, [0; 4]));
+    }
+    /// Moves every pixel of the frame `amount / 255` of the way towards a colour
+    pub fn tint(&mut self, p: Colour, amount: u8) {
+        self.tint_rows(0, self.height, p, amount);
+    }
+    /// Moves the pixels of rows `y0` up to (not including) `y1` `amount / 255` of the way towards a colour
+    pub fn tint_rows(&mut self, y0: u32, y1: u32, p: Colour, amount: u8) {
+        self.for_rows(y0, y1, |pixels| simd::blend_towards(pixels, p.array(), amount));
+    }
+    /// Moves row `y` `dx` pixels to the right (or to the left if negative),
+    /// repeating the pixel at the edge it moves away from
+    pub fn shift_row(&mut self, y: u32, dx: i32) {
+        if y >= self.height || dx == 0 {
+            return;
+        }
+        let start = coords_to_index(0, y, self.stride) * 4;
+        let row = &mut self.buffer[start..start + self.width as usize * 4];
+        let shift = dx.unsigned_abs().min(self.width - 1) as usize * 4;
+        let len = row.len();
+        let edge = if dx > 0 {
+            row.copy_within(0..len - shift, shift);
+            shift
+        } else {
+            row.copy_within(shift.., 0);
+            len - shift - 4
+        };
+        let edge_pixel: [u8; 4] = row[edge..edge + 4].try_into().unwrap();
+        let moved_away = if dx > 0 { 0..shift } else { len - shift..len };
+        for pixel in row[moved_away].chunks_exact_mut(4) {
+            pixel.copy_from_slice(&edge_pixel);
+        }
+    }
+    /// Moves the pixels of column `x` from `y0` up to (not including) `y1` `amount / 255` of the way towards a colour
+    pub fn tint_column(&mut self, x: u32, y0: u32, y1: u32, p: Colour, amount: u8) {
+        if x >= self.width {
+            return;
+        }
+        let p = p.array();
+        for y in y0..y1.min(self.height) {
+            let i = coords_to_index(x, y, self.stride) * 4;
+            simd::blend_towards(&mut self.buffer[i..i+4], p, amount);
+        }
+    }
+    /// Draws a row of pixels starting at (`x`, `y`), cutting off what goes past the right edge
+    pub fn blend_row(&mut self, x: u32, y: u32, row: &[TColour]) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let len = row.len().min((self.width - x) as usize);
+        let i = coords_to_index(x, y, self.stride) * 4;
+        simd::blend_over(&mut self.buffer[i..i + len * 4], &row[..len]);
+    }
+    /// Fills column `x` from `y0` up to (not including) `y1` with a colour
+    pub fn fill_column(&mut self, x: u32, y0: u32, y1: u32, p: Colour) {
+        if x >= self.width {
+            return;
+        }
+        let p = p.array();
+        for y in y0..y1.min(self.height) {
+            let i = coords_to_index(x, y, self.stride) * 4;
+            self.buffer[i..i+4].copy_from_slice(&p);
+        }
+    }
+    /// Draws a single pixel, which can be outside the frame
+    fn plot(&mut self, x: i32, y: i32, p: TColour) {
+        if x >= 0 && y >= 0 {
+            self.draw_rgba(x as u32, y as u32, p);
+        }
+    }
+    /// Draws a line one pixel thick from (`x0`, `y0`) to (`x1`, `y1`), both ends included
+    pub fn draw_line(&mut self, (x0, y0): (i32, i32), (x1, y1): (i32, i32), p: TColour) {
+        // Bresenham's line algorithm, stepping along whichever axis is longer
+        let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+        let (step_x, step_y) = ((x1 - x0).signum(), (y1 - y0).signum());
+        let (mut x, mut y) = (x0, y0);
+        let mut error = dx + dy;
+        loop {
+            self.plot(x, y, p);
+            if (x, y) == (x1, y1) {
+                break;
+            }
+            if 2 * error >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if 2 * error <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+    /// Draws the one pixel thick outline of a `width` by `height` rectangle with its top left corner at (`x`, `y`)
+    pub fn draw_rect(&mut self, x: i32, y: i32, width: u32, height: u32, p: TColour) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let (right, bottom) = (x + width as i32 - 1, y + height as i32 - 1);
+        self.fill_rect(x, y, width, 1, p);
+        if height > 1 {
+            self.fill_rect(x, bottom, width, 1, p);
+        }
+        if height > 2 {
+            self.fill_rect(x, y + 1, 1, height - 2, p);
+            if width > 1 {
+                self.fill_rect(right, y + 1, 1, height - 2, p);
+            }
+        }
+    }
+    /// Fills a `width` by `height` rectangle with its top left corner at (`x`, `y`), cutting off what's outside the frame
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: u32, height: u32, p: TColour) {
+        let (x0, y0) = (x.max(0), y.max(0));
+        let x1 = (x + width as i32).min(self.width as i32);
+        let y1 = (y + height as i32).min(self.height as i32);
+        if x0 >= x1 {
+            return;
+        }
+        let row = vec![p; (x1 - x0) as usize];
+        for y in y0..y1 {
+            self.blend_row(x0 as u32, y as u32, &row);
+        }
+    }
+    /// Draws the one pixel thick outline of a circle around (`cx`, `cy`)
+    pub fn draw_circle(&mut self, cx: i32, cy: i32, radius: u32, p: TColour) {
+        // Midpoint circle algorithm, going around one eighth of the circle and mirroring it
+        let (mut x, mut y) = (radius as i32, 0);
+        let mut error = 1 - x;
+        while x >= y {
+            let mut points = [(x, y), (y, x), (-y, x), (-x, y), (-x, -y), (-y, -x), (y, -x), (x, -y)].to_vec();
+            // Where the eighths meet the same pixel would be drawn twice, which shows with see-through colours
+            points.sort_unstable();
+            points.dedup();
+            for (dx, dy) in points {
+                self.plot(cx + dx, cy + dy, p);
+            }
+            y += 1;
+            if error < 0 {
+                error += 2 * y + 1;
             } else {
-                let i = coords_to_index(x, y);
-                if let Some(orig) = self.buffer.get(i*4..i*4+3) {
-                    let orig = Colour::new(orig[0], orig[1], orig[2]).alpha(255);
-
-                    self.draw_rgb(x, y, p.on(orig).rgb());
-                }
+                x -= 1;
+                error += 2 * (y - x) + 1;
             }
         }
     }
+    /// Draws the texels from the iterator in column `x` from `y0` up to (not including) `y1`
+    pub fn blit_column<I: IntoIterator<Item=TColour>>(&mut self, x: u32, y0: u32, y1: u32, texels: I) {
+        if x >= self.width {
+            return;
+        }
+        for (y, p) in (y0..y1.min(self.height)).zip(texels) {
+            // SAFETY: x was checked to be in the frame and y stops at its bottom
+            unsafe { self.draw_rgba_unchecked(x, y, p) };
+        }
+    }
+}
+
+/// Draws `p` onto the 4 bytes of a pixel in the frame buffer
+#[inline]
+fn blend_pixel(pixel: &mut [u8], p: TColour) {
+    match p.a {
+        0 => (),
+        255 => pixel.copy_from_slice(&p.array()),
+        _ => {
+            let orig = Colour::new(pixel[0], pixel[1], pixel[2]).alpha(255);
+            pixel.copy_from_slice(&p.on(orig).array());
+        }
+    }
 }
 
 pub const fn u8_frac_mul(a: u8, b: u8) -> u8 {
     ((a as u16 * b as u16) / 255) as u8
 }
 
-pub const fn index_to_coords(i: usize) -> (u32, u32) {
-    let x = (i % WIDTH as usize) as u32;
-    let y = (i / WIDTH as usize) as u32;
+pub const fn index_to_coords(i: usize, width: u32) -> (u32, u32) {
+    let x = (i % width as usize) as u32;
+    let y = (i / width as usize) as u32;
 
     (x, y)
 }
 
-pub const fn coords_to_index(x: u32, y: u32) -> usize {
-    y as usize * WIDTH as usize + x as usize
+pub const fn coords_to_index(x: u32, y: u32, width: u32) -> usize {
+    y as usize * width as usize + x as usize
 }
 
 #[test]
 fn test() {
-    let (x, y) = index_to_coords(124);
-    assert_eq!(index_to_coords(124), index_to_coords(coords_to_index(x, y)));
-    assert_eq!(124, coords_to_index(x, y));
+    let (x, y) = index_to_coords(124, 320);
+    assert_eq!(index_to_coords(124, 320), index_to_coords(coords_to_index(x, y, 320), 320));
+    assert_eq!(124, coords_to_index(x, y, 320));
+}
+
+#[test]
+fn shifting_rows_repeats_the_edge() {
+    let mut buffer: Vec<u8> = (0..8).flat_map(|i| [i, 0, 0, 255]).collect();
+    let mut frame = Frame { buffer: &mut buffer, width: 4, height: 2, stride: 4 };
+    frame.shift_row(0, 1);
+    frame.shift_row(1, -2);
+    let reds: Vec<_> = buffer.chunks_exact(4).map(|p| p[0]).collect();
+    assert_eq!(reds, [0, 0, 1, 2, 6, 7, 7, 7]);
+}
+
+#[test]
+fn pixels_past_the_edge_do_not_wrap() {
+    let mut buffer = vec![0; 4 * 2 * 2];
+    let mut frame = Frame { buffer: &mut buffer, width: 2, height: 2, stride: 2 };
+    frame.draw_rgb(2, 0, Colour::new(1, 0, 0));
+    frame.draw_rgba(0, 2, TColour::new(1, 0, 0, 255));
+    assert!(buffer.iter().all(|&b| b == 0));
+}
+
+#[test]
+fn primitives_are_clipped() {
+    const P: TColour = TColour::new(1, 0, 0, 255);
+    let mut buffer = vec![0; 4 * 4 * 3];
+    let mut frame = Frame { buffer: &mut buffer, width: 4, height: 3, stride: 4 };
+    frame.draw_line((-1, 2), (2, -1), P);
+    frame.fill_rect(3, 1, 5, 5, P);
+    let reds: Vec<_> = buffer.chunks_exact(4).map(|p| p[0]).collect();
+    assert_eq!(reds, [
+        0, 1, 0, 0,
+        1, 0, 0, 1,
+        0, 0, 0, 1,
+    ]);
+}
+
+#[test]
+fn viewports_clip_what_is_drawn() {
+    const P: TColour = TColour::new(1, 0, 0, 255);
+    let mut buffer = vec![0; 4 * 4 * 3];
+    let mut frame = Frame { buffer: &mut buffer, width: 4, height: 3, stride: 4 };
+    frame.with_viewport(Rect { x: 1, y: 1, width: 2, height: 5 }, |f| {
+        assert_eq!((f.width(), f.height()), (2, 2));
+        f.fill_rect(-1, -1, 10, 10, P);
+        f.draw_rgba(2, 0, TColour::new(2, 0, 0, 255));
+    });
+    let reds: Vec<_> = buffer.chunks_exact(4).map(|p| p[0]).collect();
+    assert_eq!(reds, [
+        0, 0, 0, 0,
+        0, 1, 1, 0,
+        0, 1, 1, 0,
+    ]);
+    // Nothing is left of viewports starting at the edges or past them
+    let mut frame = Frame { buffer: &mut buffer, width: 4, height: 3, stride: 4 };
+    let outside = [
+        Rect { x: 1, y: 3, width: 2, height: 2 },
+        Rect { x: 4, y: 2, width: 2, height: 2 },
+        Rect { x: 9, y: 1, width: 2, height: 2 },
+        Rect { x: 1, y: 7, width: 2, height: 2 },
+        Rect { x: 9, y: 7, width: 2, height: 2 },
+    ];
+    for rect in outside {
+        frame.with_viewport(rect, |f| {
+            assert_eq!(f.width() * f.height(), 0);
+            f.fill_rect(0, 0, 2, 2, P);
+        });
+    }
+}
+
+#[test]
+fn regions_are_cut_out_flipped_and_clipped() {
+    // Texels numbered 0 to 5 in rows of 3
+    let tex = Texture::from_texels((0..6).map(|i| TColour::new(i, 0, 0, 255)).collect(), 3);
+    let reds = |buffer: &[u8]| buffer.chunks_exact(4).map(|p| p[0]).collect::<Vec<_>>();
+
+    let mut buffer = vec![9; 4 * 4 * 2];
+    let mut frame = Frame { buffer: &mut buffer, width: 4, height: 2, stride: 4 };
+    tex.draw_region(&mut frame, Rect { x: 1, y: 0, width: 2, height: 2 }, 0, 0, true, false);
+    tex.draw_region(&mut frame, tex.rect(), 2, -1, false, true);
+    // Nothing to draw from or to leaves the frame alone
+    tex.draw_region_scaled(&mut frame, tex.rect(), (0, 0), (0, 2), false, false);
+    tex.draw_region_scaled(&mut frame, Rect { x: 0, y: 0, width: 0, height: 2 }, (0, 0), (4, 2), true, true);
+    assert_eq!(reds(&buffer), [2, 1, 0, 1, 5, 4, 9, 9]);
+}
+
+#[test]
+fn nine_patches_keep_their_corners() {
+    // A 3×3 texture with a different texel in each of the nine parts
+    let tex = Texture::from_texels((0..9).map(|i| TColour::new(i, 0, 0, 255)).collect(), 3);
+    let mut buffer = vec![0; 4 * 5 * 4];
+    let mut frame = Frame { buffer: &mut buffer, width: 5, height: 4, stride: 5 };
+    tex.draw_nine_patch(&mut frame, 0, 0, 5, 4, 1);
+    let reds: Vec<_> = buffer.chunks_exact(4).map(|p| p[0]).collect();
+    assert_eq!(reds, [
+        0, 1, 1, 1, 2,
+        3, 4, 4, 4, 5,
+        3, 4, 4, 4, 5,
+        6, 7, 7, 7, 8,
+    ]);
+}
+
+#[test]
+fn downsampling_averages_blocks() {
+    // 4×2 pixels down to 2×1, the left block is half black and half white
+    let mut src = vec![0; 4 * 2 * 4];
+    src[4..8].copy_from_slice(&[0xff; 4]);
+    src[4 * 4 + 4..4 * 4 + 8].copy_from_slice(&[0xff; 4]);
+    src[8..16].copy_from_slice(&[0x40; 8]);
+    src[4 * 4 + 8..4 * 4 + 16].copy_from_slice(&[0x40; 8]);
+    let mut buffer = vec![0; 2 * 4];
+    let mut frame = Frame::new(&mut buffer, 2, 1);
+    frame.downsample(&src, 2);
+    assert_eq!(buffer, [0x80, 0x80, 0x80, 0x80, 0x40, 0x40, 0x40, 0x40]);
+}
+
+#[test]
+fn column_averages_leave_out_see_through_texels() {
+    let (red, clear) = (TColour::new(0xff, 0, 0, 0xff), TColour::new(0, 0, 0xff, 0));
+    let blue = TColour::new(0, 0, 0xff, 0xff);
+    // Two columns, the left one red over see-through and the right one red over blue
+    let tex = Texture::from_texels(vec![red, red, clear, blue].into_boxed_slice(), 2);
+    assert_eq!(tex.column_average(0.25), Colour::new(0xff, 0, 0));
+    assert_eq!(tex.column_average(0.75), Colour::new(0x7f, 0, 0x7f));
+    assert_eq!(tex.average_colour(), Colour::new(0xaa, 0, 0x55));
 }
@@ -0,0 +1,103 @@
+//! Small textures packed together into one bigger texture
+//!
+//! Textures are put on shelves, rows as tall as the tallest texture on them, with the tallest textures first.
+
+use super::{Frame, Rect, TColour, Texture};
+
+const EMPTY: TColour = TColour::new(0, 0, 0, 0);
+
+#[derive(Debug, Clone)]
+pub struct Atlas {
+    texture: Texture,
+    /// Where each of the packed textures ended up, in the order they were given
+    rects: Vec<Rect>,
+}
+
+impl Atlas {
+    /// Packs the textures into an atlas `width` texels wide, or as wide as the widest texture if that's wider
+    pub fn pack(textures: &[Texture], width: u32) -> Self {
+        let width = textures.iter().map(|tex| tex.width() as u32).fold(width, u32::max);
+        let mut order: Vec<_> = (0..textures.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(textures[i].height()));
+
+        let mut rects = vec![Rect::default(); textures.len()];
+        let (mut x, mut shelf_y, mut shelf_height) = (0, 0, 0);
+        for i in order {
+            let (w, h) = (textures[i].width() as u32, textures[i].height() as u32);
+            if x + w > width {
+                shelf_y += shelf_height;
+                x = 0;
+                shelf_height = 0;
+            }
+            rects[i] = Rect { x, y: shelf_y, width: w, height: h };
+            x += w;
+            shelf_height = shelf_height.max(h);
+        }
+
+        let height = shelf_y + shelf_height;
+        let mut buffer = vec![EMPTY; width as usize * height as usize];
+        for (tex, rect) in textures.iter().zip(&rects) {
+            for (ty, row) in tex.buffer.chunks_exact(tex.width()).enumerate() {
+                let start = (rect.y as usize + ty) * width as usize + rect.x as usize;
+                buffer[start..start + row.len()].copy_from_slice(row);
+            }
+        }
+
+        Atlas { texture: Texture::from_texels(buffer.into_boxed_slice(), width as u16), rects }
+    }
+    /// The texture everything is packed into
+    #[cfg(test)]
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+    /// View of texture `i` of the ones that were packed
+    pub fn region(&self, i: usize) -> Region<'_> {
+        Region { texture: &self.texture, rect: self.rects[i] }
+    }
+}
+
+/// Part of an atlas that holds one of the packed textures
+#[derive(Debug, Clone, Copy)]
+pub struct Region<'a> {
+    texture: &'a Texture,
+    rect: Rect,
+}
+
+impl Region<'_> {
+    pub fn width(&self) -> usize {
+        self.rect.width as usize
+    }
+    pub fn height(&self) -> usize {
+        self.rect.height as usize
+    }
+    /// Texel at (`x`, `y`) of the region
+    #[cfg(test)]
+    pub fn get_pixel(&self, x: u32, y: u32) -> TColour {
+        self.texture.buffer[(self.rect.y + y) as usize * self.texture.width() + (self.rect.x + x) as usize]
+    }
+    /// Draws the region stretched like `Texture::draw_scaled`
+    pub fn draw_scaled(&self, frame: &mut Frame, x: i32, y: i32, width: u32, height: u32) {
+        self.texture.draw_region_scaled(frame, self.rect, (x, y), (width, height), false, false);
+    }
+}
+
+#[test]
+fn packing_keeps_every_texel() {
+    let colour = |i: u8| TColour::new(i, 0, 0, 0xff);
+    let textures = [
+        Texture::solid(colour(1), 3, 2),
+        Texture::solid(colour(2), 4, 5),
+        Texture::solid(colour(3), 2, 2),
+        Texture::solid(colour(4), 6, 1),
+    ];
+    let atlas = Atlas::pack(&textures, 8);
+
+    for (i, tex) in textures.iter().enumerate() {
+        let region = atlas.region(i);
+        assert_eq!((region.width(), region.height()), (tex.width(), tex.height()));
+        for (x, y) in (0..tex.width() as u32).flat_map(|x| (0..tex.height() as u32).map(move |y| (x, y))) {
+            assert_eq!(region.get_pixel(x, y), colour(i as u8 + 1));
+        }
+    }
+    assert!(atlas.texture().width() <= 8);
+}
@@ -0,0 +1,215 @@
+//! Drawing the view of the world one column at a time
+//!
+//! Columns of a frame are spread out across its rows, so drawing them touches a new row for every pixel.
+//! At some resolutions (mostly ones whose rows are a multiple of a large power of two bytes long)
+//! it's faster to draw into a buffer that keeps each column together and copy that into the frame afterwards.
+//! Which one is faster differs between machines, so `ViewBuffer` tries both and keeps the faster one.
+
+use std::time::Duration;
+
+use log::info;
+
+use super::{blend_pixel, simd, Colour, Frame, TColour, coords_to_index};
+
+/// Frames drawn with each layout before picking one
+const TRIAL_FRAMES: u32 = 16;
+/// Size of the squares the column buffer is copied to the frame in, so both stay in the cache while copying
+const BLOCK: u32 = 16;
+
+/// Something the view of the world can be drawn to
+pub trait Columns {
+    fn width(&self) -> u32;
+    fn height(&self) -> u32;
+    /// Fills everything with a colour
+    fn clear(&mut self, p: Colour);
+    /// Fills column `x` from `y0` up to (not including) `y1` with a colour
+    fn fill_column(&mut self, x: u32, y0: u32, y1: u32, p: Colour);
+    /// Draws the texels from the iterator in column `x` from `y0` up to (not including) `y1`
+    fn blit_column<I: IntoIterator<Item=TColour>>(&mut self, x: u32, y0: u32, y1: u32, texels: I);
+    /// Moves the pixels of column `x` from `y0` up to (not including) `y1` `amount / 255` of the way towards a colour
+    fn tint_column(&mut self, x: u32, y0: u32, y1: u32, p: Colour, amount: u8);
+    /// Copies all of column `from` over column `to`
+    fn copy_column(&mut self, from: u32, to: u32);
+    /// Draws a pixel without checking that it's inside
+    ///
+    /// # Safety
+    /// `x` has to be less than the width and `y` less than the height
+    unsafe fn draw_rgb_unchecked(&mut self, x: u32, y: u32, p: Colour);
+}
+
+impl Columns for Frame<'_> {
+    fn width(&self) -> u32 {
+        Frame::width(self)
+    }
+    fn height(&self) -> u32 {
+        Frame::height(self)
+    }
+    fn clear(&mut self, p: Colour) {
+        Frame::clear(self, p)
+    }
+    fn fill_column(&mut self, x: u32, y0: u32, y1: u32, p: Colour) {
+        Frame::fill_column(self, x, y0, y1, p)
+    }
+    fn blit_column<I: IntoIterator<Item=TColour>>(&mut self, x: u32, y0: u32, y1: u32, texels: I) {
+        Frame::blit_column(self, x, y0, y1, texels)
+    }
+    fn tint_column(&mut self, x: u32, y0: u32, y1: u32, p: Colour, amount: u8) {
+        Frame::tint_column(self, x, y0, y1, p, amount)
+    }
+    fn copy_column(&mut self, from: u32, to: u32) {
+        if from >= self.width || to >= self.width {
+            return;
+        }
+        for y in 0..self.height {
+            let row = coords_to_index(0, y, self.stride) * 4;
+            self.buffer.copy_within(row + from as usize * 4..row + from as usize * 4 + 4, row + to as usize * 4);
+        }
+    }
+    unsafe fn draw_rgb_unchecked(&mut self, x: u32, y: u32, p: Colour) {
+        Frame::draw_rgb_unchecked(self, x, y, p)
+    }
+}
+
+/// Pixels stored column by column
+#[derive(Debug, Default)]
+pub struct ColumnBuffer {
+    buffer: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+impl ColumnBuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        ColumnBuffer { buffer: vec![0; width as usize * height as usize * 4], width, height }
+    }
+    /// The bytes of column `x` from `y0` up to (not including) `y1`, which are cut off at the bottom
+    fn column(&mut self, x: u32, y0: u32, y1: u32) -> &mut [u8] {
+        if x >= self.width {
+            return &mut [];
+        }
+        let y1 = y1.min(self.height);
+        let y0 = y0.min(y1);
+        let start = coords_to_index(y0, x, self.height) * 4;
+        &mut self.buffer[start..start + (y1 - y0) as usize * 4]
+    }
+    /// Copies all of the pixels to the frame, which has to be the same size
+    pub fn copy_to(&self, frame: &mut Frame) {
+        debug_assert_eq!((self.width, self.height), (frame.width, frame.height));
+        for block_x in (0..self.width).step_by(BLOCK as usize) {
+            for block_y in (0..self.height).step_by(BLOCK as usize) {
+                for x in block_x..(block_x + BLOCK).min(self.width) {
+                    for y in block_y..(block_y + BLOCK).min(self.height) {
+                        let from = coords_to_index(y, x, self.height) * 4;
+                        let to = coords_to_index(x, y, frame.stride) * 4;
+                        frame.buffer[to..to + 4].copy_from_slice(&self.buffer[from..from + 4]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Columns for ColumnBuffer {
+    fn width(&self) -> u32 {
+        self.width
+    }
+    fn height(&self) -> u32 {
+        self.height
+    }
+    fn clear(&mut self, p: Colour) {
+        simd::fill(&mut self.buffer, p.array());
+    }
+    fn fill_column(&mut self, x: u32, y0: u32, y1: u32, p: Colour) {
+        simd::fill(self.column(x, y0, y1), p.array());
+    }
+    fn blit_column<I: IntoIterator<Item=TColour>>(&mut self, x: u32, y0: u32, y1: u32, texels: I) {
+        for (pixel, p) in self.column(x, y0, y1).chunks_exact_mut(4).zip(texels) {
+            blend_pixel(pixel, p);
+        }
+    }
+    fn tint_column(&mut self, x: u32, y0: u32, y1: u32, p: Colour, amount: u8) {
+        simd::blend_towards(self.column(x, y0, y1), p.array(), amount);
+    }
+    fn copy_column(&mut self, from: u32, to: u32) {
+        if from >= self.width || to >= self.width {
+            return;
+        }
+        let len = self.height as usize * 4;
+        let from = coords_to_index(0, from, self.height) * 4;
+        self.buffer.copy_within(from..from + len, coords_to_index(0, to, self.height) * 4);
+    }
+    unsafe fn draw_rgb_unchecked(&mut self, x: u32, y: u32, p: Colour) {
+        debug_assert!(x < self.width && y < self.height);
+        let i = coords_to_index(y, x, self.height) * 4;
+        self.buffer.get_unchecked_mut(i..i+4).copy_from_slice(&p.array());
+    }
+}
+
+/// Where the view of the world is drawn before it ends up in the frame,
+/// either straight into the frame or into a column buffer, whichever turned out faster
+#[derive(Debug, Default)]
+pub struct ViewBuffer {
+    columns: ColumnBuffer,
+    /// How long drawing has taken with and without the column buffer, and how many frames that was
+    trial: [(Duration, u32); 2],
+    /// Whether the column buffer is used, once it has been decided
+    use_columns: Option<bool>,
+}
+
+impl ViewBuffer {
+    /// The column buffer to draw the next frame in, `None` to draw straight into the frame
+    ///
+    /// Changing the size of the frame starts the trial over.
+    pub fn target(&mut self, width: u32, height: u32) -> Option<&mut ColumnBuffer> {
+        if (self.columns.width, self.columns.height) != (width, height) {
+            *self = ViewBuffer { columns: ColumnBuffer::new(width, height), ..ViewBuffer::default() };
+        }
+        // The trial takes turns
+        let use_columns = self.use_columns.unwrap_or(self.trial[0].1 > self.trial[1].1);
+        use_columns.then_some(&mut self.columns)
+    }
+    /// Copies what was drawn to the column buffer to the frame if it was used,
+    /// `time` is how long the frame took to draw including that
+    pub fn finish(&mut self, frame: &mut Frame, used_columns: bool, time: Duration) {
+        if used_columns {
+            self.columns.copy_to(frame);
+        }
+        if self.use_columns.is_some() {
+            return;
+        }
+        let (total, frames) = &mut self.trial[used_columns as usize];
+        *total += time;
+        *frames += 1;
+
+        let [(direct, _), (columns, frames)] = self.trial;
+        if frames >= TRIAL_FRAMES {
+            let faster = columns < direct;
+            info!("drawing the view {} at {}×{}", if faster { "through a column buffer" } else { "straight into the frame" }, frame.width, frame.height);
+            self.use_columns = Some(faster);
+        }
+    }
+}
+
+#[test]
+fn column_buffer_draws_like_a_frame() {
+    let draw = |target: &mut dyn FnMut(u32, u32, u32, Colour)| {
+        target(0, 1, 3, Colour::new(1, 0, 0));
+        target(2, 0, 9, Colour::new(2, 0, 0));
+        target(1, 2, 3, Colour::new(3, 0, 0));
+    };
+
+    let mut direct = vec![0; 3 * 3 * 4];
+    let mut frame = Frame { buffer: &mut direct, width: 3, height: 3, stride: 3 };
+    frame.clear(Colour::new(9, 0, 0));
+    draw(&mut |x, y0, y1, p| frame.fill_column(x, y0, y1, p));
+    Columns::copy_column(&mut frame, 2, 0);
+
+    let mut columns = ColumnBuffer::new(3, 3);
+    Columns::clear(&mut columns, Colour::new(9, 0, 0));
+    draw(&mut |x, y0, y1, p| columns.fill_column(x, y0, y1, p));
+    columns.copy_column(2, 0);
+    let mut copied = vec![0; 3 * 3 * 4];
+    columns.copy_to(&mut Frame { buffer: &mut copied, width: 3, height: 3, stride: 3 });
+
+    assert_eq!(direct, copied);
+}
@@ -0,0 +1,70 @@
+//! Decoding textures on another thread, with stand-ins of a single colour until they're done
+//!
+//! The stand-ins take the average colour of the texture from the last time it was decoded,
+//! so textures coming back on a reloaded map or another level look about right straight away.
+
+use std::{collections::HashMap, sync::{Mutex, OnceLock, mpsc::{self, Receiver, TryRecvError}}};
+
+use super::{Colour, Texture};
+
+/// Colour of the stand-in for a texture that hasn't been decoded before
+const UNKNOWN_COLOUR: Colour = Colour::new(0x80, 0x80, 0x80);
+
+static AVERAGES: OnceLock<Mutex<HashMap<String, Colour>>> = OnceLock::new();
+
+pub(super) fn remember_average(path: &str, texture: &Texture) {
+    let averages = AVERAGES.get_or_init(Default::default);
+    averages.lock().unwrap().insert(path.to_owned(), texture.average_colour());
+}
+/// Texture shown until the one at `path` has been decoded
+pub fn placeholder(path: &str) -> Texture {
+    let average = AVERAGES.get().and_then(|averages| averages.lock().unwrap().get(path).copied());
+    Texture::solid(average.unwrap_or(UNKNOWN_COLOUR).alpha(0xff), 1, 1)
+}
+
+/// Textures being decoded, each with a key saying where it goes
+#[derive(Debug)]
+pub struct Decoding<K> {
+    receiver: Receiver<(K, Result<Texture, String>)>,
+}
+
+impl<K: Send + 'static> Decoding<K> {
+    /// Starts decoding the textures at the paths, which is done right away in the browser since it has no threads
+    pub fn start(textures: Vec<(K, Box<str>)>) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let decode_all = move || for (key, path) in textures {
+            // The textures aren't wanted anymore if the map has been left
+            if sender.send((key, Texture::try_from_file(&path))).is_err() {
+                return;
+            }
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::spawn(decode_all);
+        #[cfg(target_arch = "wasm32")]
+        decode_all();
+
+        Decoding { receiver }
+    }
+    /// Gives `f` the textures that have been decoded since last time, or why they couldn't be,
+    /// returns whether there are more to come
+    pub fn receive(&self, mut f: impl FnMut(K, Result<Texture, String>)) -> bool {
+        loop {
+            match self.receiver.try_recv() {
+                Ok((key, texture)) => f(key, texture),
+                Err(TryRecvError::Empty) => return true,
+                Err(TryRecvError::Disconnected) => return false,
+            }
+        }
+    }
+}
+
+#[test]
+fn placeholders_remember_the_average_colour() {
+    use super::TColour;
+
+    let path = "tex/placeholder_test.png";
+    assert_eq!(placeholder(path), Texture::solid(UNKNOWN_COLOUR.alpha(0xff), 1, 1));
+    let texture = Texture::solid(TColour::new(0x20, 0x40, 0x60, 0xff), 4, 4);
+    remember_average(path, &texture);
+    assert_eq!(placeholder(path), Texture::solid(TColour::new(0x20, 0x40, 0x60, 0xff), 1, 1));
+}
@@ -0,0 +1,72 @@
+//! Colour grading of a whole frame, which gives a map its mood without new textures
+
+use super::{Colour, Frame};
+
+/// How the colours of a frame are changed, in the order saturation, contrast and tint
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColourGrade {
+    /// Each channel is multiplied by this over 255, so white leaves the colours as they are
+    pub tint: Colour,
+    /// How far colours are pushed away from the middle grey, 1 leaves them as they are
+    pub contrast: f32,
+    /// How far colours are pushed away from their grey, 0 makes everything grey and 1 leaves them as they are
+    pub saturation: f32,
+}
+
+/// A colour grade worked out for every value of each channel
+#[derive(Debug, Clone)]
+pub struct GradeLut {
+    grade: ColourGrade,
+    /// Saturation in 8.8 fixed point
+    saturation: i32,
+    channels: [[u8; 256]; 3],
+}
+
+impl GradeLut {
+    pub fn new(grade: ColourGrade) -> Self {
+        let tint = grade.tint.array();
+        let channels = [0, 1, 2].map(|channel| {
+            let mut lut = [0; 256];
+            for (value, out) in lut.iter_mut().enumerate() {
+                let contrasted = (value as f32 - 127.5) * grade.contrast + 127.5;
+                *out = (contrasted * tint[channel] as f32 / 255.).round().clamp(0., 255.) as u8;
+            }
+            lut
+        });
+        GradeLut { grade, saturation: (grade.saturation.max(0.) * 256.) as i32, channels }
+    }
+    pub fn grade(&self) -> ColourGrade {
+        self.grade
+    }
+    fn apply(&self, pixels: &mut [u8]) {
+        for pixel in pixels.chunks_exact_mut(4) {
+            let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(i32::from);
+            let grey = (77 * r + 150 * g + 29 * b) >> 8;
+            for (channel, value) in [r, g, b].into_iter().enumerate() {
+                let saturated = (grey + (((value - grey) * self.saturation) >> 8)).clamp(0, 255);
+                pixel[channel] = self.channels[channel][saturated as usize];
+            }
+        }
+    }
+}
+
+impl Frame<'_> {
+    /// Changes the colours of every pixel by a colour grade
+    pub fn grade(&mut self, lut: &GradeLut) {
+        self.for_rows(0, self.height, |pixels| lut.apply(pixels));
+    }
+}
+
+#[test]
+fn neutral_grade_changes_nothing() {
+    let lut = GradeLut::new(ColourGrade { tint: Colour::new(0xff, 0xff, 0xff), contrast: 1., saturation: 1. });
+    let mut pixels = [0x12, 0x80, 0xf0, 0xff, 0, 0xff, 0x40, 0xff];
+    let before = pixels;
+    lut.apply(&mut pixels);
+    assert_eq!(pixels, before);
+
+    // Without saturation everything is grey
+    let lut = GradeLut::new(ColourGrade { saturation: 0., ..lut.grade() });
+    lut.apply(&mut pixels);
+    assert!(pixels[0] == pixels[1] && pixels[1] == pixels[2]);
+}
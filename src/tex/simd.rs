@@ -0,0 +1,172 @@
+//! Span operations on RGBA8 pixel buffers
+//!
+//! Each operation has a scalar version and an AVX2 version that is picked at runtime when the CPU supports it.
+//! All of them give exactly the same results.
+
+use super::TColour;
+
+/// Fills every pixel of `buf` with `p`
+pub fn fill(buf: &mut [u8], p: [u8; 4]) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // SAFETY: we just checked that AVX2 is available
+        unsafe { avx2::fill(buf, p) };
+        return;
+    }
+    scalar::fill(buf, p)
+}
+
+/// Moves every pixel of `buf` `amount / 255` of the way towards `p`
+pub fn blend_towards(buf: &mut [u8], p: [u8; 4], amount: u8) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // SAFETY: we just checked that AVX2 is available
+        unsafe { avx2::blend_towards(buf, p, amount) };
+        return;
+    }
+    scalar::blend_towards(buf, p, amount)
+}
+
+/// Draws the pixels of `src` over those in `buf` according to their alpha
+pub fn blend_over(buf: &mut [u8], src: &[TColour]) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // SAFETY: we just checked that AVX2 is available
+        unsafe { avx2::blend_over(buf, src) };
+        return;
+    }
+    scalar::blend_over(buf, src)
+}
+
+#[inline(always)]
+fn texel_bytes(src: &[TColour]) -> &[u8] {
+    // SAFETY: `TColour` is `repr(C)` and consists of four `u8`s
+    unsafe { std::slice::from_raw_parts(src.as_ptr() as *const u8, src.len() * 4) }
+}
+
+mod scalar {
+    use super::{TColour, texel_bytes};
+
+    #[inline(always)]
+    const fn mix(a: u8, b: u8, b_amount: u8) -> u8 {
+        ((a as u16 * (255 - b_amount) as u16 + b as u16 * b_amount as u16) / 255) as u8
+    }
+
+    pub fn fill(buf: &mut [u8], p: [u8; 4]) {
+        for pixel in buf.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&p);
+        }
+    }
+
+    pub fn blend_towards(buf: &mut [u8], p: [u8; 4], amount: u8) {
+        for pixel in buf.chunks_exact_mut(4) {
+            for (c, p) in pixel.iter_mut().zip(p) {
+                *c = mix(*c, p, amount);
+            }
+        }
+    }
+
+    pub fn blend_over(buf: &mut [u8], src: &[TColour]) {
+        for (pixel, s) in buf.chunks_exact_mut(4).zip(texel_bytes(src).chunks_exact(4)) {
+            let a = s[3];
+            for (c, &s) in pixel[..3].iter_mut().zip(s) {
+                *c = mix(*c, s, a);
+            }
+            pixel[3] = 0xff;
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    use super::{TColour, texel_bytes};
+
+    /// Divides each 16-bit lane by 255, exact for values up to 255 * 255
+    #[inline(always)]
+    unsafe fn div255(x: __m256i) -> __m256i {
+        let x = _mm256_add_epi16(x, _mm256_add_epi16(_mm256_set1_epi16(1), _mm256_srli_epi16(x, 8)));
+        _mm256_srli_epi16(x, 8)
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn fill(buf: &mut [u8], p: [u8; 4]) {
+        let v = _mm256_set1_epi32(i32::from_le_bytes(p));
+
+        let mut chunks = buf.chunks_exact_mut(32);
+        for chunk in &mut chunks {
+            _mm256_storeu_si256(chunk.as_mut_ptr() as *mut __m256i, v);
+        }
+        super::scalar::fill(chunks.into_remainder(), p);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn blend_towards(buf: &mut [u8], p: [u8; 4], amount: u8) {
+        // The products can go above `i16::MAX`, but the lanes are treated as unsigned
+        let [r, g, b, a] = p.map(|c| (c as u16 * amount as u16) as i16);
+        let target = _mm256_setr_epi16(r, g, b, a, r, g, b, a, r, g, b, a, r, g, b, a);
+        let keep = _mm256_set1_epi16(255 - amount as i16);
+        let zero = _mm256_setzero_si256();
+
+        let mut chunks = buf.chunks_exact_mut(32);
+        for chunk in &mut chunks {
+            let v = _mm256_loadu_si256(chunk.as_ptr() as *const __m256i);
+            let lo = _mm256_unpacklo_epi8(v, zero);
+            let hi = _mm256_unpackhi_epi8(v, zero);
+            let lo = div255(_mm256_add_epi16(_mm256_mullo_epi16(lo, keep), target));
+            let hi = div255(_mm256_add_epi16(_mm256_mullo_epi16(hi, keep), target));
+            _mm256_storeu_si256(chunk.as_mut_ptr() as *mut __m256i, _mm256_packus_epi16(lo, hi));
+        }
+        super::scalar::blend_towards(chunks.into_remainder(), p, amount);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn blend_over(buf: &mut [u8], src: &[TColour]) {
+        let len = buf.len().min(src.len() * 4);
+        let (buf, buf_rest) = buf[..len].split_at_mut(len - len % 32);
+        let (src, src_rest) = src.split_at(buf.len() / 4);
+
+        let zero = _mm256_setzero_si256();
+        let max = _mm256_set1_epi16(255);
+        let opaque = _mm256_set1_epi32(0xff00_0000_u32 as i32);
+
+        for (chunk, s) in buf.chunks_exact_mut(32).zip(texel_bytes(src).chunks_exact(32)) {
+            let d = _mm256_loadu_si256(chunk.as_ptr() as *const __m256i);
+            let s = _mm256_loadu_si256(s.as_ptr() as *const __m256i);
+
+            let mut out = [zero; 2];
+            for (out, (d, s)) in out.iter_mut().zip([
+                (_mm256_unpacklo_epi8(d, zero), _mm256_unpacklo_epi8(s, zero)),
+                (_mm256_unpackhi_epi8(d, zero), _mm256_unpackhi_epi8(s, zero)),
+            ]) {
+                // Spread the alpha of each pixel over all four of its lanes
+                let a = _mm256_shufflehi_epi16(_mm256_shufflelo_epi16(s, 0xff), 0xff);
+                let inv_a = _mm256_sub_epi16(max, a);
+                *out = div255(_mm256_add_epi16(_mm256_mullo_epi16(s, a), _mm256_mullo_epi16(d, inv_a)));
+            }
+
+            let v = _mm256_or_si256(_mm256_packus_epi16(out[0], out[1]), opaque);
+            _mm256_storeu_si256(chunk.as_mut_ptr() as *mut __m256i, v);
+        }
+        super::scalar::blend_over(buf_rest, src_rest);
+    }
+}
+
+#[test]
+fn simd_matches_scalar() {
+    let src: Vec<_> = (0..37u8).map(|i| TColour::new(i.wrapping_mul(97), i.wrapping_mul(13), 255 - i, i.wrapping_mul(71))).collect();
+    let start: Vec<_> = (0..37 * 4).map(|i| (i as u8).wrapping_mul(31) | 3).collect();
+
+    let mut scalar = start.clone();
+    scalar::blend_over(&mut scalar, &src);
+    let mut simd = start.clone();
+    blend_over(&mut simd, &src);
+    assert_eq!(scalar, simd);
+
+    let mut scalar = start.clone();
+    scalar::blend_towards(&mut scalar, [10, 200, 30, 255], 177);
+    let mut simd = start;
+    blend_towards(&mut simd, [10, 200, 30, 255], 177);
+    assert_eq!(scalar, simd);
+}
@@ -0,0 +1,42 @@
+//! `std::time::Instant` panics in the browser, so there the time comes from `performance.now()` instead
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::Instant;
+
+#[cfg(target_arch = "wasm32")]
+pub use self::web::Instant;
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use std::{ops::{Add, Sub}, time::Duration};
+
+    /// Milliseconds since the page was loaded
+    #[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+    pub struct Instant(f64);
+
+    impl Instant {
+        pub fn now() -> Self {
+            let performance = web_sys::window()
+                .and_then(|w| w.performance())
+                .expect("performance.now() to be available");
+            Instant(performance.now())
+        }
+        pub fn elapsed(&self) -> Duration {
+            Self::now() - *self
+        }
+    }
+
+    impl Sub for Instant {
+        type Output = Duration;
+        fn sub(self, rhs: Self) -> Duration {
+            Duration::from_secs_f64((self.0 - rhs.0).max(0.) / 1000.)
+        }
+    }
+
+    impl Add<Duration> for Instant {
+        type Output = Instant;
+        fn add(self, rhs: Duration) -> Instant {
+            Instant(self.0 + rhs.as_secs_f64() * 1000.)
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Screen shown when the game starts, which plays demos in turn when it's left alone
+
+use log::{info, warn};
+
+use crate::{assets, font, lang::tr, render::Renderer, settings::Settings, tex::{Colour, Frame}, world::{World, TICK, demo::{Demo, Playback}}};
+
+const BACKGROUND: Colour = Colour::new(0x10, 0x10, 0x10);
+const TITLE_COLOUR: Colour = Colour::new(0xff, 0xff, 0x60);
+const TEXT_COLOUR: Colour = Colour::new(0xe0, 0xe0, 0xe0);
+/// How much the demo is darkened behind the text
+const DIM: u8 = 0x60;
+/// Seconds without anything being pressed before a demo is played
+const IDLE_TIME: f32 = 8.;
+
+pub struct Title {
+    /// Paths of the demos to play
+    demos: Vec<String>,
+    /// Index of the demo to play next
+    next: usize,
+    /// Seconds since something was pressed or a demo ended
+    idle: f32,
+    /// The demo being played and the world it's played in
+    attract: Option<(Box<World>, Playback)>,
+    /// Time not played of the demo yet
+    leftover: f32,
+}
+
+impl Title {
+    pub fn new(settings: &Settings) -> Self {
+        Title { demos: settings.demos.clone(), next: 0, idle: 0., attract: None, leftover: 0. }
+    }
+    /// Something was pressed, which stops the demo if one is playing.
+    /// Returns whether the game should start, which it does when pressed on the title itself
+    pub fn press(&mut self) -> bool {
+        self.idle = 0.;
+        self.attract.take().is_none()
+    }
+    pub fn tick(&mut self, delta: f32, settings: &Settings) {
+        let Some((world, playback)) = &mut self.attract else {
+            self.idle += delta;
+            if self.idle >= IDLE_TIME {
+                self.idle = 0.;
+                self.attract = self.next_demo(settings);
+            }
+            return;
+        };
+        self.leftover += delta;
+        while self.leftover >= TICK {
+            self.leftover -= TICK;
+            if !playback.tick(world) || world.exited.is_some() {
+                self.attract = None;
+                self.leftover = 0.;
+                return;
+            }
+        }
+        // Nobody hears the demo, so what happened in it is thrown away
+        world.events.clear();
+    }
+    /// Loads the next demo that can be played, if any can
+    fn next_demo(&mut self, settings: &Settings) -> Option<(Box<World>, Playback)> {
+        for _ in 0..self.demos.len() {
+            let path = &self.demos[self.next];
+            self.next = (self.next + 1) % self.demos.len();
+            let demo = assets::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|src| Demo::parse(&src));
+            match demo.and_then(|demo| Ok((World::from_map_file(&demo.map, settings)?, demo))) {
+                Ok((world, demo)) => {
+                    info!("playing demo {path}");
+                    return Some((Box::new(world), Playback::new(demo)));
+                }
+                Err(e) => warn!("could not play demo {path}: {e}"),
+            }
+        }
+        None
+    }
+    pub fn draw(&self, frame: &mut Frame, renderer: &mut dyn Renderer) {
+        let lines = match &self.attract {
+            Some((world, _)) => {
+                renderer.render(world, &world.camera_at((self.leftover / TICK).min(1.)), frame);
+                frame.tint(BACKGROUND, DIM);
+                [(tr("title.demo"), TITLE_COLOUR), (tr("title.press_any_key"), TEXT_COLOUR)]
+            }
+            None => {
+                frame.clear(BACKGROUND);
+                [(tr("title.name"), TITLE_COLOUR), (tr("title.press_any_key"), TEXT_COLOUR)]
+            }
+        };
+        let top = (frame.height() / 2).saturating_sub(lines.len() as u32 * font::LINE_HEIGHT / 2);
+        for (i, (line, colour)) in lines.iter().enumerate() {
+            let x = (frame.width() / 2).saturating_sub(font::text_width(line) / 2);
+            font::draw_text(frame, x, top + i as u32 * font::LINE_HEIGHT, line, *colour);
+        }
+    }
+}
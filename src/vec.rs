@@ -99,7 +99,7 @@ impl Div<f32> for Vector2 {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Point2 {
     pub x: f32,
     pub y: f32,
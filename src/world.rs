@@ -1,60 +1,553 @@
-use std::f32::consts;
+use std::{f32::consts, mem, path::{Path, PathBuf}};
 
-use log::info;
+use log::{error, info};
 
-use crate::{map::Map, tex::{Texture, Colour, Frame}, vec::{Point2, Vector2}, fov::Fov, WIDTH, HEIGHT, FOV};
+use crate::{map::{Map, LoadedMap, Exit, RenderHit, TexSlot, HAZARD_INTERVAL}, tex::{Texture, Colour, TColour, Frame, columns::Columns, decode::Decoding}, vec::{Point2, Vector2}, fov::Fov, profile::{Stage, Timer}, settings::Settings, palette::Palette, sound::{Sound, SoundEvent}};
 
 pub mod thing;
+pub mod grid;
+pub mod effects;
+pub mod shake;
+pub mod screen;
+mod combat;
+mod elevator;
+pub mod talk;
+mod objectives;
+mod challenge;
+pub mod projectile;
+mod shooting;
+pub mod boss;
+pub mod enemy;
+pub mod spawner;
+pub mod survival;
+pub mod faction;
+pub mod viewmodel;
+pub mod particles;
+pub mod events;
+pub mod pickups;
+mod lives;
+mod cutscene;
+mod reprojection;
+pub mod daylight;
+pub mod doors;
+mod triggers;
+mod automap;
+mod stealth;
+pub mod systems;
+pub mod demo;
+#[cfg(test)]
+mod replay;
 
 use self::thing::*;
+use self::grid::ThingGrid;
+use self::effects::{StatusEffects, Effect};
+use self::shake::Shake;
+use self::elevator::Ride;
+use self::talk::Talk;
+use self::survival::Survival;
+use self::viewmodel::Viewmodel;
+use self::particles::Particles;
+use self::projectile::Projectiles;
+use self::pickups::Pickup;
+use self::lives::Checkpoint;
+use self::cutscene::Playing;
+use self::daylight::DayNight;
+use self::doors::MovingDoor;
+use self::triggers::Signals;
+pub use self::combat::{Kill, DamageKind};
+pub use self::events::GameEvent;
+pub use self::reprojection::Reprojection;
+
+pub const PLAYER_WIDTH: f32 = 0.25;
+const PLAYER_HEALTH: f32 = 100.;
+/// Lives at the start of a game
+const START_LIVES: u32 = 3;
+/// Length of a simulation step in seconds
+pub const TICK: f32 = 1. / 60.;
+/// Distance walked between footsteps
+const STRIDE: f32 = 0.8;
+/// Speed below which sliding comes to a stop
+const STOP_SPEED: f32 = 0.05;
+/// How much of the walking speed is left sneaking
+const SNEAK_SPEED: f32 = 0.5;
+/// How much of the distance footsteps are heard from is left sneaking
+const SNEAK_NOISE: f32 = 0.3;
+const GUN_ZOOM_FOV: f32 = 35.;
+/// Seconds it takes to zoom all the way in or out
+const ZOOM_TIME: f32 = 0.2;
+/// How many times across a wrapping map can be seen at most
+const WRAPPED_VIEW_LAPS: f32 = 2.;
+/// Projected height in pixels below which walls are drawn in the average colour of their column instead of textured
+const DISTANT_WALL_HEIGHT: f32 = 2.;
+
+
+/// How the world goes on to another map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    /// Keeps the armor, score and lives
+    NextLevel,
+    /// Keeps the lives, less the ones the score gave during the level, and the score from the start of the level
+    Restart,
+    /// Keeps nothing but the player's settings
+    NewGame,
+}
 
 /// Representation of the application state. In this example, a box will bounce around the screen.
 pub struct World {
     player_p: Point2,
     player_angle: f32,
+    /// Where the player was before the last update, for drawing between updates
+    prev_player_p: Point2,
+    prev_player_angle: f32,
+    /// How fast the player is moving in units per second, which only lingers on slippery surfaces
+    player_vel: Vector2,
+    pub player_health: f32,
+    /// Armor points, which take part of most damage instead of health until they run out
+    pub player_armor: f32,
+    pub player_effects: StatusEffects,
     things: Vec<Thing>,
+    /// Particles on the current layer, the ones left behind on other layers are gone by the time they'd be seen
+    particles: Particles,
+    /// Projectiles on the current layer, which are gone like the particles when the player leaves it
+    projectiles: Projectiles,
+    /// Things on each layer of the map, except for the current layer whose things are in `things`
+    layer_things: Vec<Vec<Thing>>,
+    thing_grid: ThingGrid,
     thing_texes: Vec<Texture>,
+    player_tex: usize,
+    particle_tex: usize,
+    projectile_tex: usize,
+    /// How bright the light flash of an explosion currently is, from 0 to 1
+    flash: f32,
+    shake: Shake,
+    ride: Option<Ride>,
+    /// Doors on their way open or closed
+    doors: Vec<MovingDoor>,
+    signals: Signals,
+    /// Which cells of each layer the player has seen, row by row
+    seen: Vec<Vec<bool>>,
+    /// Conversation the player is in, which pauses everything else
+    talk: Option<Talk>,
+    /// The cutscene being played, during which the world stands still
+    cutscene: Option<Playing>,
+    /// Whether a boss has seen the player yet, which plays the `boss` cutscene
+    boss_revealed: bool,
     pub map: Map,
+    /// Time of day, if the map has one
+    day_night: Option<DayNight>,
+    /// Wall textures of the map still being decoded in the background
+    decoding: Option<Decoding<TexSlot>>,
     pub fov: Fov,
-    pub gun: Texture,
+    pub gun: Viewmodel,
+    /// FOV in degrees when fully zoomed in with the gun
+    pub gun_zoom: f32,
+    /// How far zoomed in the view is, from 0 to 1
+    zoom: f32,
     pub clip: bool,
+    /// Player can't take damage
+    pub god: bool,
+    pub ammo: u32,
+    /// Seconds since the level started
+    pub time: f32,
+    /// Seconds the level has been played for, which stands still during cutscenes and dialogue
+    pub clock: f32,
+    /// Degrees turned per second with the keyboard
+    pub turn_speed: f32,
+    /// Degrees turned per pixel the mouse moves
+    pub mouse_sensitivity: f32,
+    /// Render distance used instead of the map's
+    pub render_distance: Option<f32>,
+    pub palette: Palette,
+    /// Things are drawn with outlines
+    high_contrast: bool,
+    /// Flashes are only shown around the edges of the screen
+    pub reduce_flashing: bool,
+    /// Set once the player has reached an exit of the level
+    pub exited: Option<Exit>,
+    /// Things destroyed on this level
+    pub kills: u32,
+    /// Points from treasure, kept from level to level
+    pub score: u32,
+    /// Score when the level started, which is what starting it over goes back to
+    score_at_start: u32,
+    pub lives: u32,
+    /// Where the player comes back after dying, the start until a checkpoint is reached
+    checkpoint: Checkpoint,
+    /// Seconds the player has been dead
+    dead_time: f32,
+    /// Set once the player has died with no lives left
+    pub game_over: bool,
+    /// Treasure picked up on this level and how much of it there is on the whole map
+    treasure_found: u32,
+    treasure_total: u32,
+    /// Who hurt the player last, they get the kill if the player dies
+    player_last_attacker: Option<EntityId>,
+    /// Id the next thing added to the world gets
+    next_id: u32,
+    /// Distance walked since the last footstep
+    stride: f32,
+    /// What has happened since the events were last taken, which happens after every update
+    pub events: Vec<GameEvent>,
+    /// Sounds made since enemies last listened, kept apart from the events which are taken after every update
+    unheard: Vec<SoundEvent>,
+    /// The player is standing in liquid
+    wading: bool,
+    /// Stamina left for sprinting, from 0 to 1
+    stamina: f32,
+    sprinting: bool,
+    /// How much noise the player has made lately, from 0 to 1
+    noise: f32,
+    /// Seconds until a hazardous floor hurts the player again
+    hazard_cooldown: f32,
+    /// Whether each objective of the map has been completed
+    objectives_done: Vec<bool>,
+    /// Seconds picked up on top of the time limit of the map
+    bonus_time: f32,
+    /// Set once the time limit of the map has run out
+    pub out_of_time: bool,
+    /// File the map was loaded from, so the level can be restarted
+    map_path: PathBuf,
+    /// The waves of survival mode, if playing it
+    pub survival: Option<Survival>,
+}
+
+/// Where the world is drawn from
+#[derive(Debug, Copy, Clone)]
+pub struct Camera {
+    pub pos: Point2,
+    pub angle: f32,
+    /// How far above normal eye height the view is, in wall heights
+    pub height: f32,
+    /// How far it is from the last update to the next one, moving things are drawn that far in between
+    pub alpha: f32,
+}
+
+/// What the player is doing during an update
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Controls {
+    pub turn_left: bool,
+    pub turn_right: bool,
+    pub forwards: bool,
+    pub backwards: bool,
+    pub strafe_left: bool,
+    pub strafe_right: bool,
+    /// How many pixels the mouse has moved to the right
+    pub mouse_dx: f32,
+    pub zoom: bool,
+    pub sprint: bool,
+    /// Walking slower and quieter, unless sprinting
+    pub sneak: bool,
 }
 
 impl World {
     /// Create a new `World` instance that can draw a moving box.
-    pub fn new() -> Self {
-        let (map, x, y, s, things, mut thing_texes) = Map::from_file("map.txt");
+    pub fn new(settings: &Settings) -> Self {
+        Self::from_map_file("map.txt", settings).unwrap()
+    }
+    /// Loads the map at `path`, with the problem with it as an error if it can't be
+    pub fn from_map_file<P: AsRef<Path>>(path: P, settings: &Settings) -> Result<Self, String> {
+        Self::from_map_file_with_progress(path, settings, |_| ())
+    }
+    /// Like `from_map_file`, telling how much of the map (from 0 to 1) has been loaded as it goes
+    pub fn from_map_file_with_progress<P: AsRef<Path>>(path: P, settings: &Settings, progress: impl FnMut(f32)) -> Result<Self, String> {
+        let map_path = path.as_ref().to_owned();
+        Ok(Self::from_loaded_map(Map::from_file_with_progress(path, settings.background_textures, progress)?, map_path, settings))
+    }
+    /// World of a map that isn't in a file, for tests
+    #[cfg(test)]
+    pub(crate) fn from_map_src(src: &str) -> Self {
+        Self::from_loaded_map(Map::from_src(src).unwrap(), PathBuf::new(), &Settings::default())
+    }
+    /// Sets up the world for a map that was loaded from `map_path`
+    fn from_loaded_map(loaded: LoadedMap, map_path: PathBuf, settings: &Settings) -> Self {
+        let (mut map, x, y, s, mut layer_things, mut thing_texes) = loaded;
+        let pending_textures = map.take_pending_textures();
+        let day_night = map.day_night;
+        let signals = Signals::new(&map);
+        let decoding = (!pending_textures.is_empty()).then(|| Decoding::start(pending_textures));
+        let things = mem::take(&mut layer_things[map.layer()]);
         info!("Map name: {}", map.name);
 
+        let player_tex = thing_texes.len();
         thing_texes.push(Texture::from_file("tex/player.png"));
+        let particle_tex = thing_texes.len();
+        thing_texes.push(Texture::solid(TColour::new(0xff, 0xa0, 0x20, 0xff), 1, 1));
+        let projectile_tex = thing_texes.len();
+        thing_texes.push(Texture::solid(TColour::new(0xc0, 0x40, 0xff, 0xff), 1, 1));
+
+        let mut thing_grid = ThingGrid::new(map.width(), map.height());
+        thing_grid.rebuild(&things);
 
-        Self {
+        let mut world = Self {
             map,
             things,
+            particles: Particles::default(),
+            projectiles: Projectiles::default(),
+            layer_things,
+            thing_grid,
+            player_tex,
+            particle_tex,
+            projectile_tex,
+            flash: 0.,
+            shake: Shake::default(),
+            ride: None,
+            doors: Vec::new(),
+            signals,
+            seen: Vec::new(),
+            talk: None,
+            cutscene: None,
+            boss_revealed: false,
             thing_texes,
+            day_night,
+            decoding,
             player_p: Point2::new(x as f32 + 0.5, y as f32 + 0.5),
             player_angle: s.into_unit_vector().direction_angle(),
-            fov: Fov::new_from_degrees(FOV),
+            prev_player_p: Point2::new(x as f32 + 0.5, y as f32 + 0.5),
+            prev_player_angle: s.into_unit_vector().direction_angle(),
+            player_vel: Vector2::new(0., 0.),
+            player_health: PLAYER_HEALTH,
+            player_armor: 0.,
+            player_effects: StatusEffects::default(),
+            fov: Fov::new_from_degrees(settings.fov, settings.width, settings.height),
             clip: true,
-            gun: Texture::from_file("tex/gun.png"),
+            god: false,
+            ammo: 0,
+            time: 0.,
+            clock: 0.,
+            gun: Viewmodel::new(Texture::from_file("tex/gun.png")),
+            gun_zoom: GUN_ZOOM_FOV,
+            zoom: 0.,
+            turn_speed: settings.turn_speed,
+            mouse_sensitivity: settings.mouse_sensitivity,
+            render_distance: settings.render_distance,
+            palette: settings.palette(),
+            high_contrast: false,
+            reduce_flashing: settings.reduce_flashing,
+            exited: None,
+            kills: 0,
+            score: 0,
+            score_at_start: 0,
+            lives: START_LIVES,
+            checkpoint: Checkpoint { cell: (x, y), angle: s.into_unit_vector().direction_angle(), layer: 0 },
+            dead_time: 0.,
+            game_over: false,
+            treasure_found: 0,
+            treasure_total: 0,
+            player_last_attacker: None,
+            next_id: 1,
+            stride: 0.,
+            events: Vec::new(),
+            unheard: Vec::new(),
+            wading: false,
+            stamina: 1.,
+            sprinting: false,
+            noise: 0.,
+            hazard_cooldown: 0.,
+            objectives_done: Vec::new(),
+            bonus_time: 0.,
+            out_of_time: false,
+            map_path,
+            survival: None,
+        };
+        world.number_things();
+        world.checkpoint.layer = world.map.layer();
+        world.treasure_total = world.things.iter().chain(world.layer_things.iter().flatten())
+            .filter(|thing| matches!(thing.pickup, Some(Pickup::Treasure(_))))
+            .count() as u32;
+        world.objectives_done = vec![false; world.map.objectives().len()];
+        world.seen = vec![vec![false; (world.map.width() * world.map.height()) as usize]; world.map.layers()];
+        world.play_cutscene("intro");
+        if settings.high_contrast {
+            world.outline_things();
+        }
+        world
+    }
+    /// Gives every thing of the map, on every layer, its own id
+    fn number_things(&mut self) {
+        let mut next_id = self.next_id;
+        for thing in self.things.iter_mut().chain(self.layer_things.iter_mut().flatten()) {
+            thing.id = EntityId(next_id);
+            next_id += 1;
+        }
+        self.next_id = next_id;
+    }
+    /// Adds a thing to the current layer with an id of its own
+    fn add_thing(&mut self, mut thing: Thing) {
+        thing.id = EntityId(self.next_id);
+        self.next_id += 1;
+        self.things.push(thing);
+    }
+    /// What to call the player or a thing in the kill feed, `None` if it's gone or has no name
+    fn name_of(&self, id: EntityId) -> Option<String> {
+        if id == EntityId::PLAYER {
+            return Some("player".to_owned());
+        }
+        let thing = self.things.iter().chain(self.layer_things.iter().flatten()).find(|thing| thing.id == id)?;
+        self.map.thing_name(thing.tex()).map(str::to_owned)
+    }
+    /// Gives all things outlines in the colour of the palette
+    fn outline_things(&mut self) {
+        if !self.high_contrast {
+            self.high_contrast = true;
+            for tex in &mut self.thing_texes {
+                *tex = tex.outlined(self.palette.outline);
+            }
         }
     }
 
-    /// Update the `World` internal state; bounce the box around the screen.
-    pub fn update(&mut self, delta: f32, left: bool, right: bool, forwards: bool, backwards: bool, go_left: bool, go_right: bool) {
-        const TURN_SPEED: f32 = 105.  /* degrees */ / 180. * consts::PI;
-        const WALK_SPEED: f32 = 2.3;
+    /// Replaces the map, keeping the player's settings, or keeps the current one if the new one can't be loaded
+    pub fn load_map<P: AsRef<Path>>(&mut self, path: P, settings: &Settings) -> Result<(), String> {
+        self.replace_with(Self::from_map_file(path, settings)?);
+        Ok(())
+    }
+    /// Replaces the world with a freshly loaded one, keeping the player's settings
+    fn replace_with(&mut self, fresh: World) {
+        let high_contrast = self.high_contrast;
+        *self = World {
+            fov: self.fov,
+            clip: self.clip,
+            god: self.god,
+            turn_speed: self.turn_speed,
+            mouse_sensitivity: self.mouse_sensitivity,
+            render_distance: self.render_distance,
+            palette: self.palette,
+            reduce_flashing: self.reduce_flashing,
+            ..fresh
+        };
+        if high_contrast {
+            self.outline_things();
+        }
+    }
+    /// Path of the map file the world was loaded from
+    pub fn map_path(&self) -> &Path {
+        &self.map_path
+    }
+    /// Goes on to a world that was loaded for the transition
+    pub fn switch_to(&mut self, fresh: World, transition: Transition) {
+        let (armor, score, score_at_start, lives) = (self.player_armor, self.score, self.score_at_start, self.lives);
+        self.replace_with(fresh);
+        match transition {
+            Transition::NextLevel => {
+                self.player_armor = armor;
+                self.score = score;
+                self.score_at_start = score;
+                self.lives = lives;
+            }
+            Transition::Restart => {
+                self.score = score_at_start;
+                self.score_at_start = score_at_start;
+                // Otherwise the same treasure could be picked up again and again for lives
+                let extra_lives = score / pickups::EXTRA_LIFE_SCORE - score_at_start / pickups::EXTRA_LIFE_SCORE;
+                self.lives = lives.saturating_sub(extra_lives);
+            }
+            Transition::NewGame => (),
+        }
+    }
+    pub fn teleport(&mut self, p: Point2) {
+        self.player_p = p;
+        self.prev_player_p = p;
+        self.player_vel = Vector2::new(0., 0.);
+    }
+
+    /// Moves the player to the same place on another layer of the map
+    pub fn change_layer(&mut self, layer: usize) {
+        let old = self.map.layer();
+        self.map.set_layer(layer);
+        self.layer_things[old] = mem::take(&mut self.things);
+        self.things = mem::take(&mut self.layer_things[layer]);
+        self.particles.clear();
+        self.projectiles.clear();
+        // Doors on the way open or closed stop where they are, they're on the old layer
+        self.doors.clear();
+        self.thing_grid.rebuild(&self.things);
+        info!("went to layer {layer}");
+    }
 
-        if left || right {
-            self.player_angle += delta * TURN_SPEED * (right as i8 - left as i8) as f32;
+    /// Starts an update by moving time along and remembering where everything was
+    fn begin_update(&mut self, delta: f32) {
+        self.time += delta;
+        self.map.set_time(self.time);
+        self.tick_day(delta);
+        self.tick_ride(delta);
+        self.tick_doors(delta);
+
+        self.prev_player_p = self.player_p;
+        self.prev_player_angle = self.player_angle;
+        for thing in &mut self.things {
+            thing.remember_pos();
+        }
+        self.particles.remember_pos();
+        self.projectiles.remember_pos();
+    }
+    /// Swaps in the wall textures that have been decoded since the last tick
+    fn receive_textures(&mut self) {
+        let Some(decoding) = &self.decoding else { return };
+        let (map, events) = (&mut self.map, &mut self.events);
+        let more = decoding.receive(|slot, texture| match texture {
+            Ok(texture) => map.set_texture(slot, texture),
+            // The stand-in is kept
+            Err(e) => {
+                error!("{e}");
+                events.push(GameEvent::TextureFailed(e));
+            }
+        });
+        if !more {
+            self.decoding = None;
+        }
+    }
+
+    /// Zooms and turns the view
+    fn update_view(&mut self, delta: f32, controls: Controls) {
+        let Controls { turn_left: left, turn_right: right, mouse_dx, zoom, .. } = controls;
+
+        let zoom_step = delta / ZOOM_TIME;
+        self.zoom = if zoom { (self.zoom + zoom_step).min(1.) } else { (self.zoom - zoom_step).max(0.) };
+
+        if left || right || mouse_dx != 0. {
+            // The mouse turns slower when zoomed in so aiming feels the same
+            let mouse_scale = self.view_fov().tan_half_fov / self.fov.tan_half_fov;
+            let turn = delta * self.turn_speed * (right as i8 - left as i8) as f32 + self.mouse_sensitivity * mouse_scale * mouse_dx;
+            self.player_angle += turn.to_radians();
             self.player_angle %= consts::TAU;
         }
+    }
 
-        if (forwards ^ backwards) || (go_left ^ go_right) {
+    /// Moves the player
+    fn update_player(&mut self, delta: f32, controls: Controls) {
+        const WALK_SPEED: f32 = 2.3;
+        const SPRINT_SPEED: f32 = 1.6;
+        const FAST_SPEED: f32 = 1.5;
+        let Controls { forwards, backwards, strafe_left: go_left, strafe_right: go_right, sprint, sneak, .. } = controls;
+
+        let walking = !self.riding() && self.talk.is_none() && ((forwards ^ backwards) || (go_left ^ go_right));
+        self.update_stamina(delta, walking && forwards && !backwards && sprint);
+        let surface = self.map.surface_at(self.player_p);
+        let target_vel = if walking {
             let dv = Vector2::unit_from_angle(self.player_angle);
-            let dp = dv * (forwards as i8 - backwards as i8) as f32 + dv.hat() * (go_right as i8 - go_left as i8) as f32;
-            let dp = dp.set_len(delta * WALK_SPEED);
+            let dir = dv * (forwards as i8 - backwards as i8) as f32 + dv.hat() * (go_right as i8 - go_left as i8) as f32;
+            let mut speed = WALK_SPEED * surface.speed();
+            if self.sprinting {
+                speed *= SPRINT_SPEED;
+            } else if sneak {
+                speed *= SNEAK_SPEED;
+            }
+            if self.player_effects.has(Effect::Fast) {
+                speed *= FAST_SPEED;
+            }
+            dir.set_len(speed)
+        } else {
+            Vector2::new(0., 0.)
+        };
+        // How much of the way to the walking speed the player gets this update, which is all of it unless it's slippery
+        let grip = 1. - (-surface.traction() * delta).exp();
+        self.player_vel = self.player_vel + grip * (target_vel - self.player_vel);
+        if !walking && self.player_vel.norm() < STOP_SPEED {
+            self.player_vel = Vector2::new(0., 0.);
+        }
 
+        let mut walked = 0.;
+        if self.player_vel != Vector2::new(0., 0.) {
+            let dp = delta * self.player_vel;
             let orig_p = self.player_p;
 
             self.player_p = self.player_p + dp;
@@ -62,83 +555,421 @@ impl World {
             if self.clip {
                 self.player_p = self.player_p - self.map.move_ray_cast(orig_p, dp);
             }
-        }
-    }
+            // Sliding into a wall stops
+            self.player_vel = (1. / delta) * (self.player_p - orig_p);
+
+            // Stairs only do something when walked onto, so arriving on the other end doesn't go straight back
+            let cell = |p: Point2| (p.x.floor(), p.y.floor());
+            if cell(self.player_p) != cell(orig_p) {
+                let destination = self.map.stairs_at(self.player_p).and_then(|s| self.map.stairs_destination(s));
+                if let Some(layer) = destination {
+                    self.change_layer(layer);
+                }
+            }
 
-    /// Draw the `World` state to the frame buffer.
-    ///
-    /// Assumes the default texture format: `wgpu::TextureFormat::Rgba8UnormSrgb`
-    pub fn draw(&self, mut frame: Frame) {
-        let ref player_thing = Thing::new(self.player_p, 0.25, self.thing_texes.len()-1);
-        let mut things = Vec::with_capacity(self.things.len()+1);
+            let wading = self.map.surface_at(self.player_p).is_liquid();
+            if wading && !self.wading {
+                self.emit_sound(SoundEvent::new(Sound::Splash, self.player_p));
+            }
+            self.wading = wading;
 
-        let dir = Vector2::unit_from_angle(self.player_angle);
+            // Walking faster takes steps faster, walking into a wall doesn't take any
+            walked = (self.player_p - orig_p).norm();
+            self.stride += walked;
+        }
+        self.gun.tick(delta, walked, self.player_vel.norm() / WALK_SPEED);
+        if walking {
+            if self.stride >= STRIDE {
+                self.stride %= STRIDE;
+                let sound = Sound::Footstep(self.map.surface_at(self.player_p));
+                let mut step = SoundEvent::new(sound, self.player_p);
+                if sneak && !self.sprinting {
+                    step.radius *= SNEAK_NOISE;
+                }
+                self.emit_sound(step);
+            }
+        } else {
+            // The first step comes right away when starting to walk again
+            self.stride = STRIDE;
+        }
+        self.wrap_player();
 
-        // Unit vector pointing to the right
-        let right_dir = dir.hat();
-        const HALF_WIDTH: f32 = (WIDTH / 2) as f32;
-        let first_ray = dir / self.fov.tan_half_fov - dir.hat();
+        // Exit walls only have to be touched
+        const EXIT_REACH: f32 = 0.05;
+        if self.exited.is_none() && self.player_health > 0. {
+            self.exited = self.map.exit_near(self.player_p, PLAYER_WIDTH + EXIT_REACH);
+        }
+        self.pick_up_things();
+        self.look_around();
+    }
+    /// Walking off the edge of a wrapping map comes back on the other side without a jump in between
+    fn wrap_player(&mut self) {
+        let wrapped = self.map.wrap_point(self.player_p);
+        self.prev_player_p = self.prev_player_p + (wrapped - self.player_p);
+        self.player_p = wrapped;
+    }
+    /// Lets what the player is standing in or on and the effects they're under affect them
+    fn update_surroundings(&mut self, delta: f32) {
+        if !self.riding() {
+            if let Some(conveyor) = self.map.conveyor_at(self.player_p) {
+                let dp = delta * conveyor.speed * conveyor.dir.into_unit_vector();
+                let orig_p = self.player_p;
+                self.player_p = self.player_p + dp;
+                if self.clip {
+                    self.player_p = self.player_p - self.map.move_ray_cast(orig_p, dp);
+                }
+                self.wrap_player();
+            }
+        }
 
-        for (x, ray) in (0..WIDTH).map(|x| (x, first_ray + right_dir * (x as f32 / HALF_WIDTH))) {
-            let lines = self.map.render_ray_cast(self.player_p, ray);
-            let line_len = lines.len();
-            let mut i = 0;
+        // Stepping onto a hazard hurts right away and then every interval while staying on it
+        self.hazard_cooldown = (self.hazard_cooldown - delta).max(0.);
+        match self.map.hazard_at(self.player_p) {
+            Some(damage) if self.hazard_cooldown <= 0. && self.player_health > 0. => {
+                self.damage_player(damage, DamageKind::Environment, None);
+                self.emit_sound(SoundEvent::new(Sound::Sizzle, self.player_p));
+                self.hazard_cooldown = HAZARD_INTERVAL;
+            }
+            Some(_) => (),
+            None => self.hazard_cooldown = 0.,
+        }
+        if let Some((damage, seconds)) = self.map.poison_at(self.player_p).filter(|_| self.player_health > 0.) {
+            self.player_effects.poison(seconds, damage);
+        }
+        self.tick_time_limit();
 
-            let fisheye_correction_factor = ray.dot(dir) / ray.norm();
+        let poison = self.player_effects.tick(delta);
+        self.damage_player(poison, DamageKind::Poison, None);
+    }
 
-            for (dark, u, for_things, dist, mat) in lines.into_iter().rev() {
-                // Calculate height of line to draw on screen
-                let line_height = self.fov.height_coefficient / dist / fisheye_correction_factor;
-                let line_height = if line_height.is_infinite() { i32::MAX } else { line_height as i32 };
+    /// Sprints if `sprint` and there's stamina for it, which comes back while not sprinting
+    fn update_stamina(&mut self, delta: f32, sprint: bool) {
+        /// Seconds of sprinting with full stamina
+        const SPRINT_TIME: f32 = 4.;
+        /// Seconds it takes to get all stamina back
+        const RECOVERY_TIME: f32 = 6.;
+        /// Stamina needed to start sprinting again, so running out doesn't stutter between sprinting and not
+        const START_SPRINT: f32 = 0.25;
+
+        let can_sprint = if self.sprinting { self.stamina > 0. } else { self.stamina >= START_SPRINT };
+        self.sprinting = sprint && can_sprint;
+        self.stamina = if self.sprinting {
+            (self.stamina - delta / SPRINT_TIME).max(0.)
+        } else {
+            (self.stamina + delta / RECOVERY_TIME).min(1.)
+        };
+    }
+    /// How much stamina is left for sprinting, from 0 to 1
+    pub fn stamina(&self) -> f32 {
+        self.stamina
+    }
 
-                // doing the halving for each term eliminates overflow and looks smoother
-                const HALF_HEIGHT: i32 = HEIGHT as i32 / 2;
-                let half_line_height = line_height / 2;
+    /// Moves things along and pushes them apart
+    fn update_physics(&mut self, delta: f32) {
+        for thing in &mut self.things {
+            if thing.vel != Vector2::new(0., 0.) {
+                thing.move_by(delta * thing.vel, &self.map);
+            }
+            // Only things that can be pushed around are carried by conveyors
+            if let Some(conveyor) = self.map.conveyor_at(thing.pos).filter(|_| thing.dynamic) {
+                thing.move_by(delta * conveyor.speed * conveyor.dir.into_unit_vector(), &self.map);
+            }
+            let damage = thing.effects.tick(delta);
+            if let Some(health) = &mut thing.health {
+                *health -= damage;
+            }
+        }
+        self.particles.update(delta, &self.map);
+        self.projectiles.update(delta, &self.map);
+        separate_things(&mut self.things, &self.thing_grid, &self.map);
+        self.thing_grid.rebuild(&self.things);
+        self.crush(delta);
+    }
 
-                let mat_top = HALF_HEIGHT - half_line_height;
-                let mat_bot = HALF_HEIGHT + half_line_height;
+    /// Fades out the flashes and shaking of explosions
+    fn animate(&mut self, delta: f32) {
+        const FLASH_FADE_SPEED: f32 = 4.;
+        self.flash = (self.flash - delta * FLASH_FADE_SPEED).max(0.);
+        self.shake.tick(delta);
+    }
 
-                for y in 0..HEIGHT as i32 {
-                    let below_ceiling = mat_top <= y;
-                    let over_ground = y <= mat_bot;
+    /// Draws what's in front of the eyes of the player on top of the view: being underwater, flashes and the gun
+    ///
+    /// Assumes the default texture format: `wgpu::TextureFormat::Rgba8UnormSrgb`
+    pub fn draw_overlays(&self, frame: &mut Frame, camera: &Camera) {
+        let height = frame.height();
+        let time = self.time - (1. - camera.alpha) * TICK;
+        if self.wading {
+            // The bottom third of the view is below the surface
+            screen::submerged(frame, height - height / 3, time);
+        }
 
-                    let c = match (over_ground, below_ceiling) {
-                        (true, false) => Colour::new(0x00, 0x00, 0xff).alpha(0xff),
-                        (false, true) => Colour::new(0xff, 0x00, 0x00).alpha(0xff),
-                        _ => {
-                            let tex = self.map.get_tex(mat, dark);
-                            let v = (y - mat_top) as f32 / (mat_bot - mat_top) as f32;
+        if let Some(day_night) = self.day_night {
+            // Darkness isn't a flash, so it covers all of the view even when flashing is reduced
+            frame.tint(Colour::new(0, 0, 0), ((1. - day_night.light()) * 255.) as u8);
+        }
+        screen::flash(frame, combat::EXPLOSION_FLASH_COLOUR, (self.flash * 160.) as u8, self.reduce_flashing);
+        for (colour, strength) in self.player_effects.tints() {
+            screen::flash(frame, colour, strength, self.reduce_flashing);
+        }
 
-                            tex.get_pixel_f(u, v)
-                        }
-                    };
+        let _hud_timer = Timer::start(Stage::Hud);
+        if self.cutscene.is_none() {
+            self.gun.draw(frame);
+        }
+    }
+    /// Draws the walls, floors, ceilings (if `walls`) and things seen by the camera, one column at a time
+    ///
+    /// One ray is cast for every `strip` columns, drawn in the first of them and copied across the rest.
+    /// Reprojection only works with strips of 1.
+    pub fn draw_view<C: Columns>(&self, frame: &mut C, camera: &Camera, walls: bool, strip: u32, reprojection: Option<&mut Reprojection>) {
+        let (camera, alpha) = (*camera, camera.alpha);
+        // Particles and projectiles come last so indices from the thing grid still point at the right things
+        let things: Vec<_> = self.things.iter().map(|t| t.interpolated(alpha))
+            .chain(self.particles.things(alpha))
+            .chain(self.projectiles.things(alpha, self.projectile_tex))
+            // On a wrapping map things are drawn where they're nearest, which may be across an edge
+            .map(|mut t| {
+                t.pos = self.map.nearest_image(t.pos, camera.pos);
+                t
+            })
+            .collect();
+        let time = self.time - (1. - alpha) * TICK;
+        let player_thing = Thing::new(self.player_p_at(alpha), PLAYER_WIDTH, self.player_tex);
+        let mut reflected_things = Vec::with_capacity(things.len()+1);
+
+        let dir = Vector2::unit_from_angle(camera.angle);
+
+        let width = frame.width();
+        // The frame can be bigger than the window when supersampling
+        let fov = self.view_fov().for_size(width, frame.height());
+        let max_dist = self.view_distance();
+
+        let raycast_timer = Timer::start(Stage::Raycast);
+        let strip = strip.max(1);
+        let columns: Vec<_> = match reprojection.filter(|_| strip == 1) {
+            Some(reprojection) => {
+                let reprojected = reprojection.reproject(camera.pos, dir, &fov, width);
+                let columns: Vec<_> = (0..width).zip(reprojected)
+                    .map(|(x, hit)| {
+                        let ray = fov.column_ray(dir, x);
+                        (ray, hit.map_or_else(|| self.map.render_ray_cast(camera.pos, ray, max_dist), |hit| vec![hit]))
+                    })
+                    .collect();
+                reprojection.remember(camera.pos, width, columns.iter().map(|(_, hits)| &hits[..]));
+                columns
+            }
+            // The ray through the middle of the strip
+            None => (0..width).step_by(strip as usize)
+                .map(|x| fov.column_ray(dir, (x + strip / 2).min(width - 1)))
+                .map(|ray| (ray, self.map.render_ray_cast(camera.pos, ray, max_dist)))
+                .collect(),
+        };
+        raycast_timer.stop();
+
+        // Things can't be seen directly any further away than the rays go before being reflected
+        let max_view_dist = columns.iter()
+            .flat_map(|(_, hits)| hits.iter().filter(|hit| !hit.reflected).map(|hit| hit.dist))
+            .fold(0., f32::max);
+        let visible_things = self.visible_things(&things, camera, max_view_dist);
+
+        let height_factor = 0.5 * fov.height_coefficient;
+
+        // Columns where the rays only go into the void aren't drawn over
+        if walls {
+            frame.clear(self.map.fog_colour);
+        }
 
-                    frame.draw_rgba(x, y as u32, c);
+        for (x0, (ray, hits)) in (0..width).step_by(strip as usize).zip(&columns) {
+            let strip_end = (x0 + strip).min(width);
+            // Without the walls, what's under the things (like walls from the GPU) differs between the columns of the strip
+            let drawn = if walls { x0..x0 + 1 } else { x0..strip_end };
+            for hit in hits.iter().rev() {
+                if walls {
+                    self.draw_hit(frame, x0, hit, *ray, camera, time);
                 }
 
-                let (p, dist, last_dist) = for_things;
-
-                let height_factor = 0.5 * self.fov.height_coefficient;
-                things.clear();
-                i += 1;
-                if i != line_len {
-                    things.push(player_thing);
-                };
-                for thing in &self.things {
-                    let dist = (thing.pos - p).norm();
-                    let i = things.binary_search_by(|t| (t.pos - p).norm().total_cmp(&dist).reverse()).unwrap_or_else(|e| e);
-                    things.insert(i, thing);
+                let _sprites_timer = Timer::start(Stage::Sprites);
+                if hit.reflected {
+                    // Reflections can show anything (including the player), so these have to be found and sorted per segment
+                    let p = hit.seg_start;
+                    reflected_things.clear();
+                    reflected_things.push(&player_thing);
+                    for thing in self.thing_grid.query_segment(p, hit.seg).map(|i| &things[i]) {
+                        let dist = (thing.pos - p).norm();
+                        let i = reflected_things.binary_search_by(|t| (t.pos - p).norm().total_cmp(&dist).reverse()).unwrap_or_else(|e| e);
+                        reflected_things.insert(i, thing);
+                    }
+
+                    let ray = SpriteRay { start: p, seg: hit.seg, dist_before: hit.seg_dist, height_factor, eye_height: camera.height };
+                    for x in drawn.clone() {
+                        for thing in &reflected_things {
+                            thing.draw_x(frame, x, &self.thing_texes, &ray);
+                        }
+                    }
+                } else {
+                    let ray = SpriteRay { start: hit.seg_start, seg: hit.seg, dist_before: hit.seg_dist, height_factor, eye_height: camera.height };
+                    for x in drawn.clone() {
+                        for &i in &visible_things {
+                            things[i].draw_x(frame, x, &self.thing_texes, &ray);
+                        }
+                    }
                 }
+            }
+            for x in drawn.end..strip_end {
+                frame.copy_column(x0, x);
+            }
+        }
+    }
 
-                for thing in &things {
-                    thing.draw_x(&mut frame, x, &self.thing_texes, last_dist, p, dist, height_factor);
-                }
+    /// Draws the ceiling, the wall (or fog) and the floor that `hit` shows in column `x`
+    fn draw_hit<C: Columns>(&self, frame: &mut C, x: u32, hit: &RenderHit, ray: Vector2, camera: Camera, time: f32) {
+        let _walls_timer = Timer::start(Stage::Walls);
+        let height = frame.height();
+        let fov = self.view_fov().for_size(frame.width(), height);
+        // The ray is one unit long along the view, so this turns distances along it into depths
+        let fisheye_correction_factor = 1. / ray.norm();
+        // Calculate height of line to draw on screen
+        let line_height = fov.projected_height(hit.dist * fisheye_correction_factor);
+
+        // The floor is half a wall height below the eyes and the ceiling half above,
+        // converting to integers saturates so walls right in front of the camera don't overflow
+        let half_height = (height / 2) as f32;
+        let mat_top = (half_height - (0.5 - camera.height) * line_height) as i32;
+        let mat_bot = (half_height + (0.5 + camera.height) * line_height) as i32;
+        // Moving blocks that aren't all the way down only cover the top of the column with their bottom part
+        let extent = hit.mat.map_or(1., |mat| self.map.extent(mat, time));
+        let block_bot = (mat_top as f32 + extent * (mat_bot as f32 - mat_top as f32)) as i32;
+
+        let wall_top = mat_top.clamp(0, height as i32) as u32;
+        let wall_bot = block_bot.saturating_add(1).clamp(0, height as i32) as u32;
+
+        frame.fill_column(x, 0, wall_top, self.sky_colour());
+        if let Some(mat) = hit.mat {
+            let tex = self.map.get_tex(mat, hit.side, self.time, hit.side.cell_of_hit(hit.seg_start + hit.seg));
+            if line_height < DISTANT_WALL_HEIGHT {
+                // Sampling would only pick a texel or two at random, which shimmers as the view moves
+                frame.fill_column(x, wall_top, wall_bot, tex.column_average(hit.u));
+            } else {
+                let dv = 1. / (mat_bot as f32 - mat_top as f32);
+                let v = 1. - extent + (wall_top as i32 - mat_top) as f32 * dv;
+                frame.blit_column(x, wall_top, wall_bot, tex.column_texels(hit.u, v, dv));
             }
+            // Everything seen in the mirror has been drawn by now and is inside its span
+            if let Some((colour, amount)) = self.map.reflection_tint(mat) {
+                frame.tint_column(x, wall_top, wall_bot, colour, amount);
+            }
+        } else {
+            frame.fill_column(x, wall_top, wall_bot, self.map.fog_colour);
+        }
+        if extent >= 1. {
+            self.draw_floor(frame, x, wall_bot, ray, camera, time);
+        }
+    }
+
+    /// Fills column `x` from row `y0` down with the floor seen along `ray`, drawing the conveyors `time` seconds in
+    fn draw_floor<C: Columns>(&self, frame: &mut C, x: u32, y0: u32, ray: Vector2, camera: Camera, time: f32) {
+        let height = frame.height();
+        frame.fill_column(x, y0, height, self.palette.floor);
+        let Some(tex) = self.map.conveyor_tex() else { return };
+        if x >= frame.width() {
+            return;
+        }
+
+        let half_height = (height / 2) as f32;
+        let fov = self.view_fov().for_size(frame.width(), height);
+        for y in y0..height {
+            // Depth of the floor seen through the middle of the pixel, which is half a wall height (and how high the eyes are) below the view
+            let depth = (0.5 + camera.height) * fov.height_coefficient / (y as f32 + 0.5 - half_height);
+            if depth <= 0. {
+                continue;
+            }
+            let p = camera.pos + depth * ray;
+            let Some(conveyor) = self.map.conveyor_at(p) else { continue };
+
+            // The texture points north, so it's turned to point the way the conveyor goes and scrolled along it
+            let dir = conveyor.dir.into_unit_vector();
+            let in_cell = Vector2::new(p.x.fract() - 0.5, p.y.fract() - 0.5);
+            let u = 0.5 + in_cell.dot(dir.hat());
+            let v = 0.5 - in_cell.dot(dir) + conveyor.speed * time;
+            // SAFETY: x was checked to be in the frame and y only goes down to its bottom
+            unsafe { frame.draw_rgb_unchecked(x, y, tex.get_pixel_f(u, v).rgb()) };
+        }
+    }
+
+    /// The player's view with the screen shake on top
+    pub fn camera(&self) -> Camera {
+        self.camera_at(1.)
+    }
+    /// The view `alpha` of the way from the player's last position to the current one
+    pub fn camera_at(&self, alpha: f32) -> Camera {
+        if let Some(camera) = self.cutscene_camera(alpha) {
+            return camera;
+        }
+        // Turning the short way around when the angle wraps
+        let mut turned = (self.player_angle - self.prev_player_angle).rem_euclid(consts::TAU);
+        if turned > consts::PI {
+            turned -= consts::TAU;
         }
+        let (push, turn) = self.shake.offset();
+        Camera {
+            pos: self.player_p_at(alpha) + push,
+            angle: self.prev_player_angle + alpha * turned + turn,
+            height: self.ride.map_or(0., |ride| ride.height_at(alpha)),
+            alpha,
+        }
+    }
+    fn player_p_at(&self, alpha: f32) -> Point2 {
+        self.prev_player_p + alpha * (self.player_p - self.prev_player_p)
+    }
+
+    /// Everything in the world other than the player
+    pub fn things(&self) -> &[Thing] {
+        &self.things
+    }
+    pub fn projectiles(&self) -> &Projectiles {
+        &self.projectiles
+    }
+    /// How far rays are cast before the fog colour is drawn instead
+    pub fn view_distance(&self) -> f32 {
+        let mut distance = self.render_distance.or(self.map.render_distance).unwrap_or(f32::INFINITY);
+        if let Some(day_night) = self.day_night {
+            distance = distance.min(day_night.fog_distance());
+        }
+        if self.map.wrap {
+            // Rays could go around a wrapping map forever
+            distance.min(WRAPPED_VIEW_LAPS * self.map.width().max(self.map.height()) as f32)
+        } else {
+            distance
+        }
+    }
+    /// The FOV the world is seen with right now, which is narrower while zooming in
+    pub fn view_fov(&self) -> Fov {
+        self.fov.zoomed(self.gun_zoom, self.zoom)
+    }
+
+    /// Indices of the things inside the view frustum no further away than `max_dist`, sorted from furthest to nearest
+    fn visible_things(&self, things: &[Thing], camera: Camera, max_dist: f32) -> Vec<usize> {
+        let dir = Vector2::unit_from_angle(camera.angle);
+        let tan_half_fov = self.view_fov().tan_half_fov;
+        // How much further out to the side than the frustum edge a circle's centre can be while still reaching into it
+        let edge_margin = (1. + tan_half_fov * tan_half_fov).sqrt();
+
+        let mut visible: Vec<_> = things.iter().enumerate().filter_map(|(i, thing)| {
+            // Position of the thing in camera space
+            let to_thing = thing.pos - camera.pos;
+            let depth = to_thing.dot(dir);
+            let lateral = to_thing.dot(dir.hat());
+
+            let w = thing.width();
+            let in_front = depth > -w && depth - w <= max_dist;
+            let in_frustum = lateral.abs() - w * edge_margin <= depth * tan_half_fov;
+
+            (in_front && in_frustum).then_some((i, depth))
+        }).collect();
 
-        let gun_x = (WIDTH - self.gun.width() as u32) / 2;
-        let gun_y = HEIGHT - self.gun.height() as u32;
-        self.gun.draw_at(&mut frame, gun_x, gun_y);
+        visible.sort_unstable_by(|a, b| b.1.total_cmp(&a.1));
+        visible.into_iter().map(|(i, _)| i).collect()
     }
 }
 
@@ -0,0 +1,54 @@
+//! The cells of the map the player has seen, which is all of the map the tactical view shows
+
+use crate::vec::Point2;
+
+use super::World;
+
+/// Cells further away from the player than this many cells aren't seen
+const SEEN_DISTANCE: i32 = 8;
+
+impl World {
+    /// Marks the cells the player can see from where they are as seen
+    pub(super) fn look_around(&mut self) {
+        let (px, py) = (self.player_p.x.floor() as i32, self.player_p.y.floor() as i32);
+        let (width, height) = (self.map.width(), self.map.height());
+        let seen = &mut self.seen[self.map.layer()];
+        for y in (py - SEEN_DISTANCE).max(0)..(py + SEEN_DISTANCE + 1).min(height) {
+            for x in (px - SEEN_DISTANCE).max(0)..(px + SEEN_DISTANCE + 1).min(width) {
+                let i = (y * width + x) as usize;
+                if seen[i] {
+                    continue;
+                }
+                // A wall is seen when it's the first thing in the way
+                let centre = Point2::new(x as f32 + 0.5, y as f32 + 0.5);
+                seen[i] = self.map.first_solid_cell(self.player_p, centre).is_none_or(|cell| cell == (x, y));
+            }
+        }
+    }
+    /// Whether the player has seen a cell of the current layer
+    pub fn seen(&self, x: i32, y: i32) -> bool {
+        let width = self.map.width();
+        (0..width).contains(&x) && (0..self.map.height()).contains(&y) && self.seen[self.map.layer()][(y * width + x) as usize]
+    }
+    /// Lets the player see every cell of every layer
+    pub fn reveal_map(&mut self) {
+        for layer in &mut self.seen {
+            layer.fill(true);
+        }
+    }
+}
+
+#[test]
+fn only_what_is_in_sight_is_seen_until_revealed() {
+    let mut world = World::from_map_src(&crate::testing::test_map("Automap", &[], &[], "\
+bbbbbbb
+b> b  b
+bbbbbbb
+"));
+    world.look_around();
+    assert!(world.seen(2, 1) && world.seen(3, 1));
+    // Behind the wall in the middle
+    assert!(!world.seen(4, 1) && !world.seen(6, 1));
+    world.reveal_map();
+    assert!(world.seen(4, 1) && world.seen(6, 1));
+}
@@ -0,0 +1,166 @@
+//! Bosses, big things that attack the player differently as they lose health
+
+use std::f32::consts;
+
+use crate::vec::Vector2;
+
+use super::{World, DamageKind, PLAYER_WIDTH};
+
+/// How fast a boss walks towards the player between attacks
+const WALK_SPEED: f32 = 0.8;
+/// Bosses stop walking this close to the player
+const KEEP_DISTANCE: f32 = 2.5;
+const PROJECTILE_SPEED: f32 = 4.;
+const PROJECTILE_DAMAGE: f32 = 10.;
+/// Angle in radians between the projectiles of a spread
+const SPREAD: f32 = 0.25;
+/// Projectiles fired all around at the end of a charge
+const RING_PROJECTILES: usize = 8;
+const CHARGE_SPEED: f32 = 5.;
+const CHARGE_TIME: f32 = 0.6;
+const CHARGE_DAMAGE: f32 = 25.;
+/// How many things a boss can summon in total
+const MAX_SUMMONS: u32 = 6;
+
+/// What a boss does, which changes as it gets hurt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Fires at the player
+    Ranged,
+    /// Fires spreads of projectiles and summons help every third attack
+    Summon,
+    /// Charges into the player and fires all around after each charge
+    Charge,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Boss {
+    max_health: f32,
+    /// Index of the thing of the map it summons
+    pub summon: Option<usize>,
+    /// Seconds until the next attack
+    cooldown: f32,
+    attacks: u32,
+    summoned: u32,
+    /// Seconds left of the current charge
+    charging: f32,
+    /// Whether the current charge has already hit the player
+    charge_hit: bool,
+}
+
+impl Boss {
+    pub fn new(max_health: f32) -> Self {
+        Boss { max_health, summon: None, cooldown: 1., attacks: 0, summoned: 0, charging: 0., charge_hit: false }
+    }
+    /// How much health is left, from 0 to 1
+    pub fn health_left(&self, health: f32) -> f32 {
+        (health / self.max_health).clamp(0., 1.)
+    }
+    pub fn phase(&self, health: f32) -> Phase {
+        match self.health_left(health) {
+            h if h > 2. / 3. => Phase::Ranged,
+            h if h > 1. / 3. => Phase::Summon,
+            _ => Phase::Charge,
+        }
+    }
+}
+
+impl World {
+    /// Lets every boss that can see the player walk towards them and attack
+    pub(super) fn update_bosses(&mut self, delta: f32) {
+        let player_p = self.player_p;
+        let player_alive = self.player_health > 0.;
+        // Where projectiles are fired from, at what angle and by whom
+        let mut shots = Vec::new();
+        let mut summons = Vec::new();
+        // Bosses that charged into the player
+        let mut chargers = Vec::new();
+        let mut revealed = false;
+
+        for thing in &mut self.things {
+            let (width, shooter) = (thing.width(), thing.attacker());
+            let Some(boss) = &mut thing.boss else { continue };
+            let to_player = player_p - thing.pos;
+            let stop = Vector2::new(0., 0.);
+
+            if boss.charging > 0. {
+                boss.charging -= delta;
+                if !boss.charge_hit && player_alive && to_player.norm() <= width + PLAYER_WIDTH {
+                    boss.charge_hit = true;
+                    chargers.push(shooter);
+                }
+                if boss.charging <= 0. {
+                    thing.vel = stop;
+                    shots.extend((0..RING_PROJECTILES).map(|i| (thing.pos, i as f32 / RING_PROJECTILES as f32 * consts::TAU, shooter)));
+                }
+                continue;
+            }
+            if !player_alive || !self.map.line_of_sight(thing.pos, player_p) {
+                thing.vel = stop;
+                continue;
+            }
+            revealed = true;
+            thing.vel = if to_player.norm() > KEEP_DISTANCE { to_player.set_len(WALK_SPEED) } else { stop };
+
+            boss.cooldown -= delta;
+            if boss.cooldown > 0. {
+                continue;
+            }
+            boss.attacks += 1;
+            let aim = to_player.direction_angle();
+            match boss.phase(thing.health.unwrap_or(0.)) {
+                Phase::Ranged => {
+                    shots.push((thing.pos, aim, shooter));
+                    boss.cooldown = 1.2;
+                }
+                Phase::Summon => {
+                    match boss.summon {
+                        Some(summon) if boss.attacks % 3 == 0 && boss.summoned < MAX_SUMMONS => {
+                            boss.summoned += 1;
+                            summons.push((summon, thing.pos, to_player.set_len(width)));
+                        }
+                        _ => shots.extend([-SPREAD, 0., SPREAD].map(|offset| (thing.pos, aim + offset, shooter))),
+                    }
+                    boss.cooldown = 1.5;
+                }
+                Phase::Charge => {
+                    boss.charging = CHARGE_TIME;
+                    boss.charge_hit = false;
+                    thing.vel = to_player.set_len(CHARGE_SPEED);
+                    boss.cooldown = 1.;
+                }
+            }
+        }
+
+        if revealed && !self.boss_revealed {
+            self.boss_revealed = true;
+            self.play_cutscene("boss");
+        }
+        for charger in chargers {
+            self.damage_player(CHARGE_DAMAGE, DamageKind::Melee, Some(charger));
+        }
+        for (p, angle, shooter) in shots {
+            self.fire(p, PROJECTILE_SPEED * Vector2::unit_from_angle(angle), PROJECTILE_DAMAGE, shooter);
+        }
+        for (summon, p, towards_player) in summons {
+            let mut thing = self.map.summon(summon);
+            thing.place(p);
+            // Summoned things appear in front of the boss unless there's a wall
+            thing.move_by(towards_player + towards_player.set_len(thing.width()), &self.map);
+            thing.remember_pos();
+            self.add_thing(thing);
+        }
+    }
+    /// How much health the first boss on the layer has left from 0 to 1, if there is a boss
+    pub fn boss_health(&self) -> Option<f32> {
+        self.things.iter().find_map(|thing| Some(thing.boss?.health_left(thing.health?)))
+    }
+}
+
+#[test]
+fn phases_follow_health() {
+    let boss = Boss::new(90.);
+    let phases: Vec<_> = [90., 61., 59., 31., 29., -5.].into_iter().map(|h| boss.phase(h)).collect();
+    assert_eq!(phases, [Phase::Ranged, Phase::Ranged, Phase::Summon, Phase::Summon, Phase::Charge, Phase::Charge]);
+    assert_eq!(boss.health_left(-5.), 0.);
+}
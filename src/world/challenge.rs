@@ -0,0 +1,19 @@
+//! Levels that have to be finished within a time limit
+
+use log::info;
+
+use super::World;
+
+impl World {
+    /// Seconds left before the time limit runs out, if the map has one
+    pub fn time_left(&self) -> Option<f32> {
+        self.map.time_limit.map(|limit| (limit + self.bonus_time - self.clock).max(0.))
+    }
+    /// Runs out of time once there's none left
+    pub(super) fn tick_time_limit(&mut self) {
+        if !self.out_of_time && self.exited.is_none() && self.time_left() == Some(0.) {
+            self.out_of_time = true;
+            info!("ran out of time");
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use log::info;
+
+use crate::{vec::{Point2, Vector2}, tex::Colour, sound::{Sound, SoundEvent}, map::Exit};
+
+use super::{World, GameEvent, thing::{Attacker, EntityId}, effects::Effect, shake::Shake, PLAYER_WIDTH};
+
+const EXPLOSION_RADIUS: f32 = 2.5;
+const EXPLOSION_DAMAGE: f32 = 80.;
+const EXPLOSION_PARTICLES: usize = 16;
+pub(super) const EXPLOSION_FLASH_COLOUR: Colour = Colour::new(0xff, 0xc0, 0x40);
+/// Damage taken at once that is enough to shake the view
+const HEAVY_DAMAGE: f32 = 20.;
+/// Damage per second to everything under a moving block that's coming down
+const CRUSH_DAMAGE: f32 = 300.;
+
+/// What hurt the player, which decides how much of it armor takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DamageKind {
+    Bullet,
+    Explosive,
+    Melee,
+    /// Hazardous floors and being crushed, which armor does nothing against
+    Environment,
+    /// Poison in the blood, which armor does nothing against either
+    Poison,
+}
+
+impl DamageKind {
+    /// The part of the damage armor takes instead of health, as long as there's armor left
+    pub const fn absorbed(self) -> f32 {
+        match self {
+            DamageKind::Bullet => 0.5,
+            DamageKind::Explosive => 0.3,
+            DamageKind::Melee => 0.6,
+            DamageKind::Environment | DamageKind::Poison => 0.,
+        }
+    }
+}
+
+/// Something dying, named like in the kill feed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Kill {
+    /// Who got the kill, if anyone did
+    pub killer: Option<String>,
+    pub victim: String,
+}
+
+impl World {
+    /// Damages everything within `radius` of `p` that isn't shielded by a wall,
+    /// with the damage falling off linearly from `damage` at the centre to nothing at the edge
+    ///
+    /// Explosions hurt everyone, whoever set them off gets the blame.
+    pub fn explode(&mut self, p: Point2, radius: f32, damage: f32, attacker: Option<Attacker>) {
+        let falloff = |dist: f32| damage * (1. - dist / radius).max(0.);
+
+        let hit_things: Vec<_> = self.thing_grid.query_radius(p, radius).collect();
+        for i in hit_things {
+            let thing = &self.things[i];
+            if thing.health.is_none() {
+                continue;
+            }
+            let dist = ((thing.pos - p).norm() - thing.width()).max(0.);
+            if dist <= radius && self.map.line_of_sight(p, thing.pos) {
+                self.damage_thing(i, falloff(dist), attacker.map(|attacker| Attacker { faction: None, ..attacker }));
+            }
+        }
+
+        let player_dist = ((self.player_p - p).norm() - PLAYER_WIDTH).max(0.);
+        if player_dist <= radius && self.map.line_of_sight(p, self.player_p) {
+            self.damage_player(falloff(player_dist), DamageKind::Explosive, attacker);
+        }
+
+        let (x0, y0) = ((p.x - radius).floor() as i32, (p.y - radius).floor() as i32);
+        let (x1, y1) = ((p.x + radius).floor() as i32, (p.y + radius).floor() as i32);
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                if !self.map.is_breakable(x, y) {
+                    continue;
+                }
+                // Closest point of the cell to the explosion
+                let closest = Point2::new(p.x.clamp(x as f32, x as f32 + 1.), p.y.clamp(y as f32, y as f32 + 1.));
+                let dist = (closest - p).norm();
+                // The cell is only exposed if it's the first thing in the way towards its centre
+                let centre = Point2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let exposed = match self.map.first_solid_cell(p, centre) {
+                    Some(cell) => cell == (x, y),
+                    None => true,
+                };
+
+                if dist <= radius && exposed {
+                    self.map.damage_cell(x, y, falloff(dist));
+                }
+            }
+        }
+    }
+
+    pub fn damage_player(&mut self, damage: f32, kind: DamageKind, attacker: Option<Attacker>) {
+        if damage <= 0. || self.god || self.player_effects.has(Effect::Invulnerable) {
+            return;
+        }
+        let absorbed = (damage * kind.absorbed()).min(self.player_armor);
+        self.player_armor -= absorbed;
+        let was_alive = self.player_health > 0.;
+        self.player_health -= damage - absorbed;
+        self.events.push(GameEvent::Damage { victim: EntityId::PLAYER, amount: damage - absorbed, attacker: attacker.map(|a| a.id) });
+        if let Some(attacker) = attacker {
+            self.player_last_attacker = Some(attacker.id);
+        }
+        if was_alive && self.player_health <= 0. {
+            self.player_died();
+        }
+        // Poison hurts a little every update and has its own tint instead of the flash
+        if kind != DamageKind::Poison {
+            self.player_effects.add(Effect::Damaged, 0.3);
+        }
+        if damage >= HEAVY_DAMAGE {
+            self.shake.add(Shake::new(0.02 * (damage / HEAVY_DAMAGE).min(3.), 12., 8.));
+        }
+    }
+
+    /// Puts the player's death in the kill feed, credited to whoever hurt them last
+    pub(super) fn player_died(&mut self) {
+        info!("player died");
+        let killer = self.player_last_attacker.and_then(|id| self.name_of(id));
+        self.events.push(GameEvent::Killed(Kill { killer, victim: "player".to_owned() }));
+    }
+    /// Damages the player and things caught under moving blocks
+    pub(super) fn crush(&mut self, delta: f32) {
+        if self.map.crushes(self.player_p) {
+            self.damage_player(CRUSH_DAMAGE * delta, DamageKind::Environment, None);
+        }
+        for i in 0..self.things.len() {
+            if self.things[i].health.is_some() && self.map.crushes(self.things[i].pos) {
+                self.damage_thing(i, CRUSH_DAMAGE * delta, None);
+            }
+        }
+    }
+    /// Damages thing `i` like `Thing::damage_by`, or like `Thing::damage` if nobody is to blame
+    pub(super) fn damage_thing(&mut self, i: usize, damage: f32, attacker: Option<Attacker>) {
+        let thing = &mut self.things[i];
+        let before = thing.health;
+        match attacker {
+            Some(attacker) => thing.damage_by(damage, attacker),
+            None => thing.damage(damage),
+        }
+        if let (Some(before), Some(after)) = (before, thing.health) {
+            if after < before {
+                self.events.push(GameEvent::Damage { victim: thing.id, amount: before - after, attacker: attacker.map(|a| a.id) });
+            }
+        }
+    }
+
+    /// Removes dead things from the world, setting off the explosive ones
+    ///
+    /// Explosions can kill more explosive things, so this keeps going until the chain reaction is over
+    pub(super) fn reap_dead_things(&mut self) {
+        loop {
+            let mut dead = Vec::new();
+            self.things.retain(|thing| if thing.is_dead() {
+                dead.push(*thing);
+                false
+            } else {
+                true
+            });
+            self.thing_grid.rebuild(&self.things);
+            let killed: Vec<_> = dead.iter().filter(|t| t.health.is_some_and(|h| h <= 0.)).collect();
+            self.kills += killed.len() as u32;
+            for thing in killed {
+                let victim = self.map.thing_name(thing.tex()).unwrap_or_default().to_owned();
+                let killer = thing.last_attacker.and_then(|id| self.name_of(id));
+                self.events.push(GameEvent::Killed(Kill { killer, victim }));
+            }
+            // Beating a boss finishes the level
+            if dead.iter().any(|t| t.boss.is_some()) && self.player_health > 0. {
+                self.exited.get_or_insert(Exit::Normal);
+            }
+
+            // Whoever blew something up is to blame for its explosion
+            let explosions: Vec<_> = dead.into_iter().filter(|t| t.explosive).map(|t| (t.pos, t.last_attacker)).collect();
+            if explosions.is_empty() {
+                break;
+            }
+            for (p, blamed) in explosions {
+                let attacker = blamed.map(|id| Attacker { id, faction: None });
+                self.explode(p, EXPLOSION_RADIUS, EXPLOSION_DAMAGE, attacker);
+                self.explosion_effects(p);
+            }
+        }
+    }
+
+    fn explosion_effects(&mut self, p: Point2) {
+        const PARTICLE_SPEED: f32 = 4.;
+        const PARTICLE_LIFETIME: f32 = 0.4;
+
+        for i in 0..EXPLOSION_PARTICLES {
+            // Spread them around evenly with a bit of variation in speed
+            let angle = i as f32 / EXPLOSION_PARTICLES as f32 * std::f32::consts::TAU;
+            let speed = PARTICLE_SPEED * (0.6 + 0.4 * ((i * 7) % 5) as f32 / 4.);
+
+            self.particles.spawn(p, speed * Vector2::unit_from_angle(angle), PARTICLE_LIFETIME, self.particle_tex);
+        }
+        self.emit_sound(SoundEvent::new(Sound::Explosion, p));
+
+        // Explosions in view light up the screen, less so the further away they are
+        if self.map.line_of_sight(self.player_p, p) {
+            let brightness = 1. - (p - self.player_p).norm() / (4. * EXPLOSION_RADIUS);
+            self.flash = self.flash.max(brightness);
+        }
+        // Explosions can be felt through walls
+        let closeness = 1. - (p - self.player_p).norm() / (3. * EXPLOSION_RADIUS);
+        if closeness > 0. {
+            self.shake.add(Shake::new(0.08 * closeness, 18., 5.));
+        }
+    }
+}
+
+#[test]
+fn armor_takes_part_of_the_damage() {
+    let mut world = World::from_map_src(crate::testing::CORRIDOR);
+    let health = world.player_health;
+    world.player_armor = 10.;
+    world.damage_player(10., DamageKind::Melee, None);
+    assert_eq!(world.player_armor, 4.);
+    assert_eq!(world.player_health, health - 4.);
+
+    // Only as much as there's armor left
+    world.damage_player(20., DamageKind::Bullet, None);
+    assert_eq!(world.player_armor, 0.);
+    assert_eq!(world.player_health, health - 20.);
+
+    world.player_armor = 10.;
+    world.damage_player(5., DamageKind::Environment, None);
+    assert_eq!(world.player_armor, 10.);
+    assert_eq!(world.player_health, health - 25.);
+}
+
+#[test]
+fn poison_hurts_over_time_unless_invulnerable() {
+    use crate::testing::{test_map, wait_for};
+    use super::{Controls, TICK};
+
+    let mut world = World::from_map_src(&test_map("Poison", &["poison p 10 1"], &[], "\
+bbbbb
+b>  b
+bbbbb
+
+.....
+..p..
+.....
+"));
+    let health = world.player_health;
+    world.player_p.x = 2.5;
+    world.player_armor = 50.;
+    world.update(TICK, Controls::default());
+    world.player_p.x = 1.5;
+    wait_for(&mut world, 2.);
+    assert!((world.player_health - (health - 10.)).abs() < 0.5);
+    assert_eq!(world.player_armor, 50.);
+
+    world.player_effects.add(Effect::Invulnerable, 5.);
+    world.player_p.x = 2.5;
+    world.update(TICK, Controls::default());
+    world.player_p.x = 1.5;
+    let health = world.player_health;
+    wait_for(&mut world, 2.);
+    assert_eq!(world.player_health, health);
+}
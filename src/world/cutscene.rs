@@ -0,0 +1,74 @@
+//! Cutscenes of the map playing, which take the camera away from the player and stop the world until they're over
+
+use log::info;
+
+use crate::map::Cutscene;
+
+use super::{World, Camera, TICK};
+
+/// A cutscene being played
+#[derive(Debug, Clone)]
+pub(super) struct Playing {
+    cutscene: Cutscene,
+    /// Seconds since it started
+    time: f32,
+}
+
+impl World {
+    /// Starts the cutscene of the map called `name`, returns whether there is one
+    pub fn play_cutscene(&mut self, name: &str) -> bool {
+        let Some(cutscene) = self.map.cutscene(name) else { return false };
+        info!("playing cutscene {name}");
+        self.cutscene = Some(Playing { cutscene: cutscene.clone(), time: 0. });
+        true
+    }
+    /// Gives control back to the player before the cutscene is over
+    pub fn skip_cutscene(&mut self) {
+        if self.cutscene.take().is_some() {
+            info!("skipped cutscene");
+        }
+    }
+    pub fn in_cutscene(&self) -> bool {
+        self.cutscene.is_some()
+    }
+    /// Subtitle of the part of the cutscene being played
+    pub fn cutscene_subtitle(&self) -> Option<&str> {
+        let playing = self.cutscene.as_ref()?;
+        playing.cutscene.at(playing.time).2
+    }
+    pub(super) fn tick_cutscene(&mut self, delta: f32) {
+        let Some(playing) = &mut self.cutscene else { return };
+        playing.time += delta;
+        if playing.time >= playing.cutscene.duration() {
+            self.cutscene = None;
+        }
+    }
+    /// Where the cutscene has the camera `alpha` of the way to the next update, if one is playing
+    pub(super) fn cutscene_camera(&self, alpha: f32) -> Option<Camera> {
+        let playing = self.cutscene.as_ref()?;
+        let (pos, angle, _) = playing.cutscene.at((playing.time - (1. - alpha) * TICK).max(0.));
+        Some(Camera { pos, angle, height: 0., alpha })
+    }
+}
+
+#[test]
+fn the_clock_stands_still_during_cutscenes() {
+    use super::Controls;
+
+    let mut world = World::from_map_src(&crate::testing::test_map("Intro", &["time_limit 60"], &[], "\
+bbbbb
+b>  b
+bbbbb
+
+cutscene intro
+shot 1.5 1.5 3.5 1.5 1
+"));
+    assert!(world.in_cutscene());
+    crate::testing::wait_for(&mut world, 0.5);
+    assert_eq!((world.clock, world.time_left()), (0., Some(60.)));
+    while world.in_cutscene() {
+        world.update(TICK, Controls::default());
+    }
+    world.update(TICK, Controls::default());
+    assert!(world.clock > 0. && world.time_left() < Some(60.));
+}
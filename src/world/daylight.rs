@@ -0,0 +1,116 @@
+//! Time of day on maps that have it, which takes the light, the sky and the fog from day to night and back
+//!
+//! The map header `day_night <seconds per day> [hour]` turns it on, starting at the hour given or noon.
+
+use crate::tex::Colour;
+
+use super::World;
+
+/// Hours in a day
+const DAY: f32 = 24.;
+const NOON: f32 = 12.;
+
+/// How the world looks at an hour of the day, with the hours in between blended from the ones around them
+struct Keyframe {
+    hour: f32,
+    /// How much of the light is left, from 0 to 1
+    light: f32,
+    sky: Colour,
+    /// One over the distance the fog starts at
+    fog_density: f32,
+}
+
+const KEYFRAMES: [Keyframe; 6] = [
+    Keyframe { hour: 0., light: 0.35, sky: Colour::new(0x08, 0x0a, 0x20), fog_density: 1. / 6. },
+    Keyframe { hour: 5., light: 0.4, sky: Colour::new(0x10, 0x10, 0x30), fog_density: 1. / 8. },
+    Keyframe { hour: 7., light: 0.8, sky: Colour::new(0xd0, 0x80, 0x60), fog_density: 1. / 20. },
+    Keyframe { hour: 12., light: 1., sky: Colour::new(0x70, 0xa0, 0xe0), fog_density: 0. },
+    Keyframe { hour: 18., light: 0.8, sky: Colour::new(0xe0, 0x70, 0x40), fog_density: 1. / 20. },
+    Keyframe { hour: 20., light: 0.45, sky: Colour::new(0x20, 0x18, 0x40), fog_density: 1. / 8. },
+];
+
+/// Names of times of day that can be set instead of an hour
+pub const NAMED_HOURS: [(&str, f32); 4] = [("night", 0.), ("dawn", 6.), ("day", NOON), ("dusk", 19.)];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DayNight {
+    /// Seconds a whole day takes
+    pub length: f32,
+    /// Hours since midnight
+    hour: f32,
+}
+
+impl DayNight {
+    pub fn new(length: f32, hour: Option<f32>) -> Self {
+        DayNight { length, hour: hour.unwrap_or(NOON).rem_euclid(DAY) }
+    }
+    pub fn hour(&self) -> f32 {
+        self.hour
+    }
+    pub fn set_hour(&mut self, hour: f32) {
+        self.hour = hour.rem_euclid(DAY);
+    }
+    fn advance(&mut self, delta: f32) {
+        self.set_hour(self.hour + DAY * delta / self.length);
+    }
+    /// The keyframes before and after the hour and how far it is between them, eased so the changes start and stop gently
+    fn between(&self) -> (&'static Keyframe, &'static Keyframe, f32) {
+        let next = KEYFRAMES.iter().position(|k| k.hour > self.hour).unwrap_or(0);
+        let prev = (next + KEYFRAMES.len() - 1) % KEYFRAMES.len();
+        let (from, to) = (&KEYFRAMES[prev], &KEYFRAMES[next]);
+        let span = (to.hour - from.hour).rem_euclid(DAY);
+        let t = (self.hour - from.hour).rem_euclid(DAY) / span;
+        (from, to, t * t * (3. - 2. * t))
+    }
+    /// How much of the light is left, from 0 to 1
+    pub fn light(&self) -> f32 {
+        let (from, to, t) = self.between();
+        from.light + t * (to.light - from.light)
+    }
+    pub fn sky(&self) -> Colour {
+        let (from, to, t) = self.between();
+        let [from, to] = [from.sky.array(), to.sky.array()];
+        let channel = |i: usize| (from[i] as f32 + t * (to[i] as f32 - from[i] as f32)).round() as u8;
+        Colour::new(channel(0), channel(1), channel(2))
+    }
+    /// How far away the fog starts, infinitely far when there is none
+    pub fn fog_distance(&self) -> f32 {
+        let (from, to, t) = self.between();
+        1. / (from.fog_density + t * (to.fog_density - from.fog_density))
+    }
+}
+
+impl World {
+    pub(super) fn tick_day(&mut self, delta: f32) {
+        if let Some(day_night) = &mut self.day_night {
+            day_night.advance(delta);
+        }
+    }
+    /// Sets the time of day to `hour`, returns whether the map has a time of day
+    pub fn set_time_of_day(&mut self, hour: f32) -> bool {
+        let Some(day_night) = &mut self.day_night else { return false };
+        day_night.set_hour(hour);
+        true
+    }
+    /// Colour of the ceiling, which is the sky when there's a time of day
+    pub fn sky_colour(&self) -> Colour {
+        self.day_night.map_or(self.palette.ceiling, |day_night| day_night.sky())
+    }
+}
+
+#[test]
+fn nights_are_darker_than_days() {
+    let mut day_night = DayNight::new(60., None);
+    assert_eq!(day_night.light(), 1.);
+    assert!(day_night.fog_distance().is_infinite());
+
+    // Half the length is half a day, so noon turns into midnight
+    day_night.advance(30.);
+    assert!(day_night.hour() < 1e-3 || day_night.hour() > DAY - 1e-3);
+    assert!(day_night.light() < 0.5);
+    assert!(day_night.fog_distance() < 10.);
+
+    // Between keyframes it's in between them
+    day_night.set_hour(15.);
+    assert!(day_night.light() < 1. && day_night.light() > 0.8);
+}
@@ -0,0 +1,295 @@
+//! Recorded play of a map, which can be played back tick by tick
+//!
+//! Demos are text files. The first line names the map, an `ammo` line can give the player ammo to start with,
+//! and each line after them holds a number of ticks and the controls held during them, for example:
+//!
+//! ```text
+//! map map.txt
+//! ammo 10
+//! 30 forwards sprint
+//! 1 use mouse=-4.5
+//! 20
+//! ```
+//!
+//! `use`, `fire` and `choice=` (how many choices of a dialogue the selection moved down) happen
+//! on the first of the ticks of their line, and `mouse=` is how far the mouse moved during each of them. Lines starting with `#` are comments.
+
+use std::fmt::Write;
+
+use super::{World, Controls, TICK};
+
+/// What the player did during some ticks in a row
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Step {
+    pub ticks: u32,
+    pub controls: Controls,
+    pub interact: bool,
+    pub fire: bool,
+    /// How far the selected choice of the dialogue moved, down being positive
+    pub choice: i32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Demo {
+    /// Path of the map the demo was recorded on
+    pub map: String,
+    /// Ammo the player had when the demo started
+    pub ammo: u32,
+    pub steps: Vec<Step>,
+}
+
+impl Demo {
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut lines = src.lines()
+            .enumerate()
+            .map(|(n, line)| (n + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+        let map = lines.next()
+            .and_then(|(_, line)| line.strip_prefix("map "))
+            .ok_or("a demo has to start with the map it was recorded on")?
+            .trim()
+            .to_owned();
+        let mut lines = lines.peekable();
+        let ammo = match lines.next_if(|(_, line)| line.starts_with("ammo ")) {
+            Some((n, line)) => line["ammo ".len()..].trim().parse().map_err(|_| format!("line {n}: expected an amount of ammo"))?,
+            None => 0,
+        };
+
+        let mut steps = Vec::new();
+        for (n, line) in lines {
+            let mut words = line.split_whitespace();
+            let ticks = words.next()
+                .and_then(|w| w.parse().ok())
+                .ok_or(format!("line {n}: expected a number of ticks"))?;
+            let mut step = Step { ticks, ..Step::default() };
+            for word in words {
+                let controls = &mut step.controls;
+                match word {
+                    "turn_left" => controls.turn_left = true,
+                    "turn_right" => controls.turn_right = true,
+                    "forwards" => controls.forwards = true,
+                    "backwards" => controls.backwards = true,
+                    "strafe_left" => controls.strafe_left = true,
+                    "strafe_right" => controls.strafe_right = true,
+                    "zoom" => controls.zoom = true,
+                    "sprint" => controls.sprint = true,
+                    "sneak" => controls.sneak = true,
+                    "use" => step.interact = true,
+                    "fire" => step.fire = true,
+                    _ if word.starts_with("choice=") => {
+                        step.choice = word["choice=".len()..].parse().map_err(|_| format!("line {n}: expected how far the choice moved"))?;
+                    }
+                    _ => {
+                        controls.mouse_dx = word.strip_prefix("mouse=")
+                            .and_then(|dx| dx.parse().ok())
+                            .ok_or(format!("line {n}: unknown control {word}"))?;
+                    }
+                }
+            }
+            steps.push(step);
+        }
+        Ok(Demo { map, ammo, steps })
+    }
+    pub fn to_src(&self) -> String {
+        let mut s = format!("map {}\n", self.map);
+        if self.ammo > 0 {
+            let _ = writeln!(s, "ammo {}", self.ammo);
+        }
+        for step in &self.steps {
+            let c = &step.controls;
+            // Writing to a string can't fail
+            let _ = write!(s, "{}", step.ticks);
+            let held = [
+                (c.turn_left, "turn_left"),
+                (c.turn_right, "turn_right"),
+                (c.forwards, "forwards"),
+                (c.backwards, "backwards"),
+                (c.strafe_left, "strafe_left"),
+                (c.strafe_right, "strafe_right"),
+                (c.zoom, "zoom"),
+                (c.sprint, "sprint"),
+                (c.sneak, "sneak"),
+                (step.interact, "use"),
+                (step.fire, "fire"),
+            ];
+            for (_, name) in held.iter().filter(|(held, _)| *held) {
+                let _ = write!(s, " {name}");
+            }
+            if step.choice != 0 {
+                let _ = write!(s, " choice={}", step.choice);
+            }
+            if c.mouse_dx != 0. {
+                let _ = write!(s, " mouse={}", c.mouse_dx);
+            }
+            s.push('\n');
+        }
+        s
+    }
+}
+
+/// Where a demo being played back has got to
+#[derive(Debug, Clone)]
+pub struct Playback {
+    demo: Demo,
+    step: usize,
+    /// Ticks of the current step that have been played
+    tick: u32,
+    started: bool,
+}
+
+impl Playback {
+    pub fn new(demo: Demo) -> Self {
+        Playback { demo, step: 0, tick: 0, started: false }
+    }
+    pub fn finished(&self) -> bool {
+        self.step >= self.demo.steps.len()
+    }
+    /// Updates the world by one tick of the demo, returning `false` once there's no more of it
+    pub fn tick(&mut self, world: &mut World) -> bool {
+        // Steps without ticks would only hold up the playback
+        while self.demo.steps.get(self.step).is_some_and(|step| self.tick >= step.ticks) {
+            self.step += 1;
+            self.tick = 0;
+        }
+        let Some(step) = self.demo.steps.get(self.step) else { return false };
+
+        if !self.started {
+            self.started = true;
+            world.ammo = self.demo.ammo;
+        }
+        if self.tick == 0 {
+            if step.interact {
+                world.interact();
+            }
+            if step.fire {
+                world.shoot();
+            }
+            if step.choice != 0 {
+                world.select_choice(step.choice);
+            }
+        }
+        world.update(TICK, step.controls);
+        self.tick += 1;
+        true
+    }
+}
+
+/// Writes down what the player does, tick by tick
+#[derive(Debug, Clone, Default)]
+pub struct Recorder {
+    demo: Demo,
+    interact: bool,
+    fire: bool,
+    choice: i32,
+}
+
+impl Recorder {
+    /// Starts recording the world as it is now
+    pub fn new(world: &World) -> Self {
+        let demo = Demo { map: world.map_path().to_string_lossy().into_owned(), ammo: world.ammo, steps: Vec::new() };
+        Recorder { demo, ..Recorder::default() }
+    }
+    /// The player used what's in front of them, which is recorded with the next tick
+    pub fn interact(&mut self) {
+        self.interact = true;
+    }
+    /// The player fired, which is recorded with the next tick
+    pub fn fire(&mut self) {
+        self.fire = true;
+    }
+    /// The player moved the selected choice of the dialogue by `step`, which is recorded with the next tick
+    pub fn select_choice(&mut self, step: i32) {
+        self.choice += step;
+    }
+    /// Records a tick played with `controls`
+    pub fn tick(&mut self, controls: Controls) {
+        let step = Step {
+            ticks: 1,
+            controls,
+            interact: std::mem::take(&mut self.interact),
+            fire: std::mem::take(&mut self.fire),
+            choice: std::mem::take(&mut self.choice),
+        };
+        let once = step.interact || step.fire || step.choice != 0;
+        match self.demo.steps.last_mut() {
+            // Ticks with the same controls go on the same line, after the one `use`, `fire` or choice happens on
+            Some(last) if !once && step.controls.mouse_dx == 0. && last.controls.mouse_dx == 0. && last.controls == step.controls => last.ticks += 1,
+            _ => self.demo.steps.push(step),
+        }
+    }
+    pub fn demo(&self) -> &Demo {
+        &self.demo
+    }
+}
+
+#[cfg(test)]
+fn hallway() -> String {
+    crate::testing::test_map("Hallway", &[], &["d door tex/door.png tex/door_dark.png"], "\
+bbbbbbbbbbbb
+b>  d      b
+bbbbbbbbbbbb
+")
+}
+
+#[test]
+fn demos_are_read_back_as_written() {
+    let src = "\
+# Comments and blank lines are skipped
+map map.txt
+ammo 5
+
+30 forwards sprint
+1 use fire choice=-1 mouse=-4.5
+20 forwards sneak
+";
+    let demo = Demo::parse(src).unwrap();
+    assert_eq!(demo.map, "map.txt");
+    assert_eq!(demo.ammo, 5);
+    assert_eq!(demo.steps.len(), 3);
+    assert!(demo.steps[0].controls.forwards && demo.steps[0].controls.sprint);
+    assert!(demo.steps[1].interact && demo.steps[1].fire && demo.steps[1].choice == -1);
+    assert!(demo.steps[2].controls.sneak);
+    assert_eq!(demo.steps[1].controls.mouse_dx, -4.5);
+    assert_eq!(Demo::parse(&demo.to_src()), Ok(demo));
+
+    assert!(Demo::parse("30 forwards").is_err());
+    assert!(Demo::parse("map map.txt\n30 jump").is_err());
+}
+
+#[test]
+fn recordings_play_back_the_same() {
+    let forwards = Controls { forwards: true, ..Controls::default() };
+    let mut world = World::from_map_src(&hallway());
+    let mut recorder = Recorder::new(&world);
+    for i in 0..120 {
+        // Opens the door on the way
+        if i == 50 {
+            recorder.interact();
+            world.interact();
+        }
+        recorder.tick(forwards);
+        world.update(TICK, forwards);
+    }
+    // The rest of the walk goes on the line the door is opened on
+    assert_eq!(recorder.demo().steps.len(), 2);
+    assert!(world.player_p.x > 5., "the door should have been opened");
+
+    // The world of a map that isn't in a file has no map to write down
+    let demo = Demo { map: "hallway.txt".into(), ..recorder.demo().clone() };
+    let mut playback = Playback::new(Demo::parse(&demo.to_src()).unwrap());
+    let mut replayed = World::from_map_src(&hallway());
+    while playback.tick(&mut replayed) {}
+    assert!(playback.finished());
+    assert_eq!(replayed.player_p, world.player_p);
+}
+
+#[test]
+fn the_bundled_demos_play_to_the_end() {
+    for path in crate::settings::Settings::default().demos {
+        let demo = Demo::parse(&crate::assets::read_to_string(&path).unwrap()).unwrap();
+        let mut world = World::from_map_src(&crate::assets::read_to_string(&demo.map).unwrap());
+        let mut playback = Playback::new(demo);
+        while playback.tick(&mut world) {}
+        assert!(world.exited.is_none() && world.player_health > 0., "{path} should leave the player in the level");
+    }
+}
@@ -0,0 +1,142 @@
+//! Doors the player opens and closes by using them, telling everyone else through events as they go
+
+use log::info;
+
+use crate::{sound::SoundEvent, vec::Point2};
+
+use super::{World, GameEvent, PLAYER_WIDTH};
+
+/// Seconds a door takes to open or close all the way
+const DOOR_TIME: f32 = 1.;
+
+/// What a door did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoorChange {
+    Opening,
+    Closing,
+    /// It couldn't close with something in the way
+    Blocked,
+    /// Someone tried to open it while it's locked
+    Locked,
+}
+
+impl DoorChange {
+    pub const fn name(self) -> &'static str {
+        match self {
+            DoorChange::Opening => "open",
+            DoorChange::Closing => "close",
+            DoorChange::Blocked => "blocked",
+            DoorChange::Locked => "locked",
+        }
+    }
+}
+
+/// A door on its way open or closed
+#[derive(Debug, Clone, Copy)]
+pub(super) struct MovingDoor {
+    cell: (i32, i32),
+    opening: bool,
+    /// How far open it is, from 0 to 1
+    open: f32,
+}
+
+impl World {
+    /// Opens or closes the door in front of the player, returns whether there is one
+    pub(super) fn use_door(&mut self) -> bool {
+        let Some((cell, state)) = self.in_reach(|map, (x, y)| map.door_state(x, y)) else { return false };
+        if self.map.door_locked(cell.0, cell.1) {
+            self.door_changed(cell, DoorChange::Locked);
+            return true;
+        }
+        // A door on its way somewhere turns around, one standing still goes the other way
+        let opening = match self.doors.iter().find(|door| door.cell == cell) {
+            Some(door) => !door.opening,
+            None => state < u8::MAX,
+        };
+        self.move_door(cell, opening);
+        true
+    }
+    /// Starts the door in a cell opening or closing unless it's already on its way there,
+    /// a door with something in the way doesn't close
+    pub(super) fn move_door(&mut self, cell: (i32, i32), opening: bool) {
+        let Some(state) = self.map.door_state(cell.0, cell.1) else { return };
+        if !opening && self.in_doorway(cell) {
+            self.door_changed(cell, DoorChange::Blocked);
+            return;
+        }
+        match self.doors.iter_mut().find(|door| door.cell == cell) {
+            Some(door) if door.opening == opening => return,
+            Some(door) => door.opening = opening,
+            None if (state == u8::MAX) == opening => return,
+            None => self.doors.push(MovingDoor { cell, opening, open: state as f32 / 255. }),
+        }
+        self.door_changed(cell, if opening { DoorChange::Opening } else { DoorChange::Closing });
+    }
+    /// Moves the doors that are opening or closing, closing doors with something in the way go back open
+    pub(super) fn tick_doors(&mut self, delta: f32) {
+        for i in 0..self.doors.len() {
+            let cell = self.doors[i].cell;
+            if !self.doors[i].opening && self.in_doorway(cell) {
+                self.doors[i].opening = true;
+                self.door_changed(cell, DoorChange::Blocked);
+            }
+            let door = &mut self.doors[i];
+            let step = delta / DOOR_TIME;
+            door.open = if door.opening { (door.open + step).min(1.) } else { (door.open - step).max(0.) };
+            self.map.set_state(cell.0, cell.1, (door.open * 255.).round() as u8);
+        }
+        self.doors.retain(|door| if door.opening { door.open < 1. } else { door.open > 0. });
+    }
+    /// Whether the player or a thing is in the way of the door in a cell closing
+    fn in_doorway(&self, cell: (i32, i32)) -> bool {
+        in_cell(cell, self.player_p, PLAYER_WIDTH) || self.things.iter().any(|t| !t.is_dead() && in_cell(cell, t.pos, t.width()))
+    }
+    fn door_changed(&mut self, cell: (i32, i32), change: DoorChange) {
+        info!("door at {cell:?}: {}", change.name());
+        let pos = Point2::new(cell.0 as f32 + 0.5, cell.1 as f32 + 0.5);
+        self.events.push(GameEvent::DoorChanged { cell, change });
+        self.emit_sound(SoundEvent::new(self.map.door_sound(cell.0, cell.1, change), pos));
+    }
+}
+
+/// Whether a circle of radius `r` around `p` overlaps a cell
+fn in_cell((x, y): (i32, i32), p: Point2, r: f32) -> bool {
+    p.x + r > x as f32 && p.x - r < (x + 1) as f32 && p.y + r > y as f32 && p.y - r < (y + 1) as f32
+}
+
+#[test]
+fn doors_open_and_wont_close_on_the_player() {
+    use crate::testing::{test_map, wait_for};
+
+    let mut world = World::from_map_src(&test_map("Doors", &[], &[
+        "d door tex/door.png tex/door_dark.png",
+        "l door locked door_sound=jail tex/door.png tex/door_dark.png",
+    ], "\
+bbbbbbb
+b> d lb
+bbbbbbb
+"));
+    let changes = |world: &World| world.events.iter()
+        .filter_map(|e| match e {
+            GameEvent::DoorChanged { change, .. } => Some(*change),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    world.player_p = Point2::new(2.5, 1.5);
+    world.interact();
+    wait_for(&mut world, DOOR_TIME);
+    assert_eq!(world.map.door_state(3, 1), Some(u8::MAX));
+
+    // Standing in the doorway keeps it open
+    world.player_p = Point2::new(3.5, 1.5);
+    world.interact();
+    assert_eq!(changes(&world), [DoorChange::Opening, DoorChange::Blocked]);
+
+    // The locked door rattles with its own sound
+    world.player_p = Point2::new(4.5, 1.5);
+    world.interact();
+    assert_eq!(changes(&world).last(), Some(&DoorChange::Locked));
+    let Some(GameEvent::SoundEmitted(sound)) = world.events.last() else { panic!("the locked door made no sound") };
+    assert_eq!(world.map.sound_file_name(sound.sound), "jail_locked.wav");
+}
@@ -0,0 +1,72 @@
+use crate::tex::Colour;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Effect {
+    /// Short flash after taking damage
+    Damaged,
+    /// Deals damage over time
+    Poisoned,
+    /// Can't take damage
+    Invulnerable,
+    /// Moves faster
+    Fast,
+}
+
+impl Effect {
+    const ALL: [Effect; 4] = [Effect::Damaged, Effect::Poisoned, Effect::Invulnerable, Effect::Fast];
+
+    /// Colour the screen is tinted with while the player has this effect and how strongly (out of 255)
+    const fn tint(self) -> (Colour, u8) {
+        match self {
+            Effect::Damaged => (Colour::new(0xff, 0x00, 0x00), 0x60),
+            Effect::Poisoned => (Colour::new(0x20, 0xd0, 0x20), 0x30),
+            Effect::Invulnerable => (Colour::new(0xff, 0xe0, 0x00), 0x28),
+            Effect::Fast => (Colour::new(0x40, 0xa0, 0xff), 0x20),
+        }
+    }
+}
+
+/// Timed status effects of the player or a thing
+#[derive(Debug, Copy, Clone, Default)]
+pub struct StatusEffects {
+    /// Seconds left of each effect, indexed by `Effect`
+    remaining: [f32; Effect::ALL.len()],
+    poison_per_second: f32,
+}
+
+impl StatusEffects {
+    /// Gives the effect for `duration` seconds, unless it already has it for longer
+    pub fn add(&mut self, effect: Effect, duration: f32) {
+        let remaining = &mut self.remaining[effect as usize];
+        *remaining = remaining.max(duration);
+    }
+    pub fn poison(&mut self, duration: f32, damage_per_second: f32) {
+        if !self.has(Effect::Poisoned) {
+            self.poison_per_second = 0.;
+        }
+        self.add(Effect::Poisoned, duration);
+        self.poison_per_second = self.poison_per_second.max(damage_per_second);
+    }
+    pub fn has(&self, effect: Effect) -> bool {
+        self.remaining[effect as usize] > 0.
+    }
+    /// Counts down the effects, returning the damage taken from effects over time
+    pub fn tick(&mut self, delta: f32) -> f32 {
+        let poisoned_for = self.remaining[Effect::Poisoned as usize].min(delta).max(0.);
+        let damage = if self.has(Effect::Invulnerable) { 0. } else { poisoned_for * self.poison_per_second };
+
+        for remaining in &mut self.remaining {
+            *remaining = (*remaining - delta).max(0.);
+        }
+
+        damage
+    }
+    /// Screen tints of the active effects, fading out over the last half second of each
+    pub fn tints(&self) -> impl Iterator<Item=(Colour, u8)> + '_ {
+        Effect::ALL.into_iter().filter(|&e| self.has(e)).map(|e| {
+            let (colour, strength) = e.tint();
+            let fade = (2. * self.remaining[e as usize]).min(1.);
+            (colour, (strength as f32 * fade) as u8)
+        })
+    }
+}
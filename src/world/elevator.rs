@@ -0,0 +1,78 @@
+//! Elevators, which take the player to the same place on another layer of the map
+
+use log::info;
+
+use crate::{map::Map, vec::Vector2};
+
+use super::{World, TICK};
+
+/// Seconds it takes to get to the next layer
+const RIDE_TIME: f32 = 1.;
+/// How far in front of the player something can be used from
+const USE_REACH: f32 = 1.2;
+/// Steps along the way in front of the player that are checked for something to use
+const USE_STEPS: u32 = 12;
+
+/// An elevator on its way to another layer with the player in it
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Ride {
+    to: usize,
+    up: bool,
+    /// Seconds since the ride started
+    time: f32,
+}
+
+impl Ride {
+    /// How far the view has moved up (or down if negative) in wall heights, `alpha` of the way to the next update
+    pub(super) fn height_at(&self, alpha: f32) -> f32 {
+        let t = ((self.time - (1. - alpha) * TICK) / RIDE_TIME).clamp(0., 1.);
+        let eased = t * t * (3. - 2. * t);
+        if self.up { eased } else { -eased }
+    }
+}
+
+impl World {
+    /// Uses whatever the player is standing in or in front of, or goes on with the conversation the player is in
+    pub fn interact(&mut self) {
+        if self.in_cutscene() {
+            self.skip_cutscene();
+            return;
+        }
+        if self.talk.is_some() {
+            self.continue_talking();
+            return;
+        }
+        if self.ride.is_some() || self.player_health <= 0. || self.start_talking() {
+            return;
+        }
+        if let Some(to) = self.map.elevator_destination(self.player_p) {
+            info!("riding elevator to layer {to}");
+            self.ride = Some(Ride { to, up: to > self.map.layer(), time: 0. });
+            self.map.lock_doors(true);
+        } else if !self.use_switch() {
+            self.use_door();
+        }
+    }
+    /// The first cell in front of the player within reach that `f` finds something to use in, and what it found
+    pub(super) fn in_reach<T>(&self, f: impl Fn(&Map, (i32, i32)) -> Option<T>) -> Option<((i32, i32), T)> {
+        let dir = Vector2::unit_from_angle(self.player_angle);
+        (1..=USE_STEPS)
+            .map(|i| self.player_p + (USE_REACH * i as f32 / USE_STEPS as f32) * dir)
+            .map(|p| (p.x.floor() as i32, p.y.floor() as i32))
+            .find_map(|cell| Some((cell, f(&self.map, cell)?)))
+    }
+    /// Whether the player is in a moving elevator and can't walk
+    pub fn riding(&self) -> bool {
+        self.ride.is_some()
+    }
+    pub(super) fn tick_ride(&mut self, delta: f32) {
+        let Some(ride) = &mut self.ride else { return };
+        ride.time += delta;
+        if ride.time >= RIDE_TIME {
+            let to = ride.to;
+            self.ride = None;
+            self.map.lock_doors(false);
+            self.change_layer(to);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! Enemies, things that chase the player and hit them up close
+
+use crate::vec::{Point2, Vector2};
+
+use super::{World, DamageKind, faction::{Faction, can_hurt}, PLAYER_WIDTH};
+
+const CHASE_SPEED: f32 = 1.4;
+const HIT_DAMAGE: f32 = 8.;
+/// Seconds between hits
+const HIT_INTERVAL: f32 = 0.8;
+/// How far from touching its target an enemy can hit it
+const HIT_REACH: f32 = 0.1;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Enemy {
+    /// Seconds until it can hit again
+    cooldown: f32,
+    /// Faction that hurt it, which it goes after instead of the player while it can see any of them
+    provoked_by: Option<Faction>,
+    /// Way it's looking, it sees all around until it has looked somewhere
+    facing: Option<Vector2>,
+    /// Whether it has noticed the player, after which it goes after them whenever it can see them
+    alerted: bool,
+    /// Where what it's going for was at the last update, `None` while it isn't going for anything
+    target: Option<Point2>,
+}
+
+impl Enemy {
+    pub fn facing_along(facing: Vector2) -> Self {
+        Enemy { facing: Some(facing), ..Enemy::default() }
+    }
+    pub(super) fn provoke(&mut self, faction: Faction) {
+        self.provoked_by = Some(faction);
+    }
+    pub(super) fn look_at(&mut self, dir: Vector2) {
+        self.facing = Some(dir.set_len(1.));
+    }
+    pub(super) fn alert(&mut self) {
+        self.alerted = true;
+    }
+    pub fn alerted(&self) -> bool {
+        self.alerted
+    }
+    pub fn facing(&self) -> Option<Vector2> {
+        self.facing
+    }
+    pub fn target(&self) -> Option<Point2> {
+        self.target
+    }
+}
+
+/// What an enemy is going for
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    Player,
+    Thing(usize),
+}
+
+impl World {
+    /// Lets every enemy go for what it's after and hit it once close enough
+    pub(super) fn update_enemies(&mut self, delta: f32) {
+        let player_alive = self.player_health > 0.;
+        let mut hits = Vec::new();
+
+        for i in 0..self.things.len() {
+            let Some(mut enemy) = self.things[i].enemy else { continue };
+            let (p, attacker) = (self.things[i].pos, self.things[i].attacker());
+            enemy.cooldown = (enemy.cooldown - delta).max(0.);
+
+            let rival = enemy.provoked_by.and_then(|rivals| self.nearest_in_sight(p, rivals, i));
+            if rival.is_none() {
+                enemy.provoked_by = None;
+            }
+            if player_alive && !enemy.alerted && self.notices_player(p, &enemy) {
+                enemy.alert();
+            }
+            let target = match rival {
+                Some(j) => Some((Target::Thing(j), self.things[j].pos, self.things[j].width())),
+                None if player_alive && enemy.alerted && self.map.line_of_sight(p, self.player_p) => Some((Target::Player, self.player_p, PLAYER_WIDTH)),
+                None => None,
+            };
+
+            enemy.target = target.map(|(_, target_p, _)| target_p);
+            let thing = &mut self.things[i];
+            thing.vel = Vector2::new(0., 0.);
+            if let Some((target, target_p, target_width)) = target {
+                let to_target = target_p - p;
+                enemy.facing = Some(to_target.set_len(1.));
+                if to_target.norm() > thing.width() + target_width + HIT_REACH {
+                    thing.vel = to_target.set_len(CHASE_SPEED);
+                } else if enemy.cooldown <= 0. {
+                    enemy.cooldown = HIT_INTERVAL;
+                    hits.push((target, attacker));
+                }
+            }
+            thing.enemy = Some(enemy);
+        }
+
+        for (target, attacker) in hits {
+            match target {
+                Target::Player if can_hurt(attacker.faction, Some(Faction::Player)) => self.damage_player(HIT_DAMAGE, DamageKind::Melee, Some(attacker)),
+                Target::Player => (),
+                Target::Thing(j) => self.damage_thing(j, HIT_DAMAGE, Some(attacker)),
+            }
+        }
+    }
+    /// Index of the closest living thing of a faction that can be seen from `p`, other than thing `except`
+    fn nearest_in_sight(&self, p: Point2, faction: Faction, except: usize) -> Option<usize> {
+        self.things.iter().enumerate()
+            .filter(|&(j, thing)| j != except && thing.faction == Some(faction) && !thing.is_dead())
+            .filter(|(_, thing)| self.map.line_of_sight(p, thing.pos))
+            .min_by(|(_, a), (_, b)| (a.pos - p).norm().total_cmp(&(b.pos - p).norm()))
+            .map(|(j, _)| j)
+    }
+}
@@ -0,0 +1,39 @@
+//! Things that happen in the world, for whatever else wants to know about them
+//!
+//! The world only records what happened. Sounds, the HUD and statistics go through the events afterwards
+//! instead of the world reaching into them.
+
+use crate::sound::SoundEvent;
+
+use super::{doors::DoorChange, thing::EntityId, pickups::Pickup, Kill, World};
+
+#[derive(Debug, Clone)]
+pub enum GameEvent {
+    /// The player (`EntityId::PLAYER`) or a thing lost `amount` health
+    Damage { victim: EntityId, amount: f32, attacker: Option<EntityId> },
+    Killed(Kill),
+    PickedUp(Pickup),
+    /// The score passed another multiple of `pickups::EXTRA_LIFE_SCORE`
+    ExtraLife,
+    /// The player stepped on a checkpoint they'll come back at after dying
+    CheckpointReached,
+    SoundEmitted(SoundEvent),
+    /// The door in a cell changed or couldn't
+    DoorChanged { cell: (i32, i32), change: DoorChange },
+    /// A wall texture decoded in the background couldn't be, with why
+    TextureFailed(String),
+    /// Index of a trigger of the map that went off
+    TriggerFired(usize),
+    /// Index of an objective of the map that was just completed
+    ObjectiveCompleted(usize),
+    /// The player got to a page of a dialogue
+    DialoguePage { dialogue: usize, page: usize },
+}
+
+impl World {
+    /// Makes a sound, which is taken along with the other events and heard by enemies on their next update
+    pub(super) fn emit_sound(&mut self, sound: SoundEvent) {
+        self.unheard.push(sound);
+        self.events.push(GameEvent::SoundEmitted(sound));
+    }
+}
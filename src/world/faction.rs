@@ -0,0 +1,48 @@
+//! Sides things are on, which decide who can hurt whom
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Faction {
+    Player,
+    Guards,
+    Monsters,
+}
+
+/// Whether members of one faction can hurt members of another, indexed by `Faction`
+const HOSTILE: [[bool; 3]; 3] = [
+    // Player, guards, monsters
+    [false, true, true],
+    [true, false, true],
+    [true, true, false],
+];
+
+impl Faction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "player" => Some(Faction::Player),
+            "guards" => Some(Faction::Guards),
+            "monsters" => Some(Faction::Monsters),
+            _ => None,
+        }
+    }
+    pub const fn hostile_to(self, other: Faction) -> bool {
+        HOSTILE[self as usize][other as usize]
+    }
+}
+
+/// Whether something from the `attacker` faction can hurt something in the `target` faction,
+/// things outside of any faction can hurt and be hurt by anything
+pub fn can_hurt(attacker: Option<Faction>, target: Option<Faction>) -> bool {
+    match (attacker, target) {
+        (Some(attacker), Some(target)) => attacker.hostile_to(target),
+        _ => true,
+    }
+}
+
+#[test]
+fn only_hostile_factions_hurt() {
+    assert!(!can_hurt(Some(Faction::Monsters), Some(Faction::Monsters)));
+    assert!(can_hurt(Some(Faction::Guards), Some(Faction::Monsters)));
+    assert!(can_hurt(Some(Faction::Monsters), Some(Faction::Player)));
+    assert!(can_hurt(None, Some(Faction::Guards)));
+    assert!(can_hurt(Some(Faction::Guards), None));
+}
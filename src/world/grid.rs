@@ -0,0 +1,77 @@
+use crate::vec::{Point2, Vector2};
+
+use super::thing::Thing;
+
+/// Uniform grid over the map with a bucket of thing indices per cell
+///
+/// Each thing is only put in the bucket its centre is in, so queries are widened
+/// by the widest thing to catch things reaching over into neighbouring cells.
+#[derive(Debug, Clone, Default)]
+pub struct ThingGrid {
+    buckets: Vec<Vec<usize>>,
+    width: i32,
+    height: i32,
+    max_width: f32,
+}
+
+impl ThingGrid {
+    pub fn new(width: i32, height: i32) -> Self {
+        ThingGrid {
+            buckets: vec![Vec::new(); (width * height) as usize],
+            width,
+            height,
+            max_width: 0.,
+        }
+    }
+    /// Cell of a point, clamped to the grid so things outside the map aren't lost
+    fn cell(&self, p: Point2) -> (i32, i32) {
+        let x = (p.x.floor() as i32).clamp(0, self.width - 1);
+        let y = (p.y.floor() as i32).clamp(0, self.height - 1);
+        (x, y)
+    }
+    pub fn rebuild(&mut self, things: &[Thing]) {
+        for bucket in &mut self.buckets {
+            bucket.clear();
+        }
+        self.max_width = 0.;
+
+        if self.buckets.is_empty() {
+            return;
+        }
+
+        for (i, thing) in things.iter().enumerate() {
+            let (x, y) = self.cell(thing.pos);
+            self.buckets[(y * self.width + x) as usize].push(i);
+            self.max_width = self.max_width.max(thing.width());
+        }
+    }
+    /// Indices of all things that could overlap the axis-aligned box between `min` and `max`
+    ///
+    /// This is a broad phase, callers still have to do the exact test
+    pub fn query_box(&self, min: Point2, max: Point2) -> impl Iterator<Item=usize> + '_ {
+        let (x0, y0, x1, y1) = if self.buckets.is_empty() {
+            (0, 0, -1, -1)
+        } else {
+            let margin = Vector2::new(self.max_width, self.max_width);
+            let (x0, y0) = self.cell(min - margin);
+            let (x1, y1) = self.cell(max + margin);
+            (x0, y0, x1, y1)
+        };
+
+        (y0..=y1)
+            .flat_map(move |y| (x0..=x1).map(move |x| (x, y)))
+            .flat_map(move |(x, y)| self.buckets[(y * self.width + x) as usize].iter().copied())
+    }
+    /// Indices of all things that could be within `r` of `p`
+    pub fn query_radius(&self, p: Point2, r: f32) -> impl Iterator<Item=usize> + '_ {
+        let r = Vector2::new(r, r);
+        self.query_box(p - r, p + r)
+    }
+    /// Indices of all things that could touch the line segment from `start` going `dist`
+    pub fn query_segment(&self, start: Point2, dist: Vector2) -> impl Iterator<Item=usize> + '_ {
+        let end = start + dist;
+        let min = Point2::new(start.x.min(end.x), start.y.min(end.y));
+        let max = Point2::new(start.x.max(end.x), start.y.max(end.y));
+        self.query_box(min, max)
+    }
+}
@@ -0,0 +1,110 @@
+//! Lives and checkpoints, so dying only sends the player back to the last checkpoint while they have lives left
+
+use log::info;
+
+use crate::vec::Point2;
+
+use super::{World, GameEvent, effects::StatusEffects, PLAYER_HEALTH};
+
+/// Seconds between dying and coming back at the checkpoint or the game being over
+const RESPAWN_DELAY: f32 = 2.;
+
+/// Where the player comes back after dying
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct Checkpoint {
+    /// The cell to come back in the middle of
+    pub(super) cell: (i32, i32),
+    pub(super) angle: f32,
+    pub(super) layer: usize,
+}
+
+impl World {
+    /// Remembers checkpoints the player steps on and brings them back after they've been dead for a while
+    pub(super) fn update_lives(&mut self, delta: f32) {
+        if self.player_health > 0. {
+            self.reach_checkpoint();
+            return;
+        }
+        // Survival mode ends with the first death instead
+        if self.survival.is_some() || self.game_over {
+            return;
+        }
+        self.dead_time += delta;
+        if self.dead_time >= RESPAWN_DELAY {
+            if self.lives > 0 {
+                self.respawn();
+            } else {
+                info!("game over");
+                self.game_over = true;
+            }
+        }
+    }
+    fn reach_checkpoint(&mut self) {
+        if !self.map.checkpoint_at(self.player_p) {
+            return;
+        }
+        let cell = (self.player_p.x.floor() as i32, self.player_p.y.floor() as i32);
+        let layer = self.map.layer();
+        if self.checkpoint.cell != cell || self.checkpoint.layer != layer {
+            info!("reached checkpoint at {}, {}", cell.0, cell.1);
+            self.checkpoint = Checkpoint { cell, angle: self.player_angle, layer };
+            self.events.push(GameEvent::CheckpointReached);
+        }
+    }
+    /// Uses up a life to bring the player back at the last checkpoint, leaving the rest of the level as it is
+    fn respawn(&mut self) {
+        self.lives -= 1;
+        info!("respawning with {} lives left", self.lives);
+        let Checkpoint { cell: (x, y), angle, layer } = self.checkpoint;
+        if self.ride.take().is_some() {
+            self.map.lock_doors(false);
+        }
+        self.talk = None;
+        if self.map.layer() != layer {
+            self.change_layer(layer);
+        }
+        self.teleport(Point2::new(x as f32 + 0.5, y as f32 + 0.5));
+        self.player_angle = angle;
+        self.prev_player_angle = angle;
+        self.player_health = PLAYER_HEALTH;
+        self.player_effects = StatusEffects::default();
+        self.player_last_attacker = None;
+        self.hazard_cooldown = 0.;
+        self.stamina = 1.;
+        self.dead_time = 0.;
+    }
+}
+
+#[test]
+fn dying_goes_back_to_the_last_checkpoint() {
+    use crate::testing::{test_map, replay, wait_for};
+    use super::{Controls, DamageKind, TICK};
+
+    let mut world = World::from_map_src(&test_map("Checkpoints", &["checkpoint c"], &[], "\
+bbbbbbbbbbbb
+b>         b
+bbbbbbbbbbbb
+
+............
+....c.......
+............
+"));
+    // The player comes back on the tick after the delay is over
+    let wait_out_death = |world: &mut World| wait_for(world, RESPAWN_DELAY + TICK);
+    let forwards = Controls { forwards: true, ..Controls::default() };
+    replay(&mut world, &[(120, forwards)]);
+    assert!(world.player_p.x > 6.);
+    assert!(world.events.iter().any(|e| matches!(e, GameEvent::CheckpointReached)));
+
+    let lives = world.lives;
+    world.damage_player(1000., DamageKind::Environment, None);
+    wait_out_death(&mut world);
+    assert_eq!(world.lives, lives - 1);
+    assert_eq!(world.player_health, PLAYER_HEALTH);
+    assert!((world.player_p - Point2::new(4.5, 1.5)).norm() < 1e-3);
+
+    world.lives = 0;
+    world.damage_player(1000., DamageKind::Environment, None);
+    wait_out_death(&mut world);
+    assert!(world.game_over);
+}
@@ -0,0 +1,36 @@
+//! Keeping track of which objectives of the map have been completed
+
+use log::info;
+
+use crate::map::{Goal, Objective};
+
+use super::{World, GameEvent, PLAYER_WIDTH, thing::Thing};
+
+impl World {
+    /// Picks up things the player has found and completes objectives whose things are all gone
+    pub(super) fn track_objectives(&mut self) {
+        let objectives = self.map.objectives();
+        let player_p = self.player_p;
+        let found = |thing: &Thing| {
+            thing.objective.is_some_and(|i| objectives[i].goal == Goal::Find)
+                && (thing.pos - player_p).norm() <= PLAYER_WIDTH + thing.width()
+        };
+        if self.things.iter().any(found) {
+            self.things.retain(|thing| !found(thing));
+            self.thing_grid.rebuild(&self.things);
+        }
+
+        for (i, done) in self.objectives_done.iter_mut().enumerate().filter(|(_, done)| !**done) {
+            let left = self.things.iter().chain(self.layer_things.iter().flatten()).any(|thing| thing.objective == Some(i));
+            if !left {
+                *done = true;
+                self.events.push(GameEvent::ObjectiveCompleted(i));
+                info!("objective completed: {}", objectives[i].text);
+            }
+        }
+    }
+    /// Every objective of the map and whether it has been completed
+    pub fn objectives(&self) -> impl Iterator<Item = (&Objective, bool)> {
+        self.map.objectives().iter().zip(self.objectives_done.iter().copied())
+    }
+}
@@ -0,0 +1,69 @@
+//! Particles, kept apart from the other things since there can be lots of them and all they do is fly and fade
+//!
+//! Each property of the particles is kept in its own array, so updating them only goes through what's needed.
+
+use crate::{map::Map, vec::{Point2, Vector2}};
+
+use super::thing::Thing;
+
+/// How wide particles are drawn
+const PARTICLE_WIDTH: f32 = 0.05;
+
+#[derive(Debug, Default, Clone)]
+pub struct Particles {
+    pos: Vec<Point2>,
+    /// Position before the last update, particles are drawn somewhere between this and `pos`
+    prev_pos: Vec<Point2>,
+    vel: Vec<Vector2>,
+    /// Seconds left until each particle disappears
+    lifetime: Vec<f32>,
+    tex: Vec<usize>,
+}
+
+impl Particles {
+    pub fn spawn(&mut self, p: Point2, vel: Vector2, lifetime: f32, tex: usize) {
+        self.pos.push(p);
+        self.prev_pos.push(p);
+        self.vel.push(vel);
+        self.lifetime.push(lifetime);
+        self.tex.push(tex);
+    }
+    fn len(&self) -> usize {
+        self.pos.len()
+    }
+    pub fn clear(&mut self) {
+        *self = Particles::default();
+    }
+    /// Marks the start of an update
+    pub fn remember_pos(&mut self) {
+        self.prev_pos.copy_from_slice(&self.pos);
+    }
+    /// Moves the particles, clipping them against the walls, and removes the ones whose time is up
+    pub fn update(&mut self, delta: f32, map: &Map) {
+        for (p, &vel) in self.pos.iter_mut().zip(&self.vel) {
+            let dp = delta * vel;
+            *p = *p + dp - map.move_ray_cast(*p, dp);
+        }
+        for lifetime in &mut self.lifetime {
+            *lifetime -= delta;
+        }
+
+        let mut i = 0;
+        while i < self.len() {
+            if self.lifetime[i] <= 0. {
+                self.pos.swap_remove(i);
+                self.prev_pos.swap_remove(i);
+                self.vel.swap_remove(i);
+                self.lifetime.swap_remove(i);
+                self.tex.swap_remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+    /// The particles as things to draw, `alpha` of the way from their last positions to their current ones
+    pub fn things(&self, alpha: f32) -> impl Iterator<Item = Thing> + '_ {
+        self.pos.iter().zip(&self.prev_pos).zip(&self.tex)
+            .map(move |((&p, &prev), &tex)| Thing::new(prev + alpha * (p - prev), PARTICLE_WIDTH, tex))
+    }
+}
@@ -0,0 +1,92 @@
+//! Things that are picked up by walking into them
+
+use log::info;
+
+use super::{World, GameEvent, PLAYER_WIDTH, thing::Thing, effects::Effect};
+
+/// What the player gets for picking up a thing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pickup {
+    /// Seconds added to the time limit
+    BonusTime(f32),
+    /// Seconds of moving faster
+    Speed(f32),
+    /// Seconds of not taking damage
+    Invulnerable(f32),
+    /// Armor points, up to `MAX_ARMOR`
+    Armor(f32),
+    /// Points added to the score
+    Treasure(u32),
+}
+
+/// Most armor points armor pickups give
+pub const MAX_ARMOR: f32 = 200.;
+/// Score it takes for each extra life
+pub const EXTRA_LIFE_SCORE: u32 = 20_000;
+
+impl World {
+    /// Picks up every thing the player touches that can be
+    pub(super) fn pick_up_things(&mut self) {
+        let player_p = self.player_p;
+        let touched = |thing: &Thing| thing.pickup.is_some() && (thing.pos - player_p).norm() <= PLAYER_WIDTH + thing.width();
+        if !self.things.iter().any(touched) {
+            return;
+        }
+        let picked_up: Vec<_> = self.things.iter().filter(|thing| touched(thing)).filter_map(|thing| thing.pickup).collect();
+        self.things.retain(|thing| !touched(thing));
+        self.thing_grid.rebuild(&self.things);
+
+        for pickup in picked_up {
+            match pickup {
+                Pickup::BonusTime(seconds) => self.bonus_time += seconds,
+                Pickup::Speed(seconds) => self.player_effects.add(Effect::Fast, seconds),
+                Pickup::Invulnerable(seconds) => self.player_effects.add(Effect::Invulnerable, seconds),
+                Pickup::Armor(points) => self.player_armor = (self.player_armor + points).min(MAX_ARMOR).max(self.player_armor),
+                Pickup::Treasure(points) => {
+                    self.treasure_found += 1;
+                    self.add_score(points);
+                }
+            }
+            info!("picked up {pickup:?}");
+            self.events.push(GameEvent::PickedUp(pickup));
+        }
+    }
+    /// Adds to the score, giving an extra life every time it passes a multiple of `EXTRA_LIFE_SCORE`
+    pub fn add_score(&mut self, points: u32) {
+        let lives_before = self.score / EXTRA_LIFE_SCORE;
+        self.score = self.score.saturating_add(points);
+        for _ in lives_before..self.score / EXTRA_LIFE_SCORE {
+            self.lives += 1;
+            info!("extra life at {} points", self.score);
+            self.events.push(GameEvent::ExtraLife);
+        }
+    }
+    /// How much of the treasure of the level has been picked up, as a percentage, `None` if there isn't any
+    pub fn treasure_percent(&self) -> Option<u32> {
+        (self.treasure_total > 0).then(|| self.treasure_found * 100 / self.treasure_total)
+    }
+}
+
+#[test]
+fn score_gives_extra_lives() {
+    let mut world = World::from_map_src(crate::testing::CORRIDOR);
+    let lives = world.lives;
+    world.add_score(EXTRA_LIFE_SCORE - 1);
+    assert_eq!(world.lives, lives);
+    // Passing two thresholds at once gives two lives
+    world.add_score(EXTRA_LIFE_SCORE + 1);
+    assert_eq!(world.lives, lives + 2);
+    assert_eq!(world.events.iter().filter(|e| matches!(e, GameEvent::ExtraLife)).count(), 2);
+}
+
+#[test]
+fn restarting_takes_back_the_lives_the_score_gave() {
+    use super::Transition;
+
+    let mut world = World::from_map_src(crate::testing::CORRIDOR);
+    let lives = world.lives;
+    world.add_score(EXTRA_LIFE_SCORE);
+    assert_eq!(world.lives, lives + 1);
+    world.switch_to(World::from_map_src(crate::testing::CORRIDOR), Transition::Restart);
+    assert_eq!((world.score, world.lives), (0, lives));
+}
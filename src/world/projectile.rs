@@ -0,0 +1,124 @@
+//! Things flying in a straight line that hurt what they hit, unless it's on the same side
+//!
+//! Like particles, projectiles are kept apart from the other things with each property in its own array,
+//! since there can be lots of them and all they do is fly until they hit something.
+
+use crate::{map::Map, vec::{Point2, Vector2}};
+
+use super::{World, DamageKind, thing::{Thing, Attacker}, faction::{Faction, can_hurt}, PLAYER_WIDTH};
+
+pub const PROJECTILE_WIDTH: f32 = 0.08;
+/// Seconds before a projectile that hasn't hit anything disappears
+const PROJECTILE_LIFETIME: f32 = 5.;
+
+#[derive(Debug, Default, Clone)]
+pub struct Projectiles {
+    pos: Vec<Point2>,
+    /// Position before the last update, projectiles are drawn somewhere between this and `pos`
+    prev_pos: Vec<Point2>,
+    vel: Vec<Vector2>,
+    /// Seconds left until each projectile disappears, which are used up once it hits something
+    lifetime: Vec<f32>,
+    damage: Vec<f32>,
+    /// Who fired each projectile, who gets the blame for what it hits
+    shooter: Vec<Attacker>,
+}
+
+impl Projectiles {
+    fn spawn(&mut self, p: Point2, vel: Vector2, damage: f32, shooter: Attacker) {
+        self.pos.push(p);
+        self.prev_pos.push(p);
+        self.vel.push(vel);
+        self.lifetime.push(PROJECTILE_LIFETIME);
+        self.damage.push(damage);
+        self.shooter.push(shooter);
+    }
+    pub fn len(&self) -> usize {
+        self.pos.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.pos.is_empty()
+    }
+    pub fn clear(&mut self) {
+        *self = Projectiles::default();
+    }
+    /// Marks the start of an update
+    pub fn remember_pos(&mut self) {
+        self.prev_pos.copy_from_slice(&self.pos);
+    }
+    /// Moves the projectiles, going across the edges of a wrapping map, and removes the ones that are spent
+    pub fn update(&mut self, delta: f32, map: &Map) {
+        for ((p, prev), &vel) in self.pos.iter_mut().zip(&mut self.prev_pos).zip(&self.vel) {
+            let dp = delta * vel;
+            let moved = *p + dp - map.move_ray_cast(*p, dp);
+            let wrapped = map.wrap_point(moved);
+            *prev = *prev + (wrapped - moved);
+            *p = wrapped;
+        }
+        for lifetime in &mut self.lifetime {
+            *lifetime -= delta;
+        }
+
+        let mut i = 0;
+        while i < self.len() {
+            if self.lifetime[i] <= 0. {
+                self.pos.swap_remove(i);
+                self.prev_pos.swap_remove(i);
+                self.vel.swap_remove(i);
+                self.lifetime.swap_remove(i);
+                self.damage.swap_remove(i);
+                self.shooter.swap_remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+    /// Where the projectiles are `alpha` of the way from their last positions to their current ones
+    pub fn positions(&self, alpha: f32) -> impl Iterator<Item = Point2> + '_ {
+        self.pos.iter().zip(&self.prev_pos).map(move |(&p, &prev)| prev + alpha * (p - prev))
+    }
+    /// The projectiles as things to draw with the texture `tex`
+    pub fn things(&self, alpha: f32, tex: usize) -> impl Iterator<Item = Thing> + '_ {
+        self.positions(alpha).map(move |p| Thing::new(p, PROJECTILE_WIDTH, tex))
+    }
+}
+
+impl World {
+    /// Fires a projectile from `p` moving at `vel` that does `damage` to what it hits
+    pub fn fire(&mut self, p: Point2, vel: Vector2, damage: f32, shooter: Attacker) {
+        self.projectiles.spawn(p, vel, damage, shooter);
+    }
+    /// Hurts whatever hostile the projectiles hit and stops the ones about to hit a wall
+    pub(super) fn update_projectiles(&mut self, delta: f32) {
+        // What was hit (the player if `None`), how much and by whom
+        let mut hits = Vec::new();
+        let projectiles = &mut self.projectiles;
+        for i in 0..projectiles.len() {
+            let (p, shooter) = (projectiles.pos[i], projectiles.shooter[i]);
+
+            let touches_player = (p - self.player_p).norm() <= PROJECTILE_WIDTH + PLAYER_WIDTH;
+            let target = if touches_player && can_hurt(shooter.faction, Some(Faction::Player)) && self.player_health > 0. {
+                Some(None)
+            } else {
+                self.thing_grid.query_radius(p, PROJECTILE_WIDTH).find(|&j| {
+                    let thing = &self.things[j];
+                    thing.id != shooter.id && thing.health.is_some()
+                        && (thing.pos - p).norm() <= PROJECTILE_WIDTH + thing.width() && can_hurt(shooter.faction, thing.faction)
+                }).map(Some)
+            };
+
+            if let Some(target) = target {
+                hits.push((target, projectiles.damage[i], shooter));
+            }
+            if target.is_some() || !self.map.line_of_sight(p, p + delta * projectiles.vel[i]) {
+                projectiles.lifetime[i] = 0.;
+            }
+        }
+        for (target, damage, shooter) in hits {
+            match target {
+                Some(j) => self.damage_thing(j, damage, Some(shooter)),
+                None => self.damage_player(damage, DamageKind::Bullet, Some(shooter)),
+            }
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! Canned input played back on small maps, checking where the player ends up
+//!
+//! These catch changes to how the player moves, slides along walls and is kept out of them.
+
+use crate::testing::{test_map, replay, CORRIDOR};
+use super::{World, Controls};
+
+/// How far off the player may end up from where they're expected
+const EPSILON: f32 = 1e-3;
+
+fn room() -> String {
+    test_map("Room", &[], &[], "\
+bbbbbbbbb
+b       b
+b >     b
+b       b
+b       b
+bbbbbbbbb
+")
+}
+
+fn assert_ends_at(world: &World, x: f32, y: f32) {
+    let p = world.player_p;
+    assert!((p.x - x).abs() < EPSILON && (p.y - y).abs() < EPSILON, "player ended at ({}, {}), not ({x}, {y})", p.x, p.y);
+}
+
+const FORWARDS: Controls = Controls { forwards: true, ..NOTHING };
+const NOTHING: Controls = Controls {
+    turn_left: false,
+    turn_right: false,
+    forwards: false,
+    backwards: false,
+    strafe_left: false,
+    strafe_right: false,
+    mouse_dx: 0.,
+    zoom: false,
+    sprint: false,
+    sneak: false,
+};
+
+#[test]
+fn walks_and_comes_to_a_stop() {
+    let mut world = World::from_map_src(CORRIDOR);
+    replay(&mut world, &[(60, FORWARDS), (60, NOTHING)]);
+    assert_ends_at(&world, 3.8, 1.5);
+}
+
+#[test]
+fn stops_at_walls() {
+    let mut world = World::from_map_src(CORRIDOR);
+    replay(&mut world, &[(600, FORWARDS)]);
+    // Kept just off the wall by the push away from it
+    assert_ends_at(&world, 10.995, 1.5);
+
+    let mut world = World::from_map_src(CORRIDOR);
+    replay(&mut world, &[(600, Controls { backwards: true, ..NOTHING })]);
+    assert_ends_at(&world, 1.005, 1.5);
+}
+
+#[test]
+fn slides_along_walls() {
+    // Up and to the right, into the top wall and then along it
+    let mut world = World::from_map_src(&room());
+    replay(&mut world, &[(90, Controls { forwards: true, strafe_left: true, ..NOTHING })]);
+    assert_ends_at(&world, 4.9395, 1.005);
+}
+
+#[test]
+fn turns_and_walks() {
+    let mut world = World::from_map_src(&room());
+    // Half a second at the default turning speed, then down and to the right
+    replay(&mut world, &[(30, Controls { turn_right: true, ..NOTHING }), (40, FORWARDS)]);
+    assert_ends_at(&world, 3.4334, 3.7165);
+}
+
+#[test]
+fn wraps_around_the_edges() {
+    let src = test_map("Ring", &["wrap"], &["o nonsolid transparent tex/brick1.png tex/brick1_dark.png"], "\
+bbbbbb
+o  > o
+bbbbbb
+");
+    let mut world = World::from_map_src(&src);
+    replay(&mut world, &[(120, FORWARDS), (60, NOTHING)]);
+    // Off the right edge and back in on the left
+    assert_ends_at(&world, 2.1, 1.5);
+    assert!((world.prev_player_p - world.player_p).norm() < 0.1, "the last step shouldn't go across the map");
+}
+
+#[test]
+fn sprints_until_out_of_stamina() {
+    let sprint = Controls { forwards: true, sprint: true, ..NOTHING };
+    let mut walked = World::from_map_src(CORRIDOR);
+    let mut sprinted = World::from_map_src(CORRIDOR);
+    replay(&mut walked, &[(60, FORWARDS)]);
+    replay(&mut sprinted, &[(60, sprint)]);
+    assert!(sprinted.player_p.x > walked.player_p.x + 0.5);
+    assert!(sprinted.stamina() < 1.);
+
+    // Running into the wall for longer than the stamina lasts, at a very low frame rate
+    for _ in 0..40 {
+        sprinted.update(0.25, sprint);
+        assert!(sprinted.player_p.x < 11.);
+    }
+    // Not enough to start sprinting again while still holding it down
+    assert!(sprinted.stamina() < 0.25);
+    assert!((sprinted.player_p.x - 10.995).abs() < EPSILON);
+}
+
+#[test]
+fn sneaking_is_slower_and_quieter() {
+    let mut walked = World::from_map_src(CORRIDOR);
+    let mut sneaked = World::from_map_src(CORRIDOR);
+    replay(&mut walked, &[(60, FORWARDS)]);
+    replay(&mut sneaked, &[(60, Controls { sneak: true, ..FORWARDS })]);
+    assert!(sneaked.player_p.x < walked.player_p.x - 0.5);
+    assert!(sneaked.noise() < walked.noise());
+}
@@ -0,0 +1,119 @@
+//! Temporal reprojection, which only casts rays for every other column each frame
+//! and fills in the others by moving the walls the columns next to them saw the frame before
+//!
+//! Only hits straight from the camera onto a wall are kept, anything seen through or in something else is always cast.
+//! Columns nothing lands close enough to are cast as well, so turning and moving mostly just costs more rays.
+
+use crate::{map::RenderHit, fov::Fov, vec::{Point2, Vector2}};
+
+/// Furthest the camera can move between frames before nothing from the frame before is used
+const MAX_MOVE: f32 = 0.5;
+/// Furthest from the middle of a column, in columns, a wall point can land and still be used for it
+const MAX_ERROR: f32 = 0.5;
+const MAX_ERROR_QUALITY: f32 = 0.25;
+
+#[derive(Debug)]
+pub struct Reprojection {
+    /// Only uses wall points that land close to the middle of a column, which casts more rays but smears less
+    pub quality: bool,
+    /// Whether the odd columns are cast this frame
+    odd: bool,
+    /// Where the camera was the frame before
+    pos: Point2,
+    width: u32,
+    /// Wall hits of the columns that were cast the frame before
+    hits: Vec<RenderHit>,
+}
+
+impl Reprojection {
+    pub fn new(quality: bool) -> Self {
+        Reprojection { quality, odd: false, pos: Point2::new(0., 0.), width: 0, hits: Vec::new() }
+    }
+    /// Starts a frame, giving the hits moved into columns that don't have to be cast this time
+    ///
+    /// The camera is at `pos` looking along the unit vector `dir`.
+    pub(super) fn reproject(&mut self, pos: Point2, dir: Vector2, fov: &Fov, width: u32) -> Vec<Option<RenderHit>> {
+        self.odd = !self.odd;
+        let mut reprojected = vec![None; width as usize];
+        if self.width != width || (pos - self.pos).norm() > MAX_MOVE {
+            return reprojected;
+        }
+        let max_error = if self.quality { MAX_ERROR_QUALITY } else { MAX_ERROR };
+        let right = dir.hat();
+
+        for hit in &self.hits {
+            let d = (hit.seg_start + hit.seg) - pos;
+            let depth = d.dot(dir);
+            if depth <= 1e-3 {
+                continue;
+            }
+            // Where on the camera plane the point is seen, from -1 at the left edge to 1 at the right
+            let plane_x = d.dot(right) / (depth * fov.tan_half_fov);
+            let column = 0.5 * (plane_x + 1.) * width as f32 - 0.5;
+            let x = column.round();
+            if x < 0. || x >= width as f32 || (column - x).abs() > max_error {
+                continue;
+            }
+            let x = x as u32;
+            if self.is_cast(x) {
+                continue;
+            }
+            let ray = fov.column_ray(dir, x);
+            let moved = RenderHit {
+                seg_start: pos,
+                seg: depth * ray,
+                seg_dist: 0.,
+                dist: depth * ray.norm(),
+                reflected: false,
+                ..*hit
+            };
+            // The nearest wall point covers the ones behind it
+            let slot = &mut reprojected[x as usize];
+            if !slot.is_some_and(|other: RenderHit| other.dist <= moved.dist) {
+                *slot = Some(moved);
+            }
+        }
+        reprojected
+    }
+    /// Whether column `x` is always cast this frame
+    pub(super) fn is_cast(&self, x: u32) -> bool {
+        x % 2 == self.odd as u32
+    }
+    /// Keeps the hits of the columns cast this frame for the next one out of the hits of every column
+    pub(super) fn remember<'a>(&mut self, pos: Point2, width: u32, columns: impl Iterator<Item = &'a [RenderHit]>) {
+        self.pos = pos;
+        self.width = width;
+        let odd = self.odd as u32;
+        self.hits.clear();
+        self.hits.extend((0..).zip(columns).filter(|&(x, _)| x % 2 == odd).filter_map(|(_, hits)| match hits {
+            [hit] if !hit.reflected && hit.mat.is_some() => Some(*hit),
+            _ => None,
+        }));
+    }
+}
+
+#[test]
+fn still_camera_fills_in_the_other_columns() {
+    let world = super::World::from_map_src(crate::testing::CORRIDOR);
+    let fov = Fov::new_from_degrees(60., 4, 4);
+    let (pos, dir) = (world.player_p, Vector2::new(1., 0.));
+    let mut reprojection = Reprojection::new(false);
+    let cast = || (0..4)
+        .map(|x| world.map.render_ray_cast(pos, fov.column_ray(dir, x), f32::INFINITY))
+        .collect::<Vec<_>>();
+
+    // Nothing to go on the first frame
+    assert!(reprojection.reproject(pos, dir, &fov, 4).iter().all(Option::is_none));
+    let hits = cast();
+    reprojection.remember(pos, 4, hits.iter().map(Vec::as_slice));
+
+    let reprojected = reprojection.reproject(pos, dir, &fov, 4);
+    for x in 0..4 {
+        assert_eq!(reprojected[x as usize].is_some(), !reprojection.is_cast(x));
+    }
+    // Where the ray of the column would have hit
+    for (x, hit) in reprojected.iter().enumerate().filter_map(|(x, hit)| Some((x as u32, hit.as_ref()?))) {
+        let cast = world.map.render_ray_cast(pos, fov.column_ray(dir, x), f32::INFINITY)[0];
+        assert!((hit.dist - cast.dist).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,54 @@
+//! Effects covering the whole screen, like the light of explosions and the tints of status effects
+
+use std::f32::consts::TAU;
+
+use crate::tex::{Colour, Frame};
+
+/// Width in pixels of the border drawn instead of a flash
+const BORDER_WIDTH: u32 = 6;
+/// How strong a border can get, so it stays subtle
+const MAX_BORDER_STRENGTH: u8 = 0x90;
+
+const LIQUID_COLOUR: Colour = Colour::new(0x20, 0x60, 0xa0);
+const LIQUID_STRENGTH: u8 = 0x50;
+/// How far in pixels rows seen through liquid sway to each side
+const RIPPLE_AMPLITUDE: f32 = 2.;
+/// Rows between the crests of the ripples
+const RIPPLE_WAVELENGTH: f32 = 16.;
+/// How fast the ripples go by in radians per second
+const RIPPLE_SPEED: f32 = 6.;
+
+/// Tints the screen with a colour `strength / 255` of the way,
+/// or only around its edges if flashing should be reduced
+pub fn flash(frame: &mut Frame, colour: Colour, strength: u8, reduce_flashing: bool) {
+    if strength == 0 {
+        return;
+    }
+    if !reduce_flashing {
+        frame.tint(colour, strength);
+        return;
+    }
+
+    let strength = strength.min(MAX_BORDER_STRENGTH);
+    let (width, height) = (frame.width(), frame.height());
+    let border = BORDER_WIDTH.min(width / 2).min(height / 2);
+
+    for x in 0..width {
+        if x < border || x >= width - border {
+            frame.tint_column(x, 0, height, colour, strength);
+        } else {
+            frame.tint_column(x, 0, border, colour, strength);
+            frame.tint_column(x, height - border, height, colour, strength);
+        }
+    }
+}
+
+/// Makes the bottom of the screen from row `surface` down look like it's seen through liquid,
+/// with the rows swaying from side to side `time` seconds into the ripples
+pub fn submerged(frame: &mut Frame, surface: u32, time: f32) {
+    for y in surface..frame.height() {
+        let phase = TAU * y as f32 / RIPPLE_WAVELENGTH + RIPPLE_SPEED * time;
+        frame.shift_row(y, (RIPPLE_AMPLITUDE * phase.sin()).round() as i32);
+    }
+    frame.tint_rows(surface, frame.height(), LIQUID_COLOUR, LIQUID_STRENGTH);
+}
@@ -0,0 +1,75 @@
+use std::f32::consts::TAU;
+
+use crate::vec::Vector2;
+
+/// How many radians the view turns for each unit the camera is pushed
+const ANGLE_PER_UNIT: f32 = 0.3;
+/// Shakes weaker than this have died out
+const MIN_AMPLITUDE: f32 = 0.001;
+
+/// Shaking of the camera that the world is drawn from, which doesn't move the player itself
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Shake {
+    /// How far the camera is pushed at most, in map units
+    amplitude: f32,
+    /// Shakes per second
+    frequency: f32,
+    /// How fast the amplitude dies out, as a fraction lost per second on an exponential curve
+    decay: f32,
+    /// Seconds since the shaking started
+    time: f32,
+}
+
+impl Shake {
+    pub const fn new(amplitude: f32, frequency: f32, decay: f32) -> Self {
+        Shake { amplitude, frequency, decay, time: 0. }
+    }
+    /// Starts shaking, unless it's already shaking harder
+    pub fn add(&mut self, shake: Shake) {
+        if shake.amplitude >= self.amplitude {
+            *self = shake;
+        }
+    }
+    pub fn is_shaking(&self) -> bool {
+        self.amplitude > 0.
+    }
+    pub fn tick(&mut self, delta: f32) {
+        if !self.is_shaking() {
+            return;
+        }
+        self.time += delta;
+        self.amplitude *= (-self.decay * delta).exp();
+        if self.amplitude < MIN_AMPLITUDE {
+            *self = Shake::default();
+        }
+    }
+    /// How far the camera is moved and turned (in radians) right now
+    ///
+    /// Sines at frequencies that don't line up make it look random without needing random numbers
+    pub fn offset(&self) -> (Vector2, f32) {
+        if !self.is_shaking() {
+            return (Vector2::new(0., 0.), 0.);
+        }
+        let phase = TAU * self.frequency * self.time;
+        let push = Vector2::new(phase.sin(), (1.37 * phase + 1.).sin());
+        let turn = (0.71 * phase + 2.).sin();
+
+        (self.amplitude * push, self.amplitude * ANGLE_PER_UNIT * turn)
+    }
+}
+
+#[test]
+fn shake_dies_out() {
+    let mut shake = Shake::default();
+    shake.add(Shake::new(0.1, 20., 6.));
+    shake.add(Shake::new(0.05, 20., 6.));
+    assert_eq!(shake.amplitude, 0.1);
+
+    for _ in 0..120 {
+        shake.tick(1. / 60.);
+        let (push, _) = shake.offset();
+        assert!(push.norm() <= 0.1 * std::f32::consts::SQRT_2);
+    }
+    assert!(!shake.is_shaking());
+    assert_eq!(shake.offset().1, 0.);
+}
@@ -0,0 +1,36 @@
+//! The player's gun, firing bullets for as long as there's ammo
+
+use crate::{sound::{Sound, SoundEvent}, vec::Vector2};
+
+use super::{World, thing::{Attacker, EntityId}, faction::Faction, projectile::PROJECTILE_WIDTH, PLAYER_WIDTH};
+
+const GUN_DAMAGE: f32 = 25.;
+const BULLET_SPEED: f32 = 14.;
+
+impl World {
+    /// Fires the player's gun straight ahead if they have ammo left
+    pub fn shoot(&mut self) {
+        if self.ammo == 0 || self.player_health <= 0. || self.talk.is_some() || self.in_cutscene() {
+            return;
+        }
+        self.ammo -= 1;
+        let dir = Vector2::unit_from_angle(self.player_angle);
+        let shooter = Attacker { id: EntityId::PLAYER, faction: Some(Faction::Player) };
+        self.fire(self.player_p + (PLAYER_WIDTH + PROJECTILE_WIDTH) * dir, BULLET_SPEED * dir, GUN_DAMAGE, shooter);
+        self.gun.fire();
+        self.emit_sound(SoundEvent::new(Sound::Gunshot, self.player_p));
+    }
+}
+
+#[test]
+fn every_shot_takes_ammo() {
+    let mut world = World::from_map_src(crate::testing::CORRIDOR);
+    world.ammo = 1;
+    world.shoot();
+    world.shoot();
+    assert_eq!(world.ammo, 0);
+    assert_eq!(world.projectiles.len(), 1);
+    // Only the shot that was fired is heard
+    let shots = world.events.iter().filter(|e| matches!(e, super::GameEvent::SoundEmitted(s) if s.sound == Sound::Gunshot && s.pos == world.player_p)).count();
+    assert_eq!(shots, 1);
+}
@@ -0,0 +1,68 @@
+//! Spawners, things that keep creating other things
+
+use std::collections::HashMap;
+
+use super::{World, thing::EntityId};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Spawner {
+    /// Index of the thing of the map it creates
+    pub spawn: usize,
+    /// Seconds between each thing it creates
+    interval: f32,
+    /// How many of its things can be around at once
+    cap: u32,
+    /// Seconds until it creates the next thing
+    timer: f32,
+    /// How many things are left to create this wave in survival mode, where this is used instead of the cap
+    pub quota: Option<u32>,
+}
+
+impl Spawner {
+    pub fn new(interval: f32, cap: u32) -> Self {
+        Spawner { spawn: 0, interval, cap, timer: interval, quota: None }
+    }
+}
+
+impl World {
+    /// Lets every spawner create a thing if it's time to and it isn't over its cap
+    ///
+    /// Spawners are faster the more waves there have been in survival mode.
+    pub(super) fn update_spawners(&mut self, delta: f32) {
+        // How many things from each spawner are around
+        let mut around: HashMap<EntityId, u32> = HashMap::new();
+        for id in self.things.iter().filter_map(|thing| thing.spawned_by) {
+            *around.entry(id).or_default() += 1;
+        }
+        let speed_up = self.survival.map_or(1., |s| s.speed_up());
+
+        let mut spawns = Vec::new();
+        for thing in &mut self.things {
+            let id = thing.id;
+            let Some(spawner) = &mut thing.spawner else { continue };
+            spawner.timer -= delta * speed_up;
+            if spawner.timer > 0. {
+                continue;
+            }
+            spawner.timer += spawner.interval;
+
+            let allowed = match &mut spawner.quota {
+                Some(0) => false,
+                Some(quota) => {
+                    *quota -= 1;
+                    true
+                }
+                None => around.get(&id).copied().unwrap_or(0) < spawner.cap,
+            };
+            if allowed {
+                spawns.push((spawner.spawn, id, thing.pos));
+            }
+        }
+        for (spawn, id, p) in spawns {
+            let mut thing = self.map.summon(spawn);
+            thing.place(p);
+            thing.spawned_by = Some(id);
+            self.add_thing(thing);
+        }
+    }
+}
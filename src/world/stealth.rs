@@ -0,0 +1,117 @@
+//! Sneaking past enemies, who only notice the player in front of them and further away the brighter and faster the player is,
+//! and turn towards the sounds they hear
+
+use crate::vec::{Point2, Vector2};
+
+use super::{World, enemy::Enemy, PLAYER_WIDTH};
+
+/// Half the angle of what enemies see in front of them, in degrees
+const VISION_HALF_ANGLE: f32 = 60.;
+/// Furthest away enemies notice the player from when they're as visible as they get
+const SIGHT_DISTANCE: f32 = 20.;
+/// Enemies notice the player this close whichever way they're looking and however dark it is
+const NOTICE_DISTANCE: f32 = 1.;
+/// How visible the player is standing still, compared to running
+const STILL_VISIBILITY: f32 = 0.6;
+/// Speed at which the player is as visible as they get
+const RUNNING_SPEED: f32 = 3.;
+/// How much an explosion flash lights up everything
+const FLASH_LIGHT: f32 = 1.;
+/// Noise radius that fills the noise meter
+const LOUDEST_NOISE: f32 = 8.;
+/// How much of the noise meter empties each second
+const NOISE_FADE: f32 = 0.5;
+
+impl World {
+    /// How much light there is all over the map from the time of day and explosion flashes, from 0 to 1
+    pub fn light_level(&self) -> f32 {
+        let light = self.day_night.map_or(1., |day_night| day_night.light());
+        (light + FLASH_LIGHT * self.flash).min(1.)
+    }
+    /// How easily enemies see the player from the light on the map and how fast they're moving, from 0 to 1
+    pub fn player_visibility(&self) -> f32 {
+        let moving = (self.player_vel.norm() / RUNNING_SPEED).min(1.);
+        self.light_level() * (STILL_VISIBILITY + (1. - STILL_VISIBILITY) * moving)
+    }
+    /// How much noise the player has made lately, from 0 to 1
+    pub fn noise(&self) -> f32 {
+        self.noise
+    }
+    /// Lets enemies that haven't noticed the player turn towards the sounds they hear,
+    /// and fills the noise meter with the sounds made where the player is
+    pub(super) fn hear_sounds(&mut self, delta: f32) {
+        self.noise = (self.noise - NOISE_FADE * delta).max(0.);
+        for sound in self.unheard.drain(..) {
+            if (sound.pos - self.player_p).norm() < PLAYER_WIDTH {
+                self.noise = self.noise.max((sound.radius / LOUDEST_NOISE).min(1.));
+            }
+            for thing in &mut self.things {
+                let Some(enemy) = &mut thing.enemy else { continue };
+                if !enemy.alerted() && (sound.pos - thing.pos).norm() > 0. && sound.reaches(thing.pos, &self.map) {
+                    enemy.look_at(sound.pos - thing.pos);
+                }
+            }
+        }
+    }
+    /// Whether any enemy around hasn't noticed the player yet, so sneaking matters
+    pub fn unnoticed(&self) -> bool {
+        self.things.iter().any(|thing| !thing.is_dead() && thing.enemy.is_some_and(|enemy| !enemy.alerted()))
+    }
+    /// Whether an enemy at `p` notices the player, which it does when they're in its vision cone and close enough to be seen
+    pub(super) fn notices_player(&self, p: Point2, enemy: &Enemy) -> bool {
+        let to_player = self.player_p - p;
+        let distance = to_player.norm();
+        if distance > NOTICE_DISTANCE {
+            if distance > SIGHT_DISTANCE * self.player_visibility() {
+                return false;
+            }
+            if !in_vision_cone(enemy.facing(), to_player, distance) {
+                return false;
+            }
+        }
+        self.map.line_of_sight(p, self.player_p)
+    }
+}
+
+/// Whether something `to` away is inside the vision cone of an enemy looking along `facing`, which goes all around when it isn't looking anywhere
+fn in_vision_cone(facing: Option<Vector2>, to: Vector2, distance: f32) -> bool {
+    !facing.is_some_and(|facing| to.dot(facing) < distance * VISION_HALF_ANGLE.to_radians().cos())
+}
+
+#[test]
+fn enemies_only_notice_the_player_in_front_of_them() {
+    let mut world = World::from_map_src(&crate::testing::test_map("Stealth", &["day_night 60 0"], &[], "\
+bbbbbbbbbbbbbbbbbbbb
+b>                 b
+bbbbbbbbbbbbbbbbbbbb
+"));
+    // The player is at the west end of the corridor
+    let (east, west) = (Enemy::facing_along(Vector2::new(1., 0.)), Enemy::facing_along(Vector2::new(-1., 0.)));
+    let near = Point2::new(world.player_p.x + 3., world.player_p.y);
+    assert!(!world.notices_player(near, &east));
+    assert!(world.notices_player(near, &west));
+
+    // Far away in the dark the player is hard to see, but not at noon
+    let far = Point2::new(world.player_p.x + 9., world.player_p.y);
+    assert!(!world.notices_player(far, &west));
+    assert!(world.set_time_of_day(12.));
+    assert!(world.notices_player(far, &west));
+}
+
+#[test]
+fn enemies_hear_sounds_after_the_events_are_taken() {
+    use crate::sound::{Sound, SoundEvent};
+    use super::{Controls, TICK};
+
+    let mut world = World::from_map_src(&crate::testing::test_map("Hearing", &[], &["g enemy hp=10 facing=east 0.3 tex/statue.png"], "\
+bbbbbbbbbbbb
+b>     g   b
+bbbbbbbbbbbb
+"));
+    // Something goes off behind it at the end of an update, before the game takes the events
+    world.emit_sound(SoundEvent::new(Sound::Explosion, Point2::new(3.5, 1.5)));
+    world.events.clear();
+    world.update(TICK, Controls::default());
+    let enemy = world.things.iter().find_map(|thing| thing.enemy).unwrap();
+    assert!(enemy.facing().unwrap().x < 0.);
+}
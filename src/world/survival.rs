@@ -0,0 +1,110 @@
+//! Survival mode, where the spawners of the map send waves of things that grow every time
+
+use log::info;
+
+use super::World;
+
+/// Things spawned in the first wave
+const FIRST_WAVE: u32 = 3;
+/// How many more things each wave has than the one before
+const WAVE_GROWTH: u32 = 2;
+/// Seconds of rest before each wave
+const REST: f32 = 5.;
+/// How much faster spawners get each wave
+const SPEED_UP: f32 = 1.1;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Survival {
+    /// The current wave, 0 before the first one
+    pub wave: u32,
+    /// Seconds left until the next wave, while resting between waves
+    rest: f32,
+}
+
+impl Survival {
+    /// How many waves have been survived
+    pub fn survived(&self) -> u32 {
+        if self.rest > 0. { self.wave } else { self.wave.saturating_sub(1) }
+    }
+    /// How many times faster spawners are than normal
+    pub fn speed_up(&self) -> f32 {
+        SPEED_UP.powi(self.wave.saturating_sub(1) as i32)
+    }
+}
+
+impl World {
+    /// Switches to survival mode, where spawners only create the things of each wave, which needs a map with spawners
+    pub fn start_survival(&mut self) -> Result<(), String> {
+        if self.things.iter().all(|thing| thing.spawner.is_none()) {
+            return Err(format!("{} has no spawners to play survival mode on", self.map.name));
+        }
+        self.survival = Some(Survival { wave: 0, rest: REST });
+        for spawner in self.things.iter_mut().filter_map(|thing| thing.spawner.as_mut()) {
+            spawner.quota = Some(0);
+        }
+        Ok(())
+    }
+    /// Starts the next wave once the last one is over and the rest is up
+    pub(super) fn update_survival(&mut self, delta: f32) {
+        let spawners_left = self.things.iter().any(|thing| thing.spawner.is_some());
+        let Some(survival) = &mut self.survival else { return };
+        if survival.rest > 0. {
+            // Spawners can be destroyed, once they all are the run rests for good
+            if !spawners_left {
+                return;
+            }
+            survival.rest -= delta;
+            if survival.rest > 0. {
+                return;
+            }
+            survival.wave += 1;
+            info!("wave {}", survival.wave);
+
+            // The wave is shared out evenly between the spawners
+            let size = FIRST_WAVE + WAVE_GROWTH * (survival.wave - 1);
+            let mut spawners: Vec<_> = self.things.iter_mut().filter_map(|thing| thing.spawner.as_mut()).collect();
+            let count = spawners.len() as u32;
+            for (i, spawner) in spawners.iter_mut().enumerate() {
+                spawner.quota = Some(size / count + u32::from((i as u32) < size % count));
+            }
+            return;
+        }
+
+        let spawning = self.things.iter().any(|thing| thing.spawner.is_some_and(|s| s.quota != Some(0)));
+        let fighting = self.things.iter().any(|thing| thing.spawned_by.is_some());
+        if !spawning && !fighting {
+            info!("survived wave {}", survival.wave);
+            survival.rest = REST;
+        }
+    }
+}
+
+#[test]
+fn survival_needs_spawners() {
+    let mut world = World::from_map_src(crate::testing::CORRIDOR);
+    assert_eq!(world.start_survival(), Err("Corridor has no spawners to play survival mode on".to_owned()));
+    assert!(world.survival.is_none());
+
+    let mut arena = World::from_map_src(&crate::assets::read_to_string("arena.txt").unwrap());
+    assert_eq!(arena.start_survival(), Ok(()));
+}
+
+#[test]
+fn no_waves_come_once_the_spawners_are_destroyed() {
+    use crate::testing::wait_for;
+
+    let mut world = World::from_map_src(&crate::assets::read_to_string("arena.txt").unwrap());
+    world.start_survival().unwrap();
+    wait_for(&mut world, REST);
+    assert_eq!(world.survival.unwrap().wave, 1);
+
+    for thing in world.things.iter_mut().filter(|thing| thing.spawner.is_some()) {
+        thing.health = Some(0.);
+    }
+    for thing in world.things.iter_mut().filter(|thing| thing.spawned_by.is_some()) {
+        thing.health = Some(0.);
+    }
+    wait_for(&mut world, 3. * REST);
+    let survival = world.survival.unwrap();
+    assert_eq!((survival.wave, survival.survived()), (1, 1));
+}
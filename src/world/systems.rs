@@ -0,0 +1,128 @@
+//! The parts updating the world is made of and the order they run in
+//!
+//! Each system gets the world for one tick at a time. Adding one is a matter of
+//! putting it in `SYSTEMS` where it should run.
+
+use std::time::Duration;
+
+use crate::time::Instant;
+
+use super::{World, Controls};
+
+/// One part of updating the world
+#[derive(Debug, Copy, Clone)]
+pub struct System {
+    pub name: &'static str,
+    /// Whether it still runs while the player is talking
+    pub while_talking: bool,
+    /// Whether it still runs while a cutscene is playing
+    pub in_cutscene: bool,
+    run: fn(&mut World, f32, Controls),
+}
+
+/// Every system in the order they run in each tick
+pub const SYSTEMS: [System; 13] = [
+    System { name: "time", while_talking: true, in_cutscene: true, run: |world, delta, _| world.begin_update(delta) },
+    System { name: "clock", while_talking: false, in_cutscene: false, run: |world, delta, _| world.clock += delta },
+    System { name: "textures", while_talking: true, in_cutscene: true, run: |world, _, _| world.receive_textures() },
+    System { name: "cutscene", while_talking: true, in_cutscene: true, run: |world, delta, _| world.tick_cutscene(delta) },
+    System { name: "input", while_talking: true, in_cutscene: true, run: World::update_view },
+    System { name: "player", while_talking: true, in_cutscene: true, run: World::update_player },
+    System { name: "surroundings", while_talking: false, in_cutscene: false, run: |world, delta, _| world.update_surroundings(delta) },
+    System { name: "lives", while_talking: true, in_cutscene: true, run: |world, delta, _| world.update_lives(delta) },
+    System { name: "animation", while_talking: true, in_cutscene: true, run: |world, delta, _| world.animate(delta) },
+    System { name: "ai", while_talking: false, in_cutscene: false, run: |world, delta, _| world.update_ai(delta) },
+    System { name: "projectiles", while_talking: false, in_cutscene: false, run: |world, delta, _| world.update_projectiles(delta) },
+    System { name: "physics", while_talking: false, in_cutscene: false, run: |world, delta, _| world.update_physics(delta) },
+    System { name: "triggers", while_talking: false, in_cutscene: true, run: |world, delta, _| world.update_triggers(delta) },
+];
+
+impl World {
+    /// Runs every system for one tick of `delta` seconds
+    pub fn update(&mut self, delta: f32, controls: Controls) {
+        self.update_timed(delta, controls, |_, _| ());
+    }
+
+    /// Like `update`, but tells `hook` how long each system took
+    pub fn update_timed(&mut self, delta: f32, controls: Controls, mut hook: impl FnMut(&System, Duration)) {
+        // The player has no say in what happens during a cutscene
+        let controls = if self.in_cutscene() { Controls::default() } else { controls };
+        for system in &SYSTEMS {
+            // Nothing but the player's own view goes on while talking, and nothing moves or fights during a cutscene
+            if (self.talk.is_some() && !system.while_talking) || (self.in_cutscene() && !system.in_cutscene) {
+                continue;
+            }
+            let start = Instant::now();
+            (system.run)(self, delta, controls);
+            hook(system, start.elapsed());
+        }
+    }
+
+    fn update_ai(&mut self, delta: f32) {
+        self.hear_sounds(delta);
+        self.update_survival(delta);
+        self.update_spawners(delta);
+        self.update_enemies(delta);
+        self.update_bosses(delta);
+    }
+
+    /// Takes away what's dead, checks whether that completed anything and sets off the triggers of the map
+    fn update_triggers(&mut self, delta: f32) {
+        self.reap_dead_things();
+        self.track_objectives();
+        self.update_signals(delta);
+    }
+}
+
+#[test]
+fn system_names_are_unique() {
+    for (i, system) in SYSTEMS.iter().enumerate() {
+        assert!(SYSTEMS[..i].iter().all(|other| other.name != system.name), "{} is there twice", system.name);
+    }
+}
+
+#[test]
+fn the_floor_waits_while_talking() {
+    use super::{talk::Talk, TICK};
+
+    let mut world = World::from_map_src(&crate::testing::test_map("Hazard", &["hazard h 10"], &[], "\
+bbb
+b>b
+bbb
+
+...
+.h.
+...
+"));
+    let health = world.player_health;
+    world.talk = Some(Talk { dialogue: 0, page: 0, choice: 0 });
+    world.update(TICK, Controls::default());
+    assert_eq!(world.player_health, health);
+    world.talk = None;
+    world.update(TICK, Controls::default());
+    assert_eq!(world.player_health, health - 10.);
+}
+
+#[test]
+fn projectiles_wait_for_cutscenes() {
+    use crate::vec::{Point2, Vector2};
+    use super::{thing::{Attacker, EntityId}, TICK};
+
+    let mut world = World::from_map_src(&crate::testing::test_map("Intro", &[], &[], "\
+bbbbbbbbb
+b>      b
+b       b
+bbbbbbbbb
+
+cutscene intro
+shot 1.5 1.5 3.5 1.5 1
+"));
+    world.fire(Point2::new(2.5, 2.5), Vector2::new(1., 0.), 1., Attacker { id: EntityId(1), faction: None });
+    let start: Vec<_> = world.projectiles.positions(1.).collect();
+    assert!(world.in_cutscene());
+    world.update(TICK, Controls::default());
+    assert_eq!(world.projectiles.positions(1.).collect::<Vec<_>>(), start);
+    crate::testing::wait_for(&mut world, 1.);
+    assert!(!world.in_cutscene());
+    assert!(world.projectiles.positions(1.).next().unwrap().x > start[0].x);
+}
@@ -0,0 +1,66 @@
+//! Talking to friendly things
+
+use crate::{map::Dialogue, vec::Vector2};
+
+use super::{World, GameEvent};
+
+/// How far away a friendly thing can be talked to from, measured from its edge
+const TALK_REACH: f32 = 0.8;
+
+/// Where the player is in a conversation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Talk {
+    /// Index of the dialogue of the map
+    pub dialogue: usize,
+    pub page: usize,
+    /// Which of the choices is selected, once on the last page
+    pub choice: usize,
+}
+
+impl World {
+    /// Starts talking to the nearest friendly thing in front of the player, returning whether there was one
+    pub(super) fn start_talking(&mut self) -> bool {
+        let dir = Vector2::unit_from_angle(self.player_angle);
+        let nearest = self.things.iter()
+            .filter_map(|thing| {
+                let dialogue = thing.dialogue?;
+                let to_thing = thing.pos - self.player_p;
+                let dist = to_thing.norm() - thing.width();
+                (dist <= TALK_REACH && to_thing.dot(dir) > 0.).then_some((dialogue, dist))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+
+        let Some((dialogue, _)) = nearest else { return false };
+        self.talk = Some(Talk { dialogue, page: 0, choice: 0 });
+        self.events.push(GameEvent::DialoguePage { dialogue, page: 0 });
+        true
+    }
+    /// Goes to the next page, or goes where the selected choice leads on the last page
+    pub(super) fn continue_talking(&mut self) {
+        let Some(talk) = &mut self.talk else { return };
+        let dialogue = self.map.dialogue(talk.dialogue);
+        if talk.page + 1 < dialogue.pages.len() {
+            talk.page += 1;
+            self.events.push(GameEvent::DialoguePage { dialogue: talk.dialogue, page: talk.page });
+            return;
+        }
+        self.talk = dialogue.choices.get(talk.choice)
+            .and_then(|choice| choice.next)
+            .map(|dialogue| Talk { dialogue, page: 0, choice: 0 });
+        if let Some(talk) = self.talk {
+            self.events.push(GameEvent::DialoguePage { dialogue: talk.dialogue, page: 0 });
+        }
+    }
+    /// Moves the selection `step` choices down (or up if negative), wrapping around
+    pub fn select_choice(&mut self, step: i32) {
+        let Some(talk) = &mut self.talk else { return };
+        let choices = self.map.dialogue(talk.dialogue).choices.len();
+        if choices > 0 {
+            talk.choice = (talk.choice as i32 + step).rem_euclid(choices as i32) as usize;
+        }
+    }
+    /// The dialogue being talked through and where in it the player is
+    pub fn talking(&self) -> Option<(&Dialogue, Talk)> {
+        self.talk.map(|talk| (self.map.dialogue(talk.dialogue), talk))
+    }
+}
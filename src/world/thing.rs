@@ -1,31 +1,194 @@
-use crate::{vec::{Point2, Vector2}, tex::{Frame, Texture}, HEIGHT};
+use crate::{vec::{Point2, Vector2}, tex::{Texture, columns::Columns}, map::Map};
 
-use super::distance_line_circle;
+use super::{distance_line_circle, grid::ThingGrid, effects::{StatusEffects, Effect}, boss::Boss, enemy::Enemy, spawner::Spawner, faction::{Faction, can_hurt}, pickups::Pickup};
+
+/// Tells the player and each thing apart for as long as they're around
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct EntityId(pub u32);
+
+impl EntityId {
+    pub const PLAYER: EntityId = EntityId(0);
+}
+
+/// Who did some damage
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Attacker {
+    pub id: EntityId,
+    pub faction: Option<Faction>,
+}
 
 #[derive(Debug, Copy, Clone)]
 pub struct Thing {
+    /// Given by the world when the thing is added to it
+    pub id: EntityId,
     pub pos: Point2,
+    /// Position before the last update, drawn things are somewhere between this and `pos`
+    prev_pos: Point2,
+    pub vel: Vector2,
     width: f32,
+    /// How tall the thing is drawn, where 1 reaches from the floor to eye height
+    pub height: f32,
     tex: usize,
+    /// Whether the thing moves around and should be pushed apart from other things
+    pub dynamic: bool,
+    /// Things without health can't be damaged
+    pub health: Option<f32>,
+    /// Whether the thing explodes when it dies
+    pub explosive: bool,
+    pub effects: StatusEffects,
+    /// Index of the dialogue of the map that friendly things say when talked to
+    pub dialogue: Option<usize>,
+    /// Index of the objective of the map the thing is part of
+    pub objective: Option<usize>,
+    /// What the player gets for walking into it, which takes it away
+    pub pickup: Option<Pickup>,
+    pub boss: Option<Boss>,
+    pub enemy: Option<Enemy>,
+    pub spawner: Option<Spawner>,
+    /// The spawner that created the thing
+    pub spawned_by: Option<EntityId>,
+    /// Side the thing is on, things outside of any faction can be hurt by anyone
+    pub faction: Option<Faction>,
+    /// Who hurt the thing last, they get the kill if it dies
+    pub last_attacker: Option<EntityId>,
 }
 
 impl Thing {
     pub fn new(pos: Point2, width: f32, tex: usize) -> Self {
-        Thing { pos, width, tex }
+        Thing { id: EntityId::PLAYER, pos, prev_pos: pos, vel: Vector2::new(0., 0.), width, height: 1., tex, dynamic: false, health: None, explosive: false, effects: StatusEffects::default(), dialogue: None, objective: None, pickup: None, boss: None, enemy: None, spawner: None, spawned_by: None, faction: None, last_attacker: None }
+    }
+    /// Moves the thing straight to `p` without it being drawn moving there
+    pub fn place(&mut self, p: Point2) {
+        self.pos = p;
+        self.prev_pos = p;
+    }
+    /// Marks the start of an update
+    pub fn remember_pos(&mut self) {
+        self.prev_pos = self.pos;
+    }
+    /// The thing as it should be drawn `alpha` of the way from its last position to its current one
+    pub fn interpolated(&self, alpha: f32) -> Thing {
+        Thing { pos: self.prev_pos + alpha * (self.pos - self.prev_pos), ..*self }
+    }
+    /// Index of the texture of the thing
+    pub fn tex(&self) -> usize {
+        self.tex
+    }
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+    pub fn is_dead(&self) -> bool {
+        self.health.is_some_and(|h| h <= 0.)
+    }
+    /// Takes damage unless invulnerable or without health
+    pub fn damage(&mut self, damage: f32) {
+        if let Some(health) = &mut self.health {
+            if !self.effects.has(Effect::Invulnerable) {
+                *health -= damage;
+            }
+        }
+    }
+    /// The thing as the one doing damage
+    pub fn attacker(&self) -> Attacker {
+        Attacker { id: self.id, faction: self.faction }
+    }
+    /// Takes damage from `attacker` unless they're on the same side,
+    /// enemies hurt by another faction than the player's turn on that faction
+    pub fn damage_by(&mut self, damage: f32, attacker: Attacker) {
+        if !can_hurt(attacker.faction, self.faction) || self.health.is_none() {
+            return;
+        }
+        self.damage(damage);
+        self.last_attacker = Some(attacker.id);
+        if let (Some(enemy), Some(faction)) = (&mut self.enemy, attacker.faction) {
+            if faction != Faction::Player {
+                enemy.provoke(faction);
+            } else {
+                // Getting hurt gives the player away however well they're hidden
+                enemy.alert();
+            }
+        }
+    }
+    /// Moves the thing by `dp`, clipping it against the walls of the map
+    pub fn move_by(&mut self, dp: Vector2, map: &Map) {
+        let orig_p = self.pos;
+        self.pos = self.pos + dp;
+        self.pos = self.pos - map.move_ray_cast(orig_p, dp);
+
+        let wrapped = map.wrap_point(self.pos);
+        self.prev_pos = self.prev_pos + (wrapped - self.pos);
+        self.pos = wrapped;
     }
-    pub fn draw_x(&self, frame: &mut Frame, x: u32, texes: &[Texture], last_dist: f32, p: Point2, dist: Vector2, height_factor: f32) {
-        let f = distance_line_circle(p, dist, self.pos);
+    pub fn draw_x<C: Columns>(&self, frame: &mut C, x: u32, texes: &[Texture], ray: &SpriteRay) {
+        let f = distance_line_circle(ray.start, ray.seg, self.pos);
         let f_len = f.norm();
 
         if f_len <= self.width {
-            let to_thing = self.pos - p;
+            let to_thing = self.pos - ray.start;
             let u = 0.5 + f.dot(-to_thing.hat().set_len(self.width*2.)) / self.width;
 
             // Calculate height of line to draw on screen
-            let line_height = height_factor / (last_dist + to_thing.norm());
+            let line_height = ray.height_factor / (ray.dist_before + to_thing.norm());
             let line_height = if line_height.is_infinite() { i32::MAX } else { line_height as i32 }.abs();
 
-            texes[self.tex].draw_line_at(frame, x, HEIGHT / 2, u, line_height as u32)
+            // Things stand on the floor and normal things reach up to eye height
+            let bottom = frame.height() as f32 / 2. + (1. + 2. * ray.eye_height) * line_height as f32;
+            let height = self.height * line_height as f32;
+            texes[self.tex].draw_line_at(frame, x, (bottom - height) as i32, u, height as u32)
+        }
+    }
+}
+
+/// A stretch of the ray through a column of the view, and how things along it are projected
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteRay {
+    pub start: Point2,
+    pub seg: Vector2,
+    /// How far the ray went before the start, through mirrors and the like
+    pub dist_before: f32,
+    /// Half the projected height of something one unit away
+    pub height_factor: f32,
+    /// How far above normal eye height the view is, in wall heights
+    pub eye_height: f32,
+}
+
+/// Pushes overlapping things apart (treating them as circles) so dynamic things spread out instead of stacking
+///
+/// Two dynamic things share the push equally, a dynamic thing overlapping a static one is pushed all the way out.
+pub fn separate_things(things: &mut [Thing], grid: &ThingGrid, map: &Map) {
+    for i in 0..things.len() {
+        if !things[i].dynamic {
+            continue;
+        }
+
+        for j in grid.query_radius(things[i].pos, things[i].width) {
+            // Pairs of dynamic things are handled once, from the thing with the higher index
+            if j == i || (j > i && things[j].dynamic) {
+                continue;
+            }
+
+            let (a, b) = if j < i {
+                let (before, after) = things.split_at_mut(i);
+                (&mut before[j], &mut after[0])
+            } else {
+                let (before, after) = things.split_at_mut(j);
+                (&mut after[0], &mut before[i])
+            };
+
+            let d = b.pos - a.pos;
+            let overlap = a.width + b.width - d.norm();
+            if overlap <= 0. {
+                continue;
+            }
+            // Things exactly on top of each other are pushed apart along an arbitrary axis
+            let push_dir = if d.norm() > 0. { d.set_len(1.) } else { Vector2::new(1., 0.) };
+
+            if a.dynamic {
+                a.move_by(-0.5 * overlap * push_dir, map);
+                b.move_by(0.5 * overlap * push_dir, map);
+            } else {
+                b.move_by(overlap * push_dir, map);
+            }
         }
     }
 }
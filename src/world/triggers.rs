@@ -0,0 +1,254 @@
+//! Signals of switches, plates and logic, and the triggers of the map they set off
+
+use std::collections::VecDeque;
+
+use log::info;
+
+use crate::map::{Map, Logic, TriggerAction};
+
+use super::{World, GameEvent};
+
+/// What the signals and triggers of the map are at
+#[derive(Debug, Clone, Default)]
+pub(super) struct Signals {
+    /// Signals switched on by the player
+    switched: Vec<bool>,
+    /// Seconds until each switched on signal turns back off by itself
+    reverts: Vec<Option<f32>>,
+    /// Whether each signal was on the last update
+    on: Vec<bool>,
+    /// Whether each trigger was on the last update
+    triggered: Vec<bool>,
+    /// Whether each signal is set by logic, which keeps what it was set to until the logic sets it again
+    from_logic: Vec<bool>,
+    nodes: Vec<NodeState>,
+}
+
+/// What a logic node remembers between updates
+#[derive(Debug, Clone, Default)]
+struct NodeState {
+    /// Whether the input was on the last update, to tell when it turns on
+    input_was_on: bool,
+    /// Times the input has turned on
+    activations: u32,
+    /// Changes of the input on their way through a delay, with the seconds left until they come out
+    delayed: VecDeque<(f32, bool)>,
+    /// Output a random node picked
+    picked: usize,
+}
+
+impl Signals {
+    pub(super) fn new(map: &Map) -> Self {
+        let signals = map.signal_count();
+        Signals {
+            switched: vec![false; signals],
+            reverts: vec![None; signals],
+            on: vec![false; signals],
+            triggered: vec![false; map.triggers().len()],
+            from_logic: (0..signals).map(|signal| map.logic().iter().any(|node| node.outputs.contains(&signal))).collect(),
+            nodes: vec![NodeState::default(); map.logic().len()],
+        }
+    }
+}
+
+impl World {
+    /// Flips the switch in front of the player, returns whether there is one
+    pub(super) fn use_switch(&mut self) -> bool {
+        let Some((_, switch)) = self.in_reach(|map, (x, y)| map.switch_at(x, y)) else { return false };
+        let on = !self.signals.switched[switch.signal];
+        self.signals.switched[switch.signal] = on;
+        self.signals.reverts[switch.signal] = switch.revert.filter(|_| on);
+        true
+    }
+    /// Whether a signal is on
+    #[cfg(test)]
+    pub fn signal(&self, signal: usize) -> bool {
+        self.signals.on[signal]
+    }
+    /// Works out which signals are on and sets off the triggers that turned on or off
+    pub(super) fn update_signals(&mut self, delta: f32) {
+        let signals = &mut self.signals;
+        for (switched, revert) in signals.switched.iter_mut().zip(&mut signals.reverts) {
+            if let Some(seconds) = revert {
+                *seconds -= delta;
+                if *seconds <= 0. {
+                    (*switched, *revert) = (false, None);
+                }
+            }
+        }
+        for ((on, &switched), &from_logic) in signals.on.iter_mut().zip(&signals.switched).zip(&signals.from_logic) {
+            if !from_logic {
+                *on = switched;
+            }
+        }
+        // Only things that move around are heavy enough for plates
+        let standing = self.things.iter()
+            .filter(|thing| thing.dynamic && !thing.is_dead())
+            .map(|thing| thing.pos)
+            .chain((self.player_health > 0.).then_some(self.player_p));
+        for p in standing {
+            if let Some(signal) = self.map.plate_at(p) {
+                signals.on[signal] = true;
+            }
+        }
+
+        self.update_logic(delta);
+
+        for i in 0..self.signals.triggered.len() {
+            let trigger = &self.map.triggers()[i];
+            let on = trigger.signals.iter().all(|&signal| self.signals.on[signal]);
+            if on != self.signals.triggered[i] {
+                self.signals.triggered[i] = on;
+                self.set_off(i, on);
+            }
+        }
+    }
+    /// Works out the signals of the logic nodes in order, so a node uses what the ones before it worked out this update
+    fn update_logic(&mut self, delta: f32) {
+        let Signals { on, nodes, .. } = &mut self.signals;
+        for (i, (node, state)) in self.map.logic().iter().zip(nodes).enumerate() {
+            let input = match node.logic {
+                Logic::Counter { input, .. } | Logic::Delay { input, .. } | Logic::Random { input } => Some(on[input]),
+                Logic::And(_) | Logic::Or(_) => None,
+            };
+            let turned_on = input.is_some_and(|input| input && !state.input_was_on);
+            let changed = input.is_some_and(|input| input != state.input_was_on);
+            state.input_was_on = input.unwrap_or_default();
+
+            match &node.logic {
+                Logic::Counter { count, .. } => {
+                    state.activations += turned_on as u32;
+                    on[node.outputs[0]] = state.activations >= *count;
+                }
+                Logic::And(inputs) => on[node.outputs[0]] = inputs.iter().all(|&s| on[s]),
+                Logic::Or(inputs) => on[node.outputs[0]] = inputs.iter().any(|&s| on[s]),
+                Logic::Delay { seconds, .. } => {
+                    if changed {
+                        state.delayed.push_back((*seconds, state.input_was_on));
+                    }
+                    for (left, _) in &mut state.delayed {
+                        *left -= delta;
+                    }
+                    while let Some(&(_, value)) = state.delayed.front().filter(|(left, _)| *left <= 0.) {
+                        on[node.outputs[0]] = value;
+                        state.delayed.pop_front();
+                    }
+                }
+                Logic::Random { .. } => {
+                    if turned_on {
+                        state.picked = random_index(self.time, i, state.activations, node.outputs.len());
+                        state.activations += 1;
+                    }
+                    for (j, &output) in node.outputs.iter().enumerate() {
+                        on[output] = state.input_was_on && j == state.picked;
+                    }
+                }
+            }
+        }
+    }
+    /// Does what trigger `i` does when it turns on, or undoes it when it turns off if it can be undone
+    fn set_off(&mut self, i: usize, on: bool) {
+        if on {
+            info!("trigger {i} went off");
+            self.events.push(GameEvent::TriggerFired(i));
+        }
+        match self.map.triggers()[i].action.clone() {
+            TriggerAction::Open(door) => for cell in self.map.door_cells(door) {
+                self.move_door(cell, on);
+            },
+            TriggerAction::Close(door) => for cell in self.map.door_cells(door) {
+                self.move_door(cell, !on);
+            },
+            TriggerAction::Cutscene(name) if on => {
+                self.play_cutscene(&name);
+            }
+            TriggerAction::Time(hour) if on => {
+                self.set_time_of_day(hour);
+            }
+            TriggerAction::Cutscene(_) | TriggerAction::Time(_) => (),
+        }
+    }
+}
+
+/// Index below `n` that looks random, but is always the same for the same time, node and number of picks so replays work out the same
+fn random_index(time: f32, node: usize, picks: u32, n: usize) -> usize {
+    let mut h = time.to_bits().wrapping_mul(0x9e37_79b1) ^ (node as u32).wrapping_mul(0x85eb_ca77) ^ picks.wrapping_mul(0xc2b2_ae35);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2c1b_3c6d);
+    h ^= h >> 13;
+    h as usize % n
+}
+
+#[test]
+fn doors_open_for_plates_and_all_the_switches() {
+    use crate::vec::Point2;
+    use crate::testing::{test_map, wait_for};
+    use super::{Controls, TICK, doors::DoorChange};
+
+    let mut world = World::from_map_src(&test_map("Switches", &["plate p weight", "trigger weight open d", "trigger left+right open e"], &[
+        "d door locked tex/door.png tex/door_dark.png",
+        "e door locked tex/door.png tex/door_dark.png",
+        "l switch=left tex/brick2.png tex/brick2_dark.png",
+        "r switch=right,1 tex/brick2.png tex/brick2_dark.png",
+    ], "\
+bbbbbbbbb
+b>  d e b
+blrbbbbbb
+
+.........
+..p......
+.........
+"));
+    let tick = |world: &mut World| world.update(TICK, Controls::default());
+
+    // Standing on the plate opens the door and stepping off closes it again
+    world.player_p = Point2::new(2.5, 1.5);
+    tick(&mut world);
+    let changed = |world: &World, change| world.events.iter().any(|e| matches!(e, GameEvent::DoorChanged { cell: (4, 1), change: c } if *c == change));
+    assert!(changed(&world, DoorChange::Opening));
+    world.player_p = Point2::new(1.5, 1.5);
+    tick(&mut world);
+    assert!(changed(&world, DoorChange::Closing));
+
+    // Both switches, one of which turns back off after a second
+    world.player_angle = std::f32::consts::FRAC_PI_2;
+    world.interact();
+    world.player_p = Point2::new(2.5, 1.5);
+    world.interact();
+    tick(&mut world);
+    assert!(world.signals.triggered[1]);
+    wait_for(&mut world, 1.);
+    assert!(world.signal(1) && !world.signal(2) && !world.signals.triggered[1]);
+}
+
+#[test]
+fn logic_counts_waits_and_picks() {
+    use crate::vec::Point2;
+    use super::{Controls, TICK};
+
+    let headers = ["plate p step", "logic counter twice 2 step", "logic delay later 0.5 twice", "logic random heads+tails step"];
+    let mut world = World::from_map_src(&crate::testing::test_map("Logic", &headers, &[], "\
+bbbbb
+b>  b
+bbbbb
+
+.....
+..p..
+.....
+"));
+    let signal = |world: &World, name: &str| world.signal(world.map.signal_named(name).unwrap());
+    let step = |world: &mut World, x: f32| {
+        world.player_p = Point2::new(x, 1.5);
+        world.update(TICK, Controls::default());
+    };
+
+    step(&mut world, 2.5);
+    assert!(!signal(&world, "twice"));
+    assert!(signal(&world, "heads") != signal(&world, "tails"));
+    step(&mut world, 1.5);
+    assert!(!signal(&world, "heads") && !signal(&world, "tails"));
+    step(&mut world, 2.5);
+    assert!(signal(&world, "twice") && !signal(&world, "later"));
+    crate::testing::wait_for(&mut world, 0.5);
+    assert!(signal(&world, "later"));
+}
@@ -0,0 +1,96 @@
+//! The gun held in front of the view, scaled to the frame and moved around by walking and firing
+
+use std::f32::consts::PI;
+
+use image::{Rgba, RgbaImage};
+
+use crate::tex::{Frame, Texture, atlas::Atlas};
+
+/// Frame height the gun is drawn at its own pixel size for, it's scaled by how much taller or shorter the frame is
+const REFERENCE_HEIGHT: f32 = 240.;
+/// Radians the sway goes through for each unit walked, two steps make up one full sway
+const SWAY_PER_UNIT: f32 = 2. * PI / 1.6;
+/// How far the gun sways to the sides and dips at full walking speed, in frame heights
+const SWAY_X: f32 = 0.03;
+const SWAY_Y: f32 = 0.015;
+/// How fast the sway follows the walking speed, as a fraction per second on an exponential curve
+const SWAY_FOLLOW: f32 = 8.;
+/// How far the gun is pushed down right after firing, in frame heights
+const RECOIL_KICK: f32 = 0.06;
+/// How fast the gun comes back up after firing, as a fraction per second on an exponential curve
+const RECOIL_RECOVERY: f32 = 12.;
+/// Seconds the muzzle flash is shown after firing
+const FLASH_TIME: f32 = 0.06;
+/// Size of the muzzle flash texture in pixels, drawn at the same scale as the gun
+const FLASH_SIZE: u32 = 24;
+/// Index of each sprite in the atlas
+const GUN: usize = 0;
+const MUZZLE_FLASH: usize = 1;
+
+#[derive(Debug, Clone)]
+pub struct Viewmodel {
+    /// The gun and the muzzle flash
+    sprites: Atlas,
+    /// How far through a sway the gun is, in radians
+    phase: f32,
+    /// How much the gun sways, from 0 when standing still to 1 at full walking speed
+    sway: f32,
+    /// How much the gun is still kicked back from firing, from 0 to 1
+    recoil: f32,
+    /// Seconds left of the muzzle flash
+    flash: f32,
+}
+
+impl Viewmodel {
+    pub fn new(gun: Texture) -> Self {
+        let sprites = Atlas::pack(&[gun, muzzle_flash()], 0);
+        Viewmodel { sprites, phase: 0., sway: 0., recoil: 0., flash: 0. }
+    }
+    /// Moves the gun along with walking `walked` units this update at `speed` (from 0 to 1) of full walking speed
+    pub fn tick(&mut self, delta: f32, walked: f32, speed: f32) {
+        self.phase = (self.phase + SWAY_PER_UNIT * walked) % (2. * PI);
+        self.sway += (speed.clamp(0., 1.) - self.sway) * (1. - (-SWAY_FOLLOW * delta).exp());
+        self.recoil *= (-RECOIL_RECOVERY * delta).exp();
+        self.flash = (self.flash - delta).max(0.);
+    }
+    /// Kicks the gun back and shows the muzzle flash
+    pub fn fire(&mut self) {
+        self.recoil = 1.;
+        self.flash = FLASH_TIME;
+    }
+    /// How far the gun is moved right and down from where it rests, in frame heights
+    fn offset(&self) -> (f32, f32) {
+        let x = self.sway * SWAY_X * self.phase.sin();
+        // It dips with every step, so twice per sway
+        let y = self.sway * SWAY_Y * self.phase.cos().abs() + self.recoil * RECOIL_KICK;
+        (x, y)
+    }
+    /// Draws the gun at the bottom middle of the frame with the muzzle flash on top of its tip
+    pub fn draw(&self, frame: &mut Frame) {
+        let frame_height = frame.height() as f32;
+        let scale = frame_height / REFERENCE_HEIGHT;
+        let gun = self.sprites.region(GUN);
+        let (width, height) = (gun.width() as f32 * scale, gun.height() as f32 * scale);
+        let (dx, dy) = self.offset();
+        let x = (frame.width() as f32 - width) / 2. + dx * frame_height;
+        let y = frame_height - height + dy * frame_height;
+        gun.draw_scaled(frame, x.round() as i32, y.round() as i32, width.round() as u32, height.round() as u32);
+
+        if self.flash > 0. {
+            let size = FLASH_SIZE as f32 * scale;
+            let (flash_x, flash_y) = (x + (width - size) / 2., y - size / 2.);
+            self.sprites.region(MUZZLE_FLASH).draw_scaled(frame, flash_x.round() as i32, flash_y.round() as i32, size.round() as u32, size.round() as u32);
+        }
+    }
+}
+
+/// A round yellow glow fading out towards its edge
+fn muzzle_flash() -> Texture {
+    let centre = (FLASH_SIZE as f32 - 1.) / 2.;
+    let img = RgbaImage::from_fn(FLASH_SIZE, FLASH_SIZE, |x, y| {
+        let dist = (x as f32 - centre).hypot(y as f32 - centre) / centre;
+        let alpha = (1. - dist).clamp(0., 1.);
+        Rgba([0xff, 0xe0, 0x60 + (0x9f as f32 * alpha) as u8, (0xff as f32 * alpha.sqrt()) as u8])
+    });
+    Texture::from_rgba(&img)
+}